@@ -106,21 +106,32 @@ def main():
             table.add_column("Status", style="white", justify="center")
             table.add_column("Title", style="white")
             table.add_column("Server", style="red")
+            table.add_column("CNAME", style="white")
 
             for res in sorted(results, key=lambda x: x["subdomain"]):
                 sub = res.get("subdomain", "")
-                ip = res.get("ip") or "-"
+                if res.get("port"):
+                    sub = f"{sub}:{res['port']}"
+                ip = ", ".join(res.get("ips") or []) or "-"
                 status = str(res.get("status_code")) if res.get("status_code") else "-"
                 title = res.get("title") or "-"
                 server = res.get("server") or "-"
+                cname = " -> ".join(res.get("cname_chain") or []) or "-"
 
                 # Truncate long titles
                 if len(title) > 30:
                     title = title[:27] + "..."
 
-                table.add_row(sub, ip, status, title, server)
+                table.add_row(sub, ip, status, title, server, cname)
 
             console.print(table)
+
+            for res in results:
+                takeover = res.get("takeover")
+                if takeover:
+                    console.print(
+                        f"[bold red][!] Possible takeover:[/bold red] [bold white]{res['subdomain']} -> {takeover['cname']} ({takeover['service']})[/bold white]"
+                    )
         else:
             console.print("[bold red]No active subdomains found.[/bold red]")
 
@@ -0,0 +1,205 @@
+//! Concurrency limits that adjust themselves, AIMD-style: after every
+//! window of requests the limit grows by a step while errors stay rare and
+//! is halved when they spike. A scan from a fast server climbs above the
+//! configured limits; one over a weak link backs off before its resolvers
+//! and the network start dropping answers.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+/// How far above its configured value an adaptive limit may climb.
+const MAX_SCALE: usize = 4;
+
+/// How far below it may fall, as a fraction of the configured value.
+const MIN_DIVISOR: usize = 10;
+
+/// Failure rate of a window above which the limit is halved.
+const BACKOFF_RATE: f64 = 0.1;
+
+/// Failure rate of a window at or below which the limit grows.
+const GROWTH_RATE: f64 = 0.02;
+
+/// Outcomes per window at the least; otherwise one window is as many
+/// outcomes as the current limit.
+const MIN_WINDOW: usize = 50;
+
+/// A concurrency limit, fixed or adaptive. Clones share one limit.
+#[derive(Debug, Clone)]
+pub struct Concurrency {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// `"DNS"` or `"HTTP"`, for the log.
+    name: &'static str,
+    semaphore: Arc<Semaphore>,
+    /// Permits to swallow as they come back, after a decrease found them
+    /// in use.
+    debt: AtomicUsize,
+    min: usize,
+    max: usize,
+    step: usize,
+    adaptive: bool,
+    window: Mutex<Window>,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    limit: usize,
+    successes: usize,
+    failures: usize,
+    /// Skip judging the window after a decrease: its outcomes mostly come
+    /// from requests started under the old limit.
+    cooling: bool,
+}
+
+/// A slot taken from a [`Concurrency`], given back on drop.
+#[derive(Debug)]
+pub struct Permit {
+    permit: Option<OwnedSemaphorePermit>,
+    inner: Arc<Inner>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let paid = self
+            .inner
+            .debt
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |d| d.checked_sub(1))
+            .is_ok();
+        if let (true, Some(permit)) = (paid, self.permit.take()) {
+            permit.forget();
+        }
+    }
+}
+
+impl Concurrency {
+    /// Always `limit` at once.
+    pub fn fixed(limit: usize) -> Self {
+        Self::build("", limit.max(1), false)
+    }
+
+    /// Starts at `initial` and moves between a tenth of it and four times
+    /// it.
+    pub fn adaptive(name: &'static str, initial: usize) -> Self {
+        Self::build(name, initial.max(1), true)
+    }
+
+    fn build(name: &'static str, initial: usize, adaptive: bool) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                name,
+                semaphore: Arc::new(Semaphore::new(initial)),
+                debt: AtomicUsize::new(0),
+                min: (initial / MIN_DIVISOR).max(1),
+                max: if adaptive {
+                    initial.saturating_mul(MAX_SCALE)
+                } else {
+                    initial
+                },
+                step: (initial / 20).max(1),
+                adaptive,
+                window: Mutex::new(Window {
+                    limit: initial,
+                    ..Window::default()
+                }),
+            }),
+        }
+    }
+
+    /// Waits for a slot. `None` only if the limit was closed.
+    pub async fn acquire(&self) -> Option<Permit> {
+        let permit = self.inner.semaphore.clone().acquire_owned().await.ok()?;
+        Some(Permit {
+            permit: Some(permit),
+            inner: self.inner.clone(),
+        })
+    }
+
+    /// The current limit.
+    pub fn limit(&self) -> usize {
+        self.window().limit
+    }
+
+    /// The highest the limit can go.
+    pub fn max(&self) -> usize {
+        self.inner.max
+    }
+
+    /// Counts one request towards the current window. `ok` is false for
+    /// failures that suggest overload (timeouts, SERVFAILs, resets), not
+    /// for answers that are merely negative.
+    pub fn record(&self, ok: bool) {
+        if !self.inner.adaptive {
+            return;
+        }
+        let mut window = self.window();
+        if ok {
+            window.successes += 1;
+        } else {
+            window.failures += 1;
+        }
+        let total = window.successes + window.failures;
+        if total < window.limit.max(MIN_WINDOW) {
+            return;
+        }
+        let rate = window.failures as f64 / total as f64;
+        let (old, cooling) = (window.limit, window.cooling);
+        window.successes = 0;
+        window.failures = 0;
+        window.cooling = false;
+        if cooling {
+            return;
+        }
+        if rate > BACKOFF_RATE && old > self.inner.min {
+            let new = (old / 2).max(self.inner.min);
+            window.limit = new;
+            window.cooling = true;
+            self.shrink(old - new);
+            debug!(
+                "{} concurrency {} -> {} ({:.0}% failing)",
+                self.inner.name,
+                old,
+                new,
+                rate * 100.0
+            );
+        } else if rate <= GROWTH_RATE && old < self.inner.max {
+            let new = (old + self.inner.step).min(self.inner.max);
+            window.limit = new;
+            self.grow(new - old);
+            debug!("{} concurrency {} -> {}", self.inner.name, old, new);
+        }
+    }
+
+    fn window(&self) -> std::sync::MutexGuard<'_, Window> {
+        self.inner.window.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn grow(&self, n: usize) {
+        let paid = self
+            .inner
+            .debt
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |d| {
+                Some(d.saturating_sub(n))
+            })
+            .map(|d| d.min(n))
+            .unwrap_or_default();
+        self.inner.semaphore.add_permits(n - paid);
+    }
+
+    fn shrink(&self, n: usize) {
+        // Idle permits go right away; the rest as they are given back
+        let idle = self.inner.semaphore.available_permits().min(n);
+        let taken = match self.inner.semaphore.try_acquire_many(idle as u32) {
+            Ok(permits) => {
+                permits.forget();
+                idle
+            }
+            Err(_) => 0,
+        };
+        self.inner.debt.fetch_add(n - taken, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::dns::{normalize_name, ResolverPool};
+use crate::Result;
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use trust_dns_resolver::proto::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns_resolver::proto::rr::{Name, RecordType};
+
+const AXFR_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A zone leaked by a misconfigured authoritative server.
+#[derive(Debug, Clone)]
+pub struct ZoneTransfer {
+    /// The nameserver that allowed the transfer.
+    pub nameserver: String,
+    /// Every in-zone owner name found in the transfer.
+    pub names: HashSet<String>,
+}
+
+/// Tries AXFR against each authoritative nameserver of `domain` and returns
+/// the first successful transfer.
+pub async fn attempt_axfr(resolver: &ResolverPool, domain: &str) -> Option<ZoneTransfer> {
+    let ns_lookup = resolver.next().ns_lookup(domain).await.ok()?;
+    let nameservers: Vec<String> = ns_lookup
+        .iter()
+        .map(|ns| normalize_name(&ns.0.to_ascii()))
+        .collect();
+
+    for ns in nameservers {
+        let Some(host) = resolver.resolve(&ns).await else {
+            continue;
+        };
+        for ip in host.ips {
+            let Ok(ip) = ip.parse::<IpAddr>() else {
+                continue;
+            };
+            let addr = SocketAddr::new(ip, 53);
+            match timeout(AXFR_TIMEOUT, transfer(addr, domain)).await {
+                Ok(Ok(names)) if !names.is_empty() => {
+                    return Some(ZoneTransfer {
+                        nameserver: ns,
+                        names,
+                    });
+                }
+                _ => continue,
+            }
+        }
+    }
+    None
+}
+
+/// Performs a single AXFR over TCP and collects the owner names.
+pub async fn transfer(addr: SocketAddr, domain: &str) -> Result<HashSet<String>> {
+    let mut query = Message::new();
+    query
+        .set_id(rand::random())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(false)
+        .add_query(Query::query(Name::from_str(domain)?, RecordType::AXFR));
+    let payload = query.to_vec()?;
+
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.write_u16(payload.len() as u16).await?;
+    stream.write_all(&payload).await?;
+
+    let suffix = format!(".{}", domain);
+    let mut names = HashSet::new();
+    let mut soa_seen = 0;
+
+    // The zone is framed by its SOA record at the start and the end
+    while soa_seen < 2 {
+        let len = match stream.read_u16().await {
+            Ok(len) => len as usize,
+            Err(_) => break,
+        };
+        let mut buf = vec![0u8; len];
+        stream.read_exact(&mut buf).await?;
+        let msg = Message::from_vec(&buf)?;
+        if msg.response_code() != ResponseCode::NoError {
+            return Err(format!("AXFR refused: {}", msg.response_code()).into());
+        }
+        if msg.answers().is_empty() {
+            break;
+        }
+
+        for record in msg.answers() {
+            if record.record_type() == RecordType::SOA {
+                soa_seen += 1;
+            }
+            let name = normalize_name(&record.name().to_ascii());
+            if !name.contains('*') && (name == domain || name.ends_with(&suffix)) {
+                names.insert(name);
+            }
+        }
+    }
+
+    Ok(names)
+}
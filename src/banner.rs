@@ -0,0 +1,155 @@
+//! Greetings of mail, file transfer and shell servers, which an HTTP probe
+//! never sees: SMTP, FTP, POP3 and IMAP servers and sshd all introduce
+//! themselves before the client says anything.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+
+/// A service that speaks first, and the name labels that suggest it.
+#[derive(Debug, Clone, Copy)]
+pub struct Service {
+    pub name: &'static str,
+    pub port: u16,
+    /// Labels or parts of labels, split and trimmed as in
+    /// [`crate::tags::Tag::tokens`].
+    pub tokens: &'static [&'static str],
+}
+
+pub const SERVICES: &[Service] = &[
+    Service {
+        name: "ftp",
+        port: 21,
+        tokens: &["ftp", "ftps", "files"],
+    },
+    Service {
+        name: "ssh",
+        port: 22,
+        tokens: &["ssh", "sftp", "bastion", "jump", "shell"],
+    },
+    Service {
+        name: "smtp",
+        port: 25,
+        tokens: &["smtp", "mail", "mx", "relay", "mta"],
+    },
+    Service {
+        name: "pop3",
+        port: 110,
+        tokens: &["pop", "mail"],
+    },
+    Service {
+        name: "imap",
+        port: 143,
+        tokens: &["imap", "mail"],
+    },
+    Service {
+        name: "submission",
+        port: 587,
+        tokens: &["smtp", "mail", "submission"],
+    },
+];
+
+/// Which hosts get their banners read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BannerScope {
+    Off,
+    /// Hosts whose name suggests one of [`SERVICES`], on its ports.
+    #[default]
+    Named,
+    /// Every host, on every port of [`SERVICES`] (`--banners`).
+    All,
+}
+
+/// Connections in flight.
+pub const CONCURRENCY: usize = 100;
+
+/// How long a server may take to greet after accepting.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Banners are cut to this many characters.
+pub const MAX_BANNER: usize = 256;
+
+/// The ports [`SERVICES`] suggest for `name`, below the apex `domain`.
+pub fn ports_for(name: &str, domain: &str) -> Vec<u16> {
+    let name = name.strip_suffix(domain).unwrap_or(name);
+    let tokens: Vec<&str> = name
+        .split(['.', '-', '_'])
+        .map(|t| t.trim_end_matches(|c: char| c.is_ascii_digit()))
+        .filter(|t| !t.is_empty())
+        .collect();
+    SERVICES
+        .iter()
+        .filter(|s| s.tokens.iter().any(|t| tokens.contains(t)))
+        .map(|s| s.port)
+        .collect()
+}
+
+/// The first line `addr` sends after the connection opens, if it sends one
+/// in time.
+pub async fn grab(addr: SocketAddr) -> Option<String> {
+    let mut stream = timeout(crate::portscan::CONNECT_TIMEOUT, TcpStream::connect(addr))
+        .await
+        .ok()?
+        .ok()?;
+    let mut buf = [0u8; 1024];
+    let n = timeout(READ_TIMEOUT, stream.read(&mut buf))
+        .await
+        .ok()?
+        .ok()?;
+    let text = String::from_utf8_lossy(&buf[..n]);
+    let line: String = text
+        .lines()
+        .next()?
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_BANNER)
+        .collect();
+    (!line.is_empty()).then_some(line)
+}
+
+/// Banners by port for each `(name, address, ports)` target. Names sharing
+/// an address and port share one connection; names without any banner are
+/// left out.
+pub async fn grab_all(
+    targets: Vec<(String, String, Vec<u16>)>,
+) -> HashMap<String, BTreeMap<u16, String>> {
+    let mut wanted: HashMap<SocketAddr, Vec<String>> = HashMap::new();
+    for (name, ip, ports) in targets {
+        let Ok(ip) = ip.parse::<IpAddr>() else {
+            continue;
+        };
+        for port in ports {
+            wanted
+                .entry(SocketAddr::new(ip, port))
+                .or_default()
+                .push(name.clone());
+        }
+    }
+
+    let permits = Arc::new(Semaphore::new(CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    for (addr, names) in wanted {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire().await.ok()?;
+            grab(addr).await.map(|banner| (addr.port(), banner, names))
+        });
+    }
+
+    let mut out: HashMap<String, BTreeMap<u16, String>> = HashMap::new();
+    while let Some(res) = tasks.join_next().await {
+        if let Ok(Some((port, banner, names))) = res {
+            for name in names {
+                out.entry(name).or_default().insert(port, banner.clone());
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,191 @@
+use reqwest::header::HeaderMap;
+use std::net::IpAddr;
+
+/// How to tell a host sits behind a CDN or WAF edge.
+#[derive(Debug, Clone, Copy)]
+pub struct Cdn {
+    pub name: &'static str,
+    /// Published address ranges, in CIDR notation.
+    pub ranges: &'static [&'static str],
+    /// CNAME target suffixes.
+    pub cnames: &'static [&'static str],
+    /// `(header, substring)`, case-insensitive. An empty substring only
+    /// requires the header to be present.
+    pub headers: &'static [(&'static str, &'static str)],
+}
+
+pub const CDNS: &[Cdn] = &[
+    Cdn {
+        name: "Cloudflare",
+        ranges: &[
+            "173.245.48.0/20",
+            "103.21.244.0/22",
+            "103.22.200.0/22",
+            "103.31.4.0/22",
+            "141.101.64.0/18",
+            "108.162.192.0/18",
+            "190.93.240.0/20",
+            "188.114.96.0/20",
+            "197.234.240.0/22",
+            "198.41.128.0/17",
+            "162.158.0.0/15",
+            "104.16.0.0/13",
+            "104.24.0.0/14",
+            "172.64.0.0/13",
+            "131.0.72.0/22",
+            "2400:cb00::/32",
+            "2606:4700::/32",
+            "2803:f800::/32",
+            "2405:b500::/32",
+            "2405:8100::/32",
+            "2a06:98c0::/29",
+            "2c0f:f248::/32",
+        ],
+        cnames: &[".cdn.cloudflare.net"],
+        headers: &[("server", "cloudflare"), ("cf-ray", "")],
+    },
+    Cdn {
+        name: "Fastly",
+        ranges: &[
+            "23.235.32.0/20",
+            "43.249.72.0/22",
+            "103.244.50.0/24",
+            "103.245.222.0/23",
+            "103.245.224.0/24",
+            "104.156.80.0/20",
+            "140.248.64.0/18",
+            "140.248.128.0/17",
+            "146.75.0.0/17",
+            "151.101.0.0/16",
+            "157.52.64.0/18",
+            "167.82.0.0/17",
+            "167.82.128.0/20",
+            "167.82.160.0/20",
+            "167.82.224.0/20",
+            "172.111.64.0/18",
+            "185.31.16.0/22",
+            "199.27.72.0/21",
+            "199.232.0.0/16",
+            "2a04:4e40::/32",
+            "2a04:4e42::/32",
+        ],
+        cnames: &[".fastly.net", ".fastlylb.net"],
+        headers: &[("x-fastly-request-id", ""), ("x-served-by", "cache-")],
+    },
+    Cdn {
+        name: "Akamai",
+        ranges: &[],
+        cnames: &[
+            ".akamai.net",
+            ".akamaiedge.net",
+            ".akamaihd.net",
+            ".akamaized.net",
+            ".edgekey.net",
+            ".edgesuite.net",
+        ],
+        headers: &[("server", "akamaighost"), ("x-akamai-transformed", "")],
+    },
+    Cdn {
+        name: "Amazon CloudFront",
+        ranges: &[],
+        cnames: &[".cloudfront.net"],
+        headers: &[("x-amz-cf-id", ""), ("via", "cloudfront")],
+    },
+    Cdn {
+        name: "Azure Front Door",
+        ranges: &[],
+        cnames: &[".azurefd.net", ".azureedge.net", ".trafficmanager.net"],
+        headers: &[("x-azure-ref", "")],
+    },
+    Cdn {
+        name: "Google Cloud CDN",
+        ranges: &[],
+        cnames: &[],
+        headers: &[("via", "1.1 google")],
+    },
+    Cdn {
+        name: "Imperva",
+        ranges: &[],
+        cnames: &[".incapdns.net", ".impervadns.net"],
+        headers: &[("x-iinfo", ""), ("x-cdn", "imperva")],
+    },
+    Cdn {
+        name: "Sucuri",
+        ranges: &["192.88.134.0/23", "185.93.228.0/22", "66.248.200.0/22"],
+        cnames: &[".sucuri.net"],
+        headers: &[("x-sucuri-id", ""), ("server", "sucuri")],
+    },
+    Cdn {
+        name: "Edgio",
+        ranges: &[],
+        cnames: &[".edgecastcdn.net", ".systemcdn.net", ".edgio.net"],
+        headers: &[("server", "ecacc"), ("server", "ecs (")],
+    },
+    Cdn {
+        name: "StackPath",
+        ranges: &[],
+        cnames: &[".stackpathdns.com", ".stackpathcdn.com"],
+        headers: &[("x-hw", "")],
+    },
+    Cdn {
+        name: "Bunny CDN",
+        ranges: &[],
+        cnames: &[".b-cdn.net"],
+        headers: &[("server", "bunnycdn")],
+    },
+    Cdn {
+        name: "KeyCDN",
+        ranges: &[],
+        cnames: &[".kxcdn.com"],
+        headers: &[("server", "keycdn")],
+    },
+];
+
+/// The first CDN whose ranges hold one of `ips`, whose domains appear in
+/// `cname_chain`, or whose headers the response carries.
+pub fn detect(
+    ips: &[String],
+    cname_chain: &[String],
+    headers: Option<&HeaderMap>,
+) -> Option<String> {
+    let addrs: Vec<IpAddr> = ips.iter().filter_map(|ip| ip.parse().ok()).collect();
+    CDNS.iter()
+        .find(|cdn| {
+            cdn.ranges
+                .iter()
+                .any(|range| addrs.iter().any(|ip| in_range(*ip, range)))
+                || cdn.cnames.iter().any(|suffix| {
+                    cname_chain
+                        .iter()
+                        .any(|c| c.trim_end_matches('.').ends_with(suffix))
+                })
+                || headers.is_some_and(|h| {
+                    cdn.headers.iter().any(|(name, needle)| {
+                        h.get(*name)
+                            .and_then(|v| v.to_str().ok())
+                            .is_some_and(|v| v.to_ascii_lowercase().contains(needle))
+                    })
+                })
+        })
+        .map(|cdn| cdn.name.to_string())
+}
+
+pub(crate) fn in_range(ip: IpAddr, cidr: &str) -> bool {
+    let Some((net, len)) = cidr.split_once('/') else {
+        return false;
+    };
+    let (Ok(net), Ok(len)) = (net.parse::<IpAddr>(), len.parse::<u32>()) else {
+        return false;
+    };
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - len).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - len).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,253 @@
+//! Just enough X.509 to report who a TLS certificate was issued to.
+//!
+//! Only the DER structure of `tbsCertificate` is walked; nothing is
+//! verified here.
+
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Metadata of the certificate a host presented.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CertificateInfo {
+    /// Subject common name, or organisation when there is none.
+    pub subject: String,
+    pub issuer: String,
+    /// Subject organisation, which only OV and EV certificates carry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    /// DNS names from the subjectAltName extension.
+    pub sans: Vec<String>,
+    /// RFC 3339 UTC timestamps.
+    pub not_before: String,
+    pub not_after: String,
+    pub expired: bool,
+    /// The probed hostname isn't covered by the subject or any SAN.
+    pub name_mismatch: bool,
+}
+
+impl CertificateInfo {
+    /// Parses a DER certificate as presented by `host`.
+    pub fn from_der(der: &[u8], host: &str) -> Option<Self> {
+        let parsed = parse(der)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let name_mismatch = if parsed.sans.is_empty() {
+            !name_matches(parsed.subject_cn.as_deref().unwrap_or(""), host)
+        } else {
+            !parsed.sans.iter().any(|n| name_matches(n, host))
+        };
+        Some(Self {
+            organization: parsed.subject_o.clone(),
+            subject: parsed.subject_cn.or(parsed.subject_o).unwrap_or_default(),
+            issuer: parsed.issuer_cn.or(parsed.issuer_o).unwrap_or_default(),
+            sans: parsed.sans,
+            not_before: rfc3339(parsed.not_before),
+            not_after: rfc3339(parsed.not_after),
+            expired: now > parsed.not_after || now < parsed.not_before,
+            name_mismatch,
+        })
+    }
+}
+
+/// `*.example.com` covers exactly one extra label.
+pub fn name_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(base) => host
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest == base),
+        None => pattern == host,
+    }
+}
+
+struct Parsed {
+    subject_cn: Option<String>,
+    subject_o: Option<String>,
+    issuer_cn: Option<String>,
+    issuer_o: Option<String>,
+    sans: Vec<String>,
+    not_before: i64,
+    not_after: i64,
+}
+
+const SEQUENCE: u8 = 0x30;
+const OID: u8 = 0x06;
+const OCTET_STRING: u8 = 0x04;
+const CN: &[u8] = &[0x55, 0x04, 0x03];
+const ORGANIZATION: &[u8] = &[0x55, 0x04, 0x0a];
+const SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+/// GeneralName dNSName, `[2] IMPLICIT IA5String`.
+const DNS_NAME: u8 = 0x82;
+
+fn parse(der: &[u8]) -> Option<Parsed> {
+    let (_, cert, _) = read(der)?;
+    let (_, tbs, _) = read(cert)?;
+    let mut fields = Reader(tbs);
+
+    let mut next = fields.next()?;
+    if next.0 == 0xa0 {
+        // explicit version
+        next = fields.next()?;
+    }
+    let _serial = next;
+    let _signature = fields.next()?;
+    let (_, issuer) = fields.next()?;
+    let (_, validity) = fields.next()?;
+    let (_, subject) = fields.next()?;
+    let _spki = fields.next()?;
+
+    let mut sans = Vec::new();
+    for (tag, body) in fields.by_ref() {
+        // [3] EXPLICIT Extensions
+        if tag == 0xa3 {
+            sans = subject_alt_names(body).unwrap_or_default();
+        }
+    }
+
+    let mut times = Reader(validity);
+    let not_before = time(times.next()?)?;
+    let not_after = time(times.next()?)?;
+
+    Some(Parsed {
+        subject_cn: attribute(subject, CN),
+        subject_o: attribute(subject, ORGANIZATION),
+        issuer_cn: attribute(issuer, CN),
+        issuer_o: attribute(issuer, ORGANIZATION),
+        sans,
+        not_before,
+        not_after,
+    })
+}
+
+/// The first value of attribute `oid` in a Name (SEQUENCE OF SET OF
+/// SEQUENCE { type, value }).
+fn attribute(name: &[u8], oid: &[u8]) -> Option<String> {
+    for (_, set) in Reader(name) {
+        for (_, pair) in Reader(set) {
+            let mut parts = Reader(pair);
+            let (tag, kind) = parts.next()?;
+            if tag == OID && kind == oid {
+                let (_, value) = parts.next()?;
+                return Some(String::from_utf8_lossy(value).into_owned());
+            }
+        }
+    }
+    None
+}
+
+fn subject_alt_names(extensions: &[u8]) -> Option<Vec<String>> {
+    let (_, list, _) = read(extensions)?;
+    for (tag, ext) in Reader(list) {
+        if tag != SEQUENCE {
+            continue;
+        }
+        let mut parts = Reader(ext);
+        let (_, oid) = parts.next()?;
+        if oid != SUBJECT_ALT_NAME {
+            continue;
+        }
+        // `critical` is an optional BOOLEAN before the value
+        let value = parts.find(|(t, _)| *t == OCTET_STRING)?.1;
+        let (_, names, _) = read(value)?;
+        return Some(
+            Reader(names)
+                .filter(|(t, _)| *t == DNS_NAME)
+                .map(|(_, n)| String::from_utf8_lossy(n).to_lowercase())
+                .collect(),
+        );
+    }
+    None
+}
+
+/// Iterates the TLVs of a constructed value's contents.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (tag, body, rest) = read(self.0)?;
+        self.0 = rest;
+        Some((tag, body))
+    }
+}
+
+/// Splits one TLV off the front of `data`: (tag, contents, remainder).
+fn read(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)? as usize;
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let n = first & 0x7f;
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let len = data
+            .get(2..2 + n)?
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (len, 2 + n)
+    };
+    let body = data.get(header..header + len)?;
+    Some((tag, body, &data[header + len..]))
+}
+
+/// UTCTime (`YYMMDDHHMMSSZ`) or GeneralizedTime (`YYYYMMDDHHMMSSZ`) as
+/// Unix seconds.
+fn time((tag, body): (u8, &[u8])) -> Option<i64> {
+    let s = std::str::from_utf8(body).ok()?;
+    let digits = s.trim_end_matches('Z');
+    let (year, rest) = match tag {
+        0x17 => {
+            let yy: i64 = digits.get(..2)?.parse().ok()?;
+            (
+                if yy >= 50 { 1900 + yy } else { 2000 + yy },
+                digits.get(2..)?,
+            )
+        }
+        0x18 => (digits.get(..4)?.parse().ok()?, digits.get(4..)?),
+        _ => return None,
+    };
+    let field = |i: usize| -> Option<i64> { rest.get(i..i + 2)?.parse().ok() };
+    let (month, day, hour, min) = (field(0)?, field(2)?, field(4)?, field(6)?);
+    let sec = field(8).unwrap_or(0);
+    Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + min * 60 + sec)
+}
+
+/// Days since 1970-01-01 (Howard Hinnant's algorithm).
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+pub(crate) fn rfc3339(ts: i64) -> String {
+    let days = ts.div_euclid(86400);
+    let secs = ts.rem_euclid(86400);
+    // Inverse of days_from_civil
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        y,
+        m,
+        d,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,109 @@
+//! Scan progress saved to disk for `--resume`, so an interrupted or
+//! crashed scan picks up where it stopped instead of querying the passive
+//! sources and resolving everything again.
+
+use crate::dns::ResolvedHost;
+use crate::{Result, SubdomainResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Probe progress is written at most this often; phase ends always are.
+const SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Where one apex got to.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DomainCheckpoint {
+    /// Names that resolved in discovery, brute force, permutations and
+    /// recursion. `None` until those phases have finished.
+    #[serde(default)]
+    pub resolved: Option<Vec<ResolvedHost>>,
+    /// Names already sent to a resolver by those phases.
+    #[serde(default)]
+    pub queried: Vec<String>,
+    #[serde(default)]
+    pub zone_leaks: HashMap<String, String>,
+    #[serde(default)]
+    pub found_by: HashMap<String, BTreeSet<String>>,
+    /// Hosts whose probe has finished, kept or filtered.
+    #[serde(default)]
+    pub probed: HashSet<String>,
+    /// In-scope names the finished probes pointed at.
+    #[serde(default)]
+    pub leads: HashSet<String>,
+    /// Results that passed the filters so far; after `done`, the final ones.
+    #[serde(default)]
+    pub results: Vec<SubdomainResult>,
+    #[serde(default)]
+    pub done: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CheckpointFile {
+    domains: BTreeMap<String, DomainCheckpoint>,
+}
+
+/// A state file shared by every domain of a scan.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    file: Mutex<CheckpointFile>,
+    last_save: Mutex<Instant>,
+}
+
+impl Checkpoint {
+    /// Loads `path`, or starts empty if it doesn't exist yet.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => CheckpointFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+            last_save: Mutex::new(Instant::now()),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn domain(&self, domain: &str) -> Option<DomainCheckpoint> {
+        self.lock().domains.get(domain).cloned()
+    }
+
+    /// Changes `domain`'s entry and saves, at most every couple of seconds
+    /// unless `force` is set.
+    pub fn update(&self, domain: &str, force: bool, f: impl FnOnce(&mut DomainCheckpoint)) {
+        f(self.lock().domains.entry(domain.to_string()).or_default());
+        {
+            let mut last = self.last_save.lock().unwrap_or_else(|e| e.into_inner());
+            if !force && last.elapsed() < SAVE_INTERVAL {
+                return;
+            }
+            *last = Instant::now();
+        }
+        if let Err(e) = self.save() {
+            warn!("Could not write checkpoint {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// Writes the state file, replacing the old one only once the new one
+    /// is complete.
+    pub fn save(&self) -> Result<()> {
+        let json = serde_json::to_vec(&*self.lock())?;
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CheckpointFile> {
+        self.file.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
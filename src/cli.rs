@@ -0,0 +1,1079 @@
+use reqwest::header::{HeaderName, HeaderValue, COOKIE};
+use reqwest::Url;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use subpeek_core::banner::BannerScope;
+use subpeek_core::cluster::ClusterBy;
+use subpeek_core::deadline::Phase;
+use subpeek_core::dns::{DnsEngine, IpVersion, Upstream};
+use subpeek_core::logging::Verbosity;
+use subpeek_core::matchers::{self, BodyRule};
+use subpeek_core::notify::WebhookFormat;
+use subpeek_core::probe::ProbeMethod;
+use subpeek_core::profile::Profile;
+use subpeek_core::sources::cache::SourceCache;
+use subpeek_core::{
+    dns, filter, input, interop, portscan, proxy, ratelimit, rawdns, records, server, sources,
+    Config, DataSource, OutputFormat, ScanOptions,
+};
+
+pub const USAGE: &str = "Usage: subpeek_core [options] <domain | ->
+       subpeek_core [options] -l <domains.txt>
+       subpeek_core diff <old.json> <new.json> [--fail-on-new]
+                         [--notify-webhook <url>]
+       subpeek_core monitor --domains <file> [--interval 6h] [--state-dir <dir>]
+                            [--metrics-listen <addr>] [options]
+       subpeek_core merge <a.json> <b.json>... [-o <merged.json>]
+                          [--output-format <fmt>]
+       subpeek_core tui <results.json>
+       subpeek_core serve [--listen 127.0.0.1:8080] [--queue 16] [options]
+
+Options:
+  -l, --list <file>    Enumerate every domain in a file (- for stdin)
+  --config <file>      Config file (default ~/.config/subpeek/config.toml)
+  --profile <p>        passive (sources only: nothing sent to the target),
+                       stealth, normal (default) or aggressive
+  --dry-run            Print the sources, resolvers, starting names and
+                       request estimates of the scan without sending anything
+  --output-format <fmt>
+                       json (default), jsonl, csv, hosts or amass
+  --group-by-ip        Print clusters of hosts sharing addresses and content
+  --cluster-by <key>   Cluster by ip (as --group-by-ip) or body_hash: hosts
+                       serving the very same page, wherever they are
+  --envelope           Wrap json output with the schema version, scan times,
+                       options and per-phase counts
+  --report <file>      Write a self-contained HTML report
+  --report-md <file>   Write a Markdown report
+  --export-nmap <file> Write every unique address for nmap/masscan -iL
+  --export-nmap-xml <file>
+                       Write the hosts as nmap XML, with any open ports
+  --export-nmap-dir <dir>
+                       Write one -iL address list per cloud provider or CDN
+  --compare <file>     Report what changed since an earlier json/jsonl output
+  --fail-on-new        Exit with 3 if --compare or diff finds new hosts
+  --notify-webhook <url>
+                       POST new subdomains and takeovers found by
+                       --compare, diff or monitor to a webhook
+  --notify-format <fmt>
+                       json (default), slack or discord
+  --db <file>          Record the scan in a SQLite database (needs sqlite3)
+  -v, -vv              More detail on stderr; -vv includes dependencies
+  -q, --quiet          Only print warnings and errors on stderr
+  --log-json           Log JSON objects on stderr instead of [*] lines
+  --resume <file>      Save progress to this file, and continue from it if it
+                       exists
+  --stream             Print each result as soon as it is probed
+                       (any format but json)
+  -o, --output <file>  Write results to a file, replaced only once complete
+  --append             Add jsonl results to the end of the -o file instead
+  --sources <a,b,...>  Only query these passive sources
+  --exclude-sources <a,b,...>
+                       Skip these passive sources
+  --input <file>       Also verify and probe these names (- for stdin); a list
+                       or amass/subfinder JSON lines
+  --known <file>       Inventory names: probed like --input, but marked
+                       source seed and never reported as new
+  --include-regex <re> Only probe names matching this regex (repeatable)
+  --exclude-pattern <glob>
+                       Never probe names matching this, e.g. '*.cdn.example.com'
+  --out-of-scope <file>
+                       Never probe hosts in a bug-bounty out-of-scope list
+  --no-discover        Skip discovery; only verify and probe --input names
+  --no-feedback        Don't probe names found in certificates and pages
+  --cache-ttl <d>      Reuse cached passive source answers this long (default 24h)
+  --no-cache           Always query passive sources
+  --plugin-dir <dir>   Run every executable in this directory as a source
+  --crtsh-postgres     Fall back to crt.sh's PostgreSQL database
+  --wordlist <file>    Brute force subdomains from a wordlist
+  --wordlist-builtin <small|medium|large>
+                       Brute force with a built-in wordlist
+  --permute            Resolve mutations of discovered names
+  --permute-wordlist <file>
+                       Mutation words for --permute
+  --markov <n>         Resolve n names following the scheme of resolved ones
+  --recursive          Re-enumerate intermediate labels as new apexes
+  --depth <n>          Recursion depth for --recursive (default 1)
+  --axfr               Try a zone transfer against each nameserver first
+  --nsec-walk          List the names of NSEC-signed zones from their chain
+  --dangling           Report candidates whose CNAME points at a missing name
+  --vhosts             Try unresolved names as virtual hosts on found addresses
+  --records <ns,mx,txt>
+                       Also gather these records for the apex and every host
+  --scan-ports <top100|a,b,c-d>
+                       TCP connect-scan these ports on every address
+  --banners            Read SMTP/FTP/POP3/IMAP/SSH banners of every host, not
+                       only of hosts named like mail, ftp or ssh
+  --no-banners         Read no service banners
+  --ptr                Record reverse DNS names of each host's addresses
+  --dnssec             Record whether each host validates with DNSSEC
+  --asn                Tag results with the ASN and AS name of their addresses
+  --rdap               Record the apex's registrar and each address's netblock
+  --reverse-ip         Find other hostnames on each non-CDN address
+  --find-related       Suggest other domains of the same organisation
+  --related-output <file>
+                       Write them one per line, for a later -l scan
+  --geoip-db <file>    Tag results with country/city from a GeoLite2 .mmdb
+  --shodan-ports       Add Shodan-known open ports to each result
+  --screenshots <dir>  Save a headless Chrome screenshot of every live host
+  --dns-concurrency <n>
+                       DNS lookups in flight to start with (default 200,
+                       raw 5000)
+  --dns-cache <file>   Keep DNS answers and NXDOMAINs in a file between runs
+  --dns-cache-ttl <d>  Reuse DNS answers this long (default 1h; 0 disables)
+  --dns-engine <e>     resolver (default) or raw: bare UDP queries for huge
+                       wordlists
+  --ip-version <4|6|any>
+                       Only keep addresses of one family and probe over it
+  --ipv6               Same as --ip-version 6
+  --rate <n/s>         Cap DNS lookups plus HTTP requests (e.g. 50/s, 600/m)
+  --per-host-rate <n/s>
+                       Cap HTTP requests to any one host
+  --max-requests <n>   Stop probing after n HTTP requests, keeping what was
+                       found
+  --max-bandwidth <size>
+                       Stop probing after this much response data (e.g. 100MB)
+  --max-runtime <d>    End the run after this long (e.g. 20m), keeping what
+                       was found
+  --phase-deadline <phase=d,...>
+                       Time box discovery, dns, probe, vhosts or enrich
+  --retries <n>        Retry failed lookups and requests (default 0)
+  --retry-backoff <d>  Wait before the first retry, doubling after (default 500ms)
+  --wildcard-threshold <0-1>
+                       Body similarity that marks a host as a wildcard (default 0.85)
+  --keep-filtered      Output wildcard matches with the reason instead of
+                       dropping them
+  --unsorted           Output hosts in the order they finish instead of by name
+  --match-code <a,b>   Only output hosts answering with these status codes
+  --filter-code <a,b>  Leave out hosts answering with these status codes
+  --match-title-regex <re>
+                       Only output hosts whose title matches
+  --match-server-regex <re>
+                       Only output hosts whose Server header matches
+  --ports <a,b,...>    Probe these ports instead of 443/80
+  --both-schemes       Record https and http separately for every host
+  --head-first         Probe with HEAD, falling back to GET where it's rejected
+  --method <method>    Probe with this method instead of GET
+  --no-follow-redirects
+                       Report 30x responses and their Location as-is
+  --slow-threshold <ms>
+                       Mark hosts slower than this as slow
+  --capture-headers <all|a,b,...>
+                       Keep these response headers in a headers map
+  --max-body <bytes>   Read at most this much of each body (default 524288)
+  --extract <regex>    Record named groups matched in bodies (repeatable)
+  --match-body <regex> Tag hosts whose body matches (repeatable)
+  --match-body-file <file>
+                       Tag/regex rules for --match-body, as YAML
+  --header <name: value>
+                       Send this header with every probe (repeatable)
+  --cookie <a=b; c=d>  Send these cookies with every probe (repeatable)
+  --user-agent <ua>    User agent for probes (default: none)
+  --proxy <url>        Route HTTP traffic through an http://, https://,
+                       socks5:// or socks5h:// proxy
+  --favicon            Record the mmh3 hash of each host's favicon
+  --robots             Read robots.txt and sitemap.xml for paths and names
+  --jarm               Record the JARM TLS fingerprint of HTTPS hosts
+  --paths <a,b,...>    Request these paths on every host and record the ones
+                       that aren't 404, e.g. /admin,/.git/HEAD
+  --http-concurrency <n>
+                       Hosts probed at once to start with (default 50)
+  --fixed-concurrency  Keep both limits as set instead of adapting them
+  --resolver <ip[:port]>
+                       Use this DNS server (repeatable)
+  --resolvers <file>   Load DNS servers from a file, one per line
+  --no-resolver-check  Use every resolver without checking its answers
+  --doh <url>          Use a DNS-over-HTTPS server (repeatable)
+  --dot <host[:port][#name]>
+                       Use a DNS-over-TLS server (repeatable)
+
+Exit status: 0 after a scan, 1 on bad arguments or unreadable files,
+2 when every passive source failed for a domain, 3 with --fail-on-new
+when there are new hosts, 130 when interrupted.";
+
+pub struct Cli {
+    /// Apex domains to enumerate, in input order.
+    pub domains: Vec<String>,
+    pub options: ScanOptions,
+    pub format: OutputFormat,
+    pub stream: bool,
+    /// `-o`: write results here instead of stdout.
+    pub output: Option<PathBuf>,
+    /// Add to `output` rather than replace it.
+    pub append: bool,
+    pub verbosity: Verbosity,
+    /// Log JSON objects instead of `[*]` lines.
+    pub log_json: bool,
+    /// Print `--group-by-ip` clusters instead of results.
+    pub group_by_ip: bool,
+    /// What those clusters share.
+    pub cluster_by: ClusterBy,
+    /// Wrap `json` output in a versioned envelope with scan metadata.
+    pub envelope: bool,
+    pub db: Option<PathBuf>,
+    pub report: Option<PathBuf>,
+    pub report_md: Option<PathBuf>,
+    pub export_nmap: Option<PathBuf>,
+    pub export_nmap_xml: Option<PathBuf>,
+    pub export_nmap_dir: Option<PathBuf>,
+    /// Where to list `--find-related` domains.
+    pub related_output: Option<PathBuf>,
+    pub compare: Option<PathBuf>,
+    /// Print the [`subpeek_core::plan::Plan`] instead of scanning.
+    pub dry_run: bool,
+    /// Exit with status 3 when `compare` finds new hosts.
+    pub fail_on_new: bool,
+    pub notify: Option<Webhook>,
+}
+
+pub struct Webhook {
+    pub url: String,
+    pub format: WebhookFormat,
+    pub proxy: Option<Url>,
+}
+
+/// Arguments of `diff`.
+pub struct DiffArgs {
+    pub old: PathBuf,
+    pub new: PathBuf,
+    pub fail_on_new: bool,
+    pub notify: Option<Webhook>,
+}
+
+/// Arguments of `merge`.
+pub struct MergeArgs {
+    pub files: Vec<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub format: OutputFormat,
+}
+
+/// Arguments of `monitor`. Scan options are shared with a normal run.
+pub struct MonitorArgs {
+    pub domains: PathBuf,
+    pub interval: Duration,
+    pub state_dir: Option<PathBuf>,
+    /// Where to serve Prometheus `/metrics`, if anywhere.
+    pub metrics_listen: Option<SocketAddr>,
+    pub scan: Cli,
+}
+
+/// Arguments of `serve`. Scan options apply to every submitted scan.
+pub struct ServeArgs {
+    pub listen: SocketAddr,
+    pub queue: usize,
+    pub scan: Cli,
+}
+
+pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Cli, String> {
+    let cli = parse_scan(args.collect())?;
+    if cli.domains.is_empty() {
+        return Err(String::new());
+    }
+    Ok(cli)
+}
+
+pub fn parse_monitor<I: Iterator<Item = String>>(mut args: I) -> Result<MonitorArgs, String> {
+    let mut domains = None;
+    let mut interval = Duration::from_secs(6 * 3600);
+    let mut state_dir = None;
+    let mut metrics_listen = None;
+    let mut rest = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--domains" => domains = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--interval" => interval = duration(&value(&mut args, &arg)?, &arg)?,
+            "--state-dir" => state_dir = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--metrics-listen" => {
+                let raw = value(&mut args, &arg)?;
+                metrics_listen = Some(
+                    raw.parse()
+                        .map_err(|_| format!("Invalid --metrics-listen address: {}", raw))?,
+                );
+            }
+            _ => rest.push(arg),
+        }
+    }
+
+    let mut scan = parse_scan(rest)?;
+    if !scan.domains.is_empty() {
+        return Err("monitor reads its targets from --domains".to_string());
+    }
+    // Every pass has to see what the sources say now
+    scan.options.cache_dir = None;
+    scan.options.dns_cache_ttl = scan.options.dns_cache_ttl.min(interval / 2);
+    if scan.options.resume.is_some() {
+        return Err("monitor keeps its own state; --resume is for one-off scans".to_string());
+    }
+    Ok(MonitorArgs {
+        domains: domains.ok_or("monitor needs --domains <file>")?,
+        interval,
+        state_dir,
+        metrics_listen,
+        scan,
+    })
+}
+
+pub fn parse_serve<I: Iterator<Item = String>>(mut args: I) -> Result<ServeArgs, String> {
+    let mut listen = server::DEFAULT_LISTEN.to_string();
+    let mut queue = server::DEFAULT_QUEUE_SIZE;
+    let mut rest = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => listen = value(&mut args, &arg)?,
+            "--queue" => queue = number(&value(&mut args, &arg)?, &arg)?,
+            _ => rest.push(arg),
+        }
+    }
+
+    let scan = parse_scan(rest)?;
+    if !scan.domains.is_empty() {
+        return Err("serve takes its targets from POST /scans".to_string());
+    }
+    if scan.options.resume.is_some() {
+        return Err("--resume is for one-off scans, not serve".to_string());
+    }
+    Ok(ServeArgs {
+        listen: listen
+            .parse()
+            .map_err(|_| format!("Invalid --listen address: {}", listen))?,
+        queue,
+        scan,
+    })
+}
+
+/// Everything but the subcommand. `domains` is empty when none were given.
+fn parse_scan(args: Vec<String>) -> Result<Cli, String> {
+    // The config provides the defaults every other flag overrides
+    let config_path = args
+        .iter()
+        .position(|a| a == "--config")
+        .map(|i| {
+            args.get(i + 1)
+                .map(PathBuf::from)
+                .ok_or("Missing value for --config")
+        })
+        .transpose()?;
+    let config = Config::load(config_path.as_deref())
+        .map_err(|e| format!("Could not load config: {}", e))?;
+    let mut options = config
+        .to_options()
+        .map_err(|e| format!("Invalid config: {}", e))?;
+    // A profile overrides the config, and flags override both
+    if let Some(i) = args.iter().position(|a| a == "--profile") {
+        let profile: Profile = args
+            .get(i + 1)
+            .ok_or("Missing value for --profile")?
+            .parse()?;
+        profile.apply(&mut options);
+    }
+
+    let mut args = args.into_iter();
+    let mut domain = None;
+    let mut list = None;
+    let mut resolvers = Vec::new();
+    let mut dns_concurrency_set = false;
+    let mut recursive = false;
+    let mut depth = None;
+    let mut format = OutputFormat::default();
+    let mut stream = false;
+    let mut output = None;
+    let mut append = false;
+    let mut group_by_ip = false;
+    let mut cluster_by = ClusterBy::default();
+    let mut envelope = false;
+    let mut no_cache = false;
+    let mut verbosity = Verbosity::Normal;
+    let mut log_json = false;
+    let mut db = None;
+    let mut report = None;
+    let mut report_md = None;
+    let mut export_nmap = None;
+    let mut export_nmap_xml = None;
+    let mut export_nmap_dir = None;
+    let mut related_output = None;
+    let mut compare = None;
+    let mut fail_on_new = false;
+    let mut dry_run = false;
+    let mut include_sources = None;
+    let mut exclude_sources = None;
+    let mut notify_url = None;
+    let mut notify_format = WebhookFormat::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "-l" | "--list" => list = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--output-format" => format = value(&mut args, &arg)?.parse()?,
+            "--stream" => stream = true,
+            "-o" | "--output" => output = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--append" => append = true,
+            "--resume" => options.resume = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "-v" | "--verbose" => verbosity = verbosity.max(Verbosity::Normal).louder(),
+            "-vv" => verbosity = Verbosity::Trace,
+            "-q" | "--quiet" => verbosity = Verbosity::Quiet,
+            "--log-json" => log_json = true,
+            "--group-by-ip" => group_by_ip = true,
+            "--cluster-by" => {
+                cluster_by = value(&mut args, &arg)?.parse()?;
+                group_by_ip = true;
+            }
+            "--envelope" => envelope = true,
+            "--report" => report = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--report-md" => report_md = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--export-nmap" => export_nmap = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--export-nmap-xml" => export_nmap_xml = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--export-nmap-dir" => export_nmap_dir = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--compare" => compare = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--fail-on-new" => fail_on_new = true,
+            "--notify-webhook" => notify_url = Some(value(&mut args, &arg)?),
+            "--notify-format" => notify_format = value(&mut args, &arg)?.parse()?,
+            "--db" => db = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--sources" => include_sources = Some(value(&mut args, &arg)?),
+            "--exclude-sources" => exclude_sources = Some(value(&mut args, &arg)?),
+            "--plugin-dir" => options.plugin_dir = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--input" => {
+                let path = PathBuf::from(value(&mut args, &arg)?);
+                let unreadable = |e| format!("Could not read {}: {}", path.display(), e);
+                let text = input::read(&path).map_err(unreadable)?;
+                if interop::is_json_lines(&text) {
+                    for r in interop::import(&text).map_err(unreadable)? {
+                        options
+                            .candidate_sources
+                            .insert(r.subdomain.clone(), r.sources);
+                        options.candidates.push(r.subdomain);
+                    }
+                } else {
+                    options.candidates.extend(input::parse_lines(&text));
+                }
+            }
+            "--known" => {
+                let path = PathBuf::from(value(&mut args, &arg)?);
+                options.known = input::load_list(&path)
+                    .map_err(|e| format!("Could not read {}: {}", path.display(), e))?
+                    .into_iter()
+                    .collect();
+            }
+            "--include-regex" => options
+                .scope
+                .include_regex(&value(&mut args, &arg)?)
+                .map_err(|e| format!("--include-regex: invalid regex: {}", e))?,
+            "--exclude-pattern" => options
+                .scope
+                .exclude_pattern(&value(&mut args, &arg)?)
+                .map_err(|e| format!("--exclude-pattern: {}", e))?,
+            "--out-of-scope" => {
+                let path = PathBuf::from(value(&mut args, &arg)?);
+                options
+                    .scope
+                    .load_out_of_scope(&path)
+                    .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+            }
+            "--no-discover" => options.discover = false,
+            "--no-feedback" => options.feedback = false,
+            "--no-resolver-check" => options.check_resolvers = false,
+            "--fixed-concurrency" => options.adaptive_concurrency = false,
+            "--cache-ttl" => options.cache_ttl = duration(&value(&mut args, &arg)?, &arg)?,
+            "--no-cache" => no_cache = true,
+            "--crtsh-postgres" => options.crtsh_postgres = true,
+            "--wordlist" => options.wordlist = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--wordlist-builtin" => {
+                options.wordlist_builtin = Some(value(&mut args, &arg)?.parse()?)
+            }
+            "--permute" => options.permute = true,
+            "--permute-wordlist" => {
+                options.permute = true;
+                options.permute_wordlist = Some(PathBuf::from(value(&mut args, &arg)?));
+            }
+            "--markov" => options.markov = number(&value(&mut args, &arg)?, &arg)?,
+            "--recursive" => recursive = true,
+            "--depth" => depth = Some(number(&value(&mut args, &arg)?, &arg)?),
+            "--axfr" => options.axfr = true,
+            "--nsec-walk" => options.nsec_walk = true,
+            "--dangling" => options.dangling = true,
+            "--vhosts" => options.vhosts = true,
+            "--records" => options.records = records::parse_kinds(&value(&mut args, &arg)?)?,
+            "--scan-ports" => options.scan_ports = portscan::parse_ports(&value(&mut args, &arg)?)?,
+            "--banners" => options.banners = BannerScope::All,
+            "--no-banners" => options.banners = BannerScope::Off,
+            "--ptr" => options.ptr = true,
+            "--dnssec" => options.dnssec = true,
+            "--asn" => options.asn = true,
+            "--rdap" => options.rdap = true,
+            "--reverse-ip" => options.reverse_ip = true,
+            "--find-related" => options.find_related = true,
+            "--related-output" => {
+                options.find_related = true;
+                related_output = Some(PathBuf::from(value(&mut args, &arg)?));
+            }
+            "--geoip-db" => options.geoip_db = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--shodan-ports" => options.shodan_ports = true,
+            "--screenshots" => {
+                if !cfg!(feature = "screenshots") {
+                    return Err(
+                        "--screenshots needs a build with `--features screenshots`".to_string()
+                    );
+                }
+                options.screenshots = Some(PathBuf::from(value(&mut args, &arg)?));
+            }
+            "--config" | "--profile" => {
+                value(&mut args, &arg)?;
+            }
+            "--dns-concurrency" => {
+                options.dns_concurrency = number(&value(&mut args, &arg)?, &arg)?;
+                dns_concurrency_set = true;
+            }
+            "--dns-cache" => options.dns_cache = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--dns-cache-ttl" => options.dns_cache_ttl = duration(&value(&mut args, &arg)?, &arg)?,
+            "--dns-engine" => options.dns_engine = value(&mut args, &arg)?.parse()?,
+            "--ip-version" => options.ip_version = value(&mut args, &arg)?.parse()?,
+            "--ipv6" => options.ip_version = IpVersion::V6,
+            "--rate" => options.rate = Some(value(&mut args, &arg)?.parse()?),
+            "--per-host-rate" => options.per_host_rate = Some(value(&mut args, &arg)?.parse()?),
+            "--max-requests" => {
+                options.max_requests = Some(number(&value(&mut args, &arg)?, &arg)?)
+            }
+            "--max-bandwidth" => {
+                options.max_bandwidth = Some(ratelimit::parse_bytes(&value(&mut args, &arg)?)?)
+            }
+            "--max-runtime" => {
+                options.max_runtime = Some(duration(&value(&mut args, &arg)?, &arg)?)
+            }
+            "--phase-deadline" => {
+                for entry in value(&mut args, &arg)?.split(',') {
+                    let (phase, limit) = entry
+                        .split_once('=')
+                        .ok_or_else(|| format!("Expected phase=duration for {}: {}", arg, entry))?;
+                    let phase: Phase = phase.trim().parse()?;
+                    let limit = duration(limit.trim(), &arg)?;
+                    options.phase_deadlines.insert(phase, limit);
+                }
+            }
+            "--retries" => options.retries = number(&value(&mut args, &arg)?, &arg)?,
+            "--retry-backoff" => options.retry_backoff = duration(&value(&mut args, &arg)?, &arg)?,
+            "--wildcard-threshold" => {
+                let threshold: f64 = number(&value(&mut args, &arg)?, &arg)?;
+                if !(0.0..=1.0).contains(&threshold) {
+                    return Err("--wildcard-threshold must be between 0 and 1".to_string());
+                }
+                options.wildcard_threshold = threshold;
+            }
+            "--keep-filtered" => options.keep_filtered = true,
+            "--unsorted" => options.sorted = false,
+            "--match-code" => {
+                options.filter.match_codes = filter::parse_codes(&value(&mut args, &arg)?)?
+            }
+            "--filter-code" => {
+                options.filter.filter_codes = filter::parse_codes(&value(&mut args, &arg)?)?
+            }
+            "--match-title-regex" => {
+                options.filter.match_title = Some(regex_flag(&mut args, &arg)?)
+            }
+            "--match-server-regex" => {
+                options.filter.match_server = Some(regex_flag(&mut args, &arg)?)
+            }
+            "--ports" => options.probe.ports = port_list(&value(&mut args, &arg)?)?,
+            "--both-schemes" => options.probe.both_schemes = true,
+            "--head-first" => options.probe.method = ProbeMethod::HeadFirst,
+            "--method" => options.probe.method = value(&mut args, &arg)?.parse()?,
+            "--no-follow-redirects" => options.probe.follow_redirects = false,
+            "--slow-threshold" => {
+                options.probe.slow_threshold_ms = Some(number(&value(&mut args, &arg)?, &arg)?)
+            }
+            "--capture-headers" => {
+                options.probe.capture_headers = value(&mut args, &arg)?.parse()?
+            }
+            "--max-body" => options.probe.max_body_bytes = number(&value(&mut args, &arg)?, &arg)?,
+            "--extract" => {
+                let pattern = value(&mut args, &arg)?;
+                let re = regex::Regex::new(&pattern)
+                    .map_err(|e| format!("--extract: invalid regex: {}", e))?;
+                if re.capture_names().flatten().next().is_none() {
+                    return Err("--extract needs a named group, e.g. (?P<version>v[0-9.]+)".into());
+                }
+                options.probe.extract.push(re);
+            }
+            "--match-body" => options.probe.body_rules.push(
+                BodyRule::new(None, &value(&mut args, &arg)?)
+                    .map_err(|e| format!("--match-body: {}", e))?,
+            ),
+            "--match-body-file" => {
+                let path = PathBuf::from(value(&mut args, &arg)?);
+                options.probe.body_rules.extend(
+                    matchers::load(&path)
+                        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?,
+                );
+            }
+            "--header" => {
+                let (name, value) = header(&value(&mut args, &arg)?)?;
+                options.probe.headers.append(name, value);
+            }
+            "--cookie" => {
+                let raw = value(&mut args, &arg)?;
+                let cookies = match options.probe.headers.get(COOKIE) {
+                    Some(existing) => format!("{}; {}", existing.to_str().unwrap_or_default(), raw),
+                    None => raw,
+                };
+                let cookies = HeaderValue::from_str(&cookies)
+                    .map_err(|_| format!("Invalid value for --cookie: {}", cookies))?;
+                options.probe.headers.insert(COOKIE, cookies);
+            }
+            "--user-agent" => options.probe.user_agent = Some(value(&mut args, &arg)?),
+            "--proxy" => {
+                let raw = value(&mut args, &arg)?;
+                options.proxy = Some(proxy::resolve(&raw).map_err(|e| e.to_string())?);
+            }
+            "--favicon" => options.probe.favicon = true,
+            "--robots" => options.probe.robots = true,
+            "--jarm" => options.probe.jarm = true,
+            "--paths" => options.probe.paths = path_list(&value(&mut args, &arg)?)?,
+            "--http-concurrency" => {
+                options.probe.concurrency = number(&value(&mut args, &arg)?, &arg)?
+            }
+            "--resolver" => {
+                let addr =
+                    dns::parse_resolver(&value(&mut args, &arg)?).map_err(|e| e.to_string())?;
+                resolvers.push(addr);
+            }
+            "--resolvers" => {
+                let path = PathBuf::from(value(&mut args, &arg)?);
+                let addrs = dns::load_resolvers(&path)
+                    .map_err(|e| format!("Could not load {}: {}", path.display(), e))?;
+                resolvers.extend(addrs);
+            }
+            "--doh" => {
+                let upstream =
+                    dns::parse_doh(&value(&mut args, &arg)?).map_err(|e| e.to_string())?;
+                resolvers.push(upstream);
+            }
+            "--dot" => {
+                let upstream =
+                    dns::parse_dot(&value(&mut args, &arg)?).map_err(|e| e.to_string())?;
+                resolvers.push(upstream);
+            }
+            "-h" | "--help" => return Err(String::new()),
+            "-" => list = Some(PathBuf::from("-")),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ => {
+                if domain.is_some() {
+                    return Err(format!("Unexpected argument: {}", arg));
+                }
+                domain = Some(arg);
+            }
+        }
+    }
+
+    // Resolvers given on the command line replace the configured ones
+    if !resolvers.is_empty() {
+        options.resolvers = resolvers;
+    }
+    if options.dnssec
+        && !options.resolvers.is_empty()
+        && !options
+            .resolvers
+            .iter()
+            .any(|u| matches!(u, Upstream::Udp(_)))
+    {
+        return Err(
+            "--dnssec asks plain UDP resolvers to validate; add one with --resolver".to_string(),
+        );
+    }
+    if options.dns_engine == DnsEngine::Raw {
+        if options
+            .resolvers
+            .iter()
+            .any(|u| !matches!(u, Upstream::Udp(_)))
+        {
+            return Err("--dns-engine raw only speaks plain UDP; drop --doh/--dot".to_string());
+        }
+        // The default window is sized for the stub resolver
+        if !dns_concurrency_set {
+            options.dns_concurrency = options.dns_concurrency.max(rawdns::RAW_DNS_CONCURRENCY);
+        }
+    }
+    if recursive {
+        options.recursion_depth = depth.unwrap_or(1);
+    }
+    if options.passive {
+        let active = [
+            (options.wordlist.is_some(), "--wordlist"),
+            (options.wordlist_builtin.is_some(), "--wordlist-builtin"),
+            (options.permute, "--permute"),
+            (options.markov > 0, "--markov"),
+            (options.recursion_depth > 0, "--recursive"),
+            (options.axfr, "--axfr"),
+            (options.nsec_walk, "--nsec-walk"),
+            (options.dangling, "--dangling"),
+            (options.vhosts, "--vhosts"),
+            (!options.records.is_empty(), "--records"),
+            (!options.scan_ports.is_empty(), "--scan-ports"),
+            (options.banners != BannerScope::Off, "--banners"),
+            (options.ptr, "--ptr"),
+            (options.dnssec, "--dnssec"),
+            (options.screenshots.is_some(), "--screenshots"),
+        ];
+        if let Some((_, flag)) = active.iter().find(|(on, _)| *on) {
+            return Err(format!(
+                "{} needs traffic to the target, which --profile passive doesn't send",
+                flag
+            ));
+        }
+        let addressed = [
+            (options.asn, "--asn"),
+            (options.rdap, "--rdap"),
+            (options.reverse_ip, "--reverse-ip"),
+            (options.shodan_ports, "--shodan-ports"),
+            (options.geoip_db.is_some(), "--geoip-db"),
+            (options.find_related, "--find-related"),
+        ];
+        if let Some((_, flag)) = addressed.iter().find(|(on, _)| *on) {
+            return Err(format!(
+                "{} works on resolved hosts; --profile passive resolves nothing",
+                flag
+            ));
+        }
+    }
+
+    if stream && !format.streams() {
+        return Err(
+            "--stream needs a line-based --output-format (jsonl, csv, hosts or amass)".to_string(),
+        );
+    }
+
+    // Plugins are sources too, so names are checked once they are known
+    if let Some(ref dir) = options.plugin_dir {
+        let plugins = sources::plugin::load_dir(dir)
+            .map_err(|e| format!("Could not read {}: {}", dir.display(), e))?;
+        let builtin = sources::known_names(&ScanOptions::default());
+        if let Some(p) = plugins.iter().find(|p| builtin.contains(&p.name())) {
+            return Err(format!(
+                "Plugin {} has the name of a built-in source",
+                p.name()
+            ));
+        }
+    }
+    if let Some(raw) = include_sources {
+        options.sources = source_list(&raw, &options)?;
+    }
+    if let Some(raw) = exclude_sources {
+        options.exclude_sources = source_list(&raw, &options)?;
+    }
+
+    let mut domains: Vec<String> = domain.into_iter().collect();
+    if let Some(path) = list {
+        let listed = input::load_list(&path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        for d in listed {
+            if !domains.contains(&d) {
+                domains.push(d);
+            }
+        }
+    }
+    if !options.discover && options.candidates.is_empty() && options.known.is_empty() {
+        return Err("--no-discover needs names to probe from --input or --known".to_string());
+    }
+    // Supplied names without a target: scan each apex they fall under
+    if domains.is_empty() {
+        let supplied: Vec<String> = options
+            .candidates
+            .iter()
+            .chain(&options.known)
+            .cloned()
+            .collect();
+        domains = input::guess_apexes(&supplied);
+    }
+
+    if !no_cache {
+        options.cache_dir = SourceCache::default_dir();
+    }
+
+    if options.proxy.is_none() {
+        if let Some(raw) = proxy::socks_from_env() {
+            options.proxy = Some(proxy::resolve(&raw).map_err(|e| e.to_string())?);
+        }
+    }
+
+    if stream && group_by_ip {
+        return Err(
+            "--group-by-ip needs the whole result set and can't be used with --stream".into(),
+        );
+    }
+
+    if options.wordlist.is_some() && options.wordlist_builtin.is_some() {
+        return Err("--wordlist and --wordlist-builtin can't be combined".into());
+    }
+
+    if fail_on_new && compare.is_none() {
+        return Err("--fail-on-new needs --compare".into());
+    }
+
+    let lines = matches!(format, OutputFormat::Jsonl | OutputFormat::Amass);
+    if append && (output.is_none() || !lines || group_by_ip) {
+        return Err(
+            "--append needs -o and --output-format jsonl or amass, without --group-by-ip".into(),
+        );
+    }
+
+    if envelope && (stream || group_by_ip || format != OutputFormat::Json) {
+        return Err("--envelope only wraps json output, without --stream or --group-by-ip".into());
+    }
+
+    Ok(Cli {
+        domains,
+        format,
+        stream,
+        output,
+        append,
+        verbosity,
+        log_json,
+        group_by_ip,
+        cluster_by,
+        envelope,
+        db,
+        report,
+        report_md,
+        export_nmap,
+        export_nmap_xml,
+        export_nmap_dir,
+        related_output,
+        compare,
+        dry_run,
+        fail_on_new,
+        notify: notify_url.map(|url| Webhook {
+            url,
+            format: notify_format,
+            proxy: options.proxy.clone(),
+        }),
+        options,
+    })
+}
+
+pub fn parse_merge<I: Iterator<Item = String>>(mut args: I) -> Result<MergeArgs, String> {
+    let mut files = Vec::new();
+    let mut output = None;
+    let mut format = OutputFormat::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--output-format" => format = value(&mut args, &arg)?.parse()?,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+
+    if files.len() < 2 {
+        return Err("merge takes at least two result files".to_string());
+    }
+    Ok(MergeArgs {
+        files,
+        output,
+        format,
+    })
+}
+
+pub fn parse_diff<I: Iterator<Item = String>>(mut args: I) -> Result<DiffArgs, String> {
+    let mut files = Vec::new();
+    let mut fail_on_new = false;
+    let mut notify_url = None;
+    let mut notify_format = WebhookFormat::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fail-on-new" => fail_on_new = true,
+            "--notify-webhook" => notify_url = Some(value(&mut args, &arg)?),
+            "--notify-format" => notify_format = value(&mut args, &arg)?.parse()?,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+
+    let [old, new]: [PathBuf; 2] = files
+        .try_into()
+        .map_err(|_| "diff takes exactly two result files".to_string())?;
+    Ok(DiffArgs {
+        old,
+        new,
+        fail_on_new,
+        notify: notify_url.map(|url| Webhook {
+            url,
+            format: notify_format,
+            proxy: proxy::socks_from_env().and_then(|raw| proxy::resolve(&raw).ok()),
+        }),
+    })
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("Missing value for {}", flag))
+}
+
+fn number<T: std::str::FromStr>(raw: &str, flag: &str) -> Result<T, String> {
+    raw.parse()
+        .map_err(|_| format!("Invalid number for {}: {}", flag, raw))
+}
+
+fn regex_flag<I: Iterator<Item = String>>(
+    args: &mut I,
+    flag: &str,
+) -> Result<regex::Regex, String> {
+    let pattern = value(args, flag)?;
+    regex::Regex::new(&pattern).map_err(|e| format!("{}: invalid regex: {}", flag, e))
+}
+
+/// `Name: value` as given to `--header`.
+fn header(raw: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let invalid = || {
+        format!(
+            "Invalid header for --header: {} (expected 'Name: value')",
+            raw
+        )
+    };
+    let (name, value) = raw.split_once(':').ok_or_else(invalid)?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid())?;
+    let value = HeaderValue::from_str(value.trim()).map_err(|_| invalid())?;
+    Ok((name, value))
+}
+
+fn port_list(raw: &str) -> Result<Vec<u16>, String> {
+    let mut ports = Vec::new();
+    for p in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let port: u16 = number(p, "--ports")?;
+        if port == 0 {
+            return Err("Invalid number for --ports: 0".to_string());
+        }
+        if !ports.contains(&port) {
+            ports.push(port);
+        }
+    }
+    Ok(ports)
+}
+
+/// `--paths` values, each given a leading `/` if it lacks one.
+fn path_list(raw: &str) -> Result<Vec<String>, String> {
+    let mut paths = Vec::new();
+    for p in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        if p.contains(char::is_whitespace) {
+            return Err(format!("Invalid path for --paths: {}", p));
+        }
+        let path = if p.starts_with('/') {
+            p.to_string()
+        } else {
+            format!("/{}", p)
+        };
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    if paths.is_empty() {
+        return Err("No paths given to --paths".to_string());
+    }
+    Ok(paths)
+}
+
+/// `90s`, `30m`, `6h`, `1d`, or bare seconds.
+fn duration(raw: &str, flag: &str) -> Result<Duration, String> {
+    let (digits, unit) = match raw.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((i, _)) => raw.split_at(i),
+        None => (raw, "s"),
+    };
+    let n: u64 = number(digits, flag)?;
+    let scale: u64 = match unit {
+        "ms" => 1,
+        "s" => 1000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        _ => 0,
+    };
+    match n.checked_mul(scale) {
+        Some(millis) if scale > 0 => Ok(Duration::from_millis(millis)),
+        _ => Err(format!("Invalid duration for {}: {}", flag, raw)),
+    }
+}
+
+fn source_list(raw: &str, options: &ScanOptions) -> Result<Vec<String>, String> {
+    let known = sources::known_names(options);
+    raw.split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            if known.contains(&s.as_str()) {
+                Ok(s)
+            } else {
+                Err(format!(
+                    "Unknown source: {} (known: {})",
+                    s,
+                    known.join(", ")
+                ))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_take_a_unit() {
+        let cases = [
+            ("250ms", 250),
+            ("90s", 90_000),
+            ("90", 90_000),
+            ("30m", 1_800_000),
+            ("6h", 21_600_000),
+            ("1d", 86_400_000),
+            ("0s", 0),
+        ];
+        for (raw, millis) in cases {
+            assert_eq!(
+                duration(raw, "--every"),
+                Ok(Duration::from_millis(millis)),
+                "{}",
+                raw
+            );
+        }
+    }
+
+    #[test]
+    fn bad_durations_are_refused() {
+        for raw in ["", "s", "5w", "1.5h", "-1s", "1 h"] {
+            assert!(duration(raw, "--every").is_err(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn oversized_durations_are_refused() {
+        // Fits in a u64 only before scaling to milliseconds
+        assert_eq!(
+            duration("9999999999999999d", "--every"),
+            Err("Invalid duration for --every: 9999999999999999d".to_string())
+        );
+        assert_eq!(
+            duration("99999999999999999999", "--every"),
+            Err("Invalid number for --every: 99999999999999999999".to_string())
+        );
+        assert_eq!(
+            duration("18446744073709551615ms", "--every"),
+            Ok(Duration::from_millis(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn port_lists_drop_duplicates_and_zero() {
+        assert_eq!(port_list("443, 8443,,443"), Ok(vec![443, 8443]));
+        assert!(port_list("0").is_err());
+        assert!(port_list("65536").is_err());
+    }
+
+    #[test]
+    fn headers_split_on_the_first_colon() {
+        let (name, value) = header("X-Forwarded-For: 127.0.0.1:80").unwrap();
+        assert_eq!(name, "x-forwarded-for");
+        assert_eq!(value, "127.0.0.1:80");
+        assert!(header("no colon").is_err());
+        assert!(header("Bad Name: x").is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Which cloud a host lives in, from its CNAMEs and addresses.
+
+use crate::cdn::in_range;
+use std::net::IpAddr;
+
+/// A hosting service recognised by the names it hands out.
+#[derive(Debug, Clone, Copy)]
+pub struct Service {
+    pub name: &'static str,
+    /// CNAME target suffixes.
+    pub cnames: &'static [&'static str],
+    /// Address ranges only this service uses, in CIDR notation.
+    pub ranges: &'static [&'static str],
+}
+
+/// Checked before [`CLOUDS`], since they are more specific.
+pub const SERVICES: &[Service] = &[
+    Service {
+        name: "AWS S3",
+        // Regional endpoints are matched by `is_s3` as well
+        cnames: &[".s3.amazonaws.com"],
+        ranges: &[],
+    },
+    Service {
+        name: "AWS CloudFront",
+        cnames: &[".cloudfront.net"],
+        ranges: &[],
+    },
+    Service {
+        name: "AWS Elastic Beanstalk",
+        cnames: &[".elasticbeanstalk.com"],
+        ranges: &[],
+    },
+    Service {
+        name: "AWS ELB",
+        cnames: &[".elb.amazonaws.com"],
+        ranges: &[],
+    },
+    Service {
+        name: "Azure App Service",
+        cnames: &[".azurewebsites.net", ".azurestaticapps.net"],
+        ranges: &[],
+    },
+    Service {
+        name: "Azure Storage",
+        cnames: &[".blob.core.windows.net", ".web.core.windows.net"],
+        ranges: &[],
+    },
+    Service {
+        name: "Azure",
+        cnames: &[
+            ".cloudapp.net",
+            ".cloudapp.azure.com",
+            ".trafficmanager.net",
+            ".azureedge.net",
+            ".azurefd.net",
+        ],
+        ranges: &[],
+    },
+    Service {
+        name: "Google Cloud Storage",
+        cnames: &["c.storage.googleapis.com", ".storage.googleapis.com"],
+        ranges: &[],
+    },
+    Service {
+        name: "Google App Engine",
+        cnames: &[".appspot.com", "ghs.googlehosted.com"],
+        ranges: &[],
+    },
+    Service {
+        name: "Google Cloud Run",
+        cnames: &[".run.app"],
+        ranges: &[],
+    },
+    Service {
+        name: "Heroku",
+        cnames: &[".herokuapp.com", ".herokudns.com", ".herokussl.com"],
+        ranges: &[],
+    },
+    Service {
+        name: "GitHub Pages",
+        cnames: &[".github.io"],
+        ranges: &["185.199.108.0/22", "2606:50c0:8000::/46"],
+    },
+];
+
+/// A cloud provider addresses can be released back to.
+#[derive(Debug, Clone, Copy)]
+pub struct Cloud {
+    pub name: &'static str,
+    /// The largest published address blocks, in CIDR notation. Not
+    /// exhaustive.
+    pub ranges: &'static [&'static str],
+    /// Body snippets the provider serves for a resource that no longer
+    /// exists behind an address.
+    pub error_pages: &'static [&'static str],
+}
+
+pub const CLOUDS: &[Cloud] = &[
+    Cloud {
+        name: "AWS",
+        ranges: &[
+            "3.0.0.0/9",
+            "18.128.0.0/9",
+            "34.192.0.0/10",
+            "44.192.0.0/10",
+            "52.0.0.0/11",
+            "52.32.0.0/11",
+            "52.64.0.0/12",
+            "54.64.0.0/11",
+            "54.144.0.0/12",
+            "54.160.0.0/11",
+            "54.192.0.0/12",
+            "54.208.0.0/13",
+            "54.216.0.0/14",
+            "54.220.0.0/15",
+            "54.224.0.0/12",
+            "54.240.0.0/12",
+            "2600:1f00::/24",
+        ],
+        error_pages: &["NoSuchBucket", "The specified bucket does not exist"],
+    },
+    Cloud {
+        name: "Azure",
+        ranges: &[
+            "13.64.0.0/11",
+            "20.36.0.0/14",
+            "20.40.0.0/13",
+            "20.48.0.0/12",
+            "20.64.0.0/10",
+            "40.64.0.0/10",
+            "52.224.0.0/11",
+            "104.40.0.0/13",
+            "137.116.0.0/15",
+            "138.91.0.0/16",
+            "168.61.0.0/16",
+            "168.62.0.0/15",
+            "191.232.0.0/13",
+        ],
+        error_pages: &[
+            "404 Web Site not found",
+            "Error 404 - Web app not found",
+            "The specified resource does not exist",
+        ],
+    },
+    Cloud {
+        name: "Google Cloud",
+        ranges: &[
+            "34.64.0.0/10",
+            "34.128.0.0/10",
+            "35.184.0.0/13",
+            "35.192.0.0/12",
+            "35.208.0.0/12",
+            "35.224.0.0/12",
+            "35.240.0.0/13",
+            "104.196.0.0/14",
+            "130.211.0.0/16",
+            "146.148.0.0/17",
+        ],
+        error_pages: &["NoSuchBucket", "The specified bucket does not exist"],
+    },
+];
+
+/// The cloud whose ranges hold `ip`.
+pub fn cloud_for_ip(ip: &str) -> Option<&'static Cloud> {
+    let ip: IpAddr = ip.parse().ok()?;
+    CLOUDS
+        .iter()
+        .find(|cloud| cloud.ranges.iter().any(|range| in_range(ip, range)))
+}
+
+/// The service or cloud a host lives in: a known CNAME suffix anywhere in
+/// the chain first, then the ranges of its addresses.
+pub fn provider(ips: &[String], cname_chain: &[String]) -> Option<String> {
+    if cname_chain.iter().any(|c| is_s3(c)) {
+        return Some("AWS S3".to_string());
+    }
+    let by_cname = SERVICES.iter().find(|service| {
+        service
+            .cnames
+            .iter()
+            .any(|suffix| cname_chain.iter().any(|c| cname_matches(c, suffix)))
+    });
+    if let Some(service) = by_cname {
+        return Some(service.name.to_string());
+    }
+    let addrs: Vec<IpAddr> = ips.iter().filter_map(|ip| ip.parse().ok()).collect();
+    let by_range = SERVICES.iter().find(|service| {
+        service
+            .ranges
+            .iter()
+            .any(|range| addrs.iter().any(|ip| in_range(*ip, range)))
+    });
+    if let Some(service) = by_range {
+        return Some(service.name.to_string());
+    }
+    ips.iter()
+        .find_map(|ip| cloud_for_ip(ip))
+        .map(|cloud| cloud.name.to_string())
+}
+
+/// A suffix starting with a dot only matches names below it; others also
+/// match the name itself.
+fn cname_matches(cname: &str, suffix: &str) -> bool {
+    let cname = cname.trim_end_matches('.');
+    if suffix.starts_with('.') {
+        cname.ends_with(suffix)
+    } else {
+        cname == suffix || cname.ends_with(&format!(".{}", suffix))
+    }
+}
+
+/// Regional S3 and S3 website endpoints, such as
+/// `bucket.s3.eu-west-1.amazonaws.com` or
+/// `bucket.s3-website-us-east-1.amazonaws.com`.
+fn is_s3(cname: &str) -> bool {
+    let Some(rest) = cname.trim_end_matches('.').strip_suffix(".amazonaws.com") else {
+        return false;
+    };
+    rest.split('.')
+        .any(|label| label == "s3" || label.starts_with("s3-"))
+}
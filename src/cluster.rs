@@ -0,0 +1,190 @@
+//! Collapsing results into clusters of hosts that share addresses and
+//! serve the same content, for `--group-by-ip`, or that serve the very
+//! same page wherever they are, for `--cluster-by body_hash`.
+
+use crate::wildcard::similarity;
+use crate::SubdomainResult;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::str::FromStr;
+
+/// Simhash similarity above which two bodies count as the same site.
+pub const SAME_CONTENT_THRESHOLD: f64 = 0.9;
+
+/// What [`HostCluster`]s have in common.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClusterBy {
+    /// Addresses (or CDN), then content: [`cluster`].
+    #[default]
+    Ip,
+    /// The page alone: [`cluster_by_body`].
+    BodyHash,
+}
+
+impl FromStr for ClusterBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            "ip" => Ok(ClusterBy::Ip),
+            "body_hash" => Ok(ClusterBy::BodyHash),
+            _ => Err(format!("Unknown cluster key: {} (known: ip, body_hash)", s)),
+        }
+    }
+}
+
+/// Hosts answering from the same addresses (or the same CDN) with the
+/// same content.
+#[derive(Debug, Clone, Serialize)]
+pub struct HostCluster {
+    /// Addresses the hosts resolve to. Empty for CDN clusters, whose edge
+    /// addresses say nothing about the origin.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ips: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cdn: Option<String>,
+    pub status_code: Option<u16>,
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_hash: Option<String>,
+    /// The addresses serve other content to other names too, so these are
+    /// likely name-based virtual hosts.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub virtual_hosts: bool,
+    pub hosts: Vec<String>,
+}
+
+/// Groups `results` by shared address, then by content. CDN-fronted hosts
+/// are grouped per CDN instead, so their shared edges don't merge
+/// unrelated sites. Largest clusters come first.
+pub fn cluster(results: &[SubdomainResult]) -> Vec<HostCluster> {
+    // Hosts become connected through any address they share
+    let mut parent: Vec<usize> = (0..results.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut by_ip: HashMap<&str, usize> = HashMap::new();
+    let mut by_cdn: HashMap<&str, usize> = HashMap::new();
+    for (i, r) in results.iter().enumerate() {
+        let firsts: Vec<usize> = match r.cdn.as_deref() {
+            Some(cdn) => vec![*by_cdn.entry(cdn).or_insert(i)],
+            None => r
+                .ips
+                .iter()
+                .map(|ip| *by_ip.entry(ip.as_str()).or_insert(i))
+                .collect(),
+        };
+        for first in firsts {
+            let (a, b) = (root(&mut parent, first), root(&mut parent, i));
+            parent[b] = a;
+        }
+    }
+
+    let mut partitions: Vec<Vec<&SubdomainResult>> = Vec::new();
+    let mut index: HashMap<usize, usize> = HashMap::new();
+    for (i, r) in results.iter().enumerate() {
+        let group = root(&mut parent, i);
+        let slot = *index.entry(group).or_insert_with(|| {
+            partitions.push(Vec::new());
+            partitions.len() - 1
+        });
+        partitions[slot].push(r);
+    }
+
+    let mut clusters = Vec::new();
+    for members in partitions {
+        let cdn = members[0].cdn.clone();
+        let ips: BTreeSet<&String> = members.iter().flat_map(|r| &r.ips).collect();
+        let mut profiles: Vec<(&SubdomainResult, BTreeSet<&str>)> = Vec::new();
+        for r in &members {
+            match profiles.iter_mut().find(|(rep, _)| same_content(rep, r)) {
+                Some((_, hosts)) => {
+                    hosts.insert(&r.subdomain);
+                }
+                None => profiles.push((r, BTreeSet::from([r.subdomain.as_str()]))),
+            }
+        }
+        let virtual_hosts = cdn.is_none() && profiles.len() > 1;
+        for (rep, hosts) in profiles {
+            clusters.push(HostCluster {
+                ips: if cdn.is_some() {
+                    Vec::new()
+                } else {
+                    ips.iter().map(|ip| ip.to_string()).collect()
+                },
+                cdn: cdn.clone(),
+                status_code: rep.status_code,
+                title: rep.title.clone(),
+                body_hash: rep.body_hash.clone(),
+                virtual_hosts,
+                hosts: hosts.into_iter().map(str::to_string).collect(),
+            });
+        }
+    }
+    sort(&mut clusters);
+    clusters
+}
+
+/// Groups `results` by [`body_hash`](SubdomainResult::body_hash) alone,
+/// so one default page (a parking page, a bare nginx, a login portal)
+/// served by hosts on unrelated addresses becomes one cluster. Hosts
+/// without a body stay on their own. Largest clusters come first.
+pub fn cluster_by_body(results: &[SubdomainResult]) -> Vec<HostCluster> {
+    let mut by_hash: BTreeMap<&str, Vec<&SubdomainResult>> = BTreeMap::new();
+    let mut clusters = Vec::new();
+    for r in results {
+        match r.body_hash.as_deref() {
+            Some(hash) => by_hash.entry(hash).or_default().push(r),
+            None => clusters.push(from_members(&[r])),
+        }
+    }
+    clusters.extend(by_hash.values().map(|members| from_members(members)));
+    sort(&mut clusters);
+    clusters
+}
+
+/// One cluster of `members`, described by the first of them.
+fn from_members(members: &[&SubdomainResult]) -> HostCluster {
+    let rep = members[0];
+    let cdn = rep
+        .cdn
+        .clone()
+        .filter(|cdn| members.iter().all(|r| r.cdn.as_ref() == Some(cdn)));
+    let ips: BTreeSet<&String> = members.iter().flat_map(|r| &r.ips).collect();
+    let hosts: BTreeSet<&str> = members.iter().map(|r| r.subdomain.as_str()).collect();
+    HostCluster {
+        ips: ips.into_iter().cloned().collect(),
+        cdn,
+        status_code: rep.status_code,
+        title: rep.title.clone(),
+        body_hash: rep.body_hash.clone(),
+        virtual_hosts: false,
+        hosts: hosts.into_iter().map(str::to_string).collect(),
+    }
+}
+
+fn sort(clusters: &mut [HostCluster]) {
+    clusters.sort_by(|a, b| {
+        b.hosts
+            .len()
+            .cmp(&a.hosts.len())
+            .then_with(|| a.hosts.cmp(&b.hosts))
+    });
+}
+
+fn same_content(a: &SubdomainResult, b: &SubdomainResult) -> bool {
+    if a.body_hash.is_some() && a.body_hash == b.body_hash {
+        return a.status_code == b.status_code;
+    }
+    a.status_code == b.status_code
+        && a.title == b.title
+        && match (a.body_simhash, b.body_simhash) {
+            (Some(x), Some(y)) => similarity(x, y) >= SAME_CONTENT_THRESHOLD,
+            (None, None) => true,
+            _ => false,
+        }
+}
@@ -0,0 +1,404 @@
+//! `~/.config/subpeek/config.toml` plus environment overrides.
+//!
+//! Only the subset of TOML the config needs is understood: top-level keys,
+//! `[section]` tables, and string, integer and string-array values. Arrays
+//! may span lines.
+//!
+//! ```toml
+//! user_agent = "Mozilla/5.0 (compatible; subpeek)"
+//! resolvers = [
+//!     "1.1.1.1",
+//!     "9.9.9.9:53",
+//! ]
+//! dns_concurrency = 300
+//! http_concurrency = 50
+//! plugin_dir = "/home/me/.config/subpeek/plugins"
+//!
+//! [keys]
+//! virustotal = "..."
+//! censys_id = "..."
+//! censys_secret = "..."
+//! ```
+
+use crate::dns::{self, Upstream};
+use crate::{Result, ScanOptions};
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Config names of every API key and the environment variable that
+/// overrides it.
+pub const KEY_ENV_VARS: &[(&str, &str)] = &[
+    ("virustotal", "SUBPEEK_VT_API_KEY"),
+    ("securitytrails", "SUBPEEK_SECURITYTRAILS_API_KEY"),
+    ("censys_id", "SUBPEEK_CENSYS_API_ID"),
+    ("censys_secret", "SUBPEEK_CENSYS_API_SECRET"),
+    ("shodan", "SUBPEEK_SHODAN_API_KEY"),
+    ("urlscan", "SUBPEEK_URLSCAN_API_KEY"),
+    ("certspotter", "SUBPEEK_CERTSPOTTER_API_KEY"),
+    ("chaos", "SUBPEEK_CHAOS_API_KEY"),
+    ("github", "SUBPEEK_GITHUB_TOKEN"),
+];
+
+/// API keys by config name. Environment variables win over the file.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys {
+    keys: HashMap<String, String>,
+}
+
+impl ApiKeys {
+    pub fn get(&self, name: &str) -> Option<String> {
+        let from_env = KEY_ENV_VARS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .and_then(|(_, var)| env::var(var).ok());
+        from_env
+            .or_else(|| self.keys.get(name).cloned())
+            .filter(|k| !k.trim().is_empty())
+    }
+
+    pub fn set(&mut self, name: &str, key: &str) {
+        self.keys.insert(name.to_string(), key.to_string());
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub keys: ApiKeys,
+    pub resolvers: Vec<String>,
+    pub dns_concurrency: Option<usize>,
+    pub http_concurrency: Option<usize>,
+    pub user_agent: Option<String>,
+    pub plugin_dir: Option<PathBuf>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/subpeek/config.toml`, falling back to
+    /// `~/.config/subpeek/config.toml` (`%APPDATA%\subpeek` on Windows).
+    pub fn default_path() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+        Some(base.join("subpeek").join("config.toml"))
+    }
+
+    /// Loads `path`, or the default location when `None`. A missing default
+    /// file is not an error; environment overrides apply either way.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut config = match path {
+            Some(p) => Self::parse(&std::fs::read_to_string(p)?)?,
+            None => match Self::default_path() {
+                Some(p) if p.exists() => Self::parse(&std::fs::read_to_string(&p)?)?,
+                _ => Self::default(),
+            },
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut config = Self::default();
+        let mut section = String::new();
+
+        let mut lines = text.lines().enumerate();
+        while let Some((n, raw)) = lines.next() {
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |msg: &str| format!("config line {}: {}", n + 1, msg);
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| err("expected key = value"))?;
+            let key = key.trim().trim_matches('"');
+            let mut value = value.trim().to_string();
+            // An array goes on until its brackets close
+            while open_brackets(&value) > 0 {
+                let (_, next) = lines.next().ok_or_else(|| err("unterminated array"))?;
+                value.push(' ');
+                value.push_str(strip_comment(next).trim());
+            }
+            let value = parse_value(&value).map_err(|e| err(&e))?;
+
+            match (section.as_str(), key) {
+                ("keys", name) => config.keys.set(name, &value.string().map_err(|e| err(&e))?),
+                ("", "resolvers") => config.resolvers = value.list().map_err(|e| err(&e))?,
+                ("", "dns_concurrency") => {
+                    config.dns_concurrency = Some(value.integer().map_err(|e| err(&e))?)
+                }
+                ("", "http_concurrency") => {
+                    config.http_concurrency = Some(value.integer().map_err(|e| err(&e))?)
+                }
+                ("", "user_agent") => {
+                    config.user_agent = Some(value.string().map_err(|e| err(&e))?)
+                }
+                ("", "plugin_dir") => {
+                    config.plugin_dir = Some(PathBuf::from(value.string().map_err(|e| err(&e))?))
+                }
+                // Unknown keys are ignored so newer configs work with older builds
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+
+    /// `SUBPEEK_RESOLVERS` (comma separated), `SUBPEEK_DNS_CONCURRENCY`,
+    /// `SUBPEEK_HTTP_CONCURRENCY` and `SUBPEEK_USER_AGENT`.
+    fn apply_env(&mut self) -> Result<()> {
+        if let Ok(list) = env::var("SUBPEEK_RESOLVERS") {
+            self.resolvers = list.split(',').map(|s| s.trim().to_string()).collect();
+        }
+        if let Ok(n) = env::var("SUBPEEK_DNS_CONCURRENCY") {
+            self.dns_concurrency = Some(n.parse()?);
+        }
+        if let Ok(n) = env::var("SUBPEEK_HTTP_CONCURRENCY") {
+            self.http_concurrency = Some(n.parse()?);
+        }
+        if let Ok(ua) = env::var("SUBPEEK_USER_AGENT") {
+            self.user_agent = Some(ua);
+        }
+        Ok(())
+    }
+
+    /// Turns the config into starting options; command-line flags are
+    /// applied on top of these.
+    pub fn to_options(&self) -> Result<ScanOptions> {
+        let mut options = ScanOptions {
+            api_keys: self.keys.clone(),
+            ..ScanOptions::default()
+        };
+        options.resolvers = self
+            .resolvers
+            .iter()
+            .filter(|r| !r.is_empty())
+            .map(|r| parse_upstream(r))
+            .collect::<Result<_>>()?;
+        if let Some(n) = self.dns_concurrency {
+            options.dns_concurrency = n.max(1);
+        }
+        if let Some(n) = self.http_concurrency {
+            options.probe.concurrency = n.max(1);
+        }
+        if let Some(ref ua) = self.user_agent {
+            options.user_agent = ua.clone();
+        }
+        options.plugin_dir = self.plugin_dir.clone();
+        Ok(options)
+    }
+}
+
+/// Resolver entries may be plain addresses, `https://` DoH endpoints or
+/// `tls://` DoT servers.
+pub(crate) fn parse_upstream(raw: &str) -> Result<Upstream> {
+    if raw.starts_with("https://") {
+        dns::parse_doh(raw)
+    } else if let Some(rest) = raw.strip_prefix("tls://") {
+        dns::parse_dot(rest)
+    } else {
+        dns::parse_resolver(raw)
+    }
+}
+
+enum Value {
+    Str(String),
+    Int(i64),
+    List(Vec<String>),
+}
+
+impl Value {
+    fn string(self) -> std::result::Result<String, String> {
+        match self {
+            Value::Str(s) => Ok(s),
+            _ => Err("expected a string".into()),
+        }
+    }
+
+    fn integer(self) -> std::result::Result<usize, String> {
+        match self {
+            Value::Int(n) if n >= 0 => Ok(n as usize),
+            _ => Err("expected a positive integer".into()),
+        }
+    }
+
+    fn list(self) -> std::result::Result<Vec<String>, String> {
+        match self {
+            Value::List(l) => Ok(l),
+            Value::Str(s) => Ok(vec![s]),
+            _ => Err("expected an array of strings".into()),
+        }
+    }
+}
+
+fn parse_value(raw: &str) -> std::result::Result<Value, String> {
+    if let Some(inner) = raw.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+        let items = split_array(inner)?
+            .iter()
+            .map(|item| match parse_value(item)? {
+                Value::Str(s) => Ok(s),
+                _ => Err("arrays may only hold strings".to_string()),
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        return Ok(Value::List(items));
+    }
+    if let Some(inner) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) {
+        return Ok(Value::Str(unescape(inner)));
+    }
+    if let Some(inner) = raw.strip_prefix('\'').and_then(|r| r.strip_suffix('\'')) {
+        return Ok(Value::Str(inner.to_string()));
+    }
+    raw.replace('_', "")
+        .parse()
+        .map(Value::Int)
+        .map_err(|_| format!("unsupported value: {}", raw))
+}
+
+/// Tracks whether characters are inside a string: `"basic"`, where `\`
+/// escapes the next character, or `'literal'`.
+#[derive(Default)]
+struct Quotes {
+    open: Option<char>,
+    escaped: bool,
+}
+
+impl Quotes {
+    /// Takes the next character; true when it is outside any string and
+    /// isn't a quote.
+    fn step(&mut self, c: char) -> bool {
+        match self.open {
+            Some(_) if self.escaped => self.escaped = false,
+            Some('"') if c == '\\' => self.escaped = true,
+            Some(q) if c == q => self.open = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => self.open = Some(c),
+            None => return true,
+        }
+        false
+    }
+}
+
+/// How many `[` outside strings are still unclosed.
+fn open_brackets(s: &str) -> i32 {
+    let mut quotes = Quotes::default();
+    s.chars()
+        .filter(|c| quotes.step(*c))
+        .map(|c| match c {
+            '[' => 1,
+            ']' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Splits `"a", "b,c"` on commas outside quotes.
+fn split_array(inner: &str) -> std::result::Result<Vec<String>, String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut quotes = Quotes::default();
+    for c in inner.chars() {
+        if quotes.step(c) && c == ',' {
+            items.push(std::mem::take(&mut current));
+        } else {
+            current.push(c);
+        }
+    }
+    if quotes.open.is_some() {
+        return Err("unterminated string in array".into());
+    }
+    items.push(current);
+    Ok(items
+        .into_iter()
+        .map(|i| i.trim().to_string())
+        .filter(|i| !i.is_empty())
+        .collect())
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Drops a trailing `# comment` that isn't inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quotes = Quotes::default();
+    for (i, c) in line.char_indices() {
+        if quotes.step(c) && c == '#' {
+            return &line[..i];
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrays_may_span_lines() {
+        let config = Config::parse(
+            "resolvers = [\n  \"1.1.1.1\", # primary\n  \"9.9.9.9:53\",\n]\ndns_concurrency = 10\n",
+        )
+        .unwrap();
+        assert_eq!(config.resolvers, ["1.1.1.1", "9.9.9.9:53"]);
+        assert_eq!(config.dns_concurrency, Some(10));
+    }
+
+    #[test]
+    fn unterminated_arrays_are_an_error() {
+        let err = Config::parse("resolvers = [\n\"1.1.1.1\",\n").unwrap_err();
+        assert_eq!(err.to_string(), "config line 1: unterminated array");
+    }
+
+    #[test]
+    fn escaped_quotes_keep_comments_apart() {
+        let config = Config::parse("user_agent = \"a\\\"#b\" # note\n").unwrap();
+        assert_eq!(config.user_agent.as_deref(), Some("a\"#b"));
+    }
+
+    #[test]
+    fn hashes_and_brackets_in_strings_are_kept() {
+        let config = Config::parse("resolvers = [\"a#b\", 'c]d', \"e,f\"]\n").unwrap();
+        assert_eq!(config.resolvers, ["a#b", "c]d", "e,f"]);
+    }
+
+    #[test]
+    fn sections_hold_keys() {
+        let config =
+            Config::parse("plugin_dir = '/p'\n\n[keys]\nvirustotal = \"vt\"\nchaos = 'c'\n")
+                .unwrap();
+        assert_eq!(config.plugin_dir, Some(PathBuf::from("/p")));
+        assert_eq!(
+            config.keys.keys.get("virustotal").map(String::as_str),
+            Some("vt")
+        );
+        assert_eq!(config.keys.keys.get("chaos").map(String::as_str), Some("c"));
+    }
+
+    #[test]
+    fn wrong_types_name_the_line() {
+        let err = Config::parse("\ndns_concurrency = \"many\"\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "config line 2: expected a positive integer"
+        );
+        assert!(Config::parse("user_agent = bare\n").is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! Records left pointing at something that is gone: a CNAME whose target
+//! no longer exists, or a cloud address whose resource was released.
+//! Unlike [`takeover`](crate::takeover) this needs no known service, so it
+//! also catches decommissioned assets nobody can claim yet.
+
+use crate::cloud::cloud_for_ip;
+use crate::dns::{normalize_name, ResolvedHost, ResolverPool};
+use std::collections::HashSet;
+use std::sync::Arc;
+use trust_dns_resolver::proto::rr::{RData, RecordType};
+
+/// CNAME hops followed before giving up on a chain.
+const MAX_CHAIN: usize = 8;
+
+/// Why a host looks dangling, for hosts with no HTTP answer. `refused` is
+/// whether any attempt had its connection refused.
+pub fn unanswered(host: &ResolvedHost, refused: bool) -> Option<String> {
+    if host.ips.is_empty() {
+        let target = host.cname_chain.last()?;
+        return Some(format!("CNAME target {} does not exist", target));
+    }
+    if !refused {
+        return None;
+    }
+    // Every address has to be in the cloud: one live address elsewhere
+    // means the name still has a home
+    let clouds: Vec<&str> = host
+        .ips
+        .iter()
+        .map(|ip| cloud_for_ip(ip).map(|c| c.name))
+        .collect::<Option<_>>()?;
+    Some(format!(
+        "{} refused connections ({} address)",
+        host.ips.join(", "),
+        clouds[0]
+    ))
+}
+
+/// Why a host looks dangling, from the page its cloud address served.
+pub fn error_page(host: &ResolvedHost, body: &str) -> Option<String> {
+    host.ips.iter().find_map(|ip| {
+        let cloud = cloud_for_ip(ip)?;
+        let page = cloud.error_pages.iter().find(|p| body.contains(*p))?;
+        Some(format!(
+            "{} ({} address) served \"{}\"",
+            ip, cloud.name, page
+        ))
+    })
+}
+
+/// Names that didn't resolve but have a CNAME whose chain ends at a name
+/// that doesn't exist. They come back without addresses so they can be
+/// reported.
+pub async fn dangling_cnames(
+    resolver: Arc<ResolverPool>,
+    names: HashSet<String>,
+) -> Vec<ResolvedHost> {
+    let mut tasks = tokio::task::JoinSet::new();
+    for name in names {
+        let r = resolver.clone();
+        tasks.spawn(async move { check_cname(&r, &name).await });
+    }
+    let mut found = Vec::new();
+    while let Some(res) = tasks.join_next().await {
+        if let Ok(Some(host)) = res {
+            found.push(host);
+        }
+    }
+    found
+}
+
+async fn check_cname(resolver: &ResolverPool, name: &str) -> Option<ResolvedHost> {
+    let mut chain: Vec<String> = Vec::new();
+    let mut current = name.to_string();
+    while chain.len() < MAX_CHAIN {
+        let next = resolver
+            .records(&current, RecordType::CNAME)
+            .await
+            .iter()
+            .find_map(|r| match r.data() {
+                Some(RData::CNAME(target)) => Some(normalize_name(&target.0.to_ascii())),
+                _ => None,
+            });
+        match next {
+            Some(next) if !chain.contains(&next) => {
+                chain.push(next.clone());
+                current = next;
+            }
+            _ => break,
+        }
+    }
+    let target = chain.last()?;
+    // A timeout or SERVFAIL says nothing about the target
+    if !resolver.is_nxdomain(target).await {
+        return None;
+    }
+    Some(ResolvedHost {
+        subdomain: name.to_string(),
+        cname_chain: chain,
+        ..Default::default()
+    })
+}
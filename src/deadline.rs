@@ -0,0 +1,91 @@
+//! `--max-runtime` and `--phase-deadline`: time boxes for a whole run and
+//! for its phases. A phase past its deadline starts no new work; what is
+//! in flight gets [`GRACE`] to finish, and is dropped after that.
+
+use std::future::Future;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::time::timeout_at;
+
+/// The steps of a scan `--phase-deadline` can limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    /// Passive sources, zone transfers and NSEC walks.
+    Discovery,
+    /// Resolving candidates, brute force, permutations and recursion.
+    Dns,
+    /// HTTP probe rounds, with the lookups done for each.
+    Probe,
+    Vhosts,
+    /// RDAP, Shodan, screenshots and related domains after the probe.
+    Enrich,
+}
+
+impl Phase {
+    pub const NAMES: &'static [&'static str] = &["discovery", "dns", "probe", "vhosts", "enrich"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Discovery => "discovery",
+            Phase::Dns => "dns",
+            Phase::Probe => "probe",
+            Phase::Vhosts => "vhosts",
+            Phase::Enrich => "enrich",
+        }
+    }
+}
+
+impl FromStr for Phase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "discovery" | "sources" => Ok(Phase::Discovery),
+            "dns" => Ok(Phase::Dns),
+            "probe" | "http" => Ok(Phase::Probe),
+            "vhosts" => Ok(Phase::Vhosts),
+            "enrich" => Ok(Phase::Enrich),
+            _ => Err(format!(
+                "Unknown phase: {} (known: {})",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// How long in-flight work may run on after its deadline.
+pub const GRACE: Duration = Duration::from_secs(5);
+
+/// A point in time work has to stop at, or none.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    pub const NONE: Deadline = Deadline(None);
+
+    /// `limit` from now, or no deadline without a limit.
+    pub fn after(limit: Option<Duration>) -> Self {
+        Self(limit.map(|d| Instant::now() + d))
+    }
+
+    /// Whichever of the two comes first.
+    pub fn earliest(self, other: Deadline) -> Self {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => Self(Some(a.min(b))),
+            (a, b) => Self(a.or(b)),
+        }
+    }
+
+    pub fn passed(self) -> bool {
+        self.0.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// Runs `fut`, unless it is still running [`GRACE`] after the deadline.
+    pub async fn within<F: Future>(self, fut: F) -> Option<F::Output> {
+        match self.0 {
+            Some(at) => timeout_at((at + GRACE).into(), fut).await.ok(),
+            None => Some(fut.await),
+        }
+    }
+}
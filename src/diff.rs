@@ -0,0 +1,159 @@
+//! Comparing two scans of the same domain.
+
+use crate::{Result, SubdomainResult};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// One attribute of a host that differs between two scans.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub subdomain: String,
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// What happened between an older and a newer scan.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ScanDiff {
+    /// Hosts only in the new scan, leaving out `--known` seed names,
+    /// which were never news.
+    pub added: Vec<SubdomainResult>,
+    /// Hosts only in the old scan.
+    pub removed: Vec<SubdomainResult>,
+    pub changed: Vec<Change>,
+}
+
+impl ScanDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Prints the diff as `[+]`, `[-]` and `[~]` lines on stderr.
+    pub fn print_summary(&self) {
+        eprintln!(
+            "[*] {} new, {} gone, {} changed.",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        );
+        for r in &self.added {
+            eprintln!("[+] {}", r.subdomain);
+        }
+        for r in &self.removed {
+            eprintln!("[-] {}", r.subdomain);
+        }
+        for c in &self.changed {
+            eprintln!("[~] {} {}: {} -> {}", c.subdomain, c.field, c.old, c.new);
+        }
+    }
+}
+
+/// Compares two result sets by subdomain, port and scheme.
+pub fn diff(old: &[SubdomainResult], new: &[SubdomainResult]) -> ScanDiff {
+    let old_by_key: HashMap<Key<'_>, &SubdomainResult> = old.iter().map(|r| (key(r), r)).collect();
+    let new_by_key: HashMap<Key<'_>, &SubdomainResult> = new.iter().map(|r| (key(r), r)).collect();
+
+    let mut out = ScanDiff::default();
+    for r in new {
+        match old_by_key.get(&key(r)) {
+            None if r.source.as_deref() == Some("seed") => {}
+            None => out.added.push(r.clone()),
+            Some(prev) => out.changed.extend(changes(prev, r)),
+        }
+    }
+    for r in old {
+        if !new_by_key.contains_key(&key(r)) {
+            out.removed.push(r.clone());
+        }
+    }
+
+    out.added.sort_by(|a, b| a.subdomain.cmp(&b.subdomain));
+    out.removed.sort_by(|a, b| a.subdomain.cmp(&b.subdomain));
+    out.changed
+        .sort_by(|a, b| (&a.subdomain, a.field).cmp(&(&b.subdomain, b.field)));
+    out
+}
+
+type Key<'a> = (&'a str, Option<u16>, Option<&'a str>);
+
+fn key(r: &SubdomainResult) -> Key<'_> {
+    (r.subdomain.as_str(), r.port, r.scheme.as_deref())
+}
+
+fn changes(old: &SubdomainResult, new: &SubdomainResult) -> Vec<Change> {
+    let fields = [
+        ("ips", set(&old.ips), set(&new.ips)),
+        (
+            "cname_chain",
+            old.cname_chain.join(" -> "),
+            new.cname_chain.join(" -> "),
+        ),
+        ("status_code", opt(old.status_code), opt(new.status_code)),
+        (
+            "final_url",
+            opt(old.final_url.as_ref()),
+            opt(new.final_url.as_ref()),
+        ),
+        ("title", opt(old.title.as_ref()), opt(new.title.as_ref())),
+        ("server", opt(old.server.as_ref()), opt(new.server.as_ref())),
+        (
+            "technologies",
+            set(&old.technologies),
+            set(&new.technologies),
+        ),
+        (
+            "takeover",
+            opt(old.takeover.as_ref().map(|t| &t.service)),
+            opt(new.takeover.as_ref().map(|t| &t.service)),
+        ),
+    ];
+    fields
+        .into_iter()
+        .filter(|(_, a, b)| a != b)
+        .map(|(field, old_value, new_value)| Change {
+            subdomain: crate::report::display_name(new),
+            field,
+            old: old_value,
+            new: new_value,
+        })
+        .collect()
+}
+
+/// Order-insensitive, so a shuffled A record set isn't a change.
+fn set(values: &[String]) -> String {
+    let sorted: BTreeSet<&String> = values.iter().collect();
+    sorted.into_iter().cloned().collect::<Vec<_>>().join(", ")
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Reads results written with `--output-format json` or `jsonl`.
+pub fn load_results(path: &Path) -> Result<Vec<SubdomainResult>> {
+    let text = fs::read_to_string(path)?;
+    if text.trim_start().starts_with('[') {
+        return Ok(serde_json::from_str(&text)?);
+    }
+    // An --envelope document carries them keyed by domain under `results`
+    if let Ok(serde_json::Value::Object(mut doc)) = serde_json::from_str(&text) {
+        if doc.contains_key("schema_version") {
+            let results = doc.remove("results").unwrap_or_default();
+            let groups: BTreeMap<String, Vec<SubdomainResult>> = serde_json::from_value(results)?;
+            return Ok(groups.into_values().flatten().collect());
+        }
+    }
+    // Several domains' json output is an object keyed by domain
+    if let Ok(groups) = serde_json::from_str::<BTreeMap<String, Vec<SubdomainResult>>>(&text) {
+        return Ok(groups.into_values().flatten().collect());
+    }
+    text.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| Ok(serde_json::from_str(l)?))
+        .collect()
+}
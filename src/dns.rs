@@ -0,0 +1,960 @@
+use crate::adaptive::Concurrency;
+use crate::deadline::Deadline;
+use crate::dnscache::DnsCache;
+use crate::metrics::{self, DnsOutcome};
+use crate::progress::Progress;
+use crate::ratelimit::RateLimiter;
+use crate::rawdns::{RawOutcome, RawResolver};
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::{Mutex, OnceCell, Semaphore};
+use tracing::{info, warn};
+use trust_dns_resolver::config::{
+    LookupIpStrategy, NameServerConfigGroup, ResolverConfig, ResolverOpts,
+};
+use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
+use trust_dns_resolver::lookup_ip::LookupIp;
+use trust_dns_resolver::proto::op::ResponseCode;
+use trust_dns_resolver::proto::rr::{RData, Record, RecordType};
+use trust_dns_resolver::TokioAsyncResolver;
+
+const DNS_CONCURRENCY: usize = 200;
+
+/// Google public DNS, the default upstream.
+pub const GOOGLE_DNS: [SocketAddr; 2] = [
+    SocketAddr::new(IpAddr::V4(std::net::Ipv4Addr::new(8, 8, 8, 8)), 53),
+    SocketAddr::new(IpAddr::V4(std::net::Ipv4Addr::new(8, 8, 4, 4)), 53),
+];
+
+/// A name with a well-known answer, asked of every resolver by the health
+/// check, and the addresses it must return.
+const CANARY_NAME: &str = "one.one.one.one";
+const CANARY_ADDRESSES: &[&str] = &[
+    "1.1.1.1",
+    "1.0.0.1",
+    "2606:4700:4700::1111",
+    "2606:4700:4700::1001",
+];
+
+/// Zone under which a random name must not resolve; resolvers that answer
+/// it rewrite NXDOMAINs, which would turn every brute forced name into a
+/// hit.
+const CANARY_ZONE: &str = "example.com";
+
+/// Resolvers slower than this on the health check are dropped.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Lookups between two re-checks of one resolver (the next in rotation)
+/// during a scan.
+const RECHECK_INTERVAL: usize = 1000;
+
+/// A resolver the health check took out of rotation, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Eviction {
+    pub resolver: String,
+    pub reason: String,
+}
+
+/// A name that resolved, with every A and AAAA address it returned.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolvedHost {
+    pub subdomain: String,
+    pub ips: Vec<String>,
+    /// CNAME targets in the order they were followed, e.g.
+    /// `["example.myshopify.com", "shops.myshopify.com"]`.
+    pub cname_chain: Vec<String>,
+    /// Only resolved after a retry.
+    #[serde(default)]
+    pub retried: bool,
+    /// Upstream that gave the answer, e.g. `1.1.1.1:53`.
+    #[serde(default)]
+    pub resolver: Option<String>,
+    /// Time the answering query took.
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    /// Lowest TTL among the answer's records, in seconds.
+    #[serde(default)]
+    pub ttl: Option<u32>,
+}
+
+fn resolver_opts() -> ResolverOpts {
+    let mut opts = ResolverOpts::default();
+    // Ask for both families; the default stops at A when one exists
+    opts.ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+    opts
+}
+
+/// A set of upstream resolvers that queries are spread across round-robin,
+/// so no single provider sees the full query rate.
+///
+/// The concurrency limit is enforced by the pool itself, so scans sharing
+/// one pool share one DNS budget.
+pub struct ResolverPool {
+    resolvers: Vec<TokioAsyncResolver>,
+    /// How each resolver is named in [`ResolvedHost::resolver`].
+    labels: Vec<String>,
+    next: AtomicUsize,
+    permits: Concurrency,
+    limiter: Option<Arc<RateLimiter>>,
+    retries: u32,
+    retry_backoff: Duration,
+    /// `--dns-engine raw`: [`resolve`](Self::resolve) bypasses `resolvers`.
+    raw: Option<RawResolver>,
+    cache: Option<DnsCache>,
+    ip_version: IpVersion,
+    /// Resolvers the health check took out of rotation.
+    evicted: Vec<AtomicBool>,
+    /// Evictions not yet handed out by [`take_evictions`](Self::take_evictions).
+    evictions: std::sync::Mutex<Vec<Eviction>>,
+    /// The startup check, run once however many scans share the pool.
+    checked: OnceCell<()>,
+    /// Set when the startup check found resolvers answering the canaries
+    /// right, so a failed re-check means the resolver went bad rather than
+    /// that the canaries can't be reached from here.
+    monitored: AtomicBool,
+    lookups: AtomicUsize,
+}
+
+impl ResolverPool {
+    /// The classic behaviour: Google public DNS only.
+    pub fn google() -> Self {
+        let mut pool = Self::from_resolvers(vec![TokioAsyncResolver::tokio(
+            ResolverConfig::google(),
+            resolver_opts(),
+        )]);
+        pool.labels = vec!["google".to_string()];
+        pool
+    }
+
+    /// One resolver per upstream. Falls back to Google if `upstreams` is empty.
+    pub fn from_upstreams(upstreams: &[Upstream]) -> Self {
+        if upstreams.is_empty() {
+            return Self::google();
+        }
+        let resolvers = upstreams
+            .iter()
+            .map(|u| {
+                let config = ResolverConfig::from_parts(None, vec![], u.name_servers());
+                TokioAsyncResolver::tokio(config, resolver_opts())
+            })
+            .collect();
+        let mut pool = Self::from_resolvers(resolvers);
+        pool.labels = upstreams.iter().map(|u| u.to_string()).collect();
+        pool
+    }
+
+    pub fn from_resolvers(resolvers: Vec<TokioAsyncResolver>) -> Self {
+        assert!(!resolvers.is_empty(), "resolver pool needs a resolver");
+        Self {
+            labels: (1..=resolvers.len()).map(|i| format!("#{}", i)).collect(),
+            evicted: (0..resolvers.len())
+                .map(|_| AtomicBool::new(false))
+                .collect(),
+            resolvers,
+            next: AtomicUsize::new(0),
+            permits: Concurrency::fixed(DNS_CONCURRENCY),
+            limiter: None,
+            retries: 0,
+            retry_backoff: Duration::ZERO,
+            raw: None,
+            cache: None,
+            ip_version: IpVersion::Any,
+            evictions: std::sync::Mutex::default(),
+            checked: OnceCell::new(),
+            monitored: AtomicBool::new(false),
+            lookups: AtomicUsize::new(0),
+        }
+    }
+
+    /// Caps how many lookups are in flight across everything using the pool.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.permits = Concurrency::fixed(concurrency);
+        self
+    }
+
+    /// Like [`with_concurrency`](Self::with_concurrency), but the limit
+    /// starts at `concurrency` and adapts to how often lookups fail.
+    pub fn with_adaptive_concurrency(mut self, concurrency: usize) -> Self {
+        self.permits = Concurrency::adaptive("DNS", concurrency);
+        self
+    }
+
+    /// Spaces lookups by `limiter`, which may be shared with HTTP probing.
+    pub fn with_rate_limit(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Retries lookups that timed out or got SERVFAIL, each on the next
+    /// resolver in rotation, waiting `backoff` and then twice as long each
+    /// time.
+    pub fn with_retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Resolves names with the raw UDP engine against `servers` instead.
+    /// Other lookups (PTR, NS, wildcard checks) still use the resolvers.
+    pub fn with_raw_engine(mut self, servers: Vec<SocketAddr>) -> Self {
+        self.raw = Some(RawResolver::new(servers));
+        self
+    }
+
+    /// Keeps only the addresses of `version`; names left without any don't
+    /// resolve.
+    pub fn with_ip_version(mut self, version: IpVersion) -> Self {
+        self.ip_version = version;
+        self
+    }
+
+    /// Remembers answers and NXDOMAINs for `cache`'s TTL, so later phases
+    /// and scans sharing the pool don't ask again.
+    pub fn with_cache(mut self, cache: DnsCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// The most lookups that can be in flight, which an adaptive limit
+    /// may not be at right now.
+    pub fn concurrency(&self) -> usize {
+        self.permits.max()
+    }
+
+    pub fn len(&self) -> usize {
+        self.resolvers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resolvers.is_empty()
+    }
+
+    /// Resolvers still in rotation.
+    pub fn healthy(&self) -> usize {
+        self.evicted
+            .iter()
+            .filter(|e| !e.load(Ordering::Relaxed))
+            .count()
+    }
+
+    /// The next resolver in rotation.
+    pub fn next(&self) -> &TokioAsyncResolver {
+        &self.resolvers[self.next_index()]
+    }
+
+    /// The next resolver in rotation that hasn't been evicted.
+    fn next_index(&self) -> usize {
+        let len = self.resolvers.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..len)
+            .map(|k| (start + k) % len)
+            .find(|&i| !self.evicted[i].load(Ordering::Relaxed))
+            .unwrap_or(start % len)
+    }
+
+    /// Whether resolver `i` answers the canaries right: the real addresses
+    /// of [`CANARY_NAME`], nothing for a random name under [`CANARY_ZONE`],
+    /// both within [`CHECK_TIMEOUT`].
+    async fn check(&self, i: usize) -> std::result::Result<(), String> {
+        let ask = |name: String| async move {
+            tokio::time::timeout(CHECK_TIMEOUT, self.lookup_ip_on(i, &name)).await
+        };
+        let known = match ask(CANARY_NAME.to_string()).await {
+            Err(_) => return Err(format!("no answer within {}s", CHECK_TIMEOUT.as_secs())),
+            Ok(Err(e)) if is_transient(&e) => return Err(format!("failing: {}", e)),
+            Ok(Err(_)) => Vec::new(),
+            Ok(Ok(lookup)) => lookup.iter().map(|ip| ip.to_string()).collect(),
+        };
+        if !known
+            .iter()
+            .any(|ip| CANARY_ADDRESSES.contains(&ip.as_str()))
+        {
+            return Err(format!("answered {} with {:?}", CANARY_NAME, known));
+        }
+        let bogus = format!("{}.{}", crate::wildcard::random_label(), CANARY_ZONE);
+        if let Ok(Ok(lookup)) = ask(bogus).await {
+            let ips: Vec<String> = lookup.iter().map(|ip| ip.to_string()).collect();
+            if !ips.is_empty() {
+                return Err(format!("answers names that don't exist, with {:?}", ips));
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes resolver `i` out of rotation, unless it is the last one left.
+    fn evict(&self, i: usize, reason: String) {
+        if self.healthy() <= 1 || self.evicted[i].swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let label = self.labels[i].clone();
+        warn!("Dropping resolver {}: {}", label, reason);
+        if let Some(ref raw) = self.raw {
+            raw.evict(&label);
+        }
+        metrics::resolver_evicted();
+        self.evictions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Eviction {
+                resolver: label,
+                reason,
+            });
+    }
+
+    /// Every [`RECHECK_INTERVAL`]th lookup re-checks the next resolver in
+    /// rotation, so one that starts poisoning answers mid-scan is dropped.
+    async fn recheck(&self) {
+        let n = self.lookups.fetch_add(1, Ordering::Relaxed) + 1;
+        if !n.is_multiple_of(RECHECK_INTERVAL) || !self.monitored.load(Ordering::Relaxed) {
+            return;
+        }
+        let i = (n / RECHECK_INTERVAL) % self.resolvers.len();
+        if self.evicted[i].load(Ordering::Relaxed) {
+            return;
+        }
+        if let Err(reason) = self.check(i).await {
+            self.evict(i, reason);
+        }
+    }
+
+    /// Evictions since the last call.
+    pub fn take_evictions(&self) -> Vec<Eviction> {
+        std::mem::take(&mut *self.evictions.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    pub async fn lookup_ip(&self, name: &str) -> std::result::Result<LookupIp, ResolveError> {
+        self.lookup_ip_on(self.next_index(), name).await
+    }
+
+    async fn lookup_ip_on(
+        &self,
+        i: usize,
+        name: &str,
+    ) -> std::result::Result<LookupIp, ResolveError> {
+        if let Some(ref limiter) = self.limiter {
+            limiter.acquire().await;
+        }
+        self.resolvers[i].lookup_ip(name).await
+    }
+
+    /// Looks up all addresses of `name`, sorted and deduplicated.
+    /// `None` when the name doesn't resolve or has no addresses of the
+    /// pool's IP version.
+    pub async fn resolve(&self, name: &str) -> Option<ResolvedHost> {
+        let mut host = self.resolve_any(name).await?;
+        if self.ip_version != IpVersion::Any {
+            host.ips
+                .retain(|ip| ip.parse().is_ok_and(|ip| self.ip_version.allows(&ip)));
+            if host.ips.is_empty() {
+                return None;
+            }
+        }
+        Some(host)
+    }
+
+    /// [`resolve`](Self::resolve) with both families, as cached.
+    async fn resolve_any(&self, name: &str) -> Option<ResolvedHost> {
+        if let Some(ref cache) = self.cache {
+            if let Some(hit) = cache.get(name) {
+                metrics::dns_cache_hit();
+                return hit;
+            }
+        }
+        let (outcome, host) = {
+            let _permit = self.permits.acquire().await?;
+            match self.raw {
+                Some(ref raw) => self.resolve_raw(raw, name).await,
+                None => self.resolve_stub(name).await,
+            }
+        };
+        metrics::dns_lookup(outcome);
+        self.permits.record(outcome != DnsOutcome::Error);
+        self.recheck().await;
+        // Failures may be transient, so only real answers are remembered
+        if let Some(ref cache) = self.cache {
+            if outcome != DnsOutcome::Error {
+                cache.insert(name, host.clone());
+            }
+        }
+        host
+    }
+
+    async fn resolve_stub(&self, name: &str) -> (DnsOutcome, Option<ResolvedHost>) {
+        let mut attempt = 0;
+        let (i, latency, lookup) = loop {
+            let i = self.next_index();
+            let started = Instant::now();
+            match self.lookup_ip_on(i, name).await {
+                Ok(lookup) => break (i, started.elapsed(), lookup),
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    tokio::time::sleep(
+                        self.retry_backoff
+                            .saturating_mul(2u32.saturating_pow(attempt)),
+                    )
+                    .await;
+                    attempt += 1;
+                }
+                Err(e) if is_transient(&e) => return (DnsOutcome::Error, None),
+                Err(_) => return (DnsOutcome::Unresolved, None),
+            }
+        };
+        let mut ips: Vec<IpAddr> = lookup.iter().collect();
+        if ips.is_empty() {
+            return (DnsOutcome::Unresolved, None);
+        }
+        ips.sort();
+        ips.dedup();
+        let records = lookup.as_lookup().records();
+        let host = ResolvedHost {
+            subdomain: name.to_string(),
+            ips: ips.iter().map(|ip| ip.to_string()).collect(),
+            cname_chain: cname_chain(records, name),
+            retried: attempt > 0,
+            resolver: Some(self.labels[i].clone()),
+            latency_ms: Some(latency.as_millis() as u64),
+            ttl: records.iter().map(|r| r.ttl()).min(),
+        };
+        (DnsOutcome::Resolved, Some(host))
+    }
+
+    async fn resolve_raw(
+        &self,
+        raw: &RawResolver,
+        name: &str,
+    ) -> (DnsOutcome, Option<ResolvedHost>) {
+        if let Some(ref limiter) = self.limiter {
+            limiter.acquire().await;
+        }
+        match raw.lookup(name, self.retries).await {
+            (
+                RawOutcome::Resolved {
+                    ips,
+                    cname_chain,
+                    server,
+                    latency,
+                    ttl,
+                },
+                retried,
+            ) => {
+                let host = ResolvedHost {
+                    subdomain: name.to_string(),
+                    ips: ips.iter().map(|ip| ip.to_string()).collect(),
+                    cname_chain,
+                    retried: retried > 0,
+                    resolver: Some(server.to_string()),
+                    latency_ms: Some(latency.as_millis() as u64),
+                    ttl,
+                };
+                (DnsOutcome::Resolved, Some(host))
+            }
+            (RawOutcome::NoAddress, _) => (DnsOutcome::Unresolved, None),
+            (RawOutcome::Failed, _) => (DnsOutcome::Error, None),
+        }
+    }
+
+    /// Writes the `--dns-cache` file, if there is one.
+    pub fn save_cache(&self) {
+        if let Some(ref cache) = self.cache {
+            if let Err(e) = cache.save() {
+                warn!("Could not write DNS cache: {}", e);
+            }
+        }
+    }
+
+    /// PTR names of `ip`, without the trailing dot.
+    pub async fn reverse(&self, ip: IpAddr) -> Vec<String> {
+        let Some(_permit) = self.permits.acquire().await else {
+            return Vec::new();
+        };
+        if let Some(ref limiter) = self.limiter {
+            limiter.acquire().await;
+        }
+        match self.next().reverse_lookup(ip).await {
+            Ok(lookup) => lookup
+                .iter()
+                .map(|name| name.to_string().trim_end_matches('.').to_lowercase())
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Whether `name` is answered with NXDOMAIN. Timeouts and server
+    /// failures are not.
+    pub async fn is_nxdomain(&self, name: &str) -> bool {
+        let Some(_permit) = self.permits.acquire().await else {
+            return false;
+        };
+        if let Some(ref limiter) = self.limiter {
+            limiter.acquire().await;
+        }
+        match self.next().lookup(name, RecordType::A).await {
+            Ok(_) => false,
+            Err(e) => matches!(
+                e.kind(),
+                ResolveErrorKind::NoRecordsFound { response_code, .. }
+                    if *response_code == ResponseCode::NXDomain
+            ),
+        }
+    }
+
+    /// Records of one type at `name`, following CNAMEs. Empty when there
+    /// are none or the lookup failed.
+    pub async fn records(&self, name: &str, record_type: RecordType) -> Vec<Record> {
+        let Some(_permit) = self.permits.acquire().await else {
+            return Vec::new();
+        };
+        if let Some(ref limiter) = self.limiter {
+            limiter.acquire().await;
+        }
+        match self.next().lookup(name, record_type).await {
+            Ok(lookup) => lookup.records().to_vec(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// Checks every resolver of `pool` against the canaries once, dropping
+/// those that answer wrong, rewrite NXDOMAINs or are too slow. Pools of one
+/// resolver have nothing to fall back on and aren't checked. When no
+/// resolver passes, the canaries are taken to be unreachable from here
+/// (an internal resolver, say) and all of them are kept.
+pub async fn check_health(pool: &Arc<ResolverPool>) {
+    pool.checked
+        .get_or_init(|| async {
+            if pool.len() < 2 {
+                return;
+            }
+            info!("Checking {} resolvers...", pool.len());
+            let mut tasks = tokio::task::JoinSet::new();
+            for i in 0..pool.len() {
+                let pool = pool.clone();
+                tasks.spawn(async move {
+                    let Some(_permit) = pool.permits.acquire().await else {
+                        return (i, Ok(()));
+                    };
+                    (i, pool.check(i).await)
+                });
+            }
+            let mut failed = Vec::new();
+            while let Some(res) = tasks.join_next().await {
+                if let Ok((i, Err(reason))) = res {
+                    failed.push((i, reason));
+                }
+            }
+            if failed.len() == pool.len() {
+                warn!(
+                    "No resolver passed the health check ({} is unreachable?). Keeping all of them.",
+                    CANARY_NAME
+                );
+                return;
+            }
+            failed.sort();
+            for (i, reason) in failed {
+                pool.evict(i, reason);
+            }
+            pool.monitored.store(true, Ordering::Relaxed);
+        })
+        .await;
+}
+
+/// Reverse-resolves every address in `ips`, keeping those with a PTR.
+pub async fn reverse_all(
+    resolver: Arc<ResolverPool>,
+    ips: Vec<String>,
+) -> HashMap<String, Vec<String>> {
+    let mut tasks = tokio::task::JoinSet::new();
+    for ip in ips {
+        let Ok(addr) = ip.parse::<IpAddr>() else {
+            continue;
+        };
+        let r = resolver.clone();
+        tasks.spawn(async move { (ip, r.reverse(addr).await) });
+    }
+    let mut found = HashMap::new();
+    while let Some(res) = tasks.join_next().await {
+        if let Ok((ip, names)) = res {
+            if !names.is_empty() {
+                found.insert(ip, names);
+            }
+        }
+    }
+    found
+}
+
+/// Failures worth another try: timeouts, transport errors and SERVFAIL.
+/// NXDOMAIN and empty answers are final.
+fn is_transient(e: &ResolveError) -> bool {
+    match e.kind() {
+        ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+            *response_code == ResponseCode::ServFail
+        }
+        ResolveErrorKind::Timeout
+        | ResolveErrorKind::Io(_)
+        | ResolveErrorKind::Proto(_)
+        | ResolveErrorKind::NoConnections => true,
+        _ => false,
+    }
+}
+
+impl Default for ResolverPool {
+    fn default() -> Self {
+        Self::google()
+    }
+}
+
+impl fmt::Debug for ResolverPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResolverPool")
+            .field("resolvers", &self.resolvers.len())
+            .field("raw", &self.raw)
+            .finish()
+    }
+}
+
+/// Follows the CNAME records of an answer starting at `name`.
+pub fn cname_chain(records: &[Record], name: &str) -> Vec<String> {
+    let links: HashMap<String, String> = records
+        .iter()
+        .filter_map(|r| match r.data() {
+            Some(RData::CNAME(target)) => Some((
+                normalize_name(&r.name().to_ascii()),
+                normalize_name(&target.0.to_ascii()),
+            )),
+            _ => None,
+        })
+        .collect();
+
+    let mut chain = Vec::new();
+    let mut current = normalize_name(name);
+    while let Some(next) = links.get(&current) {
+        // Guard against CNAME loops
+        if chain.contains(next) || chain.len() > 16 {
+            break;
+        }
+        chain.push(next.clone());
+        current = next.clone();
+    }
+    chain
+}
+
+/// Lowercases and drops the trailing root dot.
+pub fn normalize_name(name: &str) -> String {
+    name.trim_end_matches('.').to_lowercase()
+}
+
+/// Address families a scan keeps, for `--ip-version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IpVersion {
+    /// A and AAAA alike.
+    #[default]
+    Any,
+    /// A records only; names without one don't resolve.
+    V4,
+    /// AAAA records only, and probes connect over IPv6.
+    V6,
+}
+
+impl IpVersion {
+    pub fn allows(&self, ip: &IpAddr) -> bool {
+        match self {
+            Self::Any => true,
+            Self::V4 => ip.is_ipv4(),
+            Self::V6 => ip.is_ipv6(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Any => "any",
+            Self::V4 => "4",
+            Self::V6 => "6",
+        }
+    }
+}
+
+impl std::str::FromStr for IpVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "any" | "both" => Ok(Self::Any),
+            "4" | "ipv4" | "v4" => Ok(Self::V4),
+            "6" | "ipv6" | "v6" => Ok(Self::V6),
+            _ => Err(format!("Unknown IP version: {} (known: 4, 6, any)", s)),
+        }
+    }
+}
+
+/// `"ipv4"` or `"ipv6"`, as results record families.
+pub fn family(ip: &IpAddr) -> &'static str {
+    if ip.is_ipv4() {
+        "ipv4"
+    } else {
+        "ipv6"
+    }
+}
+
+/// Resolution backend for bulk lookups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DnsEngine {
+    /// A full stub resolver per upstream (UDP with TCP fallback, DoT, DoH).
+    #[default]
+    Resolver,
+    /// Hand-built UDP queries over a socket pool, for very large wordlists.
+    /// Plain UDP upstreams only.
+    Raw,
+}
+
+impl std::str::FromStr for DnsEngine {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "resolver" | "default" => Ok(DnsEngine::Resolver),
+            "raw" => Ok(DnsEngine::Raw),
+            _ => Err(format!("Unknown DNS engine: {} (known: resolver, raw)", s)),
+        }
+    }
+}
+
+/// An upstream DNS server and the transport used to reach it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Upstream {
+    /// Plain DNS over UDP (with TCP fallback).
+    Udp(SocketAddr),
+    /// DNS-over-TLS; `name` is checked against the server certificate.
+    Tls { addr: SocketAddr, name: String },
+    /// DNS-over-HTTPS at `https://<name>/dns-query`.
+    Https { addr: SocketAddr, name: String },
+}
+
+impl Upstream {
+    fn name_servers(&self) -> NameServerConfigGroup {
+        match self {
+            Upstream::Udp(addr) => {
+                NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true)
+            }
+            Upstream::Tls { addr, name } => {
+                NameServerConfigGroup::from_ips_tls(&[addr.ip()], addr.port(), name.clone(), true)
+            }
+            Upstream::Https { addr, name } => {
+                NameServerConfigGroup::from_ips_https(&[addr.ip()], addr.port(), name.clone(), true)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Upstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Upstream::Udp(addr) => write!(f, "{}", addr),
+            Upstream::Tls { addr, name } => write!(f, "tls://{}#{}", addr, name),
+            Upstream::Https { name, .. } => write!(f, "https://{}/dns-query", name),
+        }
+    }
+}
+
+/// Parses `1.1.1.1`, `1.1.1.1:5353`, `::1` or `[::1]:53`.
+pub fn parse_resolver(raw: &str) -> Result<Upstream> {
+    let raw = raw.trim();
+    if let Ok(addr) = raw.parse::<SocketAddr>() {
+        return Ok(Upstream::Udp(addr));
+    }
+    match raw.parse::<IpAddr>() {
+        Ok(ip) => Ok(Upstream::Udp(SocketAddr::new(ip, 53))),
+        Err(_) => Err(format!("invalid resolver address: {}", raw).into()),
+    }
+}
+
+/// Parses a DNS-over-TLS server: `1.1.1.1`, `1.1.1.1:853`, `dns.google` or
+/// `1.1.1.1#cloudflare-dns.com` to set the certificate name explicitly.
+pub fn parse_dot(raw: &str) -> Result<Upstream> {
+    let (host, name) = match raw.trim().split_once('#') {
+        Some((h, n)) => (h, Some(n.to_string())),
+        None => (raw.trim(), None),
+    };
+    let (addr, host_name) = bootstrap(host, 853)?;
+    Ok(Upstream::Tls {
+        addr,
+        name: name.unwrap_or(host_name),
+    })
+}
+
+/// Parses a DNS-over-HTTPS endpoint such as `https://cloudflare-dns.com/dns-query`.
+///
+/// The server hostname is looked up once with the system resolver.
+pub fn parse_doh(raw: &str) -> Result<Upstream> {
+    let url = reqwest::Url::parse(raw.trim())?;
+    if url.scheme() != "https" {
+        return Err(format!("DoH endpoint must be https: {}", raw).into());
+    }
+    if !matches!(url.path(), "" | "/" | "/dns-query") {
+        return Err(format!("only the /dns-query path is supported: {}", raw).into());
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("DoH endpoint has no host: {}", raw))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = url.port().unwrap_or(443);
+    let (addr, name) = bootstrap(host, port)?;
+    Ok(Upstream::Https { addr, name })
+}
+
+/// Turns `host[:port]` into an address plus the name to verify TLS against.
+fn bootstrap(host: &str, default_port: u16) -> Result<(SocketAddr, String)> {
+    if let Ok(addr) = host.parse::<SocketAddr>() {
+        return Ok((addr, addr.ip().to_string()));
+    }
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok((SocketAddr::new(ip, default_port), ip.to_string()));
+    }
+    let (name, port) = match host.rsplit_once(':') {
+        Some((n, p)) => (n, p.parse::<u16>()?),
+        None => (host, default_port),
+    };
+    let addr = (name, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| format!("could not resolve {}", name))?;
+    Ok((addr, name.to_string()))
+}
+
+/// Reads one resolver per line, skipping blanks and `#` comments.
+pub fn load_resolvers(path: &Path) -> Result<Vec<Upstream>> {
+    let text = std::fs::read_to_string(path)?;
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(parse_resolver)
+        .collect()
+}
+
+pub async fn verify_dns(
+    resolver: Arc<ResolverPool>,
+    candidates: HashSet<String>,
+) -> Vec<ResolvedHost> {
+    verify_dns_until(resolver, candidates, Deadline::NONE).await
+}
+
+/// Like [`verify_dns`], starting no lookup once `deadline` has passed and
+/// giving up on those in flight after its grace period.
+pub async fn verify_dns_until(
+    resolver: Arc<ResolverPool>,
+    candidates: HashSet<String>,
+    deadline: Deadline,
+) -> Vec<ResolvedHost> {
+    let semaphore = Arc::new(Semaphore::new(resolver.concurrency()));
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let bar = Arc::new(Progress::new("DNS", candidates.len() as u64));
+    let mut tasks = Vec::new();
+
+    for sub in candidates {
+        let r = resolver.clone();
+        let s = semaphore.clone();
+        let res_list = results.clone();
+        let progress = bar.clone();
+
+        tasks.push(tokio::spawn(async move {
+            let _permit = s.acquire().await.unwrap();
+            if !deadline.passed() {
+                if let Some(host) = r.resolve(&sub).await {
+                    let mut lock = res_list.lock().await;
+                    lock.push(host);
+                }
+            }
+            progress.inc();
+        }));
+    }
+
+    for t in &mut tasks {
+        if deadline.within(t).await.is_none() {
+            break;
+        }
+    }
+    tasks.iter().for_each(|t| t.abort());
+
+    let lock = results.lock().await;
+    lock.clone()
+}
+
+/// Resolves `<word>.<domain>` for every line of a wordlist.
+///
+/// The file is streamed and a permit is taken before each lookup is spawned,
+/// so only the pool's concurrency limit worth of names is in memory at
+/// once, regardless of the wordlist size. Names in `skip` are not queried
+/// again.
+pub async fn brute_force(
+    resolver: Arc<ResolverPool>,
+    domain: &str,
+    wordlist: &Path,
+    skip: &HashSet<String>,
+) -> Result<Vec<ResolvedHost>> {
+    brute_force_until(resolver, domain, wordlist, skip, Deadline::NONE).await
+}
+
+/// Like [`brute_force`], reading no more words once `deadline` has passed.
+pub async fn brute_force_until(
+    resolver: Arc<ResolverPool>,
+    domain: &str,
+    wordlist: &Path,
+    skip: &HashSet<String>,
+    deadline: Deadline,
+) -> Result<Vec<ResolvedHost>> {
+    let file = File::open(wordlist).await?;
+    let mut lines = BufReader::new(file).split(b'\n');
+
+    let semaphore = Arc::new(Semaphore::new(resolver.concurrency()));
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let bar = Arc::new(Progress::new("brute force", 0));
+
+    while let Some(line) = lines.next_segment().await? {
+        if deadline.passed() {
+            break;
+        }
+        // Downloaded lists carry the odd Latin-1 word, which is no DNS
+        // label anyway
+        let Ok(line) = String::from_utf8(line) else {
+            continue;
+        };
+        let word = line.trim().trim_end_matches('.').to_lowercase();
+        if word.is_empty() || word.starts_with('#') {
+            continue;
+        }
+        let sub = format!("{}.{}", word, domain);
+        if skip.contains(&sub) {
+            continue;
+        }
+
+        let permit = semaphore.clone().acquire_owned().await?;
+        let r = resolver.clone();
+        let res_list = results.clone();
+        let progress = bar.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            if let Some(host) = r.resolve(&sub).await {
+                let mut lock = res_list.lock().await;
+                lock.push(host);
+            }
+            progress.inc();
+        });
+    }
+
+    // Wait for the in-flight lookups by draining every permit
+    let drained = deadline
+        .within(semaphore.acquire_many(resolver.concurrency() as u32))
+        .await;
+    let _all = drained.transpose()?;
+
+    let lock = results.lock().await;
+    Ok(lock.clone())
+}
@@ -0,0 +1,117 @@
+//! Answers and NXDOMAINs remembered by [`ResolverPool`](crate::ResolverPool),
+//! so recursion, permutations and repeat scans don't resolve a name twice.
+//! Optionally kept in a file between runs (`--dns-cache`).
+
+use crate::dns::ResolvedHost;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_DNS_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+/// Entries beyond this aren't added, so a huge brute force can't exhaust
+/// memory with NXDOMAINs.
+const MAX_ENTRIES: usize = 2_000_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    /// Unix timestamp after which the entry is ignored.
+    expires: u64,
+    /// `None` for a name that doesn't resolve.
+    #[serde(default)]
+    host: Option<ResolvedHost>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    entries: HashMap<String, Entry>,
+}
+
+#[derive(Debug)]
+pub struct DnsCache {
+    ttl: Duration,
+    path: Option<PathBuf>,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl DnsCache {
+    /// An in-memory cache for this process only.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            path: None,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A cache loaded from `path`, if it exists, and saved back by
+    /// [`save`](Self::save). Expired entries are dropped on load.
+    pub fn open(path: &Path, ttl: Duration) -> Result<Self> {
+        let file: CacheFile = match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => CacheFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let now = now();
+        let mut entries = file.entries;
+        entries.retain(|_, e| e.expires > now);
+        Ok(Self {
+            ttl,
+            path: Some(path.to_path_buf()),
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// `Some(None)` for a cached NXDOMAIN, `None` when nothing is cached.
+    pub fn get(&self, name: &str) -> Option<Option<ResolvedHost>> {
+        let entries = self.lock();
+        let entry = entries.get(name)?;
+        (entry.expires > now()).then(|| entry.host.clone())
+    }
+
+    pub fn insert(&self, name: &str, host: Option<ResolvedHost>) {
+        let mut entries = self.lock();
+        if entries.len() >= MAX_ENTRIES && !entries.contains_key(name) {
+            return;
+        }
+        let expires = now() + self.ttl.as_secs();
+        entries.insert(name.to_string(), Entry { expires, host });
+    }
+
+    /// Writes the unexpired entries to the file given to [`open`](Self::open).
+    pub fn save(&self) -> Result<()> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        let now = now();
+        let file = CacheFile {
+            entries: self
+                .lock()
+                .iter()
+                .filter(|(_, e)| e.expires > now)
+                .map(|(k, e)| (k.clone(), e.clone()))
+                .collect(),
+        };
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&file)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,406 @@
+//! DNSSEC for `--dnssec` and `--nsec-walk`. Validation is left to the
+//! upstream resolver: queries set the DO and AD bits and the answer's AD
+//! flag tells whether the name validated. Zones signed with plain NSEC
+//! chain their names together, so the chain can be followed from the apex
+//! to list the whole zone.
+
+use crate::dns::{normalize_name, ResolverPool, Upstream, GOOGLE_DNS};
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::Semaphore;
+use tokio::time::timeout;
+use tracing::debug;
+use trust_dns_resolver::proto::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns_resolver::proto::rr::{Name, RecordType};
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Advertised EDNS payload size, the DNS flag day recommendation.
+const EDNS_PAYLOAD: u16 = 1232;
+
+/// Validation queries in flight at once.
+const CHECK_CONCURRENCY: usize = 50;
+
+/// Names followed through an NSEC chain before giving up on it.
+pub const MAX_WALK: usize = 10_000;
+
+/// What the validating resolver made of a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnssecStatus {
+    /// The answer validated (AD set).
+    Secure,
+    /// The zone isn't signed, or the chain of trust stops above it.
+    Insecure,
+    /// Signed but failing validation: SERVFAIL unless checking is
+    /// disabled. Validating resolvers won't resolve the name at all.
+    Bogus,
+}
+
+impl DnssecStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Secure => "secure",
+            Self::Insecure => "insecure",
+            Self::Bogus => "bogus",
+        }
+    }
+}
+
+/// The plain UDP resolvers among `upstreams` to ask, or Google's.
+pub fn validators(upstreams: &[Upstream]) -> Vec<SocketAddr> {
+    let servers: Vec<SocketAddr> = upstreams
+        .iter()
+        .filter_map(|u| match u {
+            Upstream::Udp(addr) => Some(*addr),
+            _ => None,
+        })
+        .collect();
+    if servers.is_empty() {
+        GOOGLE_DNS.to_vec()
+    } else {
+        servers
+    }
+}
+
+/// How `name` fares with the validating resolver at `server`. `None` when
+/// it didn't answer or failed with checking disabled too.
+pub async fn status(server: SocketAddr, name: &str) -> Option<DnssecStatus> {
+    let answer = exchange(server, name, RecordType::A, true, false)
+        .await
+        .map_err(|e| debug!("DNSSEC check of {} failed: {}", name, e))
+        .ok()?;
+    match answer.response_code() {
+        ResponseCode::NoError | ResponseCode::NXDomain if answer.authentic_data() => {
+            Some(DnssecStatus::Secure)
+        }
+        ResponseCode::NoError | ResponseCode::NXDomain => Some(DnssecStatus::Insecure),
+        ResponseCode::ServFail => {
+            // A name that only resolves without validation is bogus
+            let unchecked = exchange(server, name, RecordType::A, true, true)
+                .await
+                .ok()?;
+            matches!(
+                unchecked.response_code(),
+                ResponseCode::NoError | ResponseCode::NXDomain
+            )
+            .then_some(DnssecStatus::Bogus)
+        }
+        _ => None,
+    }
+}
+
+/// The status of every name, spread over `servers`. Names without one are
+/// left out.
+pub async fn status_all(
+    servers: &[SocketAddr],
+    names: Vec<String>,
+) -> HashMap<String, DnssecStatus> {
+    let limit = Arc::new(Semaphore::new(CHECK_CONCURRENCY));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (i, name) in names.into_iter().enumerate() {
+        let server = servers[i % servers.len()];
+        let limit = limit.clone();
+        tasks.spawn(async move {
+            let _permit = limit.acquire_owned().await.ok()?;
+            status(server, &name).await.map(|s| (name, s))
+        });
+    }
+    let mut found = HashMap::new();
+    while let Some(res) = tasks.join_next().await {
+        if let Ok(Some((name, status))) = res {
+            found.insert(name, status);
+        }
+    }
+    found
+}
+
+/// The names an NSEC chain gave away.
+#[derive(Debug, Clone)]
+pub struct NsecWalk {
+    /// The nameserver that was walked.
+    pub nameserver: String,
+    /// Every in-zone owner name on the chain, the apex included.
+    pub names: HashSet<String>,
+    /// The chain led back to the apex, so `names` is the whole zone
+    /// (delegated child zones aside).
+    pub complete: bool,
+}
+
+/// Follows the NSEC chain of `domain` on its authoritative nameservers,
+/// starting from the apex. Fails with the reason for zones that can't be
+/// walked: unsigned, NSEC3 or synthesised ("black lies") NSEC records.
+pub async fn walk_nsec(resolver: &ResolverPool, domain: &str) -> Result<NsecWalk> {
+    let ns_lookup = resolver.next().ns_lookup(domain).await?;
+    let nameservers: Vec<String> = ns_lookup
+        .iter()
+        .map(|ns| normalize_name(&ns.0.to_ascii()))
+        .collect();
+
+    let mut last_error = None;
+    for ns in nameservers {
+        let Some(host) = resolver.resolve(&ns).await else {
+            continue;
+        };
+        for ip in host.ips {
+            let Ok(ip) = ip.parse::<IpAddr>() else {
+                continue;
+            };
+            match walk(SocketAddr::new(ip, 53), domain).await {
+                Ok((mut names, complete)) => {
+                    // Wildcards are on the chain like any other owner
+                    names.retain(|n| !n.contains('*'));
+                    return Ok(NsecWalk {
+                        nameserver: ns,
+                        names,
+                        complete,
+                    });
+                }
+                Err(Unwalkable::Zone(why)) => return Err(why.into()),
+                Err(Unwalkable::Server(e)) => {
+                    debug!("NSEC walk of {} on {} failed: {}", domain, ns, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| "no nameserver could be reached".into())
+        .into())
+}
+
+/// Why a walk stopped short: the zone itself, so other nameservers would
+/// answer the same, or the server asked.
+enum Unwalkable {
+    Zone(&'static str),
+    Server(String),
+}
+
+/// Asks `addr` for the NSEC record of each name in turn, starting at the
+/// apex, until the chain wraps around or leaves the zone.
+async fn walk(
+    addr: SocketAddr,
+    domain: &str,
+) -> std::result::Result<(HashSet<String>, bool), Unwalkable> {
+    let suffix = format!(".{}", domain);
+    let mut names = HashSet::from([domain.to_string()]);
+    let mut name = domain.to_string();
+    for _ in 0..MAX_WALK {
+        let answer = exchange(addr, &name, RecordType::NSEC, false, false)
+            .await
+            .map_err(|e| Unwalkable::Server(e.to_string()))?;
+        if !matches!(
+            answer.response_code(),
+            ResponseCode::NoError | ResponseCode::NXDomain
+        ) {
+            return Err(Unwalkable::Server(answer.response_code().to_string()));
+        }
+        let Some(next) = answer.next_name(&name) else {
+            return Err(Unwalkable::Zone(match (answer.has_nsec3(), names.len()) {
+                (true, _) => "the zone uses NSEC3, whose names are hashed",
+                (false, 1) => "the zone is not signed with NSEC",
+                // The chain broke off part way
+                (false, _) => return Ok((names, false)),
+            }));
+        };
+        let Some(next) = next else {
+            return Err(Unwalkable::Zone(
+                "the nameserver synthesises NSEC records for each query",
+            ));
+        };
+        if next == domain {
+            return Ok((names, true));
+        }
+        if !next.ends_with(&suffix) || !names.insert(next.clone()) {
+            return Ok((names, false));
+        }
+        name = next;
+    }
+    debug!("NSEC walk of {} stopped after {} names", domain, MAX_WALK);
+    Ok((names, false))
+}
+
+/// Record types read from answers.
+const NSEC: u16 = 47;
+const NSEC3: u16 = 50;
+
+/// An answer read straight off the wire: trust-dns only decodes NSEC and
+/// RRSIG records with its DNSSEC support, which isn't built in.
+struct Answer {
+    id: u16,
+    flags: u16,
+    /// Answer and authority records; additionals aren't needed.
+    records: Vec<WireRecord>,
+}
+
+struct WireRecord {
+    authority: bool,
+    owner: String,
+    record_type: u16,
+    rdata: Vec<u8>,
+}
+
+impl Answer {
+    fn parse(msg: &[u8]) -> Option<Self> {
+        let word = |i: usize| Some(u16::from_be_bytes([*msg.get(i)?, *msg.get(i + 1)?]));
+        let (id, flags) = (word(0)?, word(2)?);
+        let (questions, answers, authority) = (word(4)?, word(6)?, word(8)?);
+        let mut i = 12;
+        for _ in 0..questions {
+            i = read_name(msg, i)?.1 + 4;
+        }
+        let mut records = Vec::new();
+        for n in 0..answers + authority {
+            let (labels, end) = read_name(msg, i)?;
+            let len = word(end + 8)? as usize;
+            let rdata = msg.get(end + 10..end + 10 + len)?.to_vec();
+            records.push(WireRecord {
+                authority: n >= answers,
+                owner: to_name(&labels),
+                record_type: word(end)?,
+                rdata,
+            });
+            i = end + 10 + len;
+        }
+        Some(Self { id, flags, records })
+    }
+
+    fn response_code(&self) -> ResponseCode {
+        ResponseCode::from_low((self.flags & 0x000f) as u8)
+    }
+
+    fn truncated(&self) -> bool {
+        self.flags & 0x0200 != 0
+    }
+
+    fn authentic_data(&self) -> bool {
+        self.flags & 0x0020 != 0
+    }
+
+    /// The next name of the NSEC record owned by `owner`, looked for among
+    /// the answers and then the authority section (a delegation keeps its
+    /// NSEC there). `Some(None)` for the `\000.` successor minimal-answer
+    /// servers make up to cover nothing but the query.
+    fn next_name(&self, owner: &str) -> Option<Option<String>> {
+        let is_owner = |r: &&WireRecord| r.record_type == NSEC && r.owner == owner;
+        let record = self
+            .records
+            .iter()
+            .filter(|r| !r.authority)
+            .find(is_owner)
+            .or_else(|| self.records.iter().find(is_owner))?;
+        // The next name is never compressed
+        let (labels, _) = read_name(&record.rdata, 0)?;
+        if labels.first().is_some_and(|l| l.as_slice() == [0]) {
+            return Some(None);
+        }
+        Some(Some(to_name(&labels)))
+    }
+
+    fn has_nsec3(&self) -> bool {
+        self.records.iter().any(|r| r.record_type == NSEC3)
+    }
+}
+
+/// The labels of the name at `i` in `msg`, following compression
+/// pointers, and where the name ends.
+fn read_name(msg: &[u8], mut i: usize) -> Option<(Vec<Vec<u8>>, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Bounds pointer loops
+    for _ in 0..128 {
+        let len = *msg.get(i)? as usize;
+        match len {
+            0 => return Some((labels, end.unwrap_or(i + 1))),
+            1..=63 => {
+                labels.push(msg.get(i + 1..i + 1 + len)?.to_vec());
+                i += 1 + len;
+            }
+            _ if len & 0xc0 == 0xc0 => {
+                end.get_or_insert(i + 2);
+                i = ((len & 0x3f) << 8) | *msg.get(i + 1)? as usize;
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+fn to_name(labels: &[Vec<u8>]) -> String {
+    labels
+        .iter()
+        .map(|l| String::from_utf8_lossy(l).to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// One question with the DO bit set, over UDP and again over TCP if the
+/// answer was truncated. `recurse` asks a resolver rather than an
+/// authoritative server, with AD set to ask for the validation outcome and
+/// `unchecked` to turn validation off.
+async fn exchange(
+    server: SocketAddr,
+    name: &str,
+    record_type: RecordType,
+    recurse: bool,
+    unchecked: bool,
+) -> Result<Answer> {
+    let mut query = Message::new();
+    query
+        .set_id(rand::random())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(recurse)
+        .set_authentic_data(recurse)
+        .set_checking_disabled(unchecked)
+        .add_query(Query::query(Name::from_str(name)?, record_type));
+    let mut edns = Edns::new();
+    edns.set_max_payload(EDNS_PAYLOAD).set_dnssec_ok(true);
+    query.set_edns(edns);
+    let payload = query.to_vec()?;
+
+    let local = if server.is_ipv6() {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(server).await?;
+    socket.send(&payload).await?;
+    let mut buf = vec![0u8; 65535];
+    let answer = loop {
+        let len = timeout(QUERY_TIMEOUT, socket.recv(&mut buf))
+            .await
+            .map_err(|_| "timed out")??;
+        match Answer::parse(&buf[..len]) {
+            Some(answer) if answer.id == query.id() => break answer,
+            _ => continue,
+        }
+    };
+    if !answer.truncated() {
+        return Ok(answer);
+    }
+
+    let mut stream = timeout(QUERY_TIMEOUT, TcpStream::connect(server))
+        .await
+        .map_err(|_| "timed out")??;
+    stream.write_u16(payload.len() as u16).await?;
+    stream.write_all(&payload).await?;
+    let read = async {
+        let len = stream.read_u16().await? as usize;
+        let mut buf = vec![0u8; len];
+        stream.read_exact(&mut buf).await?;
+        Ok::<_, std::io::Error>(buf)
+    };
+    let buf = timeout(QUERY_TIMEOUT, read)
+        .await
+        .map_err(|_| "timed out")??;
+    Ok(Answer::parse(&buf).ok_or("malformed answer")?)
+}
@@ -0,0 +1,1645 @@
+use crate::adaptive::Concurrency;
+use crate::axfr::attempt_axfr;
+use crate::banner::BannerScope;
+use crate::checkpoint::Checkpoint;
+use crate::deadline::{Deadline, Phase};
+use crate::dns::{
+    brute_force_until, check_health, reverse_all, verify_dns_until, DnsEngine, ResolvedHost,
+    ResolverPool, Upstream, GOOGLE_DNS,
+};
+use crate::dnscache::DnsCache;
+use crate::dnssec::{self, DnssecStatus};
+use crate::enrich::geoip::GeoIp;
+use crate::enrich::rdap::Registration;
+use crate::issues::ScanIssues;
+use crate::ratelimit::{Budget, HostRateLimiter, RateLimiter};
+use crate::related::{self, RelatedDomain};
+use crate::sources::cache::SourceCache;
+use crate::wildcard::{
+    body_similarity, detect_wildcards, profile_for, wildcard_match_reason, WildcardProfile,
+};
+use crate::{
+    banner, cdn, dangling, detect_wildcard, enrich, markov, metrics, permute, portscan, probe,
+    records, sources, tags, vhost, wordlists, ScanOptions, SubdomainResult, TOP_SUBDOMAINS,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinSet;
+use tracing::{debug, info, warn};
+
+/// How many names each phase of a domain's scan handled.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanStats {
+    /// Passive sources queried.
+    #[serde(default)]
+    pub sources: usize,
+    /// Those of them that failed or stayed rate limited.
+    #[serde(default)]
+    pub sources_failed: usize,
+    /// Names looked up, from every source, list and mutation.
+    pub candidates: usize,
+    pub resolved: usize,
+    /// Results out of the HTTP probe, before wildcard and output filters.
+    pub probed: usize,
+    pub wildcard_filtered: usize,
+    pub out_of_scope: usize,
+    /// Dropped by the output filters.
+    pub unmatched: usize,
+    pub vhosts: usize,
+    pub results: usize,
+    /// Phases (`discovery`, `dns`, `probe`, ...) cut short by
+    /// `--max-runtime` or `--phase-deadline`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub truncated: Vec<String>,
+}
+
+impl ScanStats {
+    /// Whether discovery came back empty-handed because of failures rather
+    /// than because the domain has nothing to find.
+    pub fn all_sources_failed(&self) -> bool {
+        self.sources > 0 && self.sources_failed == self.sources
+    }
+}
+
+/// Hostnames outside the scanned domain, keyed by the address they share
+/// with it.
+pub type Neighbors = BTreeMap<String, Vec<String>>;
+
+/// Runs the full enumeration pipeline for a domain.
+#[derive(Debug, Clone)]
+pub struct SubpeekEngine {
+    options: ScanOptions,
+    resolvers: Arc<ResolverPool>,
+    geoip: Option<Arc<GeoIp>>,
+    checkpoint: Option<Arc<Checkpoint>>,
+    stats: Arc<Mutex<HashMap<String, ScanStats>>>,
+    issues: Arc<Mutex<HashMap<String, ScanIssues>>>,
+    neighbors: Arc<Mutex<HashMap<String, Neighbors>>>,
+    related: Arc<Mutex<HashMap<String, Vec<RelatedDomain>>>>,
+    wildcards: Arc<Mutex<HashMap<String, Vec<WildcardProfile>>>>,
+    registrations: Arc<Mutex<HashMap<String, Registration>>>,
+}
+
+impl Default for SubpeekEngine {
+    fn default() -> Self {
+        Self::with_options(ScanOptions::default())
+    }
+}
+
+impl SubpeekEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(mut options: ScanOptions) -> Self {
+        if options.probe.proxy.is_none() {
+            options.probe.proxy = options.proxy.clone();
+        }
+        if options.probe.shared_permits.is_none() {
+            let n = options.probe.concurrency;
+            options.probe.shared_permits = Some(match options.adaptive_concurrency {
+                true => Concurrency::adaptive("HTTP", n),
+                false => Concurrency::fixed(n),
+            });
+        }
+        options.probe.retries = options.retries;
+        options.probe.ip_version = options.ip_version;
+        options.probe.retry_backoff = options.retry_backoff;
+        let pool = ResolverPool::from_upstreams(&options.resolvers);
+        let mut pool = match options.adaptive_concurrency {
+            true => pool.with_adaptive_concurrency(options.dns_concurrency),
+            false => pool.with_concurrency(options.dns_concurrency),
+        }
+        .with_retries(options.retries, options.retry_backoff)
+        .with_ip_version(options.ip_version);
+        if options.dns_engine == DnsEngine::Raw {
+            let mut servers: Vec<SocketAddr> = options
+                .resolvers
+                .iter()
+                .filter_map(|u| match u {
+                    Upstream::Udp(addr) => Some(*addr),
+                    _ => None,
+                })
+                .collect();
+            if servers.is_empty() {
+                servers = GOOGLE_DNS.to_vec();
+            }
+            pool = pool.with_raw_engine(servers);
+        }
+        if !options.dns_cache_ttl.is_zero() {
+            let ttl = options.dns_cache_ttl;
+            let cache = match options.dns_cache {
+                Some(ref path) => DnsCache::open(path, ttl).unwrap_or_else(|e| {
+                    warn!("Could not load DNS cache {}: {}", path.display(), e);
+                    DnsCache::new(ttl)
+                }),
+                None => DnsCache::new(ttl),
+            };
+            pool = pool.with_cache(cache);
+        }
+        if let Some(rate) = options.rate {
+            let limiter = Arc::new(RateLimiter::new(rate));
+            options.probe.rate_limits.global = Some(limiter.clone());
+            pool = pool.with_rate_limit(limiter);
+        }
+        if let Some(rate) = options.per_host_rate {
+            options.probe.rate_limits.per_host = Some(Arc::new(HostRateLimiter::new(rate)));
+        }
+        if options.max_requests.is_some() || options.max_bandwidth.is_some() {
+            options.probe.rate_limits.budget = Some(Arc::new(Budget::new(
+                options.max_requests,
+                options.max_bandwidth,
+            )));
+        }
+        let resolvers = Arc::new(pool);
+        let geoip = options
+            .geoip_db
+            .as_ref()
+            .and_then(|path| match GeoIp::open(path) {
+                Ok(db) => Some(Arc::new(db)),
+                Err(e) => {
+                    warn!("Could not load GeoIP database {}: {}", path.display(), e);
+                    None
+                }
+            });
+        let checkpoint = options
+            .resume
+            .as_ref()
+            .and_then(|path| match Checkpoint::open(path) {
+                Ok(c) => Some(Arc::new(c)),
+                Err(e) => {
+                    warn!(
+                        "Could not load checkpoint {}: {}. Not checkpointing this scan.",
+                        path.display(),
+                        e
+                    );
+                    None
+                }
+            });
+        Self {
+            options,
+            resolvers,
+            geoip,
+            checkpoint,
+            stats: Arc::default(),
+            issues: Arc::default(),
+            neighbors: Arc::default(),
+            related: Arc::default(),
+            wildcards: Arc::default(),
+            registrations: Arc::default(),
+        }
+    }
+
+    pub fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    /// Phase counts of the last finished scan of `domain`.
+    pub fn stats(&self, domain: &str) -> Option<ScanStats> {
+        let stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        stats.get(domain).cloned()
+    }
+
+    /// Errors and warnings of the last finished scan of `domain`.
+    pub fn issues(&self, domain: &str) -> ScanIssues {
+        let issues = self.issues.lock().unwrap_or_else(|e| e.into_inner());
+        issues.get(domain).cloned().unwrap_or_default()
+    }
+
+    /// Out-of-scope hostnames `--reverse-ip` found on the addresses of the
+    /// last finished scan of `domain`.
+    pub fn neighbors(&self, domain: &str) -> Neighbors {
+        let neighbors = self.neighbors.lock().unwrap_or_else(|e| e.into_inner());
+        neighbors.get(domain).cloned().unwrap_or_default()
+    }
+
+    /// Wildcard records found in the last finished scan of `domain`, the
+    /// apex's own first.
+    pub fn wildcards(&self, domain: &str) -> Vec<WildcardProfile> {
+        let wildcards = self.wildcards.lock().unwrap_or_else(|e| e.into_inner());
+        wildcards.get(domain).cloned().unwrap_or_default()
+    }
+
+    /// The `--rdap` registration of the apex of `domain`, from its last
+    /// finished scan.
+    pub fn registration(&self, domain: &str) -> Option<Registration> {
+        let registrations = self.registrations.lock().unwrap_or_else(|e| e.into_inner());
+        registrations.get(domain).cloned()
+    }
+
+    /// Apexes `--find-related` suggested in the last finished scan of
+    /// `domain`.
+    pub fn related_domains(&self, domain: &str) -> Vec<RelatedDomain> {
+        let related = self.related.lock().unwrap_or_else(|e| e.into_inner());
+        related.get(domain).cloned().unwrap_or_default()
+    }
+
+    /// Writes the `--resume` state file and the `--dns-cache` now, e.g.
+    /// before exiting on Ctrl+C.
+    pub fn save_checkpoint(&self) {
+        self.resolvers.save_cache();
+        if let Some(ref c) = self.checkpoint {
+            match c.save() {
+                Ok(()) => info!("Saved progress to {}.", c.path().display()),
+                Err(e) => warn!("Could not write checkpoint {}: {}", c.path().display(), e),
+            }
+        }
+    }
+
+    pub async fn run(&self, domain: &str) -> Vec<SubdomainResult> {
+        self.run_streaming(domain, |_| {}).await
+    }
+
+    /// Like [`run`](Self::run), calling `on_result` with every host as soon
+    /// as it has been probed and passed the wildcard and output filters.
+    ///
+    /// Enrichment that needs the whole result set (Shodan ports, screenshots)
+    /// runs after the last callback, so streamed results don't carry it.
+    /// Callbacks come in the order hosts finish; the returned results are
+    /// [sorted](Self::sort).
+    pub async fn run_streaming<F>(&self, domain: &str, on_result: F) -> Vec<SubdomainResult>
+    where
+        F: FnMut(&SubdomainResult),
+    {
+        self.reset_budget();
+        let deadline = Deadline::after(self.options.max_runtime);
+        self.run_until(domain, deadline, on_result).await
+    }
+
+    /// `--max-requests` and `--max-bandwidth` hold per run, not per engine.
+    fn reset_budget(&self) {
+        if let Some(ref budget) = self.options.probe.rate_limits.budget {
+            budget.reset();
+        }
+    }
+
+    /// A sorted scan of `domain` that has to end at `deadline`.
+    async fn run_until<F>(
+        &self,
+        domain: &str,
+        deadline: Deadline,
+        on_result: F,
+    ) -> Vec<SubdomainResult>
+    where
+        F: FnMut(&SubdomainResult),
+    {
+        let mut results = self.scan(domain, deadline, on_result).await;
+        self.sort(&mut results);
+        results
+    }
+
+    /// Puts results in name, port and scheme order, unless `--unsorted`
+    /// asked for the order they finished in.
+    pub fn sort(&self, results: &mut [SubdomainResult]) {
+        if self.options.sorted {
+            results.sort_by(|a, b| {
+                a.subdomain
+                    .cmp(&b.subdomain)
+                    .then(a.port.cmp(&b.port))
+                    .then_with(|| a.scheme.cmp(&b.scheme))
+            });
+        }
+    }
+
+    async fn scan<F>(
+        &self,
+        domain: &str,
+        deadline: Deadline,
+        mut on_result: F,
+    ) -> Vec<SubdomainResult>
+    where
+        F: FnMut(&SubdomainResult),
+    {
+        let resumed = self.checkpoint.as_ref().and_then(|c| c.domain(domain));
+        if let Some(saved) = resumed.as_ref().filter(|saved| saved.done) {
+            info!(
+                "{} already finished in the checkpoint: {} hosts.",
+                domain,
+                saved.results.len()
+            );
+            saved.results.iter().for_each(&mut on_result);
+            self.record_stats(
+                domain,
+                ScanStats {
+                    results: saved.results.len(),
+                    ..ScanStats::default()
+                },
+            );
+            return saved.results.clone();
+        }
+
+        if self.options.passive {
+            return self.passive_scan(domain, deadline, on_result).await;
+        }
+        let mut state = ScanState {
+            deadline,
+            ..ScanState::default()
+        };
+        if self.options.check_resolvers {
+            deadline.within(check_health(&self.resolvers)).await;
+        }
+
+        // 0. Wildcard Detection
+        info!("Checking for Wildcard DNS...");
+        let dns = self.deadline(&mut state, Phase::Dns);
+        let mut wildcards: Vec<WildcardProfile> = dns
+            .within(detect_wildcard(
+                &self.resolvers,
+                domain,
+                &self.options.probe,
+            ))
+            .await
+            .flatten()
+            .into_iter()
+            .collect();
+        if let Some(profile) = wildcards.first() {
+            warn!(
+                "Wildcard DNS detected. IPs: {:?}, Title: {:?}. Filtering junk results...",
+                profile.ips, profile.title
+            );
+        }
+        let mut wildcard_zones = HashSet::from([domain.to_string()]);
+
+        let resolved = match resumed.as_ref().and_then(|saved| saved.resolved.clone()) {
+            Some(resolved) => {
+                let saved = resumed
+                    .as_ref()
+                    .expect("resolved comes from the checkpoint");
+                info!(
+                    "Resuming {} from the checkpoint: {} resolved, {} already probed.",
+                    domain,
+                    resolved.len(),
+                    saved.probed.len()
+                );
+                state.queried = saved.queried.iter().cloned().collect();
+                state.zone_leaks = saved.zone_leaks.clone();
+                state.found_by = saved.found_by.clone();
+                resolved
+            }
+            None => {
+                // 1-2. Discovery and DNS Verification
+                let mut resolved = self.enumerate(domain, &mut state).await;
+
+                // 2e. Recursive Phase
+                if self.options.recursion_depth > 0 && self.options.discover {
+                    self.recurse(domain, &mut state, &mut resolved).await;
+                }
+                if let Some(ref c) = self.checkpoint {
+                    c.update(domain, true, |saved| {
+                        saved.resolved = Some(resolved.clone());
+                        saved.queried = state.queried.iter().cloned().collect();
+                        saved.zone_leaks = state.zone_leaks.clone();
+                        saved.found_by = state.found_by.clone();
+                    });
+                }
+                resolved
+            }
+        };
+        let resolvable_count = resolved.len();
+        info!("{} subdomains resolved. Probing HTTP...", resolvable_count);
+
+        // 3-4. HTTP Probing and Filtering Phase, repeated for names the
+        // responses themselves point at
+        let feedback = self.options.feedback && self.options.discover;
+        let mut probe_options = self.options.probe.clone();
+        if feedback {
+            probe_options.scrape_domain = Some(domain.to_string());
+        }
+        let probe_deadline = self.deadline(&mut state, Phase::Probe);
+        probe_options.rate_limits.deadline = probe_deadline;
+
+        let mut final_results = Vec::new();
+        let mut filtered = 0;
+        let mut seen_ips = HashSet::new();
+        let mut asns = HashMap::new();
+        let mut ptrs = HashMap::new();
+        let mut dns_records = HashMap::new();
+        let mut dnssec_status = HashMap::new();
+        // Every name that resolved, filtered or not
+        let mut resolved_names = HashSet::new();
+        let mut open_ports = HashMap::new();
+        let mut banners = HashMap::new();
+        let mut neighbors = Neighbors::new();
+        let mut to_probe = resolved;
+        let mut out_of_scope = 0;
+        let mut unmatched = 0;
+        let mut probed_count = 0;
+        // Hosts a previous run already probed are skipped, and what it
+        // found is reported again
+        let mut probed = HashSet::new();
+        let mut resumed_leads = HashSet::new();
+        if let Some(saved) = resumed {
+            probed = saved.probed;
+            resumed_leads = saved.leads;
+            saved.results.iter().for_each(&mut on_result);
+            final_results = saved.results;
+        }
+        for round in 0..=MAX_FEEDBACK_ROUNDS {
+            if !probed.is_empty() {
+                to_probe.retain(|h| !probed.contains(&h.subdomain));
+            }
+            if !self.options.scope.is_empty() {
+                let before = to_probe.len();
+                to_probe.retain(|h| self.options.scope.allows(&h.subdomain));
+                out_of_scope += before - to_probe.len();
+            }
+
+            // Wildcards on intermediate labels (*.dev.example.com)
+            let zones = child_apexes(&to_probe, domain, &wildcard_zones);
+            if !zones.is_empty() {
+                wildcard_zones.extend(zones.iter().cloned());
+                let levels = probe_deadline
+                    .within(detect_wildcards(
+                        self.resolvers.clone(),
+                        zones,
+                        &self.options.probe,
+                    ))
+                    .await
+                    .unwrap_or_default();
+                for profile in &levels {
+                    warn!(
+                        "Wildcard DNS under {}. IPs: {:?}, Title: {:?}.",
+                        profile.zone, profile.ips, profile.title
+                    );
+                }
+                wildcards.extend(levels);
+            }
+
+            resolved_names.extend(to_probe.iter().map(|h| h.subdomain.clone()));
+            // Out of time, hosts are still reported, unprobed, but nothing
+            // is looked up for them
+            let late = probe_deadline.passed();
+            let new_ips: Vec<String> = to_probe
+                .iter()
+                .flat_map(|h| h.ips.iter())
+                .filter(|ip| !late && seen_ips.insert(ip.to_string()))
+                .cloned()
+                .collect();
+            if self.options.asn && !new_ips.is_empty() {
+                match enrich::asn::lookup(&new_ips, self.options.proxy.as_ref()).await {
+                    Ok(found) => asns.extend(found),
+                    Err(e) => {
+                        warn!("ASN lookup failed: {}", e);
+                        state.issues.warning(
+                            domain,
+                            "enrich",
+                            None,
+                            format!("ASN lookup failed: {}", e),
+                        );
+                    }
+                }
+            }
+            if !self.options.scan_ports.is_empty() && !new_ips.is_empty() {
+                info!(
+                    "Scanning {} ports on {} addresses...",
+                    self.options.scan_ports.len(),
+                    new_ips.len()
+                );
+                open_ports.extend(portscan::scan(&new_ips, &self.options.scan_ports).await);
+            }
+            let targets: Vec<(String, String, Vec<u16>)> = match self.options.banners {
+                _ if late => Vec::new(),
+                BannerScope::All => {
+                    let ports: Vec<u16> = banner::SERVICES.iter().map(|s| s.port).collect();
+                    to_probe
+                        .iter()
+                        .filter_map(|h| {
+                            Some((h.subdomain.clone(), h.ips.first()?.clone(), ports.clone()))
+                        })
+                        .collect()
+                }
+                // Unasked-for direct connections would bypass the proxy
+                BannerScope::Named if self.options.proxy.is_none() => to_probe
+                    .iter()
+                    .filter_map(|h| {
+                        let ports = banner::ports_for(&h.subdomain, domain);
+                        let ip = h.ips.first()?;
+                        (!ports.is_empty()).then(|| (h.subdomain.clone(), ip.clone(), ports))
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            if !targets.is_empty() {
+                info!("Reading service banners of {} hosts...", targets.len());
+                banners.extend(banner::grab_all(targets).await);
+            }
+            let mut leads = std::mem::take(&mut resumed_leads);
+            if self.options.reverse_ip && !new_ips.is_empty() {
+                let ips: Vec<String> = new_ips
+                    .iter()
+                    .filter(|ip| cdn::detect(std::slice::from_ref(*ip), &[], None).is_none())
+                    .cloned()
+                    .collect();
+                let client =
+                    sources::source_client(&self.options.user_agent, self.options.proxy.as_ref());
+                let (found, error) = enrich::reverseip::lookup(&client, &ips).await;
+                if let Some(e) = error {
+                    warn!("Reverse IP lookup failed: {}", e);
+                    state.issues.warning(
+                        domain,
+                        "enrich",
+                        Some("reverse-ip"),
+                        format!("Reverse IP lookup failed: {}", e),
+                    );
+                }
+                let suffix = format!(".{}", domain);
+                for (ip, names) in found {
+                    let (inside, outside): (Vec<String>, Vec<String>) = names
+                        .into_iter()
+                        .partition(|n| n == domain || n.ends_with(&suffix));
+                    leads.extend(inside);
+                    if !outside.is_empty() {
+                        neighbors.insert(ip, outside);
+                    }
+                }
+            }
+            if self.options.ptr && !new_ips.is_empty() {
+                let found = reverse_all(self.resolvers.clone(), new_ips).await;
+                // In-scope reverse names are worth resolving themselves
+                let suffix = format!(".{}", domain);
+                leads.extend(
+                    found
+                        .values()
+                        .flatten()
+                        .filter(|name| name.ends_with(&suffix))
+                        .cloned(),
+                );
+                ptrs.extend(found);
+            }
+            if !self.options.records.is_empty() && !to_probe.is_empty() && !late {
+                let names = to_probe.iter().map(|h| h.subdomain.clone()).collect();
+                let found =
+                    records::lookup_all(self.resolvers.clone(), names, &self.options.records).await;
+                // Name servers, mail servers and SPF includes in scope
+                let suffix = format!(".{}", domain);
+                leads.extend(
+                    found
+                        .values()
+                        .flat_map(|d| d.hostnames())
+                        .filter(|name| name.ends_with(&suffix))
+                        .cloned(),
+                );
+                dns_records.extend(found);
+            }
+            if self.options.dnssec && !to_probe.is_empty() && !late {
+                let servers = dnssec::validators(&self.options.resolvers);
+                let names = to_probe.iter().map(|h| h.subdomain.clone()).collect();
+                let found = dnssec::status_all(&servers, names).await;
+                for (name, _) in found.iter().filter(|(_, s)| **s == DnssecStatus::Bogus) {
+                    warn!("{} fails DNSSEC validation", name);
+                    state.issues.warning(
+                        domain,
+                        "dns",
+                        Some(name),
+                        "fails DNSSEC validation; validating resolvers won't resolve it"
+                            .to_string(),
+                    );
+                }
+                dnssec_status.extend(found);
+            }
+
+            debug!("Probing round {}: {} hosts", round, to_probe.len());
+            let mut rx = probe::probe_http_stream(to_probe, &probe_options);
+            while let Some(Some(mut r)) = probe_deadline.within(rx.recv()).await {
+                probed_count += 1;
+                if let Some(ref e) = r.probe_error {
+                    state
+                        .issues
+                        .warning(domain, "probe", Some(&r.subdomain), e.clone());
+                }
+                let found = std::mem::take(&mut r.leads);
+                if let Some(c) = self.checkpoint.as_ref().filter(|_| !r.partial) {
+                    c.update(domain, false, |saved| {
+                        saved.probed.insert(r.subdomain.clone());
+                        saved.leads.extend(found.iter().cloned());
+                    });
+                }
+                leads.extend(found);
+                r.zone_transfer = state.zone_leaks.get(&r.subdomain).cloned();
+                r.sources = state.sources(&r.subdomain);
+                if self.options.known.contains(&r.subdomain) {
+                    r.source = Some("seed".to_string());
+                }
+                if let Some(ref db) = self.geoip {
+                    if let Some(location) = r.ips.iter().find_map(|ip| db.locate(ip)) {
+                        r.country = location.country;
+                        r.city = location.city;
+                    }
+                }
+                let mut ports: Vec<u16> = r
+                    .ips
+                    .iter()
+                    .filter_map(|ip| open_ports.get(ip))
+                    .flatten()
+                    .copied()
+                    .collect();
+                ports.sort_unstable();
+                ports.dedup();
+                r.open_ports = ports;
+                r.banners = banners.get(&r.subdomain).cloned().unwrap_or_default();
+                let mut names: Vec<String> = r
+                    .ips
+                    .iter()
+                    .filter_map(|ip| ptrs.get(ip))
+                    .flatten()
+                    .cloned()
+                    .collect();
+                names.sort();
+                names.dedup();
+                r.ptr = names;
+                r.dns_records = dns_records.get(&r.subdomain).cloned();
+                r.dnssec = dnssec_status.get(&r.subdomain).copied();
+                if let Some(info) = r.ips.iter().find_map(|ip| asns.get(ip)) {
+                    r.asn = Some(info.asn);
+                    r.as_org = Some(info.org.clone());
+                }
+                r.tags = tags::classify(&r, domain);
+                if let Some(profile) = profile_for(&r, &wildcards) {
+                    if profile.shares_address(&r) {
+                        r.wildcard_similarity = body_similarity(&r, profile);
+                    }
+                    if let Some(reason) =
+                        wildcard_match_reason(&r, profile, self.options.wildcard_threshold)
+                    {
+                        filtered += 1;
+                        metrics::wildcard_filtered();
+                        if !self.options.keep_filtered {
+                            continue;
+                        }
+                        r.filtered_reason = Some(format!("wildcard: {}", reason));
+                        r.wildcard_zone = Some(profile.zone.clone());
+                    }
+                }
+                if !self.options.filter.keeps(&r) {
+                    unmatched += 1;
+                    continue;
+                }
+                on_result(&r);
+                if let Some(c) = self.checkpoint.as_ref().filter(|_| !r.partial) {
+                    c.update(domain, false, |saved| saved.results.push(r.clone()));
+                }
+                final_results.push(r);
+            }
+
+            if self.options.probe.rate_limits.exhausted()
+                || self.out_of_time(&mut state, domain, Phase::Probe)
+            {
+                break;
+            }
+            leads.retain(|l| !state.queried.contains(l));
+            if !feedback || round == MAX_FEEDBACK_ROUNDS || leads.is_empty() {
+                break;
+            }
+            info!(
+                "{} new names seen in certificates and responses. Verifying...",
+                leads.len()
+            );
+            state.queried.extend(leads.iter().cloned());
+            state.credit(&leads, "feedback");
+            to_probe = verify_dns_until(self.resolvers.clone(), leads, probe_deadline).await;
+            if to_probe.is_empty() {
+                break;
+            }
+        }
+        if let Some(budget) = self.options.probe.rate_limits.budget.as_ref() {
+            if budget.exhausted() {
+                let (requests, bytes) = budget.used();
+                let msg = format!(
+                    "HTTP budget exhausted after {} requests and {} bytes; hosts left were not probed",
+                    requests, bytes
+                );
+                warn!("{}", msg);
+                state.issues.warning(domain, "probe", None, msg);
+            }
+        }
+        // 4b. Virtual Host Phase
+        let mut vhosts = 0;
+        if self.options.vhosts && !self.out_of_time(&mut state, domain, Phase::Vhosts) {
+            for mut r in self
+                .vhost_phase(domain, &mut state, &resolved_names, &final_results)
+                .await
+            {
+                vhosts += 1;
+                r.sources = state.sources(&r.subdomain);
+                if self.options.known.contains(&r.subdomain) {
+                    r.source = Some("seed".to_string());
+                }
+                r.tags = tags::classify(&r, domain);
+                if !self.options.filter.keeps(&r) {
+                    unmatched += 1;
+                    continue;
+                }
+                on_result(&r);
+                if let Some(ref c) = self.checkpoint {
+                    c.update(domain, false, |saved| saved.results.push(r.clone()));
+                }
+                final_results.push(r);
+            }
+        }
+
+        if unmatched > 0 {
+            info!(
+                "Dropped {} hosts not matching the output filters.",
+                unmatched
+            );
+        }
+        if out_of_scope > 0 {
+            info!("Skipped {} out-of-scope hosts.", out_of_scope);
+        }
+        if !wildcards.is_empty() && self.options.keep_filtered {
+            info!("Marked {} wildcard matches as filtered.", filtered);
+        } else if !wildcards.is_empty() {
+            info!("Filtered {} false positives (Wildcard matches).", filtered);
+        }
+        if !neighbors.is_empty() {
+            info!(
+                "{} addresses also serve names outside {}:",
+                neighbors.len(),
+                domain
+            );
+            for (ip, names) in &neighbors {
+                info!("  {:<15} {}", ip, names.join(", "));
+            }
+        }
+
+        // 5. Enrichment Phase
+        let late = self.deadline(&mut state, Phase::Enrich);
+        if self.options.shodan_ports && !late.passed() {
+            match self.options.api_keys.get("shodan") {
+                Some(key) => {
+                    info!("Fetching Shodan port data...");
+                    late.within(enrich::shodan::enrich_ports(
+                        &mut final_results,
+                        &key,
+                        self.options.proxy.as_ref(),
+                    ))
+                    .await;
+                }
+                None => {
+                    warn!("--shodan-ports needs a Shodan API key.");
+                    state.issues.warning(
+                        domain,
+                        "enrich",
+                        Some("shodan"),
+                        "--shodan-ports needs a Shodan API key",
+                    );
+                }
+            }
+        }
+        if self.options.rdap && !late.passed() {
+            info!("Looking up registration data over RDAP...");
+            let client =
+                sources::source_client(&self.options.user_agent, self.options.proxy.as_ref());
+            let apex = related::apex(domain).unwrap_or_else(|| domain.to_string());
+            match late.within(enrich::rdap::domain(&client, &apex)).await {
+                Some(Ok(Some(reg))) => {
+                    info!(
+                        "{} registered {} through {}, expires {}",
+                        apex,
+                        reg.created.as_deref().unwrap_or("on an unknown date"),
+                        reg.registrar.as_deref().unwrap_or("an unknown registrar"),
+                        reg.expires.as_deref().unwrap_or("unknown"),
+                    );
+                    self.record_registration(domain, reg);
+                }
+                Some(Ok(None)) => debug!("RDAP has no record of {}", apex),
+                Some(Err(e)) => {
+                    warn!("RDAP lookup of {} failed: {}", apex, e);
+                    state.issues.warning(
+                        domain,
+                        "enrich",
+                        Some("rdap"),
+                        format!("RDAP lookup of {} failed: {}", apex, e),
+                    );
+                }
+                None => {}
+            }
+            let netblocks =
+                late.within(enrich::rdap::enrich_netblocks(&client, &mut final_results));
+            if let Some(Some(e)) = netblocks.await {
+                warn!("RDAP netblock lookup failed: {}", e);
+                state.issues.warning(
+                    domain,
+                    "enrich",
+                    Some("rdap"),
+                    format!("RDAP netblock lookup failed: {}", e),
+                );
+            }
+        }
+        #[cfg(feature = "screenshots")]
+        if let Some(dir) = self.options.screenshots.as_ref().filter(|_| !late.passed()) {
+            info!("Capturing screenshots...");
+            if let Some(Err(e)) = late
+                .within(crate::screenshot::capture_all(
+                    &mut final_results,
+                    dir,
+                    self.options.proxy.as_ref(),
+                ))
+                .await
+            {
+                warn!("Screenshots failed: {}", e);
+                state.issues.warning(
+                    domain,
+                    "enrich",
+                    Some("screenshots"),
+                    format!("Screenshots failed: {}", e),
+                );
+            }
+        }
+
+        // 6. Related Domains
+        let mut related = Vec::new();
+        if self.options.find_related && !late.passed() {
+            info!("Looking for other domains of the same organisation...");
+            let client =
+                sources::source_client(&self.options.user_agent, self.options.proxy.as_ref());
+            let search =
+                related::find(&client, domain, &final_results, self.options.proxy.as_ref());
+            match late.within(search).await {
+                Some(Ok(found)) => related = found,
+                Some(Err(e)) => {
+                    warn!("Related domain search failed: {}", e);
+                    state.issues.warning(
+                        domain,
+                        "related",
+                        None,
+                        format!("Related domain search failed: {}", e),
+                    );
+                }
+                None => {}
+            }
+            info!("{} possibly related domains.", related.len());
+            for r in &related {
+                info!("  {:<30} {}", r.domain, r.via.join(", "));
+            }
+        }
+        let enriched = self.options.shodan_ports
+            || self.options.rdap
+            || self.options.screenshots.is_some()
+            || self.options.find_related;
+        if enriched {
+            self.out_of_time(&mut state, domain, Phase::Enrich);
+        }
+
+        if let Some(ref c) = self.checkpoint {
+            c.update(domain, true, |saved| {
+                saved.results = final_results.clone();
+                saved.done = true;
+            });
+        }
+        self.resolvers.save_cache();
+        metrics::scan_finished(final_results.len());
+        self.record_stats(
+            domain,
+            ScanStats {
+                sources: state.sources.0,
+                sources_failed: state.sources.1,
+                candidates: state.queried.len(),
+                resolved: resolved_names.len(),
+                probed: probed_count,
+                wildcard_filtered: filtered,
+                out_of_scope,
+                unmatched,
+                vhosts,
+                results: final_results.len(),
+                truncated: state.truncated_phases(),
+            },
+        );
+        for e in self.resolvers.take_evictions() {
+            state.issues.warning(
+                domain,
+                "dns",
+                Some(&e.resolver),
+                format!("resolver dropped: {}", e.reason),
+            );
+        }
+        self.record_issues(domain, state.issues);
+        self.record_neighbors(domain, neighbors);
+        self.record_related(domain, related);
+        self.record_wildcards(domain, wildcards);
+        final_results
+    }
+
+    /// When `phase` has to stop: the scan's deadline, or the phase's own
+    /// `--phase-deadline` from the moment it first started if that is
+    /// sooner.
+    fn deadline(&self, state: &mut ScanState, phase: Phase) -> Deadline {
+        let limit = self.options.phase_deadlines.get(&phase).copied();
+        let run = state.deadline;
+        *state
+            .phase_deadlines
+            .entry(phase)
+            .or_insert_with(|| run.earliest(Deadline::after(limit)))
+    }
+
+    /// Whether `phase` has run out of time, reporting it as truncated the
+    /// first time.
+    fn out_of_time(&self, state: &mut ScanState, domain: &str, phase: Phase) -> bool {
+        let passed = self.deadline(state, phase).passed();
+        if passed && state.truncated.insert(phase) {
+            warn!(
+                "The {} phase of {} hit its deadline; its results are incomplete.",
+                phase.name(),
+                domain
+            );
+            state.issues.warning(
+                domain,
+                "deadline",
+                Some(phase.name()),
+                format!(
+                    "the {} phase hit its deadline; its results are incomplete",
+                    phase.name()
+                ),
+            );
+        }
+        passed
+    }
+
+    /// `--profile passive`: the names passive sources and `--input` know,
+    /// neither resolved nor probed.
+    async fn passive_scan<F>(
+        &self,
+        domain: &str,
+        deadline: Deadline,
+        mut on_result: F,
+    ) -> Vec<SubdomainResult>
+    where
+        F: FnMut(&SubdomainResult),
+    {
+        let mut state = ScanState {
+            deadline,
+            ..ScanState::default()
+        };
+        let mut names = self.supplied(domain, &mut state);
+        if self.options.discover {
+            names.extend(self.query_sources(domain, &mut state).await);
+        }
+        let found = names.len();
+        let mut names: Vec<String> = names
+            .into_iter()
+            .filter(|n| self.options.scope.is_empty() || self.options.scope.allows(n))
+            .collect();
+        let out_of_scope = found - names.len();
+        names.sort();
+        info!(
+            "{} names for {} from passive sources. Not resolving them (passive).",
+            names.len(),
+            domain
+        );
+
+        let mut results = Vec::new();
+        let mut unmatched = 0;
+        for name in names {
+            let host = ResolvedHost {
+                subdomain: name,
+                ..ResolvedHost::default()
+            };
+            let mut r = probe::unprobed(&host, None);
+            r.sources = state.sources(&r.subdomain);
+            if self.options.known.contains(&r.subdomain) {
+                r.source = Some("seed".to_string());
+            }
+            r.tags = tags::classify(&r, domain);
+            if !self.options.filter.keeps(&r) {
+                unmatched += 1;
+                continue;
+            }
+            on_result(&r);
+            results.push(r);
+        }
+
+        metrics::scan_finished(results.len());
+        self.record_stats(
+            domain,
+            ScanStats {
+                sources: state.sources.0,
+                sources_failed: state.sources.1,
+                candidates: found,
+                out_of_scope,
+                unmatched,
+                results: results.len(),
+                truncated: state.truncated_phases(),
+                ..ScanStats::default()
+            },
+        );
+        self.record_issues(domain, state.issues);
+        results
+    }
+
+    fn record_stats(&self, domain: &str, stats: ScanStats) {
+        let mut all = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        all.insert(domain.to_string(), stats);
+    }
+
+    fn record_related(&self, domain: &str, related: Vec<RelatedDomain>) {
+        let mut all = self.related.lock().unwrap_or_else(|e| e.into_inner());
+        all.insert(domain.to_string(), related);
+    }
+
+    fn record_registration(&self, domain: &str, registration: Registration) {
+        let mut all = self.registrations.lock().unwrap_or_else(|e| e.into_inner());
+        all.insert(domain.to_string(), registration);
+    }
+
+    fn record_wildcards(&self, domain: &str, wildcards: Vec<WildcardProfile>) {
+        let mut all = self.wildcards.lock().unwrap_or_else(|e| e.into_inner());
+        all.insert(domain.to_string(), wildcards);
+    }
+
+    fn record_neighbors(&self, domain: &str, neighbors: Neighbors) {
+        let mut all = self.neighbors.lock().unwrap_or_else(|e| e.into_inner());
+        all.insert(domain.to_string(), neighbors);
+    }
+
+    fn record_issues(&self, domain: &str, issues: ScanIssues) {
+        let mut all = self.issues.lock().unwrap_or_else(|e| e.into_inner());
+        all.insert(domain.to_string(), issues);
+    }
+
+    /// Scans several apexes concurrently, sharing this engine's DNS and HTTP
+    /// budgets. Results come back grouped per domain, in input order.
+    pub async fn run_many(&self, domains: &[String]) -> Vec<(String, Vec<SubdomainResult>)> {
+        self.run_many_streaming(domains, |_, _| {}).await
+    }
+
+    /// Like [`run_many`](Self::run_many), calling `on_result` with the apex
+    /// and each host as soon as it is ready.
+    pub async fn run_many_streaming<F>(
+        &self,
+        domains: &[String],
+        mut on_result: F,
+    ) -> Vec<(String, Vec<SubdomainResult>)>
+    where
+        F: FnMut(&str, &SubdomainResult),
+    {
+        self.reset_budget();
+        // Passive sources aren't covered by the shared budgets, so cap how
+        // many apexes hit them at once
+        let slots = Arc::new(Semaphore::new(MAX_PARALLEL_DOMAINS));
+        let (tx, mut rx) = mpsc::unbounded_channel::<(usize, SubdomainResult)>();
+        let mut tasks = JoinSet::new();
+
+        // Line formats mix the domains, so each host names its own
+        let label = domains.len() > 1;
+        // --max-runtime bounds the whole run, domains waiting for a slot too
+        let deadline = Deadline::after(self.options.max_runtime);
+        for (i, domain) in domains.iter().enumerate() {
+            let engine = self.clone();
+            let domain = domain.clone();
+            let slots = slots.clone();
+            let tx = tx.clone();
+            tasks.spawn(async move {
+                let _slot = slots.acquire_owned().await;
+                let apex = label.then(|| domain.clone());
+                let mut results = engine
+                    .run_until(&domain, deadline, |r| {
+                        let mut r = r.clone();
+                        r.apex.clone_from(&apex);
+                        let _ = tx.send((i, r));
+                    })
+                    .await;
+                for r in &mut results {
+                    r.apex.clone_from(&apex);
+                }
+                (i, results)
+            });
+        }
+        drop(tx);
+
+        while let Some((i, r)) = rx.recv().await {
+            on_result(&domains[i], &r);
+        }
+
+        // The returned results, unlike the streamed ones, carry enrichment
+        let mut grouped: Vec<(String, Vec<SubdomainResult>)> =
+            domains.iter().map(|d| (d.clone(), Vec::new())).collect();
+        while let Some(joined) = tasks.join_next().await {
+            if let Ok((i, results)) = joined {
+                grouped[i].1 = results;
+            }
+        }
+        grouped
+    }
+
+    /// Discovery, verification, brute force and permutations for one apex.
+    ///
+    /// Every name that gets queried is added to `state.queried` so later
+    /// phases and recursion levels don't look it up twice.
+    async fn enumerate(&self, domain: &str, state: &mut ScanState) -> Vec<ResolvedHost> {
+        let supplied = self.supplied(domain, state);
+
+        if !self.options.discover {
+            let mut candidates = supplied;
+            candidates.retain(|c| !state.queried.contains(c));
+            info!(
+                "Verifying {} supplied names for {}...",
+                candidates.len(),
+                domain
+            );
+            state.queried.extend(candidates.iter().cloned());
+            let dns = self.deadline(state, Phase::Dns);
+            let mut resolved =
+                verify_dns_until(self.resolvers.clone(), candidates.clone(), dns).await;
+            if !self.out_of_time(state, domain, Phase::Dns) {
+                self.dangling_phase(candidates, &mut resolved, dns).await;
+            }
+            return resolved;
+        }
+
+        // 0b. Zone Transfer
+        let discovery = self.deadline(state, Phase::Discovery);
+        let mut zone = None;
+        if self.options.axfr && !discovery.passed() {
+            info!("Attempting zone transfer for {}...", domain);
+            zone = discovery
+                .within(attempt_axfr(&self.resolvers, domain))
+                .await
+                .flatten();
+            if let Some(ref z) = zone {
+                warn!(
+                    "{} allowed AXFR: {} names leaked. Skipping brute force.",
+                    z.nameserver,
+                    z.names.len()
+                );
+                for name in &z.names {
+                    state.zone_leaks.insert(name.clone(), z.nameserver.clone());
+                }
+            }
+        }
+        let mut walked = None;
+        if self.options.nsec_walk && zone.is_none() && !discovery.passed() {
+            info!("Walking the NSEC chain of {}...", domain);
+            match discovery
+                .within(dnssec::walk_nsec(&self.resolvers, domain))
+                .await
+            {
+                Some(Ok(walk)) => {
+                    info!(
+                        "NSEC chain of {} on {}: {} names{}",
+                        domain,
+                        walk.nameserver,
+                        walk.names.len(),
+                        if walk.complete { "" } else { " (incomplete)" }
+                    );
+                    walked = Some(walk);
+                }
+                Some(Err(e)) => info!("Could not walk the NSEC chain of {}: {}", domain, e),
+                None => {}
+            }
+        }
+
+        // 1. Discovery Phase
+        let mut candidates = self.query_sources(domain, state).await;
+
+        for sub in TOP_SUBDOMAINS {
+            let name = format!("{}.{}", sub, domain);
+            state.credit([&name], "builtin");
+            candidates.insert(name);
+        }
+        candidates.extend(supplied);
+        if !self.options.records.is_empty() {
+            // The apex carries most NS, MX and SPF records
+            candidates.insert(domain.to_string());
+        }
+        if let Some(ref z) = zone {
+            state.credit(&z.names, "axfr");
+            candidates.extend(z.names.iter().cloned());
+        }
+        if let Some(ref w) = walked {
+            state.credit(&w.names, "nsec");
+            candidates.extend(w.names.iter().cloned());
+        }
+        candidates.retain(|c| !state.queried.contains(c));
+        let total_candidates = candidates.len();
+        info!(
+            "Found {} potential subdomains. Verifying...",
+            total_candidates
+        );
+
+        // 2. DNS Verification Phase
+        state.queried.extend(candidates.iter().cloned());
+        let dns = self.deadline(state, Phase::Dns);
+        let mut resolved = verify_dns_until(self.resolvers.clone(), candidates.clone(), dns).await;
+        if !self.out_of_time(state, domain, Phase::Dns) {
+            self.dangling_phase(candidates, &mut resolved, dns).await;
+        }
+
+        // 2b. Brute Force Phase (a leaked zone or a whole NSEC chain
+        // already lists every name)
+        let zone_leaked = zone.is_some() || walked.as_ref().is_some_and(|w| w.complete);
+        let wordlist = match (&self.options.wordlist, self.options.wordlist_builtin) {
+            _ if zone_leaked => None,
+            (Some(path), _) => Some(path.clone()),
+            (None, Some(list)) => match wordlists::path(list, self.options.proxy.as_ref()).await {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    warn!("Could not load the {} wordlist: {}", list.name(), e);
+                    state.issues.error(
+                        domain,
+                        "dns",
+                        Some(list.name()),
+                        format!("Could not load the {} wordlist: {}", list.name(), e),
+                    );
+                    None
+                }
+            },
+            (None, None) => None,
+        };
+        if let Some(path) = wordlist.filter(|_| !self.out_of_time(state, domain, Phase::Dns)) {
+            info!("Brute forcing with wordlist {}...", path.display());
+            let hits =
+                brute_force_until(self.resolvers.clone(), domain, &path, &state.queried, dns);
+            match hits.await {
+                Ok(hits) => {
+                    info!("Brute force found {} subdomains.", hits.len());
+                    state.credit(hits.iter().map(|h| &h.subdomain), "bruteforce");
+                    resolved.extend(hits);
+                }
+                Err(e) => {
+                    warn!("Brute force failed: {}", e);
+                    state.issues.error(
+                        domain,
+                        "dns",
+                        Some(&path.display().to_string()),
+                        format!("Brute force failed: {}", e),
+                    );
+                }
+            }
+        }
+
+        // 2c. Permutation Phase
+        if self.options.permute && !zone_leaked && !self.out_of_time(state, domain, Phase::Dns) {
+            self.permute_phase(domain, state, &mut resolved).await;
+        }
+
+        // 2d. Markov Phase
+        if self.options.markov > 0 && !zone_leaked && !self.out_of_time(state, domain, Phase::Dns) {
+            self.markov_phase(domain, state, &mut resolved).await;
+        }
+        self.out_of_time(state, domain, Phase::Dns);
+
+        state
+            .queried
+            .extend(resolved.iter().map(|h| h.subdomain.clone()));
+        resolved
+    }
+
+    /// Virtual hosts among the queried names that didn't resolve, on the
+    /// addresses of `results`.
+    async fn vhost_phase(
+        &self,
+        domain: &str,
+        state: &mut ScanState,
+        resolved: &HashSet<String>,
+        results: &[SubdomainResult],
+    ) -> Vec<SubdomainResult> {
+        if self.options.probe.proxy.is_some() {
+            warn!("--vhosts can't pin addresses through a proxy. Skipping virtual hosts.");
+            state.issues.warning(
+                domain,
+                "vhost",
+                None,
+                "--vhosts can't pin addresses through a proxy, skipped",
+            );
+            return Vec::new();
+        }
+        let mut ips: Vec<String> = results
+            .iter()
+            .filter(|r| !r.vhost)
+            .flat_map(|r| r.ips.iter().cloned())
+            .collect();
+        ips.sort();
+        ips.dedup();
+        let known: HashSet<&String> = results.iter().map(|r| &r.subdomain).collect();
+        let mut names: Vec<String> = state
+            .queried
+            .iter()
+            .filter(|n| !resolved.contains(*n) && !known.contains(n))
+            .filter(|n| self.options.scope.is_empty() || self.options.scope.allows(n))
+            .cloned()
+            .collect();
+        if ips.is_empty() || names.is_empty() {
+            return Vec::new();
+        }
+        names.sort();
+        if names.len() > vhost::MAX_VHOST_NAMES {
+            info!(
+                "Trying only the first {} of {} unresolved names as virtual hosts.",
+                vhost::MAX_VHOST_NAMES,
+                names.len()
+            );
+            names.truncate(vhost::MAX_VHOST_NAMES);
+        }
+        info!(
+            "Trying {} unresolved names as virtual hosts on {} addresses...",
+            names.len(),
+            ips.len()
+        );
+        let mut options = self.options.probe.clone();
+        options.rate_limits.deadline = self.deadline(state, Phase::Vhosts);
+        let found = vhost::discover(
+            ips,
+            names,
+            domain,
+            &options,
+            self.options.wildcard_threshold,
+        )
+        .await;
+        if !found.is_empty() {
+            warn!("{} virtual hosts without a DNS record found.", found.len());
+        }
+        self.out_of_time(state, domain, Phase::Vhosts);
+        found
+    }
+
+    /// The `--input` and `--known` names under `domain`, credited to where
+    /// they came from.
+    fn supplied(&self, domain: &str, state: &mut ScanState) -> HashSet<String> {
+        let suffix = format!(".{}", domain);
+        let under = |c: &&String| *c == domain || c.ends_with(&suffix);
+        for name in self.options.candidates.iter().filter(under) {
+            state.credit([name], "input");
+            for source in self
+                .options
+                .candidate_sources
+                .get(name)
+                .into_iter()
+                .flatten()
+            {
+                state.credit([name], source);
+            }
+        }
+        state.credit(self.options.known.iter().filter(under), "seed");
+        self.options
+            .candidates
+            .iter()
+            .chain(&self.options.known)
+            .filter(under)
+            .cloned()
+            .collect()
+    }
+
+    /// Queries the passive sources for `domain`, recording failures and
+    /// which source returned what.
+    async fn query_sources(&self, domain: &str, state: &mut ScanState) -> HashSet<String> {
+        if self.out_of_time(state, domain, Phase::Discovery) {
+            return HashSet::new();
+        }
+        info!("Discovering subdomains of {} concurrently...", domain);
+        let mut selected = sources::select(
+            sources::registry(&self.options),
+            &self.options.sources,
+            &self.options.exclude_sources,
+        );
+        if let Some(ref dir) = self.options.cache_dir {
+            let cache = SourceCache::new(dir.clone(), self.options.cache_ttl);
+            selected = sources::cache::with_cache(selected, &cache);
+        }
+        let client = sources::source_client(&self.options.user_agent, self.options.proxy.as_ref());
+        let deadline = self.deadline(state, Phase::Discovery);
+        let discovery =
+            sources::fetch_from_sources_until(&client, selected, domain, deadline).await;
+        state.sources.0 += discovery.reports.len();
+        state.sources.1 += discovery
+            .reports
+            .iter()
+            .filter(|r| r.error.is_some())
+            .count();
+        for report in &discovery.reports {
+            if let Some(ref e) = report.error {
+                let message = if report.rate_limited {
+                    format!("rate limited, results missing: {}", e)
+                } else {
+                    e.clone()
+                };
+                state
+                    .issues
+                    .error(domain, "source", Some(report.name), message);
+            }
+        }
+        for (name, sources) in &discovery.found_by {
+            for source in sources {
+                state.credit([name], source);
+            }
+        }
+        self.out_of_time(state, domain, Phase::Discovery);
+        discovery.subdomains
+    }
+
+    /// Adds the `candidates` that didn't resolve because their CNAME leads
+    /// nowhere, when `--dangling` is on.
+    async fn dangling_phase(
+        &self,
+        mut candidates: HashSet<String>,
+        resolved: &mut Vec<ResolvedHost>,
+        deadline: Deadline,
+    ) {
+        if !self.options.dangling {
+            return;
+        }
+        for host in resolved.iter() {
+            candidates.remove(&host.subdomain);
+        }
+        info!(
+            "Checking {} unresolved names for dangling CNAMEs...",
+            candidates.len()
+        );
+        let found = deadline
+            .within(dangling::dangling_cnames(
+                self.resolvers.clone(),
+                candidates,
+            ))
+            .await
+            .unwrap_or_default();
+        if !found.is_empty() {
+            warn!("{} names have a CNAME to a missing target.", found.len());
+        }
+        resolved.extend(found);
+    }
+
+    async fn permute_phase(
+        &self,
+        domain: &str,
+        state: &mut ScanState,
+        resolved: &mut Vec<ResolvedHost>,
+    ) {
+        let words = match self.options.permute_wordlist {
+            Some(ref path) => match permute::load_mutations(path) {
+                Ok(w) => w,
+                Err(e) => {
+                    warn!("Could not read mutation wordlist: {}", e);
+                    state.issues.error(
+                        domain,
+                        "dns",
+                        Some(&path.display().to_string()),
+                        format!("Could not read mutation wordlist: {}", e),
+                    );
+                    return;
+                }
+            },
+            None => permute::DEFAULT_MUTATIONS
+                .iter()
+                .map(|w| w.to_string())
+                .collect(),
+        };
+
+        let names: Vec<String> = resolved.iter().map(|h| h.subdomain.clone()).collect();
+        let mut mutations = permute::generate_permutations(&names, domain, &words);
+        mutations.retain(|m| !state.queried.contains(m));
+        info!("Resolving {} permutations...", mutations.len());
+
+        state.queried.extend(mutations.iter().cloned());
+        let dns = self.deadline(state, Phase::Dns);
+        let hits = verify_dns_until(self.resolvers.clone(), mutations, dns).await;
+        info!("Permutations found {} subdomains.", hits.len());
+        state.credit(hits.iter().map(|h| &h.subdomain), "permute");
+        resolved.extend(hits);
+    }
+
+    /// Resolves names a model trained on `resolved` finds likely.
+    async fn markov_phase(
+        &self,
+        domain: &str,
+        state: &mut ScanState,
+        resolved: &mut Vec<ResolvedHost>,
+    ) {
+        let names: Vec<String> = resolved.iter().map(|h| h.subdomain.clone()).collect();
+        let model = markov::Model::train(&names, domain);
+        if model.len() < markov::MIN_TRAINING_NAMES {
+            debug!(
+                "Only {} names under {} to learn from. Skipping Markov candidates.",
+                model.len(),
+                domain
+            );
+            return;
+        }
+        let mut candidates = model.generate(domain, self.options.markov);
+        candidates.retain(|c| !state.queried.contains(c));
+        info!(
+            "Resolving {} names learned from {} resolved...",
+            candidates.len(),
+            model.len()
+        );
+
+        state.queried.extend(candidates.iter().cloned());
+        let dns = self.deadline(state, Phase::Dns);
+        let hits = verify_dns_until(
+            self.resolvers.clone(),
+            candidates.into_iter().collect(),
+            dns,
+        );
+        let hits = hits.await;
+        info!("Markov candidates found {} subdomains.", hits.len());
+        state.credit(hits.iter().map(|h| &h.subdomain), "markov");
+        resolved.extend(hits);
+    }
+
+    /// Treats intermediate labels of resolved names (`corp.example.com` for
+    /// `internal.corp.example.com`) as new apexes, up to `recursion_depth`
+    /// levels deep. Each apex is enumerated at most once.
+    async fn recurse(&self, domain: &str, state: &mut ScanState, resolved: &mut Vec<ResolvedHost>) {
+        let mut seen_apexes = HashSet::from([domain.to_string()]);
+        let mut frontier = child_apexes(resolved, domain, &seen_apexes);
+
+        for level in 1..=self.options.recursion_depth {
+            if frontier.is_empty() || self.out_of_time(state, domain, Phase::Dns) {
+                break;
+            }
+            info!("Recursion level {}: {} new apexes.", level, frontier.len());
+
+            let mut next = HashSet::new();
+            for apex in frontier {
+                seen_apexes.insert(apex.clone());
+                let hits = self.enumerate(&apex, state).await;
+                let known: HashSet<String> = resolved.iter().map(|h| h.subdomain.clone()).collect();
+                let fresh: Vec<ResolvedHost> = hits
+                    .into_iter()
+                    .filter(|h| !known.contains(&h.subdomain))
+                    .collect();
+                next.extend(child_apexes(&fresh, domain, &seen_apexes));
+                resolved.extend(fresh);
+            }
+            next.retain(|a| !seen_apexes.contains(a));
+            frontier = next;
+        }
+    }
+}
+
+/// How many times newly probed hosts may lead to another probe round.
+const MAX_FEEDBACK_ROUNDS: usize = 3;
+
+/// Apexes enumerated at once by [`SubpeekEngine::run_many`].
+const MAX_PARALLEL_DOMAINS: usize = 4;
+
+/// Bookkeeping shared by every phase and recursion level of one scan.
+#[derive(Debug, Default)]
+struct ScanState {
+    /// Names already sent to a resolver.
+    queried: HashSet<String>,
+    /// Names learned from a zone transfer, mapped to the leaking nameserver.
+    zone_leaks: HashMap<String, String>,
+    /// The passive sources and phases (`axfr`, `bruteforce`, `feedback`,
+    /// ...) each candidate came from.
+    found_by: HashMap<String, BTreeSet<String>>,
+    issues: ScanIssues,
+    /// Passive source queries, recursion included, and how many of them
+    /// failed.
+    sources: (usize, usize),
+    /// When the whole scan has to end (`--max-runtime`).
+    deadline: Deadline,
+    /// Each phase's deadline, fixed when the phase first starts so
+    /// recursion levels share it.
+    phase_deadlines: HashMap<Phase, Deadline>,
+    /// Phases that ran out of time.
+    truncated: BTreeSet<Phase>,
+}
+
+impl ScanState {
+    /// Records `by` as one of the places `names` came from.
+    fn credit<'a>(&mut self, names: impl IntoIterator<Item = &'a String>, by: &str) {
+        for name in names {
+            self.found_by
+                .entry(name.clone())
+                .or_default()
+                .insert(by.to_string());
+        }
+    }
+
+    fn truncated_phases(&self) -> Vec<String> {
+        self.truncated
+            .iter()
+            .map(|p| p.name().to_string())
+            .collect()
+    }
+
+    /// Where `name` came from, sorted, for [`SubdomainResult::sources`].
+    fn sources(&self, name: &str) -> Vec<String> {
+        self.found_by
+            .get(name)
+            .map(|s| s.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Every intermediate parent of `names` that sits strictly between the name
+/// and `domain` and isn't in `seen`.
+fn child_apexes(names: &[ResolvedHost], domain: &str, seen: &HashSet<String>) -> HashSet<String> {
+    let suffix = format!(".{}", domain);
+    let mut out = HashSet::new();
+    for host in names {
+        let Some(prefix) = host.subdomain.strip_suffix(&suffix) else {
+            continue;
+        };
+        let labels: Vec<&str> = prefix.split('.').collect();
+        for i in 1..labels.len() {
+            let apex = format!("{}{}", labels[i..].join("."), suffix);
+            if !seen.contains(&apex) {
+                out.insert(apex);
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,73 @@
+//! ASN and AS organisation of addresses, from Team Cymru's bulk whois.
+
+use crate::{proxy, Result};
+use reqwest::Url;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
+
+const CYMRU_HOST: &str = "whois.cymru.com";
+const CYMRU_PORT: u16 = 43;
+
+/// Addresses sent per whois connection.
+const BATCH: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsnInfo {
+    pub asn: u32,
+    pub org: String,
+}
+
+/// Maps every address that belongs to an announced prefix to its origin
+/// AS. Addresses Cymru has no data for are left out.
+pub async fn lookup(ips: &[String], proxy: Option<&Url>) -> Result<HashMap<String, AsnInfo>> {
+    let mut found = HashMap::new();
+    for batch in ips.chunks(BATCH) {
+        let response = timeout(Duration::from_secs(60), query(batch, proxy))
+            .await
+            .map_err(|_| "Team Cymru whois timed out")??;
+        found.extend(parse(&response));
+    }
+    Ok(found)
+}
+
+async fn query(ips: &[String], proxy: Option<&Url>) -> Result<String> {
+    let mut stream = proxy::connect(proxy, CYMRU_HOST, CYMRU_PORT).await?;
+    let mut request = String::from("begin\nverbose\n");
+    for ip in ips {
+        request.push_str(ip);
+        request.push('\n');
+    }
+    request.push_str("end\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
+
+/// Lines look like
+/// `13335 | 1.1.1.1 | 1.1.1.0/24 | AU | apnic | 2011-08-11 | CLOUDFLARENET, US`,
+/// after a `Bulk mode` banner and a header row.
+fn parse(response: &str) -> HashMap<String, AsnInfo> {
+    let mut found = HashMap::new();
+    for line in response.lines() {
+        let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+        if fields.len() < 7 {
+            continue;
+        }
+        let Ok(asn) = fields[0].parse() else {
+            // Header row, or NA for unannounced space
+            continue;
+        };
+        found.insert(
+            fields[1].to_string(),
+            AsnInfo {
+                asn,
+                org: fields[6].to_string(),
+            },
+        );
+    }
+    found
+}
@@ -0,0 +1,47 @@
+//! Country and city of addresses, from a GeoLite2/GeoIP2 City or Country
+//! database.
+
+use super::mmdb::Reader;
+use crate::Result;
+use serde_json::Value;
+use std::net::IpAddr;
+use std::path::Path;
+
+#[derive(Debug)]
+pub struct GeoIp {
+    reader: Reader,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+    /// ISO 3166-1 alpha-2 code.
+    pub country: Option<String>,
+    /// English city name.
+    pub city: Option<String>,
+}
+
+impl GeoIp {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            reader: Reader::open(path)?,
+        })
+    }
+
+    /// `None` for unparsable addresses and ones the database doesn't know.
+    pub fn locate(&self, ip: &str) -> Option<Location> {
+        let ip: IpAddr = ip.parse().ok()?;
+        let record = self.reader.lookup(ip).ok()??;
+        let text = |path: &[&str]| {
+            path.iter()
+                .try_fold(&record, |v, key| v.get(key))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        let location = Location {
+            country: text(&["country", "iso_code"])
+                .or_else(|| text(&["registered_country", "iso_code"])),
+            city: text(&["city", "names", "en"]),
+        };
+        (location != Location::default()).then_some(location)
+    }
+}
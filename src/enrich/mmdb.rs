@@ -0,0 +1,416 @@
+//! A reader for MaxMind DB files (GeoLite2, DB-IP and other `.mmdb`
+//! databases), decoding records into JSON values.
+
+use crate::Result;
+use serde_json::{Map, Number, Value};
+use std::net::IpAddr;
+use std::path::Path;
+
+const METADATA_MARKER: &[u8] = b"\xab\xcd\xefMaxMind.com";
+
+/// Nested pointers and containers deeper than this are treated as corrupt.
+const MAX_DEPTH: usize = 64;
+
+pub struct Reader {
+    buf: Vec<u8>,
+    node_count: usize,
+    record_size: usize,
+    ip_version: u64,
+    /// Start of the data section.
+    data: usize,
+    /// Node where IPv4 addresses start in an IPv6 tree.
+    ipv4_start: usize,
+}
+
+impl std::fmt::Debug for Reader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reader")
+            .field("node_count", &self.node_count)
+            .field("record_size", &self.record_size)
+            .field("ip_version", &self.ip_version)
+            .finish()
+    }
+}
+
+impl Reader {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(buf: Vec<u8>) -> Result<Self> {
+        let marker = buf
+            .windows(METADATA_MARKER.len())
+            .rposition(|w| w == METADATA_MARKER)
+            .ok_or("not a MaxMind DB: metadata marker missing")?;
+        let meta_start = marker + METADATA_MARKER.len();
+        let mut decoder = Decoder {
+            buf: &buf,
+            base: meta_start,
+        };
+        let (meta, _) = decoder.decode(meta_start, 0)?;
+        let field = |name: &str| {
+            meta.get(name)
+                .and_then(Value::as_u64)
+                .ok_or_else(|| format!("MaxMind DB metadata lacks {}", name))
+        };
+        let node_count = field("node_count")? as usize;
+        let record_size = field("record_size")? as usize;
+        let ip_version = field("ip_version")?;
+        if ![24, 28, 32].contains(&record_size) {
+            return Err(format!("unsupported MaxMind DB record size {}", record_size).into());
+        }
+        let tree_size = node_count * record_size / 4;
+        let data = tree_size + 16;
+        if data > marker {
+            return Err("MaxMind DB search tree overruns the file".into());
+        }
+
+        let mut reader = Self {
+            buf,
+            node_count,
+            record_size,
+            ip_version,
+            data,
+            ipv4_start: 0,
+        };
+        if ip_version == 6 {
+            // IPv4 lives under ::/96
+            let mut node = 0;
+            for _ in 0..96 {
+                if node >= node_count {
+                    break;
+                }
+                node = reader.record(node, 0)?;
+            }
+            reader.ipv4_start = node;
+        }
+        Ok(reader)
+    }
+
+    /// The record for `ip`, if the database has one.
+    pub fn lookup(&self, ip: IpAddr) -> Result<Option<Value>> {
+        let (bytes, mut node) = match ip {
+            IpAddr::V4(v4) => (v4.octets().to_vec(), self.ipv4_start),
+            IpAddr::V6(v6) if self.ip_version == 6 => (v6.octets().to_vec(), 0),
+            IpAddr::V6(_) => return Ok(None),
+        };
+        for i in 0..bytes.len() * 8 {
+            if node >= self.node_count {
+                break;
+            }
+            let bit = (bytes[i / 8] >> (7 - i % 8)) & 1;
+            node = self.record(node, bit as usize)?;
+        }
+        if node == self.node_count {
+            return Ok(None);
+        }
+        if node < self.node_count {
+            return Err("MaxMind DB lookup ended inside the tree".into());
+        }
+        let offset = self.data + (node - self.node_count - 16);
+        let mut decoder = Decoder {
+            buf: &self.buf,
+            base: self.data,
+        };
+        Ok(Some(decoder.decode(offset, 0)?.0))
+    }
+
+    /// Left (`side` 0) or right record of `node`.
+    fn record(&self, node: usize, side: usize) -> Result<usize> {
+        let bytes_per_node = self.record_size / 4;
+        let start = node * bytes_per_node;
+        let b = self
+            .buf
+            .get(start..start + bytes_per_node)
+            .ok_or("MaxMind DB node out of range")?;
+        let be = |s: &[u8]| s.iter().fold(0usize, |acc, &x| acc << 8 | x as usize);
+        Ok(match (self.record_size, side) {
+            (24, 0) => be(&b[0..3]),
+            (24, _) => be(&b[3..6]),
+            (28, 0) => (b[3] as usize & 0xf0) << 20 | be(&b[0..3]),
+            (28, _) => (b[3] as usize & 0x0f) << 24 | be(&b[4..7]),
+            (_, 0) => be(&b[0..4]),
+            (_, _) => be(&b[4..8]),
+        })
+    }
+}
+
+struct Decoder<'a> {
+    buf: &'a [u8],
+    /// Pointers are relative to this offset.
+    base: usize,
+}
+
+impl Decoder<'_> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        self.buf
+            .get(offset..offset + len)
+            .ok_or_else(|| "MaxMind DB data out of range".into())
+    }
+
+    fn uint(&self, offset: usize, len: usize) -> Result<u128> {
+        Ok(self
+            .bytes(offset, len)?
+            .iter()
+            .fold(0u128, |acc, &b| acc << 8 | b as u128))
+    }
+
+    /// Decodes the value at `offset`, returning it and the offset after it.
+    fn decode(&mut self, offset: usize, depth: usize) -> Result<(Value, usize)> {
+        if depth > MAX_DEPTH {
+            return Err("MaxMind DB data nested too deeply".into());
+        }
+        let ctrl = *self.bytes(offset, 1)?.first().unwrap_or(&0);
+        let mut pos = offset + 1;
+        let mut kind = ctrl >> 5;
+        if kind == 1 {
+            let ss = (ctrl >> 3) & 3;
+            let vvv = (ctrl & 7) as usize;
+            let (pointer, len) = match ss {
+                0 => (vvv << 8 | self.uint(pos, 1)? as usize, 1),
+                1 => ((vvv << 16 | self.uint(pos, 2)? as usize) + 2048, 2),
+                2 => ((vvv << 24 | self.uint(pos, 3)? as usize) + 526_336, 3),
+                _ => (self.uint(pos, 4)? as usize, 4),
+            };
+            let (value, _) = self.decode(self.base + pointer, depth + 1)?;
+            return Ok((value, pos + len));
+        }
+        if kind == 0 {
+            kind = 7 + *self.bytes(pos, 1)?.first().unwrap_or(&0);
+            pos += 1;
+        }
+        let mut size = (ctrl & 0x1f) as usize;
+        match size {
+            29 => {
+                size = 29 + self.uint(pos, 1)? as usize;
+                pos += 1;
+            }
+            30 => {
+                size = 285 + self.uint(pos, 2)? as usize;
+                pos += 2;
+            }
+            31 => {
+                size = 65_821 + self.uint(pos, 3)? as usize;
+                pos += 3;
+            }
+            _ => {}
+        }
+
+        let value = match kind {
+            2 => Value::String(String::from_utf8_lossy(self.bytes(pos, size)?).into_owned()),
+            3 => {
+                let raw: [u8; 8] = self.bytes(pos, 8)?.try_into().unwrap_or_default();
+                size = 8;
+                Number::from_f64(f64::from_be_bytes(raw)).map_or(Value::Null, Value::Number)
+            }
+            4 => Value::String(
+                self.bytes(pos, size)?
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect(),
+            ),
+            5 | 6 | 9 => Value::from(self.uint(pos, size)? as u64),
+            10 => Value::String(self.uint(pos, size)?.to_string()),
+            8 => {
+                let raw = self.uint(pos, size)? as u32;
+                Value::from(raw as i32)
+            }
+            7 => {
+                let mut map = Map::new();
+                let mut next = pos;
+                for _ in 0..size {
+                    let (key, after_key) = self.decode(next, depth + 1)?;
+                    let (value, after_value) = self.decode(after_key, depth + 1)?;
+                    map.insert(key.as_str().unwrap_or_default().to_string(), value);
+                    next = after_value;
+                }
+                return Ok((Value::Object(map), next));
+            }
+            11 => {
+                let mut items = Vec::with_capacity(size.min(1024));
+                let mut next = pos;
+                for _ in 0..size {
+                    let (value, after) = self.decode(next, depth + 1)?;
+                    items.push(value);
+                    next = after;
+                }
+                return Ok((Value::Array(items), next));
+            }
+            14 => return Ok((Value::Bool(size != 0), pos)),
+            15 => {
+                let raw: [u8; 4] = self.bytes(pos, 4)?.try_into().unwrap_or_default();
+                size = 4;
+                Number::from_f64(f32::from_be_bytes(raw) as f64).map_or(Value::Null, Value::Number)
+            }
+            other => return Err(format!("unsupported MaxMind DB data type {}", other).into()),
+        };
+        Ok((value, pos + size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Control byte(s) for a value of `kind` and `size`.
+    fn ctrl(kind: u8, size: usize) -> Vec<u8> {
+        let (short, extra) = match size {
+            0..=28 => (size as u8, Vec::new()),
+            29..=284 => (29, vec![(size - 29) as u8]),
+            _ => (30, ((size - 285) as u16).to_be_bytes().to_vec()),
+        };
+        let mut out = if kind <= 7 {
+            vec![kind << 5 | short]
+        } else {
+            vec![short, kind - 7]
+        };
+        out.extend(extra);
+        out
+    }
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut out = ctrl(2, s.len());
+        out.extend_from_slice(s.as_bytes());
+        out
+    }
+
+    fn uint(kind: u8, n: u64) -> Vec<u8> {
+        let bytes: Vec<u8> = n
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        let mut out = ctrl(kind, bytes.len());
+        out.extend(bytes);
+        out
+    }
+
+    fn map(pairs: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut out = ctrl(7, pairs.len());
+        for (key, value) in pairs {
+            out.extend(string(key));
+            out.extend_from_slice(value);
+        }
+        out
+    }
+
+    fn pointer(to: usize) -> Vec<u8> {
+        assert!(to < 2048);
+        vec![1 << 5 | (to >> 8) as u8, to as u8]
+    }
+
+    /// An IPv4 database with 24-bit records mapping 192.0.2.0/24 to
+    /// `record`, which starts at `at` in `data`.
+    fn database(data: &[u8], at: usize) -> Vec<u8> {
+        let prefix: u32 = 0xc000_0200;
+        let nodes = 24;
+        let mut buf = Vec::new();
+        for i in 0..nodes {
+            let bit = (prefix >> (31 - i)) & 1;
+            let next = if i + 1 == nodes {
+                nodes + 16 + at
+            } else {
+                i + 1
+            };
+            let (left, right) = if bit == 0 {
+                (next, nodes)
+            } else {
+                (nodes, next)
+            };
+            buf.extend_from_slice(&(left as u32).to_be_bytes()[1..]);
+            buf.extend_from_slice(&(right as u32).to_be_bytes()[1..]);
+        }
+        buf.extend_from_slice(&[0; 16]);
+        buf.extend_from_slice(data);
+        buf.extend_from_slice(METADATA_MARKER);
+        buf.extend(map(&[
+            ("node_count", uint(6, nodes as u64)),
+            ("record_size", uint(5, 24)),
+            ("ip_version", uint(5, 4)),
+        ]));
+        buf
+    }
+
+    #[test]
+    fn looks_up_a_prefix() {
+        let shared = string("Netherlands");
+        let mut double = ctrl(3, 8);
+        double.extend(52.5f64.to_be_bytes());
+        let mut negative = ctrl(8, 4);
+        negative.extend((-5i32).to_be_bytes());
+        let long = "x".repeat(40);
+        let mut tags = ctrl(11, 2);
+        tags.extend(ctrl(14, 1));
+        tags.extend(uint(9, 1 << 40));
+        let record = map(&[
+            (
+                "country",
+                map(&[("iso_code", string("NL")), ("name", pointer(0))]),
+            ),
+            ("location", map(&[("latitude", double)])),
+            ("offset", negative),
+            ("long", string(&long)),
+            ("tags", tags),
+        ]);
+        let mut data = shared.clone();
+        data.extend(record);
+        let reader = Reader::from_bytes(database(&data, shared.len())).unwrap();
+
+        let found = reader.lookup("192.0.2.77".parse().unwrap()).unwrap();
+        assert_eq!(
+            found,
+            Some(json!({
+                "country": {"iso_code": "NL", "name": "Netherlands"},
+                "location": {"latitude": 52.5},
+                "offset": -5,
+                "long": long,
+                "tags": [true, 1u64 << 40],
+            }))
+        );
+        assert_eq!(reader.lookup("192.0.3.1".parse().unwrap()).unwrap(), None);
+        assert_eq!(reader.lookup("10.0.0.1".parse().unwrap()).unwrap(), None);
+        // No IPv6 tree in an IPv4 database
+        assert_eq!(reader.lookup("2001:db8::1".parse().unwrap()).unwrap(), None);
+    }
+
+    #[test]
+    fn pointer_loops_are_corrupt() {
+        let reader = Reader::from_bytes(database(&pointer(0), 0)).unwrap();
+        let err = reader.lookup("192.0.2.1".parse().unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "MaxMind DB data nested too deeply");
+    }
+
+    #[test]
+    fn files_without_metadata_are_refused() {
+        assert!(Reader::from_bytes(vec![0; 64]).is_err());
+    }
+
+    #[test]
+    fn trees_larger_than_the_file_are_refused() {
+        let mut buf = METADATA_MARKER.to_vec();
+        buf.extend(map(&[
+            ("node_count", uint(6, 1000)),
+            ("record_size", uint(5, 24)),
+            ("ip_version", uint(5, 4)),
+        ]));
+        let err = Reader::from_bytes(buf).err().unwrap();
+        assert_eq!(err.to_string(), "MaxMind DB search tree overruns the file");
+    }
+
+    #[test]
+    fn reads_28_bit_records() {
+        let reader = Reader {
+            // Left 0x1abcdef, right 0x2345678
+            buf: vec![0xab, 0xcd, 0xef, 0x12, 0x34, 0x56, 0x78],
+            node_count: 1,
+            record_size: 28,
+            ip_version: 4,
+            data: 0,
+            ipv4_start: 0,
+        };
+        assert_eq!(reader.record(0, 0).unwrap(), 0x1ab_cdef);
+        assert_eq!(reader.record(0, 1).unwrap(), 0x234_5678);
+        assert!(reader.record(1, 0).is_err());
+    }
+}
@@ -0,0 +1,17 @@
+//! Post-resolution enrichment of results with third-party context.
+
+pub mod asn;
+pub mod geoip;
+pub mod mmdb;
+pub mod rdap;
+pub mod reverseip;
+pub mod shodan;
+
+use crate::SubdomainResult;
+use std::collections::BTreeSet;
+
+/// Unique addresses across all results, in a stable order.
+pub fn unique_ips(results: &[SubdomainResult]) -> Vec<String> {
+    let set: BTreeSet<&String> = results.iter().flat_map(|r| r.ips.iter()).collect();
+    set.into_iter().cloned().collect()
+}
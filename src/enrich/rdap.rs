@@ -0,0 +1,195 @@
+//! Registration data over RDAP for `--rdap`: the registrar and dates of
+//! the apex, and the network every address belongs to and who holds it.
+//! rdap.org redirects each query to the registry or RIR responsible.
+
+use super::unique_ips;
+use crate::{Result, SubdomainResult};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::IpAddr;
+use std::time::Duration;
+use tracing::debug;
+
+const RDAP_BOOTSTRAP: &str = "https://rdap.org";
+
+/// rdap.org and the RIRs behind it throttle bursts.
+const RDAP_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Netblock lookups per domain. Addresses inside a block already looked up
+/// don't count.
+pub const MAX_NETBLOCK_LOOKUPS: usize = 100;
+
+/// Who registered a domain, and when.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Registration {
+    /// The apex looked up, which the scanned domain falls under.
+    pub domain: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registrar: Option<String>,
+    /// RFC 3339, as the registry gives it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+}
+
+/// An allocation an address falls in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Netblock {
+    /// CIDR notation, or `first - last` for ranges that aren't one prefix.
+    pub range: String,
+    /// The registrant, falling back to the network name (`CLOUDFLARENET`).
+    pub owner: Option<String>,
+    first: IpAddr,
+    last: IpAddr,
+}
+
+impl Netblock {
+    fn contains(&self, ip: IpAddr) -> bool {
+        ip.is_ipv4() == self.first.is_ipv4() && self.first <= ip && ip <= self.last
+    }
+}
+
+/// The registration of `apex`. `None` when the registry doesn't know it.
+pub async fn domain(client: &Client, apex: &str) -> Result<Option<Registration>> {
+    let Some(doc) = fetch(client, &format!("{}/domain/{}", RDAP_BOOTSTRAP, apex)).await? else {
+        return Ok(None);
+    };
+    Ok(Some(Registration {
+        domain: apex.to_string(),
+        registrar: entity_name(&doc, "registrar"),
+        created: event(&doc, "registration"),
+        expires: event(&doc, "expiration"),
+    }))
+}
+
+/// The netblock of `ip`.
+pub async fn netblock(client: &Client, ip: IpAddr) -> Result<Option<Netblock>> {
+    let Some(doc) = fetch(client, &format!("{}/ip/{}", RDAP_BOOTSTRAP, ip)).await? else {
+        return Ok(None);
+    };
+    let address = |key: &str| doc.get(key)?.as_str()?.parse::<IpAddr>().ok();
+    let (Some(first), Some(last)) = (address("startAddress"), address("endAddress")) else {
+        return Err("RDAP answer without a range".into());
+    };
+    let cidrs: Vec<String> = doc
+        .get("cidr0_cidrs")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|c| {
+            let prefix = c.get("v4prefix").or_else(|| c.get("v6prefix"))?.as_str()?;
+            Some(format!("{}/{}", prefix, c.get("length")?.as_u64()?))
+        })
+        .collect();
+    let range = if cidrs.is_empty() {
+        format!("{} - {}", first, last)
+    } else {
+        cidrs.join(", ")
+    };
+    let name = doc.get("name").and_then(Value::as_str).map(str::to_string);
+    Ok(Some(Netblock {
+        range,
+        owner: entity_name(&doc, "registrant").or(name),
+        first,
+        last,
+    }))
+}
+
+/// Fills `netblock` and `netblock_owner` of every result from its first
+/// address. Returns the last lookup error, if any.
+pub async fn enrich_netblocks(client: &Client, results: &mut [SubdomainResult]) -> Option<String> {
+    let mut blocks: Vec<Netblock> = Vec::new();
+    let mut lookups = 0;
+    let mut error = None;
+    for ip in unique_ips(results) {
+        let Ok(ip) = ip.parse::<IpAddr>() else {
+            continue;
+        };
+        if blocks.iter().any(|b| b.contains(ip)) {
+            continue;
+        }
+        if lookups == MAX_NETBLOCK_LOOKUPS {
+            debug!("RDAP lookup limit reached, {} left out", ip);
+            continue;
+        }
+        if lookups > 0 {
+            tokio::time::sleep(RDAP_INTERVAL).await;
+        }
+        lookups += 1;
+        match netblock(client, ip).await {
+            Ok(Some(block)) => blocks.push(block),
+            Ok(None) => {}
+            Err(e) => {
+                debug!("RDAP lookup of {} failed: {}", ip, e);
+                error = Some(format!("{}: {}", ip, e));
+            }
+        }
+    }
+
+    for r in results.iter_mut() {
+        let block = r
+            .ips
+            .iter()
+            .filter_map(|ip| ip.parse().ok())
+            .find_map(|ip| blocks.iter().find(|b| b.contains(ip)));
+        if let Some(b) = block {
+            r.netblock = Some(b.range.clone());
+            r.netblock_owner = b.owner.clone();
+        }
+    }
+    error
+}
+
+/// The RDAP object at `url`, `None` on a 404.
+async fn fetch(client: &Client, url: &str) -> Result<Option<Value>> {
+    let res = client
+        .get(url)
+        .header(reqwest::header::ACCEPT, "application/rdap+json")
+        .send()
+        .await?;
+    if res.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !res.status().is_success() {
+        return Err(format!("HTTP {}", res.status()).into());
+    }
+    Ok(Some(res.json().await?))
+}
+
+/// The `eventDate` of the first event with this `eventAction`.
+fn event(doc: &Value, action: &str) -> Option<String> {
+    doc.get("events")?.as_array()?.iter().find_map(|e| {
+        (e.get("eventAction")?.as_str()? == action)
+            .then(|| e.get("eventDate")?.as_str().map(str::to_string))
+            .flatten()
+    })
+}
+
+/// The vCard `fn` of the first entity with `role`, looking into nested
+/// entities too (RIRs hang the organisation below the network's contacts).
+fn entity_name(doc: &Value, role: &str) -> Option<String> {
+    let entities = doc.get("entities")?.as_array()?;
+    entities
+        .iter()
+        .find(|e| {
+            e.get("roles")
+                .and_then(Value::as_array)
+                .is_some_and(|roles| roles.iter().any(|r| r.as_str() == Some(role)))
+        })
+        .and_then(vcard_name)
+        .or_else(|| entities.iter().find_map(|e| entity_name(e, role)))
+}
+
+/// `["vcard", [["fn", {}, "text", "Example Inc."], ...]]`
+fn vcard_name(entity: &Value) -> Option<String> {
+    let properties = entity.get("vcardArray")?.as_array()?.get(1)?.as_array()?;
+    properties.iter().find_map(|p| {
+        let p = p.as_array()?;
+        (p.first()?.as_str()? == "fn")
+            .then(|| p.get(3)?.as_str().map(|s| s.trim().to_string()))
+            .flatten()
+            .filter(|s| !s.is_empty())
+    })
+}
@@ -0,0 +1,76 @@
+//! Other hostnames served from the same address, from HackerTarget's
+//! reverse IP lookup.
+
+use crate::dns::normalize_name;
+use crate::sources::{check_rate_limit, check_rate_limit_body};
+use crate::Result;
+use reqwest::Client;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+/// Addresses looked up per domain. The free tier only allows a few dozen
+/// lookups a day, so the rest would fail anyway.
+pub const MAX_ADDRESSES: usize = 50;
+
+/// The hostnames HackerTarget knows on each of `ips`, leaving out
+/// addresses it has none for; private and loopback ones aren't sent. A
+/// failure (a spent daily quota, most often) stops the lookups and is
+/// returned with what was found before it.
+pub async fn lookup(
+    client: &Client,
+    ips: &[String],
+) -> (BTreeMap<String, Vec<String>>, Option<crate::Error>) {
+    let mut found = BTreeMap::new();
+    let public = ips.iter().filter(|ip| is_public(ip));
+    for ip in public.take(MAX_ADDRESSES) {
+        match lookup_one(client, ip).await {
+            Ok(names) if !names.is_empty() => {
+                found.insert(ip.clone(), names);
+            }
+            Ok(_) => {}
+            Err(e) => return (found, Some(e)),
+        }
+    }
+    (found, None)
+}
+
+fn is_public(ip: &str) -> bool {
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V4(v4)) => {
+            !(v4.is_private() || v4.is_loopback() || v4.is_link_local() || v4.is_unspecified())
+        }
+        Ok(IpAddr::V6(v6)) => {
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_unique_local()
+                || v6.is_unicast_link_local())
+        }
+        Err(_) => false,
+    }
+}
+
+async fn lookup_one(client: &Client, ip: &str) -> Result<Vec<String>> {
+    let url = format!("https://api.hackertarget.com/reverseiplookup/?q={}", ip);
+    let res = client.get(&url).send().await?;
+    check_rate_limit(&res)?;
+    if !res.status().is_success() {
+        return Err(format!("HTTP {}", res.status()).into());
+    }
+    let text = res.text().await?;
+    check_rate_limit_body(&text)?;
+    if text.starts_with("error") {
+        return Err(text.lines().next().unwrap_or_default().to_string().into());
+    }
+    // "No DNS A records found for ..." when there are none
+    if text.starts_with("No ") {
+        return Ok(Vec::new());
+    }
+    let mut names: Vec<String> = text
+        .lines()
+        .map(normalize_name)
+        .filter(|n| !n.is_empty() && n.contains('.') && !n.contains(' '))
+        .collect();
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
@@ -0,0 +1,68 @@
+use super::unique_ips;
+use crate::{Result, SubdomainResult};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+use tracing::warn;
+
+#[derive(Deserialize)]
+struct ShodanHost {
+    #[serde(default)]
+    ports: Vec<u16>,
+}
+
+/// Shodan's API allows roughly one request per second.
+const SHODAN_INTERVAL: Duration = Duration::from_secs(1);
+
+pub async fn host_ports(client: &Client, api_key: &str, ip: &str) -> Result<Vec<u16>> {
+    let url = format!("https://api.shodan.io/shodan/host/{}", ip);
+    let res = client
+        .get(&url)
+        .query(&[("key", api_key), ("minify", "true")])
+        .send()
+        .await?;
+    if res.status().as_u16() == 404 {
+        return Ok(Vec::new());
+    }
+    if !res.status().is_success() {
+        return Err(format!("HTTP {}", res.status()).into());
+    }
+    let host: ShodanHost = res.json().await?;
+    Ok(host.ports)
+}
+
+/// Fills `shodan_ports` with the ports Shodan has seen open on any of each
+/// result's addresses.
+pub async fn enrich_ports(results: &mut [SubdomainResult], api_key: &str, proxy: Option<&Url>) {
+    let client = crate::proxy::apply(Client::builder(), proxy)
+        .timeout(Duration::from_secs(15))
+        .build()
+        .unwrap_or_default();
+
+    let mut by_ip: HashMap<String, Vec<u16>> = HashMap::new();
+    for (i, ip) in unique_ips(results).into_iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(SHODAN_INTERVAL).await;
+        }
+        match host_ports(&client, api_key, &ip).await {
+            Ok(ports) => {
+                by_ip.insert(ip, ports);
+            }
+            Err(e) => warn!("Shodan lookup for {} failed: {}", ip, e),
+        }
+    }
+
+    for r in results.iter_mut() {
+        let ports: BTreeSet<u16> = r
+            .ips
+            .iter()
+            .filter_map(|ip| by_ip.get(ip))
+            .flatten()
+            .copied()
+            .collect();
+        if r.ips.iter().any(|ip| by_ip.contains_key(ip)) {
+            r.shodan_ports = Some(ports.into_iter().collect());
+        }
+    }
+}
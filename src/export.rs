@@ -0,0 +1,117 @@
+//! Target lists for port scanners: plain `-iL` host lists nmap and masscan
+//! read directly, and an nmap-style XML file for tools that import scans.
+
+use crate::SubdomainResult;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Every unique address, one per line, IPv4 before IPv6.
+pub fn targets(results: &[SubdomainResult]) -> String {
+    lines(&addresses(results.iter()))
+}
+
+/// Writes one `-iL` list per provider (or CDN) into `dir`, named after it,
+/// with hosts on neither in `other.txt`. Returns the files written.
+pub fn write_by_provider(
+    dir: &Path,
+    results: &[SubdomainResult],
+) -> io::Result<Vec<std::path::PathBuf>> {
+    let mut groups: BTreeMap<String, Vec<&SubdomainResult>> = BTreeMap::new();
+    for r in results {
+        let group = r
+            .provider
+            .as_deref()
+            .or(r.cdn.as_deref())
+            .map(slug)
+            .unwrap_or_else(|| "other".to_string());
+        groups.entry(group).or_default().push(r);
+    }
+    std::fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for (group, members) in groups {
+        let ips = addresses(members.into_iter());
+        if ips.is_empty() {
+            continue;
+        }
+        let path = dir.join(format!("{}.txt", group));
+        std::fs::write(&path, lines(&ips))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// An nmap XML document with a host per address, carrying its hostnames
+/// and any open ports found with `--scan-ports`.
+pub fn nmap_xml(results: &[SubdomainResult]) -> String {
+    let mut hosts: BTreeMap<IpAddr, (BTreeSet<&str>, BTreeSet<u16>)> = BTreeMap::new();
+    for r in results {
+        for ip in r.ips.iter().filter_map(|ip| ip.parse::<IpAddr>().ok()) {
+            let (names, ports) = hosts.entry(ip).or_default();
+            names.insert(&r.subdomain);
+            ports.extend(&r.open_ports);
+        }
+    }
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE nmaprun>\n<nmaprun scanner=\"subpeek\" xmloutputversion=\"1.05\">\n",
+    );
+    for (ip, (names, ports)) in &hosts {
+        let addrtype = if ip.is_ipv4() { "ipv4" } else { "ipv6" };
+        let _ = writeln!(xml, "<host><status state=\"up\" reason=\"user-set\"/>");
+        let _ = writeln!(xml, "<address addr=\"{}\" addrtype=\"{}\"/>", ip, addrtype);
+        xml.push_str("<hostnames>\n");
+        for name in names {
+            let _ = writeln!(xml, "<hostname name=\"{}\" type=\"user\"/>", escape(name));
+        }
+        xml.push_str("</hostnames>\n");
+        if !ports.is_empty() {
+            xml.push_str("<ports>\n");
+            for port in ports {
+                let _ = writeln!(
+                    xml,
+                    "<port protocol=\"tcp\" portid=\"{}\"><state state=\"open\" reason=\"syn-ack\"/></port>",
+                    port
+                );
+            }
+            xml.push_str("</ports>\n");
+        }
+        xml.push_str("</host>\n");
+    }
+    let _ = writeln!(
+        xml,
+        "<runstats><hosts up=\"{0}\" down=\"0\" total=\"{0}\"/></runstats>",
+        hosts.len()
+    );
+    xml.push_str("</nmaprun>\n");
+    xml
+}
+
+fn addresses<'a>(results: impl Iterator<Item = &'a SubdomainResult>) -> BTreeSet<IpAddr> {
+    results
+        .flat_map(|r| r.ips.iter())
+        .filter_map(|ip| ip.parse().ok())
+        .collect()
+}
+
+fn lines(ips: &BTreeSet<IpAddr>) -> String {
+    ips.iter().map(|ip| format!("{}\n", ip)).collect()
+}
+
+/// `AWS CloudFront` becomes `aws-cloudfront`.
+fn slug(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
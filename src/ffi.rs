@@ -0,0 +1,167 @@
+//! A C ABI for running scans in-process (`--features ffi`), so Python and
+//! other recon frameworks can call the engine instead of spawning the
+//! binary and parsing its stdout. `subpeek.py` wraps it with ctypes.
+//!
+//! ```c
+//! char *subpeek_enumerate(const char *domain, const char *options_json);
+//! void subpeek_free(char *json);
+//! ```
+//!
+//! [`subpeek_enumerate`] answers the JSON array `subpeek -o json` writes
+//! for one domain, or `{"error": "..."}`. Either string is the caller's to
+//! release with [`subpeek_free`].
+//!
+//! Options start from the config file, like the command line's, and take
+//! these keys (`max_runtime` in seconds):
+//!
+//! ```json
+//! {
+//!   "profile": "stealth",
+//!   "sources": ["crtsh", "otx"],
+//!   "exclude_sources": [],
+//!   "discover": true,
+//!   "names": ["dev.example.com"],
+//!   "wordlist": "/usr/share/wordlists/subdomains.txt",
+//!   "resolvers": ["1.1.1.1", "https://cloudflare-dns.com/dns-query"],
+//!   "ports": [443, 8443],
+//!   "dns_concurrency": 200,
+//!   "http_concurrency": 50,
+//!   "user_agent": "Mozilla/5.0 (compatible; subpeek)",
+//!   "keys": {"virustotal": "..."},
+//!   "sorted": true,
+//!   "max_runtime": 1200
+//! }
+//! ```
+
+use crate::profile::Profile;
+use crate::{config, server, Config, Result, SubpeekEngine};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Options {
+    profile: Option<String>,
+    sources: Vec<String>,
+    exclude_sources: Vec<String>,
+    discover: Option<bool>,
+    names: Vec<String>,
+    wordlist: Option<PathBuf>,
+    resolvers: Vec<String>,
+    ports: Vec<u16>,
+    dns_concurrency: Option<usize>,
+    http_concurrency: Option<usize>,
+    user_agent: Option<String>,
+    keys: HashMap<String, String>,
+    sorted: Option<bool>,
+    max_runtime: Option<u64>,
+}
+
+/// Scans `domain` with the options in `options` (JSON, as in the module
+/// docs) and answers its results as a JSON array.
+pub fn enumerate(domain: &str, options: &str) -> Result<String> {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    if !server::is_domain(&domain) {
+        return Err(format!("invalid domain: {}", domain).into());
+    }
+    let wanted: Options = if options.trim().is_empty() {
+        Options::default()
+    } else {
+        serde_json::from_str(options).map_err(|e| format!("invalid options: {}", e))?
+    };
+
+    let mut o = Config::load(None)?.to_options()?;
+    if let Some(ref name) = wanted.profile {
+        name.parse::<Profile>()?.apply(&mut o);
+    }
+    if !wanted.sources.is_empty() {
+        o.sources = wanted.sources;
+    }
+    o.exclude_sources = wanted.exclude_sources;
+    if let Some(discover) = wanted.discover {
+        o.discover = discover;
+    }
+    o.candidates = wanted.names;
+    if wanted.wordlist.is_some() {
+        o.wordlist = wanted.wordlist;
+    }
+    if !wanted.resolvers.is_empty() {
+        o.resolvers = wanted
+            .resolvers
+            .iter()
+            .map(|r| config::parse_upstream(r))
+            .collect::<Result<_>>()?;
+    }
+    if !wanted.ports.is_empty() {
+        o.probe.ports = wanted.ports;
+    }
+    if let Some(n) = wanted.dns_concurrency {
+        o.dns_concurrency = n.max(1);
+    }
+    if let Some(n) = wanted.http_concurrency {
+        o.probe.concurrency = n.max(1);
+    }
+    if let Some(ua) = wanted.user_agent {
+        o.user_agent = ua;
+    }
+    for (name, key) in &wanted.keys {
+        o.api_keys.set(name, key);
+    }
+    if let Some(sorted) = wanted.sorted {
+        o.sorted = sorted;
+    }
+    if let Some(secs) = wanted.max_runtime {
+        o.max_runtime = Some(std::time::Duration::from_secs(secs));
+    }
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    let results = runtime.block_on(SubpeekEngine::with_options(o).run(&domain));
+    Ok(serde_json::to_string(&results)?)
+}
+
+/// C entry point of [`enumerate`]. `options_json` may be null for the
+/// defaults. Never returns null.
+///
+/// # Safety
+///
+/// `domain` and, unless null, `options_json` must be NUL-terminated
+/// strings that stay valid for the call.
+#[no_mangle]
+pub unsafe extern "C" fn subpeek_enumerate(
+    domain: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let answer = if domain.is_null() {
+        Err("no domain given".into())
+    } else {
+        let domain = CStr::from_ptr(domain).to_string_lossy();
+        let options = if options_json.is_null() {
+            "".into()
+        } else {
+            CStr::from_ptr(options_json).to_string_lossy()
+        };
+        // An unwinding panic must not cross into C
+        panic::catch_unwind(AssertUnwindSafe(|| enumerate(&domain, &options)))
+            .unwrap_or_else(|_| Err("the scan panicked".into()))
+    };
+    let json = answer.unwrap_or_else(|e| json!({ "error": e.to_string() }).to_string());
+    // JSON escapes control characters, so there is no NUL to refuse
+    CString::new(json).unwrap_or_default().into_raw()
+}
+
+/// Releases a string [`subpeek_enumerate`] returned.
+///
+/// # Safety
+///
+/// `json` must come from [`subpeek_enumerate`] and not have been freed;
+/// null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn subpeek_free(json: *mut c_char) {
+    if !json.is_null() {
+        drop(CString::from_raw(json));
+    }
+}
@@ -0,0 +1,53 @@
+//! Which probed hosts make it into the results: `--match-code`,
+//! `--filter-code`, `--match-title-regex` and `--match-server-regex`.
+
+use crate::SubdomainResult;
+use regex::Regex;
+
+/// Every set criterion must hold. The default keeps everything.
+#[derive(Debug, Clone, Default)]
+pub struct ResultFilter {
+    /// Keep only these status codes; hosts without a response are dropped.
+    pub match_codes: Vec<u16>,
+    /// Drop these status codes.
+    pub filter_codes: Vec<u16>,
+    /// Keep only hosts whose title matches.
+    pub match_title: Option<Regex>,
+    /// Keep only hosts whose `Server` header matches.
+    pub match_server: Option<Regex>,
+}
+
+impl ResultFilter {
+    pub fn is_empty(&self) -> bool {
+        self.match_codes.is_empty()
+            && self.filter_codes.is_empty()
+            && self.match_title.is_none()
+            && self.match_server.is_none()
+    }
+
+    pub fn keeps(&self, r: &SubdomainResult) -> bool {
+        let matches = |re: &Option<Regex>, field: &Option<String>| match re {
+            Some(re) => field.as_deref().is_some_and(|v| re.is_match(v)),
+            None => true,
+        };
+        (self.match_codes.is_empty()
+            || r.status_code.is_some_and(|c| self.match_codes.contains(&c)))
+            && !r
+                .status_code
+                .is_some_and(|c| self.filter_codes.contains(&c))
+            && matches(&self.match_title, &r.title)
+            && matches(&self.match_server, &r.server)
+    }
+}
+
+/// Status codes like `200,401,403`.
+pub fn parse_codes(list: &str) -> Result<Vec<u16>, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(|c| match c.parse::<u16>() {
+            Ok(code) if (100..600).contains(&code) => Ok(code),
+            _ => Err(format!("invalid status code '{}'", c)),
+        })
+        .collect()
+}
@@ -0,0 +1,47 @@
+//! Reading target lists.
+
+use crate::Result;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// One entry per line, lowercased; blank lines and `#` comments are skipped.
+pub fn parse_lines(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim().to_lowercase())
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// Reads a list from `path`, or from stdin when `path` is `-`.
+pub fn load_list(path: &Path) -> Result<Vec<String>> {
+    Ok(parse_lines(&read(path)?))
+}
+
+/// The contents of `path`, or of stdin when `path` is `-`.
+pub fn read(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        let mut buf = String::new();
+        io::stdin().read_to_string(&mut buf)?;
+        Ok(buf)
+    } else {
+        Ok(fs::read_to_string(path)?)
+    }
+}
+
+/// A best guess at the apex of each name: its last two labels. Used when
+/// candidates are supplied without a target domain.
+pub fn guess_apexes(names: &[String]) -> Vec<String> {
+    let mut apexes: Vec<String> = Vec::new();
+    for name in names {
+        let labels: Vec<&str> = name.trim_end_matches('.').rsplitn(3, '.').collect();
+        if labels.len() < 2 {
+            continue;
+        }
+        let apex = format!("{}.{}", labels[1], labels[0]);
+        if !apexes.contains(&apex) {
+            apexes.push(apex);
+        }
+    }
+    apexes
+}
@@ -0,0 +1,167 @@
+//! The JSON lines of amass (`amass enum -json`) and subfinder
+//! (`subfinder -oJ`), so subpeek can sit on either side of them in a
+//! pipeline: `--output-format amass` writes amass records, and `--input`
+//! reads both tools' records as well as plain lists.
+
+use crate::{Result, SubdomainResult};
+use serde_json::{json, Map, Value};
+
+/// One amass record for `r`. amass leaves out what it doesn't know, and so
+/// does this: `cidr`, `asn` and `desc` only appear for the first address,
+/// after `--rdap` or `--asn`.
+pub fn amass_record(r: &SubdomainResult) -> Value {
+    let domain = r.apex.clone().unwrap_or_else(|| guess_domain(&r.subdomain));
+    let addresses: Vec<Value> = r
+        .ips
+        .iter()
+        .enumerate()
+        .map(|(i, ip)| {
+            let mut address = Map::new();
+            address.insert("ip".to_string(), json!(ip));
+            if i == 0 {
+                if let Some(ref cidr) = r.netblock {
+                    address.insert("cidr".to_string(), json!(cidr));
+                }
+                if let Some(asn) = r.asn {
+                    address.insert("asn".to_string(), json!(asn));
+                }
+                if let Some(ref desc) = r.as_org.as_ref().or(r.netblock_owner.as_ref()) {
+                    address.insert("desc".to_string(), json!(desc));
+                }
+            }
+            Value::Object(address)
+        })
+        .collect();
+    json!({
+        "name": r.subdomain,
+        "domain": domain,
+        "addresses": addresses,
+        "tag": amass_tag(&r.sources),
+        "sources": r.sources,
+    })
+}
+
+/// An amass record for a host of a `--group-by-ip` cluster, which only
+/// carries the cluster's addresses.
+pub fn amass_host(name: &str, ips: &[String]) -> Value {
+    let addresses: Vec<Value> = ips.iter().map(|ip| json!({ "ip": ip })).collect();
+    json!({
+        "name": name,
+        "domain": guess_domain(name),
+        "addresses": addresses,
+        "tag": "dns",
+        "sources": [],
+    })
+}
+
+fn guess_domain(name: &str) -> String {
+    crate::related::apex(name).unwrap_or_else(|| name.to_string())
+}
+
+/// amass's tag for how a name was found, after the first of `sources`.
+fn amass_tag(sources: &[String]) -> &'static str {
+    sources
+        .iter()
+        .map(|s| match s.as_str() {
+            "bruteforce" | "builtin" => "brute",
+            "permute" | "markov" => "alt",
+            "axfr" => "axfr",
+            "nsec" => "dns",
+            "feedback" => "scrape",
+            "input" | "seed" => "ext",
+            "crtsh" | "certspotter" | "censys" => "cert",
+            "wayback" | "commoncrawl" => "archive",
+            _ => "api",
+        })
+        .next()
+        .unwrap_or("dns")
+}
+
+/// Whether `text` is JSON lines rather than a plain list of names.
+pub fn is_json_lines(text: &str) -> bool {
+    text.lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .is_some_and(|l| l.starts_with('{'))
+}
+
+/// Maps amass and subfinder records to results: the name, its apex,
+/// addresses and the sources that tool credits.
+///
+/// | amass | subfinder | result |
+/// |---|---|---|
+/// | `name` | `host` | `subdomain` |
+/// | `domain` | `input` | `apex` |
+/// | `addresses[].ip` | `ip` | `ips` |
+/// | `addresses[0].cidr` | | `netblock` |
+/// | `addresses[0].asn` | | `asn` |
+/// | `addresses[0].desc` | | `as_org` |
+/// | `sources` | `source`, `sources` | `sources` |
+///
+/// subpeek's own JSON lines are read as they are.
+pub fn import(text: &str) -> Result<Vec<SubdomainResult>> {
+    let mut out = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Value::Object(record) = serde_json::from_str(line)? else {
+            return Err(format!("line {}: not a JSON object", n + 1).into());
+        };
+        let mapped = if record.contains_key("subdomain") {
+            Value::Object(record)
+        } else if record.contains_key("name") {
+            from_amass(record)
+        } else if record.contains_key("host") {
+            from_subfinder(record)
+        } else {
+            return Err(format!(
+                "line {}: neither an amass (name) nor a subfinder (host) record",
+                n + 1
+            )
+            .into());
+        };
+        let mut result: SubdomainResult = serde_json::from_value(mapped)?;
+        result.subdomain = result.subdomain.trim_end_matches('.').to_lowercase();
+        out.push(result);
+    }
+    Ok(out)
+}
+
+fn from_amass(mut record: Map<String, Value>) -> Value {
+    let addresses = match record.remove("addresses") {
+        Some(Value::Array(a)) => a,
+        _ => Vec::new(),
+    };
+    let first = addresses.first();
+    let field = |key: &str| first.and_then(|a| a.get(key)).cloned();
+    json!({
+        "subdomain": record.remove("name"),
+        "apex": record.remove("domain"),
+        "ips": addresses.iter().filter_map(|a| a.get("ip")).collect::<Vec<_>>(),
+        "netblock": field("cidr"),
+        "asn": field("asn"),
+        "as_org": field("desc"),
+        "sources": record.remove("sources").unwrap_or_else(|| json!([])),
+    })
+}
+
+fn from_subfinder(mut record: Map<String, Value>) -> Value {
+    let sources = match (record.remove("sources"), record.remove("source")) {
+        (Some(Value::Array(s)), _) => s,
+        (_, Some(source)) => vec![source],
+        _ => Vec::new(),
+    };
+    let ips: Vec<Value> = record
+        .remove("ip")
+        .into_iter()
+        .filter(|ip| ip.as_str().is_some_and(|ip| !ip.is_empty()))
+        .collect();
+    json!({
+        "subdomain": record.remove("host"),
+        "apex": record.remove("input"),
+        "ips": ips,
+        "sources": sources,
+    })
+}
@@ -0,0 +1,107 @@
+//! Errors and warnings a scan ran into, kept so that a failed source or a
+//! host that never answered shows up in the output instead of only in the
+//! log.
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Issues of one kind printed in the end-of-scan summary; the rest are
+/// only counted.
+const SUMMARY_LIMIT: usize = 10;
+
+/// One thing that went wrong.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Issue {
+    pub domain: String,
+    /// The phase it happened in: `source`, `dns`, `probe`, `enrich`,
+    /// `vhost` or `related`.
+    pub phase: String,
+    /// The source, host or file involved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    pub message: String,
+}
+
+/// What went wrong while scanning one domain. Errors mean results are
+/// missing, such as a source that failed; warnings are about single hosts
+/// or optional steps.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanIssues {
+    #[serde(default)]
+    pub errors: Vec<Issue>,
+    #[serde(default)]
+    pub warnings: Vec<Issue>,
+}
+
+impl ScanIssues {
+    pub fn error(
+        &mut self,
+        domain: &str,
+        phase: &str,
+        target: Option<&str>,
+        message: impl Into<String>,
+    ) {
+        self.errors
+            .push(issue(domain, phase, target, message.into()));
+    }
+
+    pub fn warning(
+        &mut self,
+        domain: &str,
+        phase: &str,
+        target: Option<&str>,
+        message: impl Into<String>,
+    ) {
+        self.warnings
+            .push(issue(domain, phase, target, message.into()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+
+    pub fn extend(&mut self, other: ScanIssues) {
+        self.errors.extend(other.errors);
+        self.warnings.extend(other.warnings);
+    }
+
+    /// Logs the errors and the first few warnings, so a scan that lost a
+    /// source doesn't look like a clean one.
+    pub fn print_summary(&self) {
+        if self.is_empty() {
+            return;
+        }
+        warn!(
+            "Scan finished with {} errors and {} warnings:",
+            self.errors.len(),
+            self.warnings.len()
+        );
+        for (label, issues) in [("error", &self.errors), ("warning", &self.warnings)] {
+            for i in issues.iter().take(SUMMARY_LIMIT) {
+                warn!("  {:<7} {}", label, i);
+            }
+            if issues.len() > SUMMARY_LIMIT {
+                warn!("  ... and {} more {}s", issues.len() - SUMMARY_LIMIT, label);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.phase, self.domain)?;
+        if let Some(ref target) = self.target {
+            write!(f, " {}", target)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+fn issue(domain: &str, phase: &str, target: Option<&str>, message: String) -> Issue {
+    Issue {
+        domain: domain.to_string(),
+        phase: phase.to_string(),
+        target: target.map(str::to_string),
+        message,
+    }
+}
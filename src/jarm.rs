@@ -0,0 +1,447 @@
+//! JARM TLS server fingerprints for `--jarm`. Ten crafted ClientHellos
+//! (different versions, cipher orders, ALPN lists and extensions) are sent
+//! and what each ServerHello picks is hashed into 62 hex characters, the
+//! same as Salesforce's reference tool produces. Equal fingerprints
+//! suggest the same TLS stack, so they group hosts behind one backend or
+//! WAF product even when their names and addresses have nothing in common.
+
+use crate::hash::{hex, sha256};
+use crate::proxy;
+use crate::ratelimit::RateLimits;
+use rand::{Rng, RngCore};
+use reqwest::Url;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
+
+/// Time allowed for each of the ten handshakes.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bytes of the reply looked at, as in the reference tool.
+const MAX_REPLY: usize = 1484;
+
+const NO_ANSWER: &str = "|||";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Version {
+    Tls11,
+    Tls12,
+    Tls13,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Order {
+    Forward,
+    Reverse,
+    TopHalf,
+    BottomHalf,
+    MiddleOut,
+}
+
+/// Which `supported_versions` extension a hello carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Support {
+    None,
+    Tls12,
+    Tls13,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Probe {
+    version: Version,
+    /// `false` leaves the TLS 1.3 suites out.
+    tls13_ciphers: bool,
+    cipher_order: Order,
+    grease: bool,
+    rare_alpn: bool,
+    support: Support,
+    extension_order: Order,
+}
+
+const fn probe(
+    version: Version,
+    tls13_ciphers: bool,
+    cipher_order: Order,
+    grease: bool,
+    rare_alpn: bool,
+    support: Support,
+    extension_order: Order,
+) -> Probe {
+    Probe {
+        version,
+        tls13_ciphers,
+        cipher_order,
+        grease,
+        rare_alpn,
+        support,
+        extension_order,
+    }
+}
+
+/// The reference tool's ten hellos, in its order.
+const PROBES: [Probe; 10] = {
+    use Order::*;
+    use Version::*;
+    [
+        probe(Tls12, true, Forward, false, false, Support::Tls12, Reverse),
+        probe(Tls12, true, Reverse, false, false, Support::Tls12, Forward),
+        probe(Tls12, true, TopHalf, false, false, Support::None, Forward),
+        probe(Tls12, true, BottomHalf, false, true, Support::None, Forward),
+        probe(Tls12, true, MiddleOut, true, true, Support::None, Reverse),
+        probe(Tls11, true, Forward, false, false, Support::None, Forward),
+        probe(Tls13, true, Forward, false, false, Support::Tls13, Reverse),
+        probe(Tls13, true, Reverse, false, false, Support::Tls13, Forward),
+        probe(Tls13, false, Forward, false, false, Support::Tls13, Forward),
+        probe(Tls13, true, MiddleOut, true, false, Support::Tls13, Reverse),
+    ]
+};
+
+/// Cipher suites offered, in the reference tool's order.
+const CIPHERS: &[u16] = &[
+    0x0016, 0x0033, 0x0067, 0xc09e, 0xc0a2, 0x009e, 0x0039, 0x006b, 0xc09f, 0xc0a3, 0x009f, 0x0045,
+    0x00be, 0x0088, 0x00c4, 0x009a, 0xc008, 0xc009, 0xc023, 0xc0ac, 0xc0ae, 0xc02b, 0xc00a, 0xc024,
+    0xc0ad, 0xc0af, 0xc02c, 0xc072, 0xc073, 0xcca9, 0x1302, 0x1301, 0xcc14, 0xc007, 0xc012, 0xc013,
+    0xc027, 0xc02f, 0xc014, 0xc028, 0xc030, 0xc060, 0xc061, 0xc076, 0xc077, 0xcca8, 0x1305, 0x1304,
+    0x1303, 0xcc13, 0xc011, 0x000a, 0x002f, 0x003c, 0xc09c, 0xc0a0, 0x009c, 0x0035, 0x003d, 0xc09d,
+    0xc0a1, 0x009d, 0x0041, 0x00ba, 0x0084, 0x00c0, 0x0007, 0x0004, 0x0005,
+];
+
+/// The same suites sorted; a chosen suite is encoded as its position here.
+const CIPHER_CODES: &[u16] = &[
+    0x0004, 0x0005, 0x0007, 0x000a, 0x0016, 0x002f, 0x0033, 0x0035, 0x0039, 0x003c, 0x003d, 0x0041,
+    0x0045, 0x0067, 0x006b, 0x0084, 0x0088, 0x009a, 0x009c, 0x009d, 0x009e, 0x009f, 0x00ba, 0x00be,
+    0x00c0, 0x00c4, 0xc007, 0xc008, 0xc009, 0xc00a, 0xc011, 0xc012, 0xc013, 0xc014, 0xc023, 0xc024,
+    0xc027, 0xc028, 0xc02b, 0xc02c, 0xc02f, 0xc030, 0xc060, 0xc061, 0xc072, 0xc073, 0xc076, 0xc077,
+    0xc09c, 0xc09d, 0xc09e, 0xc09f, 0xc0a0, 0xc0a1, 0xc0a2, 0xc0a3, 0xc0ac, 0xc0ad, 0xc0ae, 0xc0af,
+    0xcc13, 0xcc14, 0xcca8, 0xcca9, 0x1301, 0x1302, 0x1303, 0x1304, 0x1305,
+];
+
+const ALPNS: &[&str] = &[
+    "http/0.9", "http/1.0", "http/1.1", "spdy/1", "spdy/2", "spdy/3", "h2", "h2c", "hq",
+];
+
+const RARE_ALPNS: &[&str] = &[
+    "http/0.9", "http/1.0", "spdy/1", "spdy/2", "spdy/3", "h2c", "hq",
+];
+
+/// The JARM fingerprint of the TLS server at `host:port`, or `None` when
+/// not one of the hellos got a ServerHello back. Each handshake counts as
+/// a request against the budget in `limits`.
+pub async fn fingerprint(
+    host: &str,
+    port: u16,
+    proxy: Option<&Url>,
+    limits: &RateLimits,
+) -> Option<String> {
+    let mut answers = Vec::with_capacity(PROBES.len());
+    for probe in &PROBES {
+        let hello = client_hello(host, probe);
+        limits.count_request();
+        let answer = match exchange(host, port, proxy, &hello).await {
+            Some(reply) => {
+                limits.count_bytes(reply.len() as u64);
+                read_server_hello(&reply)
+            }
+            None => NO_ANSWER.to_string(),
+        };
+        answers.push(answer);
+    }
+    if answers.iter().all(|a| a == NO_ANSWER) {
+        return None;
+    }
+    Some(hash(&answers))
+}
+
+/// Sends `hello` and reads the first record of the reply.
+async fn exchange(host: &str, port: u16, proxy: Option<&Url>, hello: &[u8]) -> Option<Vec<u8>> {
+    let attempt = async {
+        let mut stream = proxy::connect(proxy, host, port).await.ok()?;
+        stream.write_all(hello).await.ok()?;
+        let mut reply = Vec::new();
+        let mut buf = [0u8; MAX_REPLY];
+        while reply.len() < MAX_REPLY {
+            let n = stream
+                .read(&mut buf[..MAX_REPLY - reply.len()])
+                .await
+                .ok()?;
+            if n == 0 {
+                break;
+            }
+            reply.extend_from_slice(&buf[..n]);
+            // Done once the whole first record is in
+            if reply.len() >= 5 && reply.len() >= 5 + u16_at(&reply, 3)? as usize {
+                break;
+            }
+        }
+        Some(reply)
+    };
+    timeout(HANDSHAKE_TIMEOUT, attempt).await.ok().flatten()
+}
+
+fn client_hello(host: &str, probe: &Probe) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    let (record_version, hello_version): (&[u8], &[u8]) = match probe.version {
+        Version::Tls11 => (&[3, 2], &[3, 2]),
+        Version::Tls12 => (&[3, 3], &[3, 3]),
+        Version::Tls13 => (&[3, 1], &[3, 3]),
+    };
+
+    let mut hello = hello_version.to_vec();
+    let mut random = [0u8; 32];
+    rng.fill_bytes(&mut random);
+    hello.extend_from_slice(&random);
+    let mut session_id = [0u8; 32];
+    rng.fill_bytes(&mut session_id);
+    hello.push(session_id.len() as u8);
+    hello.extend_from_slice(&session_id);
+
+    let mut ciphers: Vec<u16> = CIPHERS
+        .iter()
+        .copied()
+        .filter(|c| probe.tls13_ciphers || c >> 8 != 0x13)
+        .collect();
+    ciphers = reorder(&ciphers, probe.cipher_order);
+    if probe.grease {
+        ciphers.insert(0, grease());
+    }
+    push_u16(&mut hello, ciphers.len() as u16 * 2);
+    for c in ciphers {
+        push_u16(&mut hello, c);
+    }
+    // One compression method, null
+    hello.extend_from_slice(&[1, 0]);
+    hello.extend_from_slice(&extensions(host, probe));
+
+    let mut handshake = vec![1, 0];
+    push_u16(&mut handshake, hello.len() as u16);
+    handshake.extend_from_slice(&hello);
+    let mut record = vec![0x16];
+    record.extend_from_slice(record_version);
+    push_u16(&mut record, handshake.len() as u16);
+    record.extend_from_slice(&handshake);
+    record
+}
+
+fn extensions(host: &str, probe: &Probe) -> Vec<u8> {
+    let mut all = Vec::new();
+    if probe.grease {
+        push_u16(&mut all, grease());
+        all.extend_from_slice(&[0, 0]);
+    }
+
+    // server_name
+    all.extend_from_slice(&[0, 0]);
+    push_u16(&mut all, host.len() as u16 + 5);
+    push_u16(&mut all, host.len() as u16 + 3);
+    all.push(0);
+    push_u16(&mut all, host.len() as u16);
+    all.extend_from_slice(host.as_bytes());
+
+    // extended_master_secret, max_fragment_length, renegotiation_info,
+    // supported_groups, ec_point_formats and session_ticket
+    all.extend_from_slice(&[0x00, 0x17, 0x00, 0x00]);
+    all.extend_from_slice(&[0x00, 0x01, 0x00, 0x01, 0x01]);
+    all.extend_from_slice(&[0xff, 0x01, 0x00, 0x01, 0x00]);
+    all.extend_from_slice(&[
+        0x00, 0x0a, 0x00, 0x0a, 0x00, 0x08, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x18, 0x00, 0x19,
+    ]);
+    all.extend_from_slice(&[0x00, 0x0b, 0x00, 0x02, 0x01, 0x00]);
+    all.extend_from_slice(&[0x00, 0x23, 0x00, 0x00]);
+
+    // application_layer_protocol_negotiation
+    let alpns = if probe.rare_alpn { RARE_ALPNS } else { ALPNS };
+    let mut list = Vec::new();
+    for alpn in reorder(alpns, probe.extension_order) {
+        list.push(alpn.len() as u8);
+        list.extend_from_slice(alpn.as_bytes());
+    }
+    all.extend_from_slice(&[0x00, 0x10]);
+    push_u16(&mut all, list.len() as u16 + 2);
+    push_u16(&mut all, list.len() as u16);
+    all.extend_from_slice(&list);
+
+    // signature_algorithms
+    all.extend_from_slice(&[
+        0x00, 0x0d, 0x00, 0x14, 0x00, 0x12, 0x04, 0x03, 0x08, 0x04, 0x04, 0x01, 0x05, 0x03, 0x08,
+        0x05, 0x05, 0x01, 0x08, 0x06, 0x06, 0x01, 0x02, 0x01,
+    ]);
+
+    // key_share with a random x25519 key
+    let mut share = Vec::new();
+    if probe.grease {
+        push_u16(&mut share, grease());
+        share.extend_from_slice(&[0x00, 0x01, 0x00]);
+    }
+    share.extend_from_slice(&[0x00, 0x1d, 0x00, 0x20]);
+    let mut key = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    share.extend_from_slice(&key);
+    all.extend_from_slice(&[0x00, 0x33]);
+    push_u16(&mut all, share.len() as u16 + 2);
+    push_u16(&mut all, share.len() as u16);
+    all.extend_from_slice(&share);
+
+    // psk_key_exchange_modes
+    all.extend_from_slice(&[0x00, 0x2d, 0x00, 0x02, 0x01, 0x01]);
+
+    // supported_versions
+    if probe.version == Version::Tls13 || probe.support == Support::Tls12 {
+        let mut offered: Vec<u16> = vec![0x0301, 0x0302, 0x0303];
+        if probe.support != Support::Tls12 {
+            offered.push(0x0304);
+        }
+        let mut versions = Vec::new();
+        if probe.grease {
+            push_u16(&mut versions, grease());
+        }
+        for v in reorder(&offered, probe.extension_order) {
+            push_u16(&mut versions, v);
+        }
+        all.extend_from_slice(&[0x00, 0x2b]);
+        push_u16(&mut all, versions.len() as u16 + 1);
+        all.push(versions.len() as u8);
+        all.extend_from_slice(&versions);
+    }
+
+    let mut out = Vec::with_capacity(all.len() + 2);
+    push_u16(&mut out, all.len() as u16);
+    out.extend_from_slice(&all);
+    out
+}
+
+/// The reference tool's reorderings of cipher, ALPN and version lists.
+fn reorder<T: Clone>(items: &[T], order: Order) -> Vec<T> {
+    let len = items.len();
+    let middle = len / 2;
+    match order {
+        Order::Forward => items.to_vec(),
+        Order::Reverse => items.iter().rev().cloned().collect(),
+        Order::BottomHalf => items[middle + len % 2..].to_vec(),
+        Order::TopHalf => {
+            let mut out = Vec::new();
+            if len % 2 == 1 {
+                out.push(items[middle].clone());
+            }
+            out.extend(reorder(&reorder(items, Order::Reverse), Order::BottomHalf));
+            out
+        }
+        Order::MiddleOut => {
+            let mut out = Vec::with_capacity(len);
+            if len % 2 == 1 {
+                out.push(items[middle].clone());
+                for i in 1..=middle {
+                    out.push(items[middle + i].clone());
+                    out.push(items[middle - i].clone());
+                }
+            } else {
+                for i in 1..=middle {
+                    out.push(items[middle - 1 + i].clone());
+                    out.push(items[middle - i].clone());
+                }
+            }
+            out
+        }
+    }
+}
+
+/// A random GREASE value (RFC 8701).
+fn grease() -> u16 {
+    let n: u16 = rand::thread_rng().gen_range(0..16);
+    let byte = (n << 4) | 0x0a;
+    (byte << 8) | byte
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]))
+}
+
+/// `cipher|version|alpn|extensions` of a ServerHello, or [`NO_ANSWER`] for
+/// an alert or anything else.
+fn read_server_hello(data: &[u8]) -> String {
+    if data.first() != Some(&0x16) || data.get(5) != Some(&0x02) {
+        return NO_ANSWER.to_string();
+    }
+    let (Some(hello_length), Some(&session_id_len)) = (u16_at(data, 3), data.get(43)) else {
+        return NO_ANSWER.to_string();
+    };
+    let counter = session_id_len as usize;
+    let cipher = data.get(counter + 44..counter + 46).unwrap_or_default();
+    let version = data.get(9..11).unwrap_or_default();
+    format!(
+        "{}|{}|{}",
+        hex(cipher),
+        hex(version),
+        server_extensions(data, counter, hello_length as usize).unwrap_or_else(|| "|".to_string())
+    )
+}
+
+/// `alpn|type-type-...` of the ServerHello extensions. `None` where the
+/// reference tool gives up on the hello.
+fn server_extensions(data: &[u8], counter: usize, hello_length: usize) -> Option<String> {
+    if *data.get(counter + 47)? == 11
+        || data.get(counter + 50..counter + 53) == Some(&[0x0e, 0xac, 0x0b][..])
+        || data.get(82..85) == Some(&[0x0f, 0xf0, 0x0b][..])
+        || counter + 42 >= hello_length
+    {
+        return None;
+    }
+    let mut at = counter + 49;
+    let end = u16_at(data, counter + 47)? as usize + at - 1;
+    let mut types = Vec::new();
+    let mut alpn = String::new();
+    while at < end {
+        let kind = data.get(at..at + 2)?;
+        let length = u16_at(data, at + 2)? as usize;
+        if kind == [0x00, 0x10] && alpn.is_empty() && length > 0 {
+            // Past the list length and the protocol length
+            let value = data.get(at + 4..at + 4 + length)?;
+            alpn = String::from_utf8_lossy(value.get(3..)?).into_owned();
+        }
+        types.push(hex(kind));
+        at += 4 + length;
+    }
+    Some(format!("{}|{}", alpn, types.join("-")))
+}
+
+/// Folds the ten answers into the fingerprint: three characters per hello
+/// for the chosen suite and version, then a truncated SHA-256 of the ALPN
+/// and extension parts.
+fn hash(answers: &[String]) -> String {
+    let mut fuzzy = String::new();
+    let mut rest = String::new();
+    for answer in answers {
+        let parts: Vec<&str> = answer.split('|').collect();
+        let part = |i: usize| parts.get(i).copied().unwrap_or_default();
+        fuzzy.push_str(&cipher_code(part(0)));
+        fuzzy.push(version_code(part(1)));
+        rest.push_str(part(2));
+        rest.push_str(part(3));
+    }
+    fuzzy.push_str(&hex(&sha256(rest.as_bytes()))[..32]);
+    fuzzy
+}
+
+fn cipher_code(cipher: &str) -> String {
+    if cipher.is_empty() {
+        return "00".to_string();
+    }
+    let position = CIPHER_CODES
+        .iter()
+        .position(|c| format!("{:04x}", c) == cipher)
+        .unwrap_or(CIPHER_CODES.len());
+    format!("{:02x}", position + 1)
+}
+
+/// `0303` (TLS 1.2) is `d`, `0301` is `b` and so on.
+fn version_code(version: &str) -> char {
+    version
+        .chars()
+        .nth(3)
+        .and_then(|c| c.to_digit(10))
+        .and_then(|d| "abcdef".chars().nth(d as usize))
+        .unwrap_or('0')
+}
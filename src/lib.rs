@@ -0,0 +1,340 @@
+//! SubPeek core: passive subdomain discovery, DNS verification, wildcard
+//! filtering and HTTP probing.
+//!
+//! The [`SubpeekEngine`] runs the whole pipeline. The individual phases are
+//! also exposed so other tools can run only the parts they need.
+
+// `output::options_summary` is one `json!` with an entry per option
+#![recursion_limit = "256"]
+
+pub mod adaptive;
+pub mod axfr;
+pub mod banner;
+pub mod cdn;
+pub mod cert;
+pub mod checkpoint;
+pub mod cloud;
+pub mod cluster;
+pub mod config;
+pub mod dangling;
+pub mod deadline;
+pub mod diff;
+pub mod dns;
+pub mod dnscache;
+pub mod dnssec;
+mod engine;
+pub mod enrich;
+pub mod export;
+pub mod favicon;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+mod hash;
+pub mod input;
+pub mod interop;
+pub mod issues;
+pub mod jarm;
+pub mod logging;
+pub mod markov;
+pub mod matchers;
+pub mod merge;
+pub mod metrics;
+pub mod monitor;
+pub mod notify;
+mod options;
+pub mod output;
+pub mod permute;
+pub mod plan;
+pub mod portscan;
+pub mod probe;
+pub mod profile;
+pub mod progress;
+pub mod proxy;
+pub mod ratelimit;
+pub mod rawdns;
+pub mod records;
+pub mod related;
+pub mod report;
+pub mod robots;
+pub mod scope;
+#[cfg(feature = "screenshots")]
+pub mod screenshot;
+pub mod server;
+pub mod sources;
+pub mod store;
+pub mod synthetic;
+pub mod tags;
+pub mod takeover;
+pub mod tech;
+pub mod title;
+pub mod vhost;
+pub mod wildcard;
+pub mod wordlists;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub use cert::CertificateInfo;
+pub use config::Config;
+pub use dns::{brute_force, verify_dns, ResolvedHost, ResolverPool, Upstream};
+pub use dnssec::DnssecStatus;
+pub use engine::{Neighbors, ScanStats, SubpeekEngine};
+pub use issues::{Issue, ScanIssues};
+pub use options::ScanOptions;
+pub use output::OutputFormat;
+pub use probe::{probe_http, HeaderCapture, ProbeOptions};
+pub use records::DnsRecords;
+pub use sources::{fetch_all_subdomains, DataSource};
+pub use store::Store;
+pub use takeover::TakeoverFinding;
+pub use wildcard::{
+    detect_wildcard, detect_wildcards, is_wildcard_match, wildcard_match_reason, WildcardProfile,
+};
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SubdomainResult {
+    pub subdomain: String,
+    /// The scanned domain this host was found under, set when several
+    /// domains are scanned at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apex: Option<String>,
+    /// `seed` for names from a `--known` inventory, which are baseline
+    /// rather than findings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// The passive sources (`crtsh`, `otx`, ...) that returned this name,
+    /// or how else it became a candidate: `input`, `seed`, `builtin`,
+    /// `axfr`, `nsec`, `bruteforce`, `permute`, `markov` or `feedback`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub ips: Vec<String>,
+    /// Resolved to both IPv4 and IPv6 addresses.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dual_stack: bool,
+    #[serde(default)]
+    pub cname_chain: Vec<String>,
+    /// DNS server that answered, e.g. `1.1.1.1:53`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolver: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns_latency_ms: Option<u64>,
+    /// Lowest TTL of the answer, in seconds. Low values hint at dynamic
+    /// infrastructure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns_ttl: Option<u32>,
+    /// Port probed when `--ports` is used; `None` for the scheme default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Scheme that answered, `https` or `http`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<String>,
+    /// HTTP version the response came over: `http/1.0`, `http/1.1`, `h2`
+    /// or `h3`, as in ALPN.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_version: Option<String>,
+    /// `ipv4` or `ipv6`: the family of the address the response came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_family: Option<String>,
+    /// Method of the reported response when it wasn't GET: `HEAD` with
+    /// `--head-first`, or the `--method` one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// URL the reported status, title and body came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_url: Option<String>,
+    /// URLs redirected through before `final_url`, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirect_chain: Vec<String>,
+    /// `Location` of an unfollowed redirect.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// Time until the final response's headers arrived, redirects included.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_time_ms: Option<u64>,
+    /// Slower than `--slow-threshold`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub slow: bool,
+    /// Resolved or answered only after a retry.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub retried: bool,
+    /// Why no request got an answer, for hosts that resolved but never did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probe_error: Option<String>,
+    pub status_code: Option<u16>,
+    pub title: Option<String>,
+    pub server: Option<String>,
+    pub content_length: Option<u64>,
+    /// [`wildcard::body_hash`] of the page: SHA-256 of its first 64 KiB
+    /// with whitespace normalised. Equal for identical default pages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_hash: Option<String>,
+    /// Authentication scheme a 401 or 407 asked for, e.g. `Basic`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_scheme: Option<String>,
+    /// Realm of that challenge, which often names the product behind it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_realm: Option<String>,
+    pub takeover: Option<TakeoverFinding>,
+    /// Points at something that is gone, see [`dangling`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dangling: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dangling_evidence: Option<String>,
+    /// Vendor whose challenge or block page answered instead of the site,
+    /// see [`synthetic`]. Its title isn't reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub waf_challenge: Option<String>,
+    /// Why the host answers like a honeypot or tarpit rather than a site.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspect_honeypot: Option<String>,
+    /// Response headers chosen with `--capture-headers`, by lowercase name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Named groups matched by `--extract` patterns.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extracted: BTreeMap<String, String>,
+    /// Tags of the `--match-body` rules the body matched, also in `tags`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub body_matches: Vec<String>,
+    /// CDN or WAF the host is fronted by, from its addresses, CNAMEs or
+    /// response headers. `None` suggests a directly exposed origin.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cdn: Option<String>,
+    /// Cloud or hosting service the host lives in, from its CNAMEs and
+    /// addresses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Products recognised from headers, cookies and body.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub technologies: Vec<String>,
+    /// Environment labels such as `dev`, `staging` or `vpn`, see [`tags`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Body similarity to the wildcard page of a wildcard whose addresses
+    /// this host shares.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wildcard_similarity: Option<f64>,
+    /// Why the wildcard check would have dropped this host, kept with
+    /// `--keep-filtered`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filtered_reason: Option<String>,
+    /// Zone of the wildcard it was taken for: `dev.example.com` for
+    /// `*.dev.example.com`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wildcard_zone: Option<String>,
+    /// Shodan/FOFA-style mmh3 hash of `/favicon.ico`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub favicon_hash: Option<i32>,
+    /// Interesting `Disallow` paths of the host's `robots.txt`, with
+    /// `--robots`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disallowed_paths: Vec<String>,
+    /// Status of each `--paths` path that didn't answer 404.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub paths: BTreeMap<String, u16>,
+    /// Certificate presented over HTTPS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<CertificateInfo>,
+    /// JARM fingerprint of the TLS server, with `--jarm`. Hosts sharing
+    /// one likely run the same TLS stack.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jarm: Option<String>,
+    /// Nameserver that leaked this name through AXFR.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone_transfer: Option<String>,
+    /// Has no DNS record; found by sending the name as the Host header to
+    /// an address of another result (`--vhosts`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub vhost: bool,
+    /// Ports that accepted a TCP connection on any of the addresses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub open_ports: Vec<u16>,
+    /// What SMTP, FTP, POP3, IMAP and SSH servers on the first address
+    /// greeted with, by port, e.g. `22: SSH-2.0-OpenSSH_9.6`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub banners: BTreeMap<u16, String>,
+    /// Reverse DNS names of the addresses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ptr: Vec<String>,
+    /// NS, MX and TXT records gathered with `--records`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns_records: Option<DnsRecords>,
+    /// Whether the name validated, with `--dnssec`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dnssec: Option<DnssecStatus>,
+    /// Origin AS of the first address that is announced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_org: Option<String>,
+    /// RDAP allocation the first address falls in, e.g. `104.16.0.0/12`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub netblock: Option<String>,
+    /// Who holds that allocation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub netblock_owner: Option<String>,
+    /// ISO country code of the first address the GeoIP database knows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    /// Open ports Shodan knows about for this host's addresses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shodan_ports: Option<Vec<u16>>,
+    /// Collected by a scan that was interrupted before it finished, so
+    /// post-scan enrichment (Shodan ports, screenshots) is missing, or
+    /// left unprobed because the HTTP budget ran out or the deadline
+    /// passed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub partial: bool,
+    /// PNG captured with `--screenshots`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub screenshot: Option<String>,
+    /// The scans a `merge`d result was seen in.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seen_in: Vec<merge::Sighting>,
+    /// [`wildcard::simhash`] of the body, for wildcard comparison.
+    #[serde(skip)]
+    pub body_simhash: Option<u64>,
+    /// In-scope hostnames seen while probing (certificate SANs, CSP header,
+    /// body), fed back into discovery. Not part of the output.
+    #[serde(skip)]
+    pub leads: Vec<String>,
+}
+
+pub const TOP_SUBDOMAINS: &[&str] = &[
+    "www",
+    "mail",
+    "remote",
+    "blog",
+    "webmail",
+    "server",
+    "ns1",
+    "ns2",
+    "smtp",
+    "secure",
+    "vpn",
+    "m",
+    "shop",
+    "ftp",
+    "mail2",
+    "test",
+    "portal",
+    "ns",
+    "ww1",
+    "host",
+    "dev",
+    "support",
+    "admin",
+    "web",
+    "api",
+    "cloud",
+    "data",
+    "app",
+    "autodiscover",
+    "autoconfig",
+];
@@ -0,0 +1,144 @@
+//! A small `tracing` subscriber for the command line: `[*]`/`[!]` lines or
+//! JSON objects on stderr, at the verbosity chosen with `-v`/`--quiet`.
+
+use serde_json::{Map, Value};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    /// Warnings and errors only.
+    Quiet,
+    /// Phase banners and findings.
+    #[default]
+    Normal,
+    /// `-v`: per-source and per-phase detail.
+    Verbose,
+    /// `-vv`: everything, dependencies (resolver, HTTP client) included.
+    Trace,
+}
+
+impl Verbosity {
+    /// One step more verbose, as each extra `-v` asks for.
+    pub fn louder(self) -> Self {
+        match self {
+            Verbosity::Quiet => Verbosity::Normal,
+            Verbosity::Normal => Verbosity::Verbose,
+            Verbosity::Verbose | Verbosity::Trace => Verbosity::Trace,
+        }
+    }
+
+    fn max_level(self) -> Level {
+        match self {
+            Verbosity::Quiet => Level::WARN,
+            Verbosity::Normal => Level::INFO,
+            Verbosity::Verbose => Level::DEBUG,
+            Verbosity::Trace => Level::TRACE,
+        }
+    }
+}
+
+/// Installs the subscriber process-wide. Has no effect if one is already
+/// set (by an embedding application, say).
+pub fn init(verbosity: Verbosity, json: bool) {
+    let logger = Logger {
+        max: verbosity.max_level(),
+        all_targets: verbosity == Verbosity::Trace,
+        json,
+    };
+    let _ = tracing::subscriber::set_global_default(logger);
+}
+
+struct Logger {
+    max: Level,
+    /// Below `-vv`, only this crate's events are shown.
+    all_targets: bool,
+    json: bool,
+}
+
+impl Subscriber for Logger {
+    fn enabled(&self, meta: &Metadata<'_>) -> bool {
+        meta.level() <= &self.max
+            && (self.all_targets || meta.target().starts_with(env!("CARGO_CRATE_NAME")))
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(LevelFilter::from_level(self.max))
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let meta = event.metadata();
+        let line = if self.json {
+            let mut obj = Map::new();
+            let ts = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or_default();
+            obj.insert("ts".into(), Value::from(ts));
+            obj.insert("level".into(), Value::from(meta.level().as_str()));
+            obj.insert("target".into(), Value::from(meta.target()));
+            obj.insert("message".into(), Value::from(fields.message));
+            for (k, v) in fields.extra {
+                obj.insert(k, Value::from(v));
+            }
+            Value::Object(obj).to_string()
+        } else {
+            let prefix = match *meta.level() {
+                Level::ERROR | Level::WARN => "[!]",
+                Level::INFO => "[*]",
+                Level::DEBUG => "[debug]",
+                Level::TRACE => "[trace]",
+            };
+            let mut line = format!("{} {}", prefix, fields.message);
+            for (k, v) in fields.extra {
+                line.push_str(&format!(" {}={}", k, v));
+            }
+            line
+        };
+        crate::progress::print_above(&line);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    extra: Vec<(String, String)>,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.extra
+                .push((field.name().to_string(), value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.extra
+                .push((field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+}
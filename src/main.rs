@@ -1,519 +1,512 @@
-use regex::Regex;
-use reqwest::{redirect, Client};
-use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+mod cli;
+mod tui;
+
 use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process;
-use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::sync::{Mutex, Semaphore};
-use trust_dns_resolver::config::{ResolverConfig, ResolverOpts};
-use trust_dns_resolver::TokioAsyncResolver;
-
-#[derive(Serialize, Debug, Clone)]
-struct SubdomainResult {
-    subdomain: String,
-    ip: Option<String>,
-    status_code: Option<u16>,
-    title: Option<String>,
-    server: Option<String>,
-    content_length: Option<u64>,
-}
-
-#[derive(Debug, Clone)]
-struct WildcardProfile {
-    ip: Option<String>,
-    status_code: Option<u16>,
-    title: Option<String>,
-    content_length: Option<u64>,
-}
-
-#[derive(Deserialize)]
-struct CrtShEntry {
-    name_value: String,
-}
-
-#[derive(Deserialize)]
-struct OtxUrl {
-    hostname: String,
-}
-
-#[derive(Deserialize)]
-struct OtxResp {
-    url_list: Vec<OtxUrl>,
-}
-
-const TOP_SUBDOMAINS: &[&str] = &[
-    "www",
-    "mail",
-    "remote",
-    "blog",
-    "webmail",
-    "server",
-    "ns1",
-    "ns2",
-    "smtp",
-    "secure",
-    "vpn",
-    "m",
-    "shop",
-    "ftp",
-    "mail2",
-    "test",
-    "portal",
-    "ns",
-    "ww1",
-    "host",
-    "dev",
-    "support",
-    "admin",
-    "web",
-    "api",
-    "cloud",
-    "data",
-    "app",
-    "autodiscover",
-    "autoconfig",
-];
+use std::time::SystemTime;
+use subpeek_core::cluster::ClusterBy;
+use subpeek_core::monitor::Monitor;
+use subpeek_core::notify::Notifier;
+use subpeek_core::plan::Plan;
+use subpeek_core::{
+    cluster, diff, export, input, logging, merge, output, progress, report, server, ScanIssues,
+    Store, SubdomainResult, SubpeekEngine,
+};
+use tracing::{info, warn};
+
+/// Bad arguments, unreadable files, or anything else failing outside a
+/// scan.
+const EXIT_USAGE: i32 = 1;
+/// Every passive source failed for at least one domain, so its results
+/// are no measure of its attack surface.
+const EXIT_SOURCES_FAILED: i32 = 2;
+/// `--fail-on-new` and the comparison found hosts the baseline lacks.
+const EXIT_NEW_HOSTS: i32 = 3;
+/// Stopped by Ctrl+C.
+const EXIT_INTERRUPTED: i32 = 130;
 
 #[tokio::main]
 async fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: subpeek_core <domain>");
-        process::exit(1);
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("diff") {
+        args.next();
+        run_diff(args).await;
+        return;
     }
-    let domain = &args[1];
-
-    // 0. Wildcard Detection
-    eprintln!("[*] Checking for Wildcard DNS...");
-    let wildcard_profile = detect_wildcard(domain).await;
-    if let Some(ref profile) = wildcard_profile {
-        eprintln!(
-            "[!] Wildcard DNS detected. IP: {:?}, Title: {:?}. Filtering junk results...",
-            profile.ip, profile.title
-        );
+    if args.peek().map(String::as_str) == Some("merge") {
+        args.next();
+        run_merge(args);
+        return;
     }
-
-    // 1. Discovery Phase
-    eprintln!("[*] Discovering subdomains concurrently...");
-    let mut candidates = fetch_all_subdomains(domain).await;
-
-    for sub in TOP_SUBDOMAINS {
-        candidates.insert(format!("{}.{}", sub, domain));
+    if args.peek().map(String::as_str) == Some("monitor") {
+        args.next();
+        run_monitor(args).await;
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("tui") {
+        args.next();
+        run_tui(args);
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("serve") {
+        args.next();
+        run_serve(args).await;
+        return;
     }
-    let total_candidates = candidates.len();
-    eprintln!(
-        "[*] Found {} potential subdomains. Verifying...",
-        total_candidates
-    );
-
-    // 2. DNS Verification Phase
-    let resolved = verify_dns(candidates).await;
-    let resolvable_count = resolved.len();
-    eprintln!(
-        "[*] {} subdomains resolved. Probing HTTP...",
-        resolvable_count
-    );
 
-    // 3. HTTP Probing Phase
-    let mut final_results = probe_http(resolved).await;
-
-    // 4. Filtering Phase
-    if let Some(profile) = wildcard_profile {
-        let before_count = final_results.len();
-        final_results.retain(|r| !is_wildcard_match(r, &profile));
-        eprintln!(
-            "[*] Filtered {} false positives (Wildcard matches).",
-            before_count - final_results.len()
-        );
+    let cli = match cli::parse(args) {
+        Ok(cli) => cli,
+        Err(msg) => {
+            if !msg.is_empty() {
+                eprintln!("{}", msg);
+            }
+            eprintln!("{}", cli::USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    init_logging(&cli);
+    if cli.dry_run {
+        print!("{}", Plan::new(&cli.options, &cli.domains));
+        return;
     }
 
-    let json_output =
-        serde_json::to_string_pretty(&final_results).unwrap_or_else(|_| "[]".to_string());
-    println!("{}", json_output);
+    // Load the baseline up front so a bad path fails before the scan
+    let baseline = match cli.compare.as_deref().map(diff::load_results).transpose() {
+        Ok(baseline) => baseline,
+        Err(e) => {
+            eprintln!("Could not load comparison file: {}", e);
+            process::exit(EXIT_USAGE);
+        }
+    };
 
-    eprintln!(
-        "[+] Done. Found {} unique valid subdomains.",
-        final_results.len()
-    );
-}
+    let store = open_store(cli.db.as_deref());
 
-async fn detect_wildcard(domain: &str) -> Option<WildcardProfile> {
-    // Generate a random subdomain unlikely to exist
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .subsec_nanos();
-    let random_sub = format!("wildcard-test-{}.{}", nanos, domain);
-
-    let resolver = TokioAsyncResolver::tokio(ResolverConfig::google(), ResolverOpts::default());
-    let ip = match resolver.lookup_ip(&random_sub).await {
-        Ok(lookup) => lookup.iter().next().map(|ip| ip.to_string()),
-        Err(_) => return None, // If DNS fails, no wildcard DNS (usually)
+    // Created up front so an unwritable path fails before the scan
+    let mut file = match cli.output.as_deref() {
+        Some(path) => match output::OutputFile::create(path, cli.append) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                eprintln!("Could not write {}: {}", path.display(), e);
+                process::exit(EXIT_USAGE);
+            }
+        },
+        None => None,
+    };
+    let mut stdout = io::stdout();
+    let mut out: &mut dyn Write = match file {
+        Some(ref mut file) => file,
+        None => &mut stdout,
     };
 
-    // If it resolves, check HTTP response to build a profile
-    // Make a fake result to reuse probe logic, but just doing a single request here for simplicity
-    let client = Client::builder()
-        .timeout(Duration::from_secs(5))
-        .danger_accept_invalid_certs(true)
-        .build()
-        .unwrap_or_default();
-
-    let protocols = ["https", "http"];
-    let mut status = None;
-    let mut title = None;
-    let mut content_length = None;
-
-    let title_regex = Regex::new(r"(?i)<title>(.*?)</title>").unwrap();
-
-    for proto in protocols {
-        let url = format!("{}://{}", proto, random_sub);
-        if let Ok(resp) = client.get(&url).send().await {
-            status = Some(resp.status().as_u16());
-            content_length = resp.content_length();
-            if let Ok(text) = resp.text().await {
-                // If content length wasn't in header, use body length
-                if content_length.is_none() {
-                    content_length = Some(text.len() as u64);
-                }
-                if let Some(caps) = title_regex.captures(&text) {
-                    if let Some(m) = caps.get(1) {
-                        title = Some(m.as_str().trim().to_string());
-                    }
+    // A file that couldn't be written in full doesn't replace the old one
+    let mut write_failed = false;
+
+    let started = SystemTime::now();
+    let engine = SubpeekEngine::with_options(cli.options);
+    if cli.stream {
+        if let Err(e) = output::write_header(&mut out, cli.format) {
+            warn!("Could not write results: {}", e);
+            write_failed = true;
+        }
+    }
+    // Everything streamed out of the engine, kept so an interrupted scan
+    // can still print what it found
+    let mut collected: Vec<(String, Vec<SubdomainResult>)> = cli
+        .domains
+        .iter()
+        .map(|d| (d.clone(), Vec::new()))
+        .collect();
+    let finished = {
+        let scan = engine.run_many_streaming(&cli.domains, |domain, r| {
+            if cli.stream {
+                if let Err(e) = output::write_record(&mut out, cli.format, r) {
+                    warn!("Could not write result: {}", e);
+                    write_failed = true;
                 }
             }
-            break;
+            if let Some((_, results)) = collected.iter_mut().find(|(d, _)| d == domain) {
+                results.push(r.clone());
+            }
+        });
+        tokio::select! {
+            groups = scan => Some(groups),
+            _ = tokio::signal::ctrl_c() => None,
+        }
+    };
+    let interrupted = finished.is_none();
+    let groups = finished.unwrap_or_else(|| {
+        warn!("Interrupted. Writing the results collected so far...");
+        engine.save_checkpoint();
+        // A second Ctrl+C gives up on the flush too
+        tokio::spawn(async {
+            let _ = tokio::signal::ctrl_c().await;
+            process::exit(EXIT_INTERRUPTED);
+        });
+        for (_, results) in &mut collected {
+            results.iter_mut().for_each(|r| r.partial = true);
+            engine.sort(results);
         }
+        collected
+    });
+    let mut issues = ScanIssues::default();
+    for (domain, _) in &groups {
+        issues.extend(engine.issues(domain));
     }
-
-    Some(WildcardProfile {
-        ip,
-        status_code: status,
-        title,
-        content_length,
-    })
-}
-
-fn is_wildcard_match(result: &SubdomainResult, profile: &WildcardProfile) -> bool {
-    // IP Match is the strongest indicator if combined with same content
-    let ip_match = result.ip == profile.ip;
-
-    if !ip_match {
-        return false;
+    if !cli.stream {
+        // A single domain keeps the plain array existing consumers expect
+        let written = match groups.as_slice() {
+            _ if cli.group_by_ip => {
+                let all: Vec<SubdomainResult> = groups
+                    .iter()
+                    .flat_map(|(_, results)| results.iter().cloned())
+                    .collect();
+                let clusters = match cli.cluster_by {
+                    ClusterBy::Ip => cluster::cluster(&all),
+                    ClusterBy::BodyHash => cluster::cluster_by_body(&all),
+                };
+                output::write_clusters(&mut out, cli.format, &clusters)
+            }
+            _ if cli.envelope => {
+                let envelope = output::Envelope::new(
+                    &groups,
+                    started,
+                    SystemTime::now(),
+                    &engine,
+                    issues.clone(),
+                );
+                output::write_envelope(&mut out, &envelope)
+            }
+            [(_, results)] => output::write_results(&mut out, cli.format, results),
+            _ => output::write_grouped(&mut out, cli.format, &groups),
+        };
+        if let Err(e) = written {
+            warn!("Could not write results: {}", e);
+            write_failed = true;
+        }
+        let _ = out.flush();
     }
-
-    // If IPs match, check content similarity
-    // 1. Title Match
-    if result.title == profile.title && result.title.is_some() {
-        return true;
+    if let Some(file) = file.filter(|_| !write_failed) {
+        let path = file.path().display().to_string();
+        match file.finish() {
+            Ok(()) => info!("Wrote results to {}", path),
+            Err(e) => warn!("Could not write results to {}: {}", path, e),
+        }
     }
-
-    // 2. Content Length Match (Allow small variance)
-    if let (Some(a), Some(b)) = (result.content_length, profile.content_length) {
-        let diff = if a > b { a - b } else { b - a };
-        if diff < 50 {
-            // If length is very similar
-            return true;
+    issues.print_summary();
+    let final_results: Vec<SubdomainResult> = groups
+        .iter()
+        .flat_map(|(_, results)| results.iter().cloned())
+        .collect();
+    let label = cli.domains.join(", ");
+
+    let mut new_hosts = false;
+    if let Some(ref old) = baseline {
+        let changes = diff::diff(old, &final_results);
+        changes.print_summary();
+        new_hosts = !changes.added.is_empty();
+        if let Some(ref hook) = cli.notify {
+            send_alert(hook, &label, &changes).await;
         }
     }
 
-    // 3. If exact status code match and default title/no title
-    if result.status_code == profile.status_code {
-        // High risk of wildcard if titles are both None or empty
-        if result.title.is_none() && profile.title.is_none() {
-            return true;
+    if let Some(ref path) = cli.report {
+        let html = report::html::render(&label, &final_results);
+        match std::fs::write(path, html) {
+            Ok(()) => info!("Wrote HTML report to {}", path.display()),
+            Err(e) => warn!("Could not write report: {}", e),
         }
     }
 
-    false
-}
-
-async fn fetch_all_subdomains(domain: &str) -> HashSet<String> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(15))
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) SubPeek/2.0")
-        .build()
-        .unwrap_or_default();
-
-    let client = Arc::new(client);
-    let subdomains = Arc::new(Mutex::new(HashSet::new()));
-    let mut handles = Vec::new();
-
-    // Source: Crt.sh
-    {
-        let c = client.clone();
-        let s = subdomains.clone();
-        let d = domain.to_string();
-        handles.push(tokio::spawn(async move {
-            if let Ok(subs) = fetch_crtsh(&c, &d).await {
-                let mut lock = s.lock().await;
-                lock.extend(subs);
-            }
-        }));
+    if let Some(ref path) = cli.report_md {
+        let md = report::markdown::render(&label, &final_results);
+        match std::fs::write(path, md) {
+            Ok(()) => info!("Wrote Markdown report to {}", path.display()),
+            Err(e) => warn!("Could not write report: {}", e),
+        }
     }
 
-    // Source: Anubis
-    {
-        let c = client.clone();
-        let s = subdomains.clone();
-        let d = domain.to_string();
-        handles.push(tokio::spawn(async move {
-            if let Ok(subs) = fetch_anubis(&c, &d).await {
-                let mut lock = s.lock().await;
-                lock.extend(subs);
-            }
-        }));
+    if let Some(ref path) = cli.export_nmap {
+        match std::fs::write(path, export::targets(&final_results)) {
+            Ok(()) => info!("Wrote nmap targets to {}", path.display()),
+            Err(e) => warn!("Could not write nmap targets: {}", e),
+        }
     }
 
-    // Source: HackerTarget
-    {
-        let c = client.clone();
-        let s = subdomains.clone();
-        let d = domain.to_string();
-        handles.push(tokio::spawn(async move {
-            if let Ok(subs) = fetch_hackertarget(&c, &d).await {
-                let mut lock = s.lock().await;
-                lock.extend(subs);
-            }
-        }));
+    if let Some(ref path) = cli.export_nmap_xml {
+        match std::fs::write(path, export::nmap_xml(&final_results)) {
+            Ok(()) => info!("Wrote nmap XML to {}", path.display()),
+            Err(e) => warn!("Could not write nmap XML: {}", e),
+        }
     }
 
-    // Source: Sublist3r
-    {
-        let c = client.clone();
-        let s = subdomains.clone();
-        let d = domain.to_string();
-        handles.push(tokio::spawn(async move {
-            if let Ok(subs) = fetch_sublist3r(&c, &d).await {
-                let mut lock = s.lock().await;
-                lock.extend(subs);
-            }
-        }));
+    if let Some(ref dir) = cli.export_nmap_dir {
+        match export::write_by_provider(dir, &final_results) {
+            Ok(files) => info!(
+                "Wrote {} nmap target lists to {}",
+                files.len(),
+                dir.display()
+            ),
+            Err(e) => warn!("Could not write nmap target lists: {}", e),
+        }
     }
 
-    // Source: AlienVault
-    {
-        let c = client.clone();
-        let s = subdomains.clone();
-        let d = domain.to_string();
-        handles.push(tokio::spawn(async move {
-            if let Ok(subs) = fetch_alienvault(&c, &d).await {
-                let mut lock = s.lock().await;
-                lock.extend(subs);
-            }
-        }));
+    if let Some(ref path) = cli.related_output {
+        let mut domains: Vec<String> = groups
+            .iter()
+            .flat_map(|(d, _)| engine.related_domains(d))
+            .map(|r| r.domain)
+            .filter(|d| !cli.domains.contains(d))
+            .collect();
+        domains.sort();
+        domains.dedup();
+        let text: String = domains.iter().map(|d| format!("{}\n", d)).collect();
+        match std::fs::write(path, text) {
+            Ok(()) => info!(
+                "Wrote {} related domains to {}",
+                domains.len(),
+                path.display()
+            ),
+            Err(e) => warn!("Could not write related domains: {}", e),
+        }
     }
 
-    for h in handles {
-        let _ = h.await;
+    if let Some(ref store) = store {
+        for (domain, results) in &groups {
+            save_scan(store, domain, started, results);
+        }
     }
 
-    let lock = subdomains.lock().await;
-    lock.clone()
+    info!(
+        "Done. Found {} unique valid subdomains.",
+        final_results.len()
+    );
+    if interrupted {
+        process::exit(EXIT_INTERRUPTED);
+    }
+    let sources_failed = groups
+        .iter()
+        .filter_map(|(domain, _)| engine.stats(domain))
+        .any(|s| s.all_sources_failed());
+    if sources_failed {
+        warn!("Every passive source failed for at least one domain.");
+        process::exit(EXIT_SOURCES_FAILED);
+    }
+    if cli.fail_on_new && new_hosts {
+        process::exit(EXIT_NEW_HOSTS);
+    }
 }
 
-// --- Fetchers ---
+async fn run_diff<I: Iterator<Item = String>>(args: I) {
+    let args = match cli::parse_diff(args) {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            eprintln!("{}", cli::USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    logging::init(logging::Verbosity::Normal, false);
+    let load = |path: &std::path::Path| {
+        diff::load_results(path).unwrap_or_else(|e| {
+            eprintln!("Could not load {}: {}", path.display(), e);
+            process::exit(EXIT_USAGE);
+        })
+    };
+    let changes = diff::diff(&load(&args.old), &load(&args.new));
 
-async fn fetch_crtsh(
-    client: &Client,
-    domain: &str,
-) -> Result<HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
-    let url = format!("https://crt.sh/?q=%.{}&output=json", domain);
-    let res = client.get(&url).send().await?;
-    if !res.status().is_success() {
-        return Err("failed".into());
+    changes.print_summary();
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&changes).unwrap_or_else(|_| "{}".to_string())
+    );
+    if let Some(ref hook) = args.notify {
+        send_alert(hook, &args.new.display().to_string(), &changes).await;
     }
-
-    let text = res.text().await?;
-    let entries: Vec<CrtShEntry> = serde_json::from_str(&text).unwrap_or_default();
-
-    let mut subs = HashSet::new();
-    let suffix = format!(".{}", domain);
-    for e in entries {
-        for line in e.name_value.split('\n') {
-            let clean = line.trim().to_lowercase();
-            if !clean.contains('*') && (clean == domain || clean.ends_with(&suffix)) {
-                subs.insert(clean);
-            }
-        }
+    if args.fail_on_new && !changes.added.is_empty() {
+        process::exit(EXIT_NEW_HOSTS);
     }
-    Ok(subs)
 }
 
-async fn fetch_anubis(
-    client: &Client,
-    domain: &str,
-) -> Result<HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
-    let url = format!("https://jldc.me/anubis/subdomains/{}", domain);
-    let res = client.get(&url).send().await?;
-    if !res.status().is_success() {
-        return Err("failed".into());
+fn run_merge<I: Iterator<Item = String>>(args: I) {
+    let args = match cli::parse_merge(args) {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            eprintln!("{}", cli::USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    logging::init(logging::Verbosity::Normal, false);
+    let scans: Vec<merge::Scan> = args
+        .files
+        .iter()
+        .map(|path| {
+            merge::Scan::load(path).unwrap_or_else(|e| {
+                eprintln!("Could not load {}: {}", path.display(), e);
+                process::exit(EXIT_USAGE);
+            })
+        })
+        .collect();
+    let total: usize = scans.iter().map(|s| s.results.len()).sum();
+    let merged = merge::merge(scans);
+    info!(
+        "Merged {} results from {} scans into {} hosts.",
+        total,
+        args.files.len(),
+        merged.len()
+    );
+
+    let written = match args.output {
+        Some(ref path) => output::OutputFile::create(path, false).and_then(|mut file| {
+            output::write_results(&mut file, args.format, &merged)?;
+            file.finish()
+        }),
+        None => output::write_results(&mut io::stdout().lock(), args.format, &merged),
+    };
+    if let Err(e) = written {
+        eprintln!("Could not write merged results: {}", e);
+        process::exit(EXIT_USAGE);
     }
-    let entries: Vec<String> = res.json().await.unwrap_or_default();
-    Ok(filter_subs(entries, domain))
 }
 
-async fn fetch_hackertarget(
-    client: &Client,
-    domain: &str,
-) -> Result<HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
-    let url = format!("https://api.hackertarget.com/hostsearch/?q={}", domain);
-    let res = client.get(&url).send().await?;
-    let text = res.text().await?;
-    let mut subs = HashSet::new();
-    let suffix = format!(".{}", domain);
-    for line in text.lines() {
-        if let Some(host) = line.split(',').next() {
-            let clean = host.trim().to_lowercase();
-            if clean == domain || clean.ends_with(&suffix) {
-                subs.insert(clean);
+async fn run_monitor<I: Iterator<Item = String>>(args: I) {
+    let args = match cli::parse_monitor(args) {
+        Ok(args) => args,
+        Err(msg) => {
+            if !msg.is_empty() {
+                eprintln!("{}", msg);
             }
+            eprintln!("{}", cli::USAGE);
+            process::exit(EXIT_USAGE);
         }
+    };
+    init_logging(&args.scan);
+    let state_dir = args
+        .state_dir
+        .or_else(Monitor::default_state_dir)
+        .unwrap_or_else(|| PathBuf::from("subpeek-monitor"));
+    let store = open_store(args.scan.db.as_deref());
+    if let Some(addr) = args.metrics_listen {
+        tokio::spawn(async move {
+            if let Err(e) = server::serve_metrics(addr).await {
+                warn!("Could not serve metrics on {}: {}", addr, e);
+            }
+        });
     }
-    Ok(subs)
-}
 
-async fn fetch_sublist3r(
-    client: &Client,
-    domain: &str,
-) -> Result<HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
-    let url = format!("https://api.sublist3r.com/search.php?domain={}", domain);
-    let res = client.get(&url).send().await?;
-    let entries: Vec<String> = res.json().await.unwrap_or_default();
-    Ok(filter_subs(entries, domain))
-}
-
-async fn fetch_alienvault(
-    client: &Client,
-    domain: &str,
-) -> Result<HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
-    let url = format!(
-        "https://otx.alienvault.com/api/v1/indicators/domain/{}/url_list?limit=100&page=1",
-        domain
+    info!(
+        "Monitoring {} every {}s, state in {}",
+        args.domains.display(),
+        args.interval.as_secs(),
+        state_dir.display()
     );
-    let res = client.get(&url).send().await?;
-    let resp: OtxResp = res.json().await.unwrap_or(OtxResp { url_list: vec![] });
-    Ok(filter_subs(
-        resp.url_list.into_iter().map(|u| u.hostname).collect(),
-        domain,
-    ))
-}
-
-fn filter_subs(raw: Vec<String>, domain: &str) -> HashSet<String> {
-    let mut s = HashSet::new();
-    let suffix = format!(".{}", domain);
-    for r in raw {
-        let clean = r.trim().to_lowercase();
-        if clean == domain || clean.ends_with(&suffix) {
-            s.insert(clean);
-        }
-    }
-    s
-}
+    let monitor = Monitor::new(SubpeekEngine::with_options(args.scan.options), state_dir);
+
+    loop {
+        // Re-read every cycle so targets can be added without a restart
+        let domains = input::load_list(&args.domains).unwrap_or_else(|e| {
+            warn!("Could not read {}: {}", args.domains.display(), e);
+            Vec::new()
+        });
+
+        for domain in domains {
+            let started = SystemTime::now();
+            let (results, event) = match monitor.scan_once(&domain).await {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    warn!("Monitoring {} failed: {}", domain, e);
+                    continue;
+                }
+            };
+            if let Some(ref store) = store {
+                save_scan(store, &domain, started, &results);
+            }
 
-async fn verify_dns(candidates: HashSet<String>) -> Vec<(String, String)> {
-    let resolver = TokioAsyncResolver::tokio(ResolverConfig::google(), ResolverOpts::default());
-    let resolver = Arc::new(resolver);
-    let semaphore = Arc::new(Semaphore::new(200));
-    let results = Arc::new(Mutex::new(Vec::new()));
-    let mut tasks = Vec::new();
-
-    for sub in candidates {
-        let r = resolver.clone();
-        let s = semaphore.clone();
-        let res_list = results.clone();
-
-        tasks.push(tokio::spawn(async move {
-            let _permit = s.acquire().await.unwrap();
-            if let Ok(lookup) = r.lookup_ip(sub.as_str()).await {
-                if let Some(ip) = lookup.iter().next() {
-                    let mut lock = res_list.lock().await;
-                    lock.push((sub, ip.to_string()));
+            match event {
+                None => info!("Baseline for {}: {} hosts.", domain, results.len()),
+                Some(e) if e.diff.is_empty() => info!("No changes for {}.", domain),
+                Some(e) => {
+                    e.diff.print_summary();
+                    if let Some(ref hook) = args.scan.notify {
+                        send_alert(hook, &domain, &e.diff).await;
+                    }
+                    if let Ok(line) = serde_json::to_string(&e) {
+                        println!("{}", line);
+                        let _ = io::stdout().flush();
+                    }
                 }
             }
-        }));
-    }
+        }
 
-    for t in tasks {
-        let _ = t.await;
+        tokio::time::sleep(args.interval).await;
     }
+}
 
-    let lock = results.lock().await;
-    lock.clone()
+fn run_tui<I: Iterator<Item = String>>(mut args: I) {
+    let (Some(path), None) = (args.next(), args.next()) else {
+        eprintln!("tui takes one results file");
+        eprintln!("{}", cli::USAGE);
+        process::exit(EXIT_USAGE);
+    };
+    let results = diff::load_results(Path::new(&path)).unwrap_or_else(|e| {
+        eprintln!("Could not load {}: {}", path, e);
+        process::exit(EXIT_USAGE);
+    });
+    if let Err(e) = tui::run(results) {
+        eprintln!("{}", e);
+        process::exit(EXIT_USAGE);
+    }
 }
 
-async fn probe_http(targets: Vec<(String, String)>) -> Vec<SubdomainResult> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(8))
-        .redirect(redirect::Policy::limited(3))
-        .danger_accept_invalid_certs(true)
-        .build()
-        .unwrap_or_default();
-
-    let client = Arc::new(client);
-    let semaphore = Arc::new(Semaphore::new(50));
-    let results = Arc::new(Mutex::new(Vec::new()));
-    let mut tasks = Vec::new();
-
-    let title_regex = Regex::new(r"(?i)<title>(.*?)</title>").unwrap();
-
-    for (sub, ip) in targets {
-        let c = client.clone();
-        let s = semaphore.clone();
-        let r_list = results.clone();
-        let re = title_regex.clone();
-
-        tasks.push(tokio::spawn(async move {
-            let _permit = s.acquire().await.unwrap();
-
-            let protocols = ["https", "http"];
-            let mut status = None;
-            let mut title = None;
-            let mut server = None;
-            let mut content_length = None;
-
-            for proto in protocols {
-                let url = format!("{}://{}", proto, sub);
-                if let Ok(resp) = c.get(&url).send().await {
-                    status = Some(resp.status().as_u16());
-                    content_length = resp.content_length();
-                    if let Some(h) = resp.headers().get("server") {
-                        server = h.to_str().ok().map(|s| s.to_string());
-                    }
-                    if let Ok(text) = resp.text().await {
-                        if content_length.is_none() {
-                            content_length = Some(text.len() as u64);
-                        }
-                        if let Some(caps) = re.captures(&text) {
-                            if let Some(m) = caps.get(1) {
-                                title = Some(m.as_str().trim().to_string());
-                            }
-                        }
-                    }
-                    break;
-                }
+async fn run_serve<I: Iterator<Item = String>>(args: I) {
+    let args = match cli::parse_serve(args) {
+        Ok(args) => args,
+        Err(msg) => {
+            if !msg.is_empty() {
+                eprintln!("{}", msg);
             }
+            eprintln!("{}", cli::USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    init_logging(&args.scan);
+    let engine = SubpeekEngine::with_options(args.scan.options);
+    if let Err(e) = server::serve(args.listen, engine, args.queue).await {
+        eprintln!("Could not serve on {}: {}", args.listen, e);
+        process::exit(EXIT_USAGE);
+    }
+}
 
-            let result = SubdomainResult {
-                subdomain: sub,
-                ip: Some(ip),
-                status_code: status,
-                title,
-                server,
-                content_length,
-            };
+async fn send_alert(hook: &cli::Webhook, target: &str, changes: &diff::ScanDiff) {
+    let notifier = Notifier::new(hook.url.clone(), hook.format, hook.proxy.as_ref());
+    match notifier.notify(target, changes).await {
+        Ok(true) => info!("Sent webhook notification for {}.", target),
+        Ok(false) => {}
+        Err(e) => warn!("Webhook notification failed: {}", e),
+    }
+}
 
-            let mut lock = r_list.lock().await;
-            lock.push(result);
-        }));
+/// Opens the store up front so a missing sqlite3 fails before any scan.
+fn open_store(path: Option<&Path>) -> Option<Store> {
+    match path.map(Store::open).transpose() {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("Could not open database: {}", e);
+            process::exit(EXIT_USAGE);
+        }
     }
+}
 
-    for t in tasks {
-        let _ = t.await;
+fn save_scan(store: &Store, domain: &str, started: SystemTime, results: &[SubdomainResult]) {
+    match store.record_scan(domain, started, SystemTime::now(), results) {
+        Ok(id) => info!("Saved scan #{} to {}", id, store.path().display()),
+        Err(e) => warn!("Could not save scan: {}", e),
     }
+}
 
-    let lock = results.lock().await;
-    lock.clone()
+fn init_logging(cli: &cli::Cli) {
+    logging::init(cli.verbosity, cli.log_json);
+    progress::enable(!cli.log_json && cli.verbosity > logging::Verbosity::Quiet);
 }
@@ -0,0 +1,250 @@
+//! Names in the target's own naming scheme for `--markov`: a token Markov
+//! model is trained on the names already resolved and its most likely
+//! unseen names are resolved as a second brute force wave. This finds
+//! hosts such as `vpn-dc3` next to `vpn-dc1` and `api-dc3` that generic
+//! wordlists don't have.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Resolved names needed before the model says anything useful.
+pub const MIN_TRAINING_NAMES: usize = 5;
+
+/// Tokens in a generated name, separators included.
+const MAX_TOKENS: usize = 9;
+
+/// Weight of the two-token context against the one-token one. Below `1.0`
+/// so tokens seen after only one of them can still follow.
+const CONTEXT_WEIGHT: f64 = 0.7;
+
+/// Count given to the number after the largest one seen in a context, so
+/// `dc1` and `dc2` make `dc3` a candidate.
+const NEXT_NUMBER_WEIGHT: f64 = 0.5;
+
+const START: &str = "^";
+const END: &str = "$";
+/// Stands for any number, so a number only `add_next_number` made up is
+/// followed by what other numbers in its place are.
+const NUMBER: &str = "#";
+
+/// Token transition counts for one domain's names.
+#[derive(Debug, Default)]
+pub struct Model {
+    /// Counts of the next token after one previous token.
+    unigram: HashMap<String, HashMap<String, f64>>,
+    /// Counts of the next token after two previous tokens.
+    bigram: HashMap<(String, String), HashMap<String, f64>>,
+    known: HashSet<String>,
+}
+
+impl Model {
+    /// Trains on the parts of `names` below `domain`.
+    pub fn train(names: &[String], domain: &str) -> Self {
+        let suffix = format!(".{}", domain);
+        let mut model = Self::default();
+        for name in names {
+            let Some(prefix) = name.strip_suffix(&suffix) else {
+                continue;
+            };
+            if !model.known.insert(prefix.to_string()) {
+                continue;
+            }
+            let mut tokens = vec![START.to_string(), START.to_string()];
+            tokens.extend(tokenize(prefix));
+            tokens.push(END.to_string());
+            for w in tokens.windows(3) {
+                for b in with_class(&w[1]) {
+                    for a in with_class(&w[0]) {
+                        *model
+                            .bigram
+                            .entry((a, b.clone()))
+                            .or_default()
+                            .entry(w[2].clone())
+                            .or_default() += 1.0;
+                    }
+                    *model
+                        .unigram
+                        .entry(b)
+                        .or_default()
+                        .entry(w[2].clone())
+                        .or_default() += 1.0;
+                }
+            }
+        }
+        for next in model.unigram.values_mut().chain(model.bigram.values_mut()) {
+            add_next_number(next);
+        }
+        model
+    }
+
+    /// How many distinct names the model was trained on.
+    pub fn len(&self) -> usize {
+        self.known.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.known.is_empty()
+    }
+
+    /// Up to `limit` names not trained on, most likely first, as full
+    /// names under `domain`.
+    pub fn generate(&self, domain: &str, limit: usize) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut seen = HashSet::new();
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse(Partial {
+            cost: 0.0,
+            tokens: vec![START.to_string(), START.to_string()],
+        }));
+        // Every expansion pushes a handful of partial names, so this bounds
+        // the work for models that can't produce `limit` new names
+        let mut budget = limit.saturating_mul(200).max(1000);
+
+        while let Some(Reverse(partial)) = queue.pop() {
+            if out.len() >= limit || budget == 0 {
+                break;
+            }
+            budget -= 1;
+            let n = partial.tokens.len();
+            for (token, p) in self.next(&partial.tokens[n - 2], &partial.tokens[n - 1]) {
+                let cost = partial.cost - p.ln();
+                if token == END {
+                    let name: String = partial.tokens[2..].concat();
+                    if is_valid(&name) && !self.known.contains(&name) && seen.insert(name.clone()) {
+                        out.push(format!("{}.{}", name, domain));
+                    }
+                    continue;
+                }
+                if n - 2 >= MAX_TOKENS {
+                    continue;
+                }
+                let mut tokens = partial.tokens.clone();
+                tokens.push(token);
+                queue.push(Reverse(Partial { cost, tokens }));
+            }
+        }
+        out
+    }
+
+    /// Probabilities of the tokens following `a b`.
+    fn next(&self, a: &str, b: &str) -> Vec<(String, f64)> {
+        let b = match self.unigram.contains_key(b) {
+            false if is_number(b) => NUMBER,
+            _ => b,
+        };
+        let Some(single) = self.unigram.get(b) else {
+            return Vec::new();
+        };
+        let single_total: f64 = single.values().sum();
+        let pair = self
+            .bigram
+            .get(&(a.to_string(), b.to_string()))
+            .or_else(|| {
+                is_number(a)
+                    .then(|| self.bigram.get(&(NUMBER.to_string(), b.to_string())))
+                    .flatten()
+            });
+        let pair_total: f64 = pair.map(|p| p.values().sum()).unwrap_or_default();
+        single
+            .iter()
+            .map(|(token, count)| {
+                let p_single = count / single_total;
+                let p = match pair {
+                    Some(pair) => {
+                        let p_pair = pair.get(token).copied().unwrap_or_default() / pair_total;
+                        CONTEXT_WEIGHT * p_pair + (1.0 - CONTEXT_WEIGHT) * p_single
+                    }
+                    None => p_single,
+                };
+                (token.clone(), p)
+            })
+            .collect()
+    }
+}
+
+/// A name being generated, ordered by its cost (`-ln` of its probability).
+#[derive(Debug)]
+struct Partial {
+    cost: f64,
+    tokens: Vec<String>,
+}
+
+impl PartialEq for Partial {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Partial {}
+
+impl PartialOrd for Partial {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Partial {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost.total_cmp(&other.cost)
+    }
+}
+
+/// `api-eu2.corp` becomes `api`, `-`, `eu`, `2`, `.`, `corp`.
+fn tokenize(name: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let class = |c: char| {
+        if c.is_ascii_digit() {
+            0
+        } else if c.is_alphanumeric() {
+            1
+        } else {
+            2
+        }
+    };
+    for c in name.chars() {
+        match tokens.last_mut() {
+            Some(last) if class(c) != 2 && last.chars().next().map(class) == Some(class(c)) => {
+                last.push(c)
+            }
+            _ => tokens.push(c.to_string()),
+        }
+    }
+    tokens
+}
+
+/// Adds the number after the largest one in `next`, keeping its width.
+fn add_next_number(next: &mut HashMap<String, f64>) {
+    let Some((width, n)) = next
+        .keys()
+        .filter(|t| is_number(t))
+        .filter_map(|t| Some((t.len(), t.parse::<u64>().ok()?)))
+        .max_by_key(|(_, n)| *n)
+    else {
+        return;
+    };
+    let following = format!("{:0width$}", n + 1, width = width);
+    next.entry(following).or_insert(NEXT_NUMBER_WEIGHT);
+}
+
+/// `token`, and [`NUMBER`] too if it is one.
+fn with_class(token: &str) -> Vec<String> {
+    let mut out = vec![token.to_string()];
+    if is_number(token) {
+        out.push(NUMBER.to_string());
+    }
+    out
+}
+
+fn is_number(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_valid(name: &str) -> bool {
+    !name.is_empty()
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+        })
+}
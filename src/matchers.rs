@@ -0,0 +1,157 @@
+//! `--match-body` rules: regexes run over every probed body, whose tags
+//! are added to the results they match. Directory listings, stack traces
+//! or strings only an organisation's internal pages carry then show up in
+//! `tags` like any other finding.
+//!
+//! Rule files are a small subset of YAML, a list of `tag`/`regex` pairs:
+//!
+//! ```yaml
+//! - tag: directory-listing
+//!   regex: '<title>Index of /'
+//! - tag: java-stack-trace
+//!   regex: 'at [\w$.]+\(\w+\.java:\d+\)'
+//! ```
+//!
+//! Values may be plain, single-quoted (`''` for a quote) or double-quoted
+//! (`\"` and `\\`; other backslashes are kept for the regex). The list may
+//! sit under a top-level `rules:` key.
+
+use crate::Result;
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct BodyRule {
+    pub tag: String,
+    pub regex: Regex,
+}
+
+impl BodyRule {
+    /// A rule tagging matches of `pattern` with `tag`, or with the pattern
+    /// itself when there is no tag.
+    pub fn new(tag: Option<&str>, pattern: &str) -> std::result::Result<Self, String> {
+        let regex = Regex::new(pattern).map_err(|e| format!("invalid regex: {}", e))?;
+        Ok(Self {
+            tag: tag.unwrap_or(pattern).to_string(),
+            regex,
+        })
+    }
+}
+
+/// Tags of the `rules` that match `body`, in rule order and without
+/// repeats.
+pub fn matching(rules: &[BodyRule], body: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for rule in rules {
+        if !tags.contains(&rule.tag) && rule.regex.is_match(body) {
+            tags.push(rule.tag.clone());
+        }
+    }
+    tags
+}
+
+/// Reads a rule file.
+pub fn load(path: &Path) -> Result<Vec<BodyRule>> {
+    Ok(parse(&fs::read_to_string(path)?)?)
+}
+
+/// Parses the rule file format described in the module docs.
+pub fn parse(text: &str) -> std::result::Result<Vec<BodyRule>, String> {
+    // (tag, regex, line the rule starts on)
+    let mut raw: Vec<(Option<String>, Option<String>, usize)> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let n = i + 1;
+        let content = line.trim();
+        if content.is_empty() || content.starts_with('#') || content == "rules:" {
+            continue;
+        }
+        let content = match content.strip_prefix('-') {
+            Some(rest) => {
+                raw.push((None, None, n));
+                rest.trim_start()
+            }
+            None => content,
+        };
+        if content.is_empty() {
+            continue;
+        }
+        let Some(rule) = raw.last_mut() else {
+            return Err(format!("line {}: expected a '- tag: ...' list item", n));
+        };
+        let (key, value) = content
+            .split_once(':')
+            .ok_or_else(|| format!("line {}: expected 'key: value'", n))?;
+        let value = scalar(value.trim()).map_err(|e| format!("line {}: {}", n, e))?;
+        match key.trim() {
+            "tag" | "name" => rule.0 = Some(value),
+            "regex" | "pattern" => rule.1 = Some(value),
+            other => return Err(format!("line {}: unknown key '{}'", n, other)),
+        }
+    }
+    raw.into_iter()
+        .map(|(tag, regex, n)| {
+            let regex = regex.ok_or_else(|| format!("line {}: rule without a regex", n))?;
+            BodyRule::new(tag.as_deref(), &regex).map_err(|e| format!("line {}: {}", n, e))
+        })
+        .collect()
+}
+
+/// One YAML scalar, with a trailing `# comment` dropped.
+fn scalar(raw: &str) -> std::result::Result<String, String> {
+    let mut chars = raw.chars();
+    let (value, rest) = match chars.next() {
+        Some('\'') => {
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some('\'') if chars.as_str().starts_with('\'') => {
+                        chars.next();
+                        value.push('\'');
+                    }
+                    Some('\'') => break,
+                    Some(c) => value.push(c),
+                    None => return Err("unterminated single-quoted value".to_string()),
+                }
+            }
+            (value, chars.as_str())
+        }
+        Some('"') => {
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some('\\') => match chars.next() {
+                        Some('"') => value.push('"'),
+                        Some('\\') => value.push('\\'),
+                        Some('n') => value.push('\n'),
+                        Some('t') => value.push('\t'),
+                        Some(c) => {
+                            value.push('\\');
+                            value.push(c);
+                        }
+                        None => return Err("unterminated double-quoted value".to_string()),
+                    },
+                    Some('"') => break,
+                    Some(c) => value.push(c),
+                    None => return Err("unterminated double-quoted value".to_string()),
+                }
+            }
+            (value, chars.as_str())
+        }
+        _ => {
+            let value = match raw.find(" #") {
+                Some(i) => &raw[..i],
+                None => raw,
+            };
+            (value.trim().to_string(), "")
+        }
+    };
+    let rest = rest.trim();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("unexpected '{}' after the value", rest));
+    }
+    if value.is_empty() {
+        return Err("empty value".to_string());
+    }
+    Ok(value)
+}
@@ -0,0 +1,147 @@
+//! Combining result files of several scans of the same targets, such as runs
+//! from different vantage points, into one result set.
+
+use crate::{diff, Result, SubdomainResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Lists a host's scans can legitimately disagree on, so every scan's
+/// entries are kept.
+const UNION_FIELDS: &[&str] = &[
+    "ips",
+    "open_ports",
+    "ptr",
+    "technologies",
+    "sources",
+    "seen_in",
+];
+
+/// Always taken from the newest scan: an interrupted old scan doesn't make
+/// the merged host partial.
+const NEWEST_FIELDS: &[&str] = &["partial"];
+
+/// One scan a merged result was seen in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Sighting {
+    /// The file the scan was read from.
+    pub scan: String,
+    /// When the scan finished (RFC 3339), or when its file was last
+    /// written if it doesn't say.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scanned_at: Option<String>,
+    /// What this scan resolved and got back, which differ between vantage
+    /// points.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ips: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+}
+
+/// The results of one saved scan.
+#[derive(Debug, Clone)]
+pub struct Scan {
+    pub name: String,
+    pub scanned_at: Option<String>,
+    pub results: Vec<SubdomainResult>,
+}
+
+impl Scan {
+    /// Reads any file [`diff::load_results`] can, taking the scan time from
+    /// an `--envelope` document's `finished_at`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let results = diff::load_results(path)?;
+        let finished_at = fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+            .and_then(|doc| doc.get("finished_at")?.as_str().map(str::to_string));
+        let scanned_at = finished_at.or_else(|| {
+            let modified = fs::metadata(path).ok()?.modified().ok()?;
+            let secs = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+            Some(crate::cert::rfc3339(secs as i64))
+        });
+        Ok(Self {
+            name: path.display().to_string(),
+            scanned_at,
+            results,
+        })
+    }
+}
+
+/// Deduplicates the results of `scans` by subdomain, port and scheme.
+/// Where scans disagree the newest value wins, fields the newest scan left
+/// empty are filled from older ones, and addresses, ports and technologies
+/// are combined. Each result lists the scans it was seen in under
+/// `seen_in`; results that already have one (an earlier merge) keep it.
+pub fn merge(mut scans: Vec<Scan>) -> Vec<SubdomainResult> {
+    // Oldest first, so every later scan overrides what came before
+    scans.sort_by(|a, b| a.scanned_at.cmp(&b.scanned_at));
+    let mut merged: BTreeMap<(String, Option<u16>, Option<String>), Value> = BTreeMap::new();
+    for scan in scans {
+        for mut r in scan.results {
+            if r.seen_in.is_empty() {
+                r.seen_in.push(Sighting {
+                    scan: scan.name.clone(),
+                    scanned_at: scan.scanned_at.clone(),
+                    ips: r.ips.clone(),
+                    status_code: r.status_code,
+                });
+            }
+            let key = (r.subdomain.clone(), r.port, r.scheme.clone());
+            let Ok(value) = serde_json::to_value(&r) else {
+                continue;
+            };
+            let value = match merged.remove(&key) {
+                Some(older) => combine(older, value),
+                None => value,
+            };
+            merged.insert(key, value);
+        }
+    }
+    merged
+        .into_values()
+        .filter_map(|v| serde_json::from_value(v).ok())
+        .collect()
+}
+
+/// `newer` with the gaps it left filled from `older`.
+fn combine(older: Value, newer: Value) -> Value {
+    let (older, mut merged) = match (older, newer) {
+        (Value::Object(older), Value::Object(newer)) => (older, newer),
+        (_, newer) => return newer,
+    };
+    for (field, old_value) in older {
+        if NEWEST_FIELDS.contains(&field.as_str()) {
+            continue;
+        }
+        match merged.get_mut(&field) {
+            Some(Value::Array(new_values)) if UNION_FIELDS.contains(&field.as_str()) => {
+                if let Value::Array(old_values) = old_value {
+                    for v in old_values {
+                        if !new_values.contains(&v) {
+                            new_values.push(v);
+                        }
+                    }
+                }
+            }
+            Some(new_value) if !is_empty(new_value) => {}
+            _ => {
+                merged.insert(field, old_value);
+            }
+        }
+    }
+    Value::Object(merged)
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.is_empty(),
+        Value::Array(a) => a.is_empty(),
+        Value::Object(o) => o.is_empty(),
+        _ => false,
+    }
+}
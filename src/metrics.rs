@@ -0,0 +1,234 @@
+//! Process-wide counters for `/metrics`, in the Prometheus text format.
+//!
+//! They are always counted, since that is a few atomic adds; `serve` and
+//! `monitor --metrics-listen` are what expose them.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the HTTP probe latency buckets.
+const LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+#[derive(Debug, Default, Clone, Copy)]
+struct SourceCounts {
+    candidates: u64,
+    failures: u64,
+    rate_limited: u64,
+}
+
+static SOURCES: Mutex<BTreeMap<&'static str, SourceCounts>> = Mutex::new(BTreeMap::new());
+
+static DNS_RESOLVED: AtomicU64 = AtomicU64::new(0);
+static DNS_UNRESOLVED: AtomicU64 = AtomicU64::new(0);
+static DNS_ERRORS: AtomicU64 = AtomicU64::new(0);
+static DNS_CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static RESOLVERS_EVICTED: AtomicU64 = AtomicU64::new(0);
+
+static PROBE_BUCKETS: [AtomicU64; LATENCY_BUCKETS.len()] =
+    [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len()];
+static PROBE_COUNT: AtomicU64 = AtomicU64::new(0);
+static PROBE_MICROS: AtomicU64 = AtomicU64::new(0);
+
+static WILDCARD_FILTERED: AtomicU64 = AtomicU64::new(0);
+static SCANS: AtomicU64 = AtomicU64::new(0);
+static RESULTS: AtomicU64 = AtomicU64::new(0);
+
+/// How a DNS lookup ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsOutcome {
+    Resolved,
+    /// NXDOMAIN or no addresses.
+    Unresolved,
+    /// Timeouts, SERVFAIL and transport errors, after any retries.
+    Error,
+}
+
+pub(crate) fn source_answered(source: &'static str, candidates: usize) {
+    let mut sources = SOURCES.lock().unwrap_or_else(|e| e.into_inner());
+    sources.entry(source).or_default().candidates += candidates as u64;
+}
+
+pub(crate) fn source_failed(source: &'static str, rate_limited: bool) {
+    let mut sources = SOURCES.lock().unwrap_or_else(|e| e.into_inner());
+    let counts = sources.entry(source).or_default();
+    counts.failures += 1;
+    if rate_limited {
+        counts.rate_limited += 1;
+    }
+}
+
+pub(crate) fn dns_lookup(outcome: DnsOutcome) {
+    let counter = match outcome {
+        DnsOutcome::Resolved => &DNS_RESOLVED,
+        DnsOutcome::Unresolved => &DNS_UNRESOLVED,
+        DnsOutcome::Error => &DNS_ERRORS,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn dns_cache_hit() {
+    DNS_CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn resolver_evicted() {
+    RESOLVERS_EVICTED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn http_probe(latency: Duration) {
+    let secs = latency.as_secs_f64();
+    for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&PROBE_BUCKETS) {
+        if secs <= *bound {
+            bucket.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    PROBE_COUNT.fetch_add(1, Ordering::Relaxed);
+    PROBE_MICROS.fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+}
+
+pub(crate) fn wildcard_filtered() {
+    WILDCARD_FILTERED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// One domain scanned to the end, with `results` hosts kept.
+pub(crate) fn scan_finished(results: usize) {
+    SCANS.fetch_add(1, Ordering::Relaxed);
+    RESULTS.fetch_add(results as u64, Ordering::Relaxed);
+}
+
+/// Every metric in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut out = String::new();
+    let sources = SOURCES.lock().unwrap_or_else(|e| e.into_inner()).clone();
+
+    header(
+        &mut out,
+        "subpeek_source_candidates_total",
+        "counter",
+        "Names returned by each passive source.",
+    );
+    for (name, c) in &sources {
+        let _ = writeln!(
+            out,
+            "subpeek_source_candidates_total{{source=\"{}\"}} {}",
+            name, c.candidates
+        );
+    }
+    header(
+        &mut out,
+        "subpeek_source_failures_total",
+        "counter",
+        "Passive source queries that failed, rate limits included.",
+    );
+    for (name, c) in &sources {
+        let _ = writeln!(
+            out,
+            "subpeek_source_failures_total{{source=\"{}\"}} {}",
+            name, c.failures
+        );
+    }
+    header(
+        &mut out,
+        "subpeek_source_rate_limited_total",
+        "counter",
+        "Passive source queries refused for a rate limit or quota.",
+    );
+    for (name, c) in &sources {
+        let _ = writeln!(
+            out,
+            "subpeek_source_rate_limited_total{{source=\"{}\"}} {}",
+            name, c.rate_limited
+        );
+    }
+
+    header(
+        &mut out,
+        "subpeek_dns_lookups_total",
+        "counter",
+        "DNS lookups by outcome.",
+    );
+    for (result, counter) in [
+        ("resolved", &DNS_RESOLVED),
+        ("unresolved", &DNS_UNRESOLVED),
+        ("error", &DNS_ERRORS),
+    ] {
+        let _ = writeln!(
+            out,
+            "subpeek_dns_lookups_total{{result=\"{}\"}} {}",
+            result,
+            counter.load(Ordering::Relaxed)
+        );
+    }
+
+    simple(
+        &mut out,
+        "subpeek_dns_cache_hits_total",
+        "Lookups answered from the DNS cache.",
+        &DNS_CACHE_HITS,
+    );
+    simple(
+        &mut out,
+        "subpeek_resolvers_evicted_total",
+        "Resolvers dropped by the health check.",
+        &RESOLVERS_EVICTED,
+    );
+
+    header(
+        &mut out,
+        "subpeek_http_probe_duration_seconds",
+        "histogram",
+        "Time to a response for each HTTP probe that got one.",
+    );
+    for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&PROBE_BUCKETS) {
+        let _ = writeln!(
+            out,
+            "subpeek_http_probe_duration_seconds_bucket{{le=\"{}\"}} {}",
+            bound,
+            bucket.load(Ordering::Relaxed)
+        );
+    }
+    let count = PROBE_COUNT.load(Ordering::Relaxed);
+    let _ = writeln!(
+        out,
+        "subpeek_http_probe_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+        count
+    );
+    let _ = writeln!(
+        out,
+        "subpeek_http_probe_duration_seconds_sum {}",
+        PROBE_MICROS.load(Ordering::Relaxed) as f64 / 1e6
+    );
+    let _ = writeln!(out, "subpeek_http_probe_duration_seconds_count {}", count);
+
+    simple(
+        &mut out,
+        "subpeek_wildcard_filtered_total",
+        "Hosts dropped as wildcard DNS answers.",
+        &WILDCARD_FILTERED,
+    );
+    simple(
+        &mut out,
+        "subpeek_scans_total",
+        "Domain scans finished.",
+        &SCANS,
+    );
+    simple(
+        &mut out,
+        "subpeek_results_total",
+        "Hosts kept by finished scans.",
+        &RESULTS,
+    );
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn simple(out: &mut String, name: &str, help: &str, counter: &AtomicU64) {
+    header(out, name, "counter", help);
+    let _ = writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed));
+}
@@ -0,0 +1,114 @@
+//! Scheduled re-scans that only report what changed.
+//!
+//! The latest results for each domain are kept as `<domain>.json` in a
+//! state directory, so deltas survive restarts.
+
+use crate::diff::{self, ScanDiff};
+use crate::{output, OutputFormat, Result, SubdomainResult, SubpeekEngine};
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The changes found by one re-scan of one domain.
+#[derive(Serialize, Debug, Clone)]
+pub struct MonitorEvent {
+    pub domain: String,
+    /// Unix timestamp of the scan.
+    pub scanned_at: u64,
+    #[serde(flatten)]
+    pub diff: ScanDiff,
+}
+
+pub struct Monitor {
+    engine: SubpeekEngine,
+    state_dir: PathBuf,
+}
+
+impl Monitor {
+    pub fn new(engine: SubpeekEngine, state_dir: PathBuf) -> Self {
+        Self { engine, state_dir }
+    }
+
+    /// `$XDG_DATA_HOME/subpeek/monitor`, falling back to `~/.local/share`.
+    pub fn default_state_dir() -> Option<PathBuf> {
+        let base = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+        Some(base.join("subpeek").join("monitor"))
+    }
+
+    /// Scans `domain` and compares it with the stored state, which is then
+    /// replaced. The first scan of a domain only records a baseline and
+    /// returns `None`.
+    ///
+    /// A scan whose sources all failed, or that was cut short, is an error
+    /// and leaves the state alone: compared with it, every host would look
+    /// removed, and then added again by the next scan.
+    pub async fn scan_once(
+        &self,
+        domain: &str,
+    ) -> Result<(Vec<SubdomainResult>, Option<MonitorEvent>)> {
+        let path = self.state_path(domain);
+        let previous = if path.exists() {
+            Some(diff::load_results(&path)?)
+        } else {
+            None
+        };
+
+        let results = self.engine.run(domain).await;
+        if let Some(why) = self.incomplete(domain, &results) {
+            return Err(format!("{}; keeping the previous state", why).into());
+        }
+        save_state(&path, &results)?;
+
+        let event = previous.map(|old| MonitorEvent {
+            domain: domain.to_string(),
+            scanned_at: now(),
+            diff: diff::diff(&old, &results),
+        });
+        Ok((results, event))
+    }
+
+    /// Why the last scan of `domain` can't stand in for the domain.
+    fn incomplete(&self, domain: &str, results: &[SubdomainResult]) -> Option<String> {
+        if let Some(stats) = self.engine.stats(domain) {
+            if stats.all_sources_failed() {
+                return Some("every passive source failed".to_string());
+            }
+            if !stats.truncated.is_empty() {
+                return Some(format!("ran out of time in {}", stats.truncated.join(", ")));
+            }
+        }
+        results
+            .iter()
+            .any(|r| r.partial)
+            .then(|| "the scan stopped before every host was probed".to_string())
+    }
+
+    fn state_path(&self, domain: &str) -> PathBuf {
+        self.state_dir.join(format!("{}.json", domain))
+    }
+}
+
+/// Writes to a temporary file first so a crash never leaves half a state.
+fn save_state(path: &Path, results: &[SubdomainResult]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&tmp)?;
+    output::write_results(&mut file, OutputFormat::Json, results)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,157 @@
+//! Pushing new findings to a webhook (Slack, Discord or plain JSON).
+
+use crate::diff::ScanDiff;
+use crate::Result;
+use reqwest::{Client, Url};
+use serde::Serialize;
+use serde_json::json;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WebhookFormat {
+    /// The [`Alert`] itself.
+    #[default]
+    Json,
+    /// `{"text": ...}` for Slack incoming webhooks.
+    Slack,
+    /// `{"content": ...}` for Discord webhooks.
+    Discord,
+}
+
+impl FromStr for WebhookFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(WebhookFormat::Json),
+            "slack" => Ok(WebhookFormat::Slack),
+            "discord" => Ok(WebhookFormat::Discord),
+            _ => Err(format!(
+                "Unknown webhook format: {} (known: json, slack, discord)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TakeoverAlert {
+    pub subdomain: String,
+    pub service: String,
+    pub cname: String,
+}
+
+/// The part of a diff worth interrupting someone for.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Alert {
+    pub target: String,
+    pub new_subdomains: Vec<String>,
+    pub takeovers: Vec<TakeoverAlert>,
+}
+
+impl Alert {
+    /// New hosts plus takeover candidates among them or among hosts that
+    /// only just started matching a fingerprint. `None` without either.
+    pub fn from_diff(target: &str, diff: &ScanDiff) -> Option<Self> {
+        let mut alert = Alert {
+            target: target.to_string(),
+            ..Alert::default()
+        };
+        for r in &diff.added {
+            alert.new_subdomains.push(r.subdomain.clone());
+            if let Some(ref t) = r.takeover {
+                alert.takeovers.push(TakeoverAlert {
+                    subdomain: r.subdomain.clone(),
+                    service: t.service.clone(),
+                    cname: t.cname.clone(),
+                });
+            }
+        }
+        for c in &diff.changed {
+            if c.field == "takeover" && c.new != "-" {
+                alert.takeovers.push(TakeoverAlert {
+                    subdomain: c.subdomain.clone(),
+                    service: c.new.clone(),
+                    cname: String::new(),
+                });
+            }
+        }
+        if alert.new_subdomains.is_empty() && alert.takeovers.is_empty() {
+            return None;
+        }
+        Some(alert)
+    }
+
+    /// Chat-friendly text for Slack and Discord.
+    pub fn text(&self) -> String {
+        let mut out = format!(
+            "SubPeek: {} new subdomain(s) for {}",
+            self.new_subdomains.len(),
+            self.target
+        );
+        for name in &self.new_subdomains {
+            out.push_str(&format!("\n• {}", name));
+        }
+        for t in &self.takeovers {
+            out.push_str(&format!(
+                "\n⚠ Possible takeover: {} ({})",
+                t.subdomain, t.service
+            ));
+            if !t.cname.is_empty() {
+                out.push_str(&format!(" -> {}", t.cname));
+            }
+        }
+        out
+    }
+}
+
+/// Discord rejects messages longer than this.
+const DISCORD_LIMIT: usize = 2000;
+
+pub struct Notifier {
+    url: String,
+    format: WebhookFormat,
+    client: Client,
+}
+
+impl Notifier {
+    pub fn new(url: String, format: WebhookFormat, proxy: Option<&Url>) -> Self {
+        let client = crate::proxy::apply(Client::builder(), proxy)
+            .timeout(Duration::from_secs(15))
+            .build()
+            .unwrap_or_default();
+        Self {
+            url,
+            format,
+            client,
+        }
+    }
+
+    /// Posts the alert for `diff`, if it has anything to say. Returns
+    /// whether a message was sent.
+    pub async fn notify(&self, target: &str, diff: &ScanDiff) -> Result<bool> {
+        let Some(alert) = Alert::from_diff(target, diff) else {
+            return Ok(false);
+        };
+        let body = match self.format {
+            WebhookFormat::Json => serde_json::to_value(&alert)?,
+            WebhookFormat::Slack => json!({ "text": alert.text() }),
+            WebhookFormat::Discord => json!({ "content": truncate(&alert.text(), DISCORD_LIMIT) }),
+        };
+        let res = self.client.post(&self.url).json(&body).send().await?;
+        if !res.status().is_success() {
+            return Err(format!("webhook returned HTTP {}", res.status()).into());
+        }
+        Ok(true)
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut out: String = text.chars().take(max_chars - 1).collect();
+    out.push('…');
+    out
+}
@@ -0,0 +1,251 @@
+use crate::banner::BannerScope;
+use crate::config::ApiKeys;
+use crate::deadline::Phase;
+use crate::dns::{DnsEngine, IpVersion, Upstream};
+use crate::dnscache::DEFAULT_DNS_CACHE_TTL;
+use crate::filter::ResultFilter;
+use crate::probe::ProbeOptions;
+use crate::ratelimit::Rate;
+use crate::records::RecordKind;
+use crate::scope::Scope;
+use crate::sources::cache::DEFAULT_CACHE_TTL;
+use crate::wildcard::DEFAULT_SIMILARITY_THRESHOLD;
+use crate::wordlists;
+use reqwest::Url;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// User agent sent to passive sources.
+pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) SubPeek/2.0";
+
+/// Knobs for a scan. `Default` reproduces the classic behaviour.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    pub api_keys: ApiKeys,
+    /// User agent for passive source requests.
+    pub user_agent: String,
+    /// Only query these passive sources. Empty means all available.
+    pub sources: Vec<String>,
+    /// Never query these passive sources.
+    pub exclude_sources: Vec<String>,
+    /// Names to verify and probe alongside discovered ones (e.g. another
+    /// tool's output). Only those under the scanned apex are used.
+    pub candidates: Vec<String>,
+    /// The sources amass or subfinder credit `candidates` with, when
+    /// `--input` was their JSON output.
+    pub candidate_sources: HashMap<String, Vec<String>>,
+    /// Names already in an asset inventory. They are scanned like
+    /// `candidates` but marked `source: "seed"`, so diffs and monitor
+    /// alerts don't report them as new.
+    pub known: HashSet<String>,
+    /// State file to resume from and keep up to date, so an interrupted
+    /// scan can continue where it stopped.
+    pub resume: Option<PathBuf>,
+    /// Hosts that may be probed. Everything is resolved, but names outside
+    /// the scope are dropped before any request or port scan reaches them.
+    pub scope: Scope,
+    /// Probed hosts that make it into the results, by status code, title
+    /// or `Server` header.
+    pub filter: ResultFilter,
+    /// Where passive source answers are cached between runs. `None`
+    /// always queries the sources.
+    pub cache_dir: Option<PathBuf>,
+    /// How long a cached source answer is reused.
+    pub cache_ttl: Duration,
+    /// Executables run as extra passive sources, see
+    /// [`sources::plugin`](crate::sources::plugin).
+    pub plugin_dir: Option<PathBuf>,
+    /// Run passive discovery, zone transfers, brute force, permutations and
+    /// recursion. When off, only `candidates` are resolved and probed.
+    pub discover: bool,
+    /// Report what passive sources and `candidates` name without resolving
+    /// or probing anything, so no traffic reaches the target.
+    pub passive: bool,
+    /// Resolve and probe in-scope hostnames found in certificates, CSP
+    /// headers and response bodies, until no new ones turn up.
+    pub feedback: bool,
+    /// Fall back to crt.sh's public PostgreSQL database when its HTTP
+    /// interface keeps failing.
+    pub crtsh_postgres: bool,
+    /// Wordlist to brute force `<word>.<domain>` with, one label per line.
+    pub wordlist: Option<PathBuf>,
+    /// Brute force with a built-in list instead, when `wordlist` isn't set.
+    pub wordlist_builtin: Option<wordlists::Builtin>,
+    /// Generate mutations of resolved names and resolve those too.
+    pub permute: bool,
+    /// Mutation words for `permute`; falls back to `permute::DEFAULT_MUTATIONS`.
+    pub permute_wordlist: Option<PathBuf>,
+    /// Resolve this many names generated by a Markov model trained on the
+    /// resolved ones. `0` disables it.
+    pub markov: usize,
+    /// How many levels of intermediate labels to re-enumerate as their own
+    /// apex. `0` disables recursion.
+    pub recursion_depth: usize,
+    /// Try a zone transfer against each authoritative nameserver first.
+    pub axfr: bool,
+    /// Follow the NSEC chain of signed zones on their authoritative
+    /// nameservers to list their names.
+    pub nsec_walk: bool,
+    /// Look up the CNAME of every candidate that didn't resolve, and report
+    /// those whose chain ends at a name that doesn't exist.
+    pub dangling: bool,
+    /// Send unresolved candidate names as the Host header to every address
+    /// found, to discover virtual hosts without a DNS record.
+    pub vhosts: bool,
+    /// Extra record types to gather for the apex and every host found.
+    pub records: Vec<RecordKind>,
+    /// TCP ports to connect-scan on every resolved address. Empty skips
+    /// the scan.
+    pub scan_ports: Vec<u16>,
+    /// Hosts whose service banners are read into `banners`.
+    pub banners: BannerScope,
+    /// Reverse-resolve every address into `ptr`. In-scope names found
+    /// this way are fed back like other leads.
+    pub ptr: bool,
+    /// Record whether each host validates with DNSSEC, asking the plain
+    /// UDP resolvers (or Google's) to validate.
+    pub dnssec: bool,
+    /// Tag results with the origin AS of their addresses (Team Cymru).
+    pub asn: bool,
+    /// Look up the registration of the apex and the netblock of every
+    /// address over RDAP.
+    pub rdap: bool,
+    /// Look up other hostnames on each address outside a CDN. In-scope
+    /// ones are resolved, the rest reported as neighbors.
+    pub reverse_ip: bool,
+    /// Suggest other apexes whose certificates or WHOIS registrant name the
+    /// same organisation.
+    pub find_related: bool,
+    /// GeoLite2/GeoIP2 City or Country `.mmdb` to tag results with the
+    /// location of their addresses.
+    pub geoip_db: Option<PathBuf>,
+    /// Look up Shodan-known open ports for every resolved address. Needs a
+    /// Shodan API key.
+    pub shodan_ports: bool,
+    /// Screenshot every live host into this directory with headless
+    /// Chrome/Chromium. Only honoured with the `screenshots` feature.
+    pub screenshots: Option<PathBuf>,
+    /// HTTP proxy for every HTTP client and for the crt.sh PostgreSQL
+    /// fallback; SOCKS proxies go through [`crate::proxy::resolve`] first.
+    /// Copied into `probe.proxy` unless that is set.
+    pub proxy: Option<Url>,
+    /// Body similarity (0 to 1) from which a host sharing a wildcard's
+    /// addresses is dropped as that wildcard.
+    pub wildcard_threshold: f64,
+    /// Output hosts the wildcard check matched, marked with
+    /// `filtered_reason`, instead of dropping them.
+    pub keep_filtered: bool,
+    /// Return each domain's results sorted by name, port and scheme, so
+    /// identical scans give identical output. Off with `--unsorted`, which
+    /// keeps the order hosts finished in; streamed results always arrive
+    /// in that order.
+    pub sorted: bool,
+    /// Upstream DNS servers to rotate queries across. Empty means Google.
+    pub resolvers: Vec<Upstream>,
+    /// Check every resolver against known answers before the first scan
+    /// and again now and then during it, dropping those that fail. Only
+    /// applies to two resolvers or more.
+    pub check_resolvers: bool,
+    /// How names are resolved in discovery and brute force.
+    pub dns_engine: DnsEngine,
+    /// Address families names resolve to and probes connect over. Copied
+    /// into `probe.ip_version`.
+    pub ip_version: IpVersion,
+    /// How long answers and NXDOMAINs are reused. Zero turns the DNS cache
+    /// off.
+    pub dns_cache_ttl: Duration,
+    /// File the DNS cache is loaded from and saved to between runs.
+    pub dns_cache: Option<PathBuf>,
+    /// Maximum DNS lookups in flight.
+    pub dns_concurrency: usize,
+    /// Treat `dns_concurrency` and `probe.concurrency` as starting points
+    /// that grow while lookups and requests succeed and shrink when they
+    /// start failing, instead of fixed limits.
+    pub adaptive_concurrency: bool,
+    /// Extra attempts for DNS lookups and HTTP requests that failed in a
+    /// way that may succeed later.
+    pub retries: u32,
+    /// Delay before the first retry, doubling for each one after.
+    pub retry_backoff: Duration,
+    /// Scan-wide cap on DNS lookups plus HTTP requests.
+    pub rate: Option<Rate>,
+    /// Cap on HTTP requests to any one hostname.
+    pub per_host_rate: Option<Rate>,
+    /// Stop probing after this many HTTP requests, redirects included.
+    pub max_requests: Option<u64>,
+    /// Stop probing after this many bytes of HTTP responses.
+    pub max_bandwidth: Option<u64>,
+    /// Time box of a whole run, all domains together.
+    pub max_runtime: Option<Duration>,
+    /// Time boxes of single phases, each counted from when it starts.
+    pub phase_deadlines: BTreeMap<Phase, Duration>,
+    pub probe: ProbeOptions,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            api_keys: ApiKeys::default(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            sources: Vec::new(),
+            exclude_sources: Vec::new(),
+            candidates: Vec::new(),
+            candidate_sources: HashMap::new(),
+            known: HashSet::new(),
+            resume: None,
+            scope: Scope::default(),
+            filter: ResultFilter::default(),
+            cache_dir: None,
+            cache_ttl: DEFAULT_CACHE_TTL,
+            plugin_dir: None,
+            discover: true,
+            passive: false,
+            feedback: true,
+            crtsh_postgres: false,
+            wordlist: None,
+            wordlist_builtin: None,
+            permute: false,
+            permute_wordlist: None,
+            markov: 0,
+            recursion_depth: 0,
+            axfr: false,
+            nsec_walk: false,
+            dangling: false,
+            vhosts: false,
+            records: Vec::new(),
+            scan_ports: Vec::new(),
+            banners: BannerScope::default(),
+            ptr: false,
+            dnssec: false,
+            asn: false,
+            rdap: false,
+            reverse_ip: false,
+            find_related: false,
+            geoip_db: None,
+            shodan_ports: false,
+            screenshots: None,
+            proxy: None,
+            wildcard_threshold: DEFAULT_SIMILARITY_THRESHOLD,
+            keep_filtered: false,
+            sorted: true,
+            resolvers: Vec::new(),
+            check_resolvers: true,
+            dns_engine: DnsEngine::default(),
+            ip_version: IpVersion::Any,
+            dns_cache_ttl: DEFAULT_DNS_CACHE_TTL,
+            dns_cache: None,
+            dns_concurrency: 200,
+            adaptive_concurrency: true,
+            retries: 0,
+            retry_backoff: Duration::from_millis(500),
+            rate: None,
+            per_host_rate: None,
+            max_requests: None,
+            max_bandwidth: None,
+            max_runtime: None,
+            phase_deadlines: BTreeMap::new(),
+            probe: ProbeOptions::default(),
+        }
+    }
+}
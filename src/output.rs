@@ -0,0 +1,649 @@
+//! Serialising results for stdout or files.
+
+use crate::cluster::HostCluster;
+use crate::enrich::rdap::Registration;
+use crate::probe::ProbeMethod;
+use crate::related::RelatedDomain;
+use crate::{
+    CertificateInfo, Neighbors, ScanIssues, ScanOptions, ScanStats, SubdomainResult, SubpeekEngine,
+    WildcardProfile,
+};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// One pretty-printed JSON array.
+    #[default]
+    Json,
+    /// One compact JSON object per line.
+    Jsonl,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// Just the subdomain, one per line.
+    Hosts,
+    /// amass's JSON lines, for pipelines built around it.
+    Amass,
+}
+
+impl OutputFormat {
+    pub const NAMES: &'static [&'static str] = &["json", "jsonl", "csv", "hosts", "amass"];
+
+    /// Whether results can be written one at a time as they arrive.
+    pub fn streams(self) -> bool {
+        !matches!(self, OutputFormat::Json)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" | "ndjson" => Ok(OutputFormat::Jsonl),
+            "csv" => Ok(OutputFormat::Csv),
+            "hosts" | "txt" => Ok(OutputFormat::Hosts),
+            "amass" => Ok(OutputFormat::Amass),
+            _ => Err(format!(
+                "Unknown output format: {} (known: {})",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// A results file for `-o`. It is written under a temporary name next to
+/// `path` and renamed over it by [`finish`](Self::finish), so an
+/// interrupted run never leaves half a file behind. In append mode records
+/// go straight to the end of `path` instead.
+pub struct OutputFile {
+    out: BufWriter<File>,
+    path: PathBuf,
+    tmp: Option<PathBuf>,
+}
+
+impl OutputFile {
+    pub fn create(path: &Path, append: bool) -> io::Result<Self> {
+        if append {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            return Ok(Self {
+                out: BufWriter::new(file),
+                path: path.to_path_buf(),
+                tmp: None,
+            });
+        }
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file name"))?;
+        let tmp = path.with_file_name(format!(
+            ".{}.{}.tmp",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        Ok(Self {
+            out: BufWriter::new(File::create(&tmp)?),
+            path: path.to_path_buf(),
+            tmp: Some(tmp),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Flushes the file to disk and moves it into place.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()?;
+        self.out.get_ref().sync_all()?;
+        if let Some(tmp) = self.tmp.take() {
+            fs::rename(tmp, &self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.out.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl Drop for OutputFile {
+    /// An unfinished file is thrown away; whatever `path` held stays.
+    fn drop(&mut self) {
+        if let Some(ref tmp) = self.tmp {
+            let _ = fs::remove_file(tmp);
+        }
+    }
+}
+
+/// Writes a complete result set.
+pub fn write_results<W: Write>(
+    out: &mut W,
+    format: OutputFormat,
+    results: &[SubdomainResult],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, results)?;
+            writeln!(out)
+        }
+        _ => {
+            write_header(out, format)?;
+            for r in results {
+                write_record(out, format, r)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Writes results of several apexes. JSON becomes an object keyed by
+/// domain; line formats list each domain's results in turn.
+pub fn write_grouped<W: Write>(
+    out: &mut W,
+    format: OutputFormat,
+    groups: &[(String, Vec<SubdomainResult>)],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, &Grouped(groups))?;
+            writeln!(out)
+        }
+        _ => {
+            write_header(out, format)?;
+            for r in groups.iter().flat_map(|(_, results)| results) {
+                write_record(out, format, r)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Version of the `--envelope` document. Bumped when a field is renamed,
+/// removed or changes meaning; new optional fields don't bump it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Scan metadata `--envelope` wraps around the results, so consumers can
+/// tell which schema and which scan they are reading.
+#[derive(Debug, serde::Serialize)]
+pub struct Envelope<'a> {
+    pub tool: &'static str,
+    pub version: &'static str,
+    pub schema_version: u32,
+    pub domains: Vec<String>,
+    /// RFC 3339, UTC.
+    pub started_at: String,
+    pub finished_at: String,
+    /// Settings that shape the results. Keys, cookies and headers are
+    /// never included.
+    pub options: serde_json::Value,
+    /// Phase counts per domain.
+    pub stats: BTreeMap<String, ScanStats>,
+    /// `errors` and `warnings` of every domain.
+    #[serde(flatten)]
+    pub issues: ScanIssues,
+    /// `--reverse-ip` hostnames outside each domain, by shared address.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub neighbors: BTreeMap<String, Neighbors>,
+    /// `--find-related` apexes per domain.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub related_domains: BTreeMap<String, Vec<RelatedDomain>>,
+    /// `--rdap` registration of each domain's apex.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub registration: BTreeMap<String, Registration>,
+    /// Wildcard records per domain, with what they answer.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub wildcards: BTreeMap<String, Vec<WildcardProfile>>,
+    /// Results keyed by domain, like the plain output of several domains.
+    #[serde(serialize_with = "serialize_grouped")]
+    pub results: &'a [(String, Vec<SubdomainResult>)],
+}
+
+impl<'a> Envelope<'a> {
+    pub fn new(
+        groups: &'a [(String, Vec<SubdomainResult>)],
+        started: SystemTime,
+        finished: SystemTime,
+        engine: &SubpeekEngine,
+        issues: ScanIssues,
+    ) -> Self {
+        Self {
+            tool: "subpeek",
+            version: env!("CARGO_PKG_VERSION"),
+            schema_version: SCHEMA_VERSION,
+            domains: groups.iter().map(|(d, _)| d.clone()).collect(),
+            started_at: timestamp(started),
+            finished_at: timestamp(finished),
+            options: options_summary(engine.options()),
+            stats: per_domain(groups, |d| engine.stats(d)),
+            issues,
+            neighbors: per_domain(groups, |d| {
+                Some(engine.neighbors(d)).filter(|n| !n.is_empty())
+            }),
+            related_domains: per_domain(groups, |d| {
+                Some(engine.related_domains(d)).filter(|r| !r.is_empty())
+            }),
+            registration: per_domain(groups, |d| engine.registration(d)),
+            wildcards: per_domain(groups, |d| {
+                Some(engine.wildcards(d)).filter(|w| !w.is_empty())
+            }),
+            results: groups,
+        }
+    }
+}
+
+/// `f` of every domain in `groups` it has something for.
+fn per_domain<T>(
+    groups: &[(String, Vec<SubdomainResult>)],
+    f: impl Fn(&str) -> Option<T>,
+) -> BTreeMap<String, T> {
+    groups
+        .iter()
+        .filter_map(|(d, _)| Some((d.clone(), f(d)?)))
+        .collect()
+}
+
+/// Writes an [`Envelope`] as pretty-printed JSON.
+pub fn write_envelope<W: Write>(out: &mut W, envelope: &Envelope) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, envelope)?;
+    writeln!(out)
+}
+
+/// The options recorded in an [`Envelope`].
+pub fn options_summary(o: &ScanOptions) -> serde_json::Value {
+    let lower = |v: &dyn std::fmt::Debug| format!("{:?}", v).to_lowercase();
+    serde_json::json!({
+        "sources": o.sources,
+        "exclude_sources": o.exclude_sources,
+        "plugin_dir": o.plugin_dir,
+        "known": o.known.len(),
+        "discover": o.discover,
+        "passive": o.passive,
+        "feedback": o.feedback,
+        "wordlist": o.wordlist,
+        "wordlist_builtin": o.wordlist_builtin.map(|w| w.name()),
+        "permute": o.permute,
+        "markov": o.markov,
+        "recursion_depth": o.recursion_depth,
+        "axfr": o.axfr,
+        "nsec_walk": o.nsec_walk,
+        "dangling": o.dangling,
+        "vhosts": o.vhosts,
+        "records": o.records.iter().map(|k| lower(k)).collect::<Vec<_>>(),
+        "scan_ports": o.scan_ports.len(),
+        "banners": lower(&o.banners),
+        "ptr": o.ptr,
+        "dnssec": o.dnssec,
+        "asn": o.asn,
+        "rdap": o.rdap,
+        "reverse_ip": o.reverse_ip,
+        "find_related": o.find_related,
+        "resolvers": o.resolvers.iter().map(|u| u.to_string()).collect::<Vec<_>>(),
+        "check_resolvers": o.check_resolvers,
+        "dns_engine": lower(&o.dns_engine),
+        "ip_version": o.ip_version.name(),
+        "dns_concurrency": o.dns_concurrency,
+        "adaptive_concurrency": o.adaptive_concurrency,
+        "retries": o.retries,
+        "max_requests": o.max_requests,
+        "max_bandwidth": o.max_bandwidth,
+        "max_runtime": o.max_runtime.map(|d| d.as_secs()),
+        "phase_deadlines": o
+            .phase_deadlines
+            .iter()
+            .map(|(p, d)| (p.name(), d.as_secs()))
+            .collect::<BTreeMap<_, _>>(),
+        "wildcard_threshold": o.wildcard_threshold,
+        "keep_filtered": o.keep_filtered,
+        "sorted": o.sorted,
+        "ports": o.probe.ports,
+        "http_concurrency": o.probe.concurrency,
+        "follow_redirects": o.probe.follow_redirects,
+        "paths": o.probe.paths,
+        "body_rules": o.probe.body_rules.iter().map(|r| &r.tag).collect::<Vec<_>>(),
+        "method": match o.probe.method {
+            ProbeMethod::Get => "GET".to_string(),
+            ProbeMethod::HeadFirst => "head-first".to_string(),
+            ProbeMethod::Other(ref m) => m.to_string(),
+        },
+        "scoped": !o.scope.is_empty(),
+    })
+}
+
+fn timestamp(t: SystemTime) -> String {
+    let secs = t
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    crate::cert::rfc3339(secs)
+}
+
+fn serialize_grouped<S: Serializer>(
+    groups: &&[(String, Vec<SubdomainResult>)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    Grouped(groups).serialize(serializer)
+}
+
+/// Writes `--group-by-ip` clusters instead of individual results. `hosts`
+/// prints one representative name per cluster.
+pub fn write_clusters<W: Write>(
+    out: &mut W,
+    format: OutputFormat,
+    clusters: &[HostCluster],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, clusters)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Jsonl => {
+            for c in clusters {
+                serde_json::to_writer(&mut *out, c)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => {
+            writeln!(
+                out,
+                "ips,cdn,status_code,title,body_hash,virtual_hosts,hosts"
+            )?;
+            for c in clusters {
+                let fields = [
+                    c.ips.join(";"),
+                    c.cdn.clone().unwrap_or_default(),
+                    opt(c.status_code),
+                    c.title.clone().unwrap_or_default(),
+                    c.body_hash.clone().unwrap_or_default(),
+                    c.virtual_hosts.to_string(),
+                    c.hosts.join(";"),
+                ];
+                let row: Vec<String> = fields.iter().map(|f| csv_escape(f)).collect();
+                writeln!(out, "{}", row.join(","))?;
+            }
+        }
+        OutputFormat::Amass => {
+            for c in clusters {
+                for host in &c.hosts {
+                    serde_json::to_writer(&mut *out, &crate::interop::amass_host(host, &c.ips))?;
+                    writeln!(out)?;
+                }
+            }
+        }
+        OutputFormat::Hosts => {
+            for host in clusters.iter().filter_map(|c| c.hosts.first()) {
+                writeln!(out, "{}", host)?;
+            }
+        }
+    }
+    out.flush()
+}
+
+/// Serialises as a map, keeping the input order of the domains.
+struct Grouped<'a>(&'a [(String, Vec<SubdomainResult>)]);
+
+impl Serialize for Grouped<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (domain, results) in self.0 {
+            map.serialize_entry(domain, results)?;
+        }
+        map.end()
+    }
+}
+
+/// Columns of the CSV format, in `SubdomainResult` field order.
+pub const CSV_COLUMNS: &[&str] = &[
+    "subdomain",
+    "apex",
+    "source",
+    "sources",
+    "ips",
+    "dual_stack",
+    "cname_chain",
+    "resolver",
+    "dns_latency_ms",
+    "dns_ttl",
+    "port",
+    "scheme",
+    "http_version",
+    "address_family",
+    "method",
+    "final_url",
+    "redirect_chain",
+    "location",
+    "response_time_ms",
+    "slow",
+    "retried",
+    "probe_error",
+    "status_code",
+    "title",
+    "server",
+    "content_length",
+    "body_hash",
+    "auth_scheme",
+    "auth_realm",
+    "takeover",
+    "dangling",
+    "dangling_evidence",
+    "waf_challenge",
+    "suspect_honeypot",
+    "headers",
+    "extracted",
+    "body_matches",
+    "cdn",
+    "provider",
+    "technologies",
+    "tags",
+    "wildcard_similarity",
+    "filtered_reason",
+    "wildcard_zone",
+    "favicon_hash",
+    "disallowed_paths",
+    "paths",
+    "tls_subject",
+    "tls_issuer",
+    "tls_organization",
+    "tls_sans",
+    "tls_not_after",
+    "jarm",
+    "zone_transfer",
+    "vhost",
+    "open_ports",
+    "banners",
+    "ptr",
+    "dns_records",
+    "dnssec",
+    "asn",
+    "as_org",
+    "netblock",
+    "netblock_owner",
+    "country",
+    "city",
+    "shodan_ports",
+    "partial",
+    "screenshot",
+    "seen_in",
+];
+
+/// Whatever has to precede the first streamed record (the CSV header).
+pub fn write_header<W: Write>(out: &mut W, format: OutputFormat) -> io::Result<()> {
+    if format == OutputFormat::Csv {
+        writeln!(out, "{}", CSV_COLUMNS.join(","))?;
+    }
+    Ok(())
+}
+
+/// Writes a single result in a streaming format and flushes it.
+pub fn write_record<W: Write>(
+    out: &mut W,
+    format: OutputFormat,
+    result: &SubdomainResult,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Jsonl => {
+            serde_json::to_writer(&mut *out, result)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv => writeln!(out, "{}", csv_row(result))?,
+        OutputFormat::Hosts => writeln!(out, "{}", result.subdomain)?,
+        OutputFormat::Amass => {
+            serde_json::to_writer(&mut *out, &crate::interop::amass_record(result))?;
+            writeln!(out)?;
+        }
+        OutputFormat::Json => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "json output can't be written per record",
+            ))
+        }
+    }
+    out.flush()
+}
+
+/// Lists inside a cell are joined with `;`.
+fn csv_row(r: &SubdomainResult) -> String {
+    let fields = [
+        r.subdomain.clone(),
+        r.apex.clone().unwrap_or_default(),
+        r.source.clone().unwrap_or_default(),
+        r.sources.join(";"),
+        r.ips.join(";"),
+        r.dual_stack.to_string(),
+        r.cname_chain.join(";"),
+        r.resolver.clone().unwrap_or_default(),
+        opt(r.dns_latency_ms),
+        opt(r.dns_ttl),
+        opt(r.port),
+        r.scheme.clone().unwrap_or_default(),
+        r.http_version.clone().unwrap_or_default(),
+        r.address_family.clone().unwrap_or_default(),
+        r.method.clone().unwrap_or_default(),
+        r.final_url.clone().unwrap_or_default(),
+        r.redirect_chain.join(";"),
+        r.location.clone().unwrap_or_default(),
+        opt(r.response_time_ms),
+        r.slow.to_string(),
+        r.retried.to_string(),
+        r.probe_error.clone().unwrap_or_default(),
+        opt(r.status_code),
+        r.title.clone().unwrap_or_default(),
+        r.server.clone().unwrap_or_default(),
+        opt(r.content_length),
+        r.body_hash.clone().unwrap_or_default(),
+        r.auth_scheme.clone().unwrap_or_default(),
+        r.auth_realm.clone().unwrap_or_default(),
+        r.takeover
+            .as_ref()
+            .map(|t| t.service.clone())
+            .unwrap_or_default(),
+        r.dangling.to_string(),
+        r.dangling_evidence.clone().unwrap_or_default(),
+        r.waf_challenge.clone().unwrap_or_default(),
+        r.suspect_honeypot.clone().unwrap_or_default(),
+        if r.headers.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&r.headers).unwrap_or_default()
+        },
+        if r.extracted.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&r.extracted).unwrap_or_default()
+        },
+        r.body_matches.join(";"),
+        r.cdn.clone().unwrap_or_default(),
+        r.provider.clone().unwrap_or_default(),
+        r.technologies.join(";"),
+        r.tags.join(";"),
+        opt(r.wildcard_similarity),
+        r.filtered_reason.clone().unwrap_or_default(),
+        r.wildcard_zone.clone().unwrap_or_default(),
+        opt(r.favicon_hash),
+        r.disallowed_paths.join(";"),
+        if r.paths.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&r.paths).unwrap_or_default()
+        },
+        tls(r, |c| c.subject.clone()),
+        tls(r, |c| c.issuer.clone()),
+        tls(r, |c| c.organization.clone().unwrap_or_default()),
+        tls(r, |c| c.sans.join(";")),
+        tls(r, |c| c.not_after.clone()),
+        r.jarm.clone().unwrap_or_default(),
+        r.zone_transfer.clone().unwrap_or_default(),
+        r.vhost.to_string(),
+        r.open_ports
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(";"),
+        if r.banners.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&r.banners).unwrap_or_default()
+        },
+        r.ptr.join(";"),
+        r.dns_records
+            .as_ref()
+            .map(|d| serde_json::to_string(d).unwrap_or_default())
+            .unwrap_or_default(),
+        r.dnssec.map(|s| s.as_str()).unwrap_or_default().to_string(),
+        opt(r.asn),
+        r.as_org.clone().unwrap_or_default(),
+        r.netblock.clone().unwrap_or_default(),
+        r.netblock_owner.clone().unwrap_or_default(),
+        r.country.clone().unwrap_or_default(),
+        r.city.clone().unwrap_or_default(),
+        r.shodan_ports
+            .as_ref()
+            .map(|p| {
+                p.iter()
+                    .map(|port| port.to_string())
+                    .collect::<Vec<_>>()
+                    .join(";")
+            })
+            .unwrap_or_default(),
+        r.partial.to_string(),
+        r.screenshot.clone().unwrap_or_default(),
+        r.seen_in
+            .iter()
+            .map(|s| s.scan.as_str())
+            .collect::<Vec<_>>()
+            .join(";"),
+    ];
+    fields
+        .iter()
+        .map(|f| csv_escape(f))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn tls(r: &SubdomainResult, field: impl Fn(&CertificateInfo) -> String) -> String {
+    r.tls.as_ref().map(field).unwrap_or_default()
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,83 @@
+use crate::Result;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Words combined with discovered labels when no mutation wordlist is given.
+pub const DEFAULT_MUTATIONS: &[&str] = &[
+    "dev", "staging", "stage", "test", "qa", "uat", "prod", "api", "admin", "internal", "beta",
+    "old", "new", "v1", "v2", "int", "preprod", "demo", "backup", "app",
+];
+
+pub fn load_mutations(path: &Path) -> Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(text
+        .lines()
+        .map(|l| l.trim().to_lowercase())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect())
+}
+
+/// Generates altdns-style variations of already resolved names.
+///
+/// Only the left-most label is mutated, so `api.corp.example.com` yields
+/// `api-dev.corp.example.com`, `dev-api.corp.example.com`, `api2.corp...`.
+/// The inputs themselves are never part of the output.
+pub fn generate_permutations(names: &[String], domain: &str, words: &[String]) -> HashSet<String> {
+    let suffix = format!(".{}", domain);
+    let mut out = HashSet::new();
+
+    for name in names {
+        let Some(prefix) = name.strip_suffix(&suffix) else {
+            continue;
+        };
+        let (label, rest) = match prefix.split_once('.') {
+            Some((l, r)) => (l, format!(".{}{}", r, suffix)),
+            None => (prefix, suffix.clone()),
+        };
+
+        for w in words {
+            if w == label {
+                continue;
+            }
+            out.insert(format!("{}-{}{}", w, label, rest));
+            out.insert(format!("{}-{}{}", label, w, rest));
+            out.insert(format!("{}{}{}", w, label, rest));
+            out.insert(format!("{}{}{}", label, w, rest));
+            out.insert(format!("{}.{}{}", w, label, rest));
+        }
+
+        for n in numeric_neighbours(label) {
+            out.insert(format!("{}{}", n, rest));
+        }
+    }
+
+    for name in names {
+        out.remove(name);
+    }
+    out
+}
+
+/// `web01` -> `web00`, `web02`; `api` -> `api1`, `api2`.
+fn numeric_neighbours(label: &str) -> Vec<String> {
+    let digits_start = label
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_ascii_digit())
+        .last()
+        .map(|(i, _)| i);
+
+    let Some(start) = digits_start else {
+        return vec![format!("{}1", label), format!("{}2", label)];
+    };
+
+    let (stem, digits) = label.split_at(start);
+    let Ok(n) = digits.parse::<u64>() else {
+        return Vec::new();
+    };
+    let width = digits.len();
+    let mut out = vec![format!("{}{:0width$}", stem, n + 1, width = width)];
+    if n > 0 {
+        out.push(format!("{}{:0width$}", stem, n - 1, width = width));
+    }
+    out
+}
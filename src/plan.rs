@@ -0,0 +1,417 @@
+//! What a scan would do, for `--dry-run`: the sources and resolvers it
+//! would use, the names it would start from and roughly how many requests
+//! it would send. Working this out sends nothing.
+
+use crate::banner::BannerScope;
+use crate::sources::{self, cache::SourceCache, KEYED_SOURCES};
+use crate::wildcard::WILDCARD_PROBES;
+use crate::{robots, ScanOptions, TOP_SUBDOMAINS};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// Domains whose source URLs are listed; the rest are only counted.
+const LISTED_DOMAINS: usize = 3;
+
+/// TLS handshakes behind one JARM fingerprint.
+const JARM_HANDSHAKES: usize = 10;
+
+/// A scan laid out before it runs.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub domains: Vec<String>,
+    /// Passive sources that would be queried, with the first URL each
+    /// requests for the first few domains. `None` for answers the source
+    /// cache holds.
+    pub sources: Vec<(&'static str, Vec<Option<String>>)>,
+    /// Keyed sources left out for lack of an API key.
+    pub unkeyed: Vec<&'static str>,
+    /// Resolvers, as given or the default.
+    pub resolvers: Vec<String>,
+    /// `resolver` or `raw`, and the lookups in flight to start with.
+    pub dns_engine: String,
+    pub dns_concurrency: usize,
+    /// Where the starting names of each domain come from, and how many.
+    pub candidates: Vec<(String, usize)>,
+    /// Requests of one domain known before anything answers, by kind.
+    pub requests: Vec<(String, usize)>,
+    /// HTTP requests per host that resolves, before redirects.
+    pub http_per_host: usize,
+    /// Steps whose request count depends on what the scan finds.
+    pub notes: Vec<String>,
+}
+
+impl Plan {
+    pub fn new(options: &ScanOptions, domains: &[String]) -> Self {
+        let discover = options.discover;
+        let mut plan = Plan {
+            domains: domains.to_vec(),
+            sources: Vec::new(),
+            unkeyed: Vec::new(),
+            resolvers: if options.resolvers.is_empty() {
+                vec!["google".to_string()]
+            } else {
+                options.resolvers.iter().map(|u| u.to_string()).collect()
+            },
+            dns_engine: format!("{:?}", options.dns_engine).to_lowercase(),
+            dns_concurrency: options.dns_concurrency,
+            candidates: Vec::new(),
+            requests: Vec::new(),
+            http_per_host: 0,
+            notes: Vec::new(),
+        };
+
+        if discover {
+            let registry = sources::registry(options);
+            plan.unkeyed = KEYED_SOURCES
+                .iter()
+                .filter(|k| !registry.iter().any(|s| s.name() == **k))
+                .filter(|k| !options.exclude_sources.iter().any(|e| e == *k))
+                .copied()
+                .collect();
+            if !options.sources.is_empty() {
+                plan.unkeyed
+                    .retain(|k| options.sources.iter().any(|s| s == k));
+            }
+            let cache = options
+                .cache_dir
+                .as_ref()
+                .map(|dir| SourceCache::new(dir.clone(), options.cache_ttl));
+            let selected = sources::select(registry, &options.sources, &options.exclude_sources);
+            for source in selected {
+                let urls = domains
+                    .iter()
+                    .take(LISTED_DOMAINS)
+                    .map(|d| match cache {
+                        Some(ref c) if c.get(source.name(), d).is_some() => None,
+                        _ => Some(source.url(d)),
+                    })
+                    .collect();
+                plan.sources.push((source.name(), urls));
+            }
+        }
+
+        // Starting names per domain
+        if discover && !options.passive {
+            plan.candidates
+                .push(("built-in common names".to_string(), TOP_SUBDOMAINS.len()));
+        }
+        if !options.candidates.is_empty() {
+            plan.candidates
+                .push(("--input".to_string(), options.candidates.len()));
+        }
+        if !options.known.is_empty() {
+            plan.candidates
+                .push(("--known".to_string(), options.known.len()));
+        }
+        if discover {
+            if let Some(ref path) = options.wordlist {
+                let label = format!("wordlist {}", path.display());
+                match count_words(path) {
+                    Some(n) => plan.candidates.push((label, n)),
+                    None => plan.notes.push(format!("{} is unreadable", label)),
+                }
+            } else if let Some(list) = options.wordlist_builtin {
+                let label = format!("{} wordlist", list.name());
+                match crate::wordlists::local_path(list).and_then(|p| count_words(&p)) {
+                    Some(n) => plan.candidates.push((label, n)),
+                    None => {
+                        plan.candidates.push((label.clone(), list.approx_len()));
+                        if list != crate::wordlists::Builtin::Small {
+                            plan.notes
+                                .push(format!("The {} is downloaded from GitHub first.", label));
+                        }
+                    }
+                }
+            }
+        }
+        if !options.records.is_empty() {
+            plan.candidates
+                .push(("the apex, for --records".to_string(), 1));
+        }
+
+        if options.passive {
+            let fetched = plan
+                .sources
+                .iter()
+                .filter(|(_, urls)| urls.first().is_some_and(Option::is_some))
+                .count();
+            if fetched > 0 {
+                plan.requests
+                    .push(("passive source queries, at least".to_string(), fetched));
+            }
+            plan.notes.push(
+                "--profile passive resolves and probes nothing: no DNS query or HTTP request goes to the target."
+                    .to_string(),
+            );
+            return plan;
+        }
+
+        // Requests known up front
+        let tries = 1 + options.retries as usize;
+        let names: usize = plan.candidates.iter().map(|(_, n)| n).sum();
+        let resolvers = options.resolvers.len();
+        if options.check_resolvers && resolvers >= 2 {
+            plan.requests
+                .push(("resolver health checks".to_string(), 2 * resolvers));
+        }
+        plan.requests.push((
+            "DNS lookups of the wildcard check".to_string(),
+            WILDCARD_PROBES,
+        ));
+        plan.requests
+            .push(("DNS lookups of starting names".to_string(), names * tries));
+        let fetched = plan
+            .sources
+            .iter()
+            .filter(|(_, urls)| urls.first().is_some_and(Option::is_some))
+            .count();
+        if fetched > 0 {
+            plan.requests
+                .push(("passive source queries, at least".to_string(), fetched));
+        }
+
+        // HTTP requests per resolved host
+        let ports = options.probe.ports.len().max(1);
+        // Both schemes, and a random path where the page answers 200
+        let mut per_host = ports * 3;
+        if options.probe.favicon {
+            per_host += ports;
+        }
+        if options.probe.robots {
+            per_host += ports * (1 + robots::MAX_SITEMAPS);
+        }
+        if !options.probe.paths.is_empty() {
+            per_host += ports * (1 + options.probe.paths.len());
+        }
+        plan.http_per_host = per_host * tries;
+        plan.notes.push(
+            "Pages answering 200 get one more request for a random path, once per address and page, to spot catch-all servers."
+                .to_string(),
+        );
+        if options.probe.jarm {
+            plan.notes.push(format!(
+                "--jarm adds {} TLS handshakes per HTTPS host.",
+                JARM_HANDSHAKES
+            ));
+        }
+
+        // Everything that scales with what is found
+        if discover {
+            if options.axfr {
+                plan.notes
+                    .push("--axfr asks each nameserver of the apex for a zone transfer.".into());
+            }
+            if options.nsec_walk {
+                plan.notes.push(format!(
+                    "--nsec-walk sends one query per name on the apex's NSEC chain, up to {}.",
+                    crate::dnssec::MAX_WALK
+                ));
+            }
+            if options.permute {
+                let words = match options.permute_wordlist {
+                    Some(ref path) => crate::permute::load_mutations(path).map(|w| w.len()).ok(),
+                    None => Some(crate::permute::DEFAULT_MUTATIONS.len()),
+                };
+                // Five joins of each word with the label, and two numbered
+                // neighbours
+                if let Some(words) = words {
+                    plan.notes.push(format!(
+                        "--permute looks up about {} mutations per resolved name.",
+                        words * 5 + 2
+                    ));
+                }
+            }
+            if options.markov > 0 {
+                plan.notes.push(format!(
+                    "--markov looks up {} generated names.",
+                    options.markov
+                ));
+            }
+            if options.recursion_depth > 0 {
+                plan.notes.push(format!(
+                    "--recursive repeats discovery for intermediate labels, {} level(s) deep.",
+                    options.recursion_depth
+                ));
+            }
+            if options.feedback {
+                plan.notes.push(
+                    "Names seen in certificates and pages are looked up and probed too."
+                        .to_string(),
+                );
+            }
+        }
+        if !options.scan_ports.is_empty() {
+            plan.notes.push(format!(
+                "--scan-ports opens {} TCP connections per address.",
+                options.scan_ports.len()
+            ));
+        }
+        match options.banners {
+            BannerScope::All => plan.notes.push(format!(
+                "--banners opens {} TCP connections per host for service banners.",
+                crate::banner::SERVICES.len()
+            )),
+            BannerScope::Named if options.proxy.is_none() => plan.notes.push(
+                "Hosts named like mail, ftp or ssh get TCP connections to those services for their banners."
+                    .to_string(),
+            ),
+            _ => {}
+        }
+        if options.ptr {
+            plan.notes
+                .push("--ptr looks up every address in reverse DNS.".to_string());
+        }
+        if options.dnssec {
+            plan.notes
+                .push("--dnssec looks up every host again with validation.".to_string());
+        }
+        for (on, what) in [
+            (options.asn, "--asn queries Team Cymru for every address."),
+            (
+                options.reverse_ip,
+                "--reverse-ip queries HackerTarget for every non-CDN address.",
+            ),
+            (
+                options.rdap,
+                "--rdap queries rdap.org for the apex and every netblock.",
+            ),
+            (
+                options.find_related,
+                "--find-related queries crt.sh and WHOIS per organisation.",
+            ),
+            (
+                options.shodan_ports,
+                "--shodan-ports queries Shodan for every address.",
+            ),
+            (
+                options.vhosts,
+                "--vhosts sends unresolved names as Host headers to found addresses.",
+            ),
+            (
+                options.dangling,
+                "--dangling follows the CNAMEs of names that don't resolve.",
+            ),
+        ] {
+            if on {
+                plan.notes.push(what.to_string());
+            }
+        }
+        if let Some(rate) = options.rate {
+            plan.notes.push(format!(
+                "--rate {} spaces DNS lookups and HTTP requests out.",
+                rate
+            ));
+        }
+        if let Some(n) = options.max_requests {
+            plan.notes.push(format!(
+                "--max-requests stops probing after {} requests.",
+                n
+            ));
+        }
+        if let Some(n) = options.max_bandwidth {
+            plan.notes
+                .push(format!("--max-bandwidth stops probing after {} bytes.", n));
+        }
+        if let Some(d) = options.max_runtime {
+            plan.notes.push(format!(
+                "--max-runtime ends the run after {}s.",
+                d.as_secs()
+            ));
+        }
+        for (phase, d) in &options.phase_deadlines {
+            plan.notes.push(format!(
+                "--phase-deadline stops the {} phase after {}s.",
+                phase.name(),
+                d.as_secs()
+            ));
+        }
+        plan
+    }
+}
+
+/// Names a wordlist would add, counted the way brute forcing reads it.
+fn count_words(path: &std::path::Path) -> Option<usize> {
+    let file = File::open(path).ok()?;
+    let mut n = 0;
+    for line in BufReader::new(file).split(b'\n') {
+        let Ok(line) = String::from_utf8(line.ok()?) else {
+            continue;
+        };
+        let word = line.trim();
+        if !word.is_empty() && !word.starts_with('#') {
+            n += 1;
+        }
+    }
+    Some(n)
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Dry run for {} domain(s): {}",
+            self.domains.len(),
+            self.domains.join(", ")
+        )?;
+        writeln!(f, "Nothing will be sent.")?;
+
+        if self.sources.is_empty() {
+            writeln!(f, "\nPassive sources: none")?;
+        } else {
+            writeln!(f, "\nPassive sources ({}):", self.sources.len())?;
+        }
+        for (name, urls) in &self.sources {
+            for (i, url) in urls.iter().enumerate() {
+                let shown = url.as_deref().unwrap_or("(answered from cache)");
+                let label = if i == 0 { *name } else { "" };
+                writeln!(f, "  {:<15} {}", label, shown)?;
+            }
+        }
+        if self.domains.len() > LISTED_DOMAINS && !self.sources.is_empty() {
+            writeln!(
+                f,
+                "  ...and the same for {} more domains",
+                self.domains.len() - LISTED_DOMAINS
+            )?;
+        }
+        if !self.unkeyed.is_empty() {
+            writeln!(
+                f,
+                "  Skipped without an API key: {}",
+                self.unkeyed.join(", ")
+            )?;
+        }
+
+        writeln!(
+            f,
+            "\nResolvers ({} engine, {} lookups at once): {}",
+            self.dns_engine,
+            self.dns_concurrency,
+            self.resolvers.join(", ")
+        )?;
+
+        writeln!(f, "\nStarting names per domain:")?;
+        for (what, n) in &self.candidates {
+            writeln!(f, "  {:>8}  {}", n, what)?;
+        }
+
+        writeln!(f, "\nRequests per domain known up front:")?;
+        for (what, n) in &self.requests {
+            writeln!(f, "  {:>8}  {}", n, what)?;
+        }
+        writeln!(
+            f,
+            "  {:>8}  HTTP requests per resolving host, at most",
+            self.http_per_host
+        )?;
+
+        if !self.notes.is_empty() {
+            writeln!(f, "\nDepending on what is found:")?;
+            for note in &self.notes {
+                writeln!(f, "  - {}", note)?;
+            }
+        }
+        Ok(())
+    }
+}
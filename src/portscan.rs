@@ -0,0 +1,92 @@
+//! A quick TCP connect scan of resolved addresses.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+
+/// nmap's 100 most common TCP ports.
+pub const TOP_100: &[u16] = &[
+    7, 9, 13, 21, 22, 23, 25, 26, 37, 53, 79, 80, 81, 88, 106, 110, 111, 113, 119, 135, 139, 143,
+    144, 179, 199, 389, 427, 443, 444, 445, 465, 513, 514, 515, 543, 544, 548, 554, 587, 631, 646,
+    873, 990, 993, 995, 1025, 1026, 1027, 1028, 1029, 1110, 1433, 1720, 1723, 1755, 1900, 2000,
+    2001, 2049, 2121, 2717, 3000, 3128, 3306, 3389, 3986, 4899, 5000, 5009, 5051, 5060, 5101, 5190,
+    5357, 5432, 5631, 5666, 5800, 5900, 6000, 6001, 6646, 7070, 8000, 8008, 8009, 8080, 8081, 8443,
+    8888, 9100, 9999, 10000, 32768, 49152, 49153, 49154, 49155, 49156, 49157,
+];
+
+/// Connection attempts in flight, independent of the DNS and HTTP caps.
+pub const SCAN_CONCURRENCY: usize = 500;
+
+/// How long a port may take to accept before it counts as closed.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// `top100`, or ports and ranges like `22,80,8000-8100`.
+pub fn parse_ports(spec: &str) -> Result<Vec<u16>, String> {
+    if spec.eq_ignore_ascii_case("top100") {
+        return Ok(TOP_100.to_vec());
+    }
+    let invalid = || format!("invalid port list '{}'", spec);
+    let mut ports = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((a, b)) => (
+                a.trim().parse::<u16>().map_err(|_| invalid())?,
+                b.trim().parse::<u16>().map_err(|_| invalid())?,
+            ),
+            None => {
+                let p = part.parse::<u16>().map_err(|_| invalid())?;
+                (p, p)
+            }
+        };
+        if start == 0 || start > end {
+            return Err(invalid());
+        }
+        ports.extend(start..=end);
+    }
+    ports.sort_unstable();
+    ports.dedup();
+    if ports.is_empty() {
+        return Err(invalid());
+    }
+    Ok(ports)
+}
+
+/// Open ports among `ports` for every address in `ips`. Addresses with
+/// nothing open are left out.
+pub async fn scan(ips: &[String], ports: &[u16]) -> HashMap<String, Vec<u16>> {
+    let permits = Arc::new(Semaphore::new(SCAN_CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    for ip in ips {
+        let Ok(addr) = ip.parse::<IpAddr>() else {
+            continue;
+        };
+        for &port in ports {
+            let permits = permits.clone();
+            let ip = ip.clone();
+            tasks.spawn(async move {
+                let _permit = permits.acquire().await.ok()?;
+                let target = SocketAddr::new(addr, port);
+                match timeout(CONNECT_TIMEOUT, TcpStream::connect(target)).await {
+                    Ok(Ok(_)) => Some((ip, port)),
+                    _ => None,
+                }
+            });
+        }
+    }
+
+    let mut open: HashMap<String, Vec<u16>> = HashMap::new();
+    while let Some(res) = tasks.join_next().await {
+        if let Ok(Some((ip, port))) = res {
+            open.entry(ip).or_default().push(port);
+        }
+    }
+    for ports in open.values_mut() {
+        ports.sort_unstable();
+    }
+    open
+}
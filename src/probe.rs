@@ -0,0 +1,1162 @@
+use crate::adaptive::Concurrency;
+use crate::dns::{self, IpVersion, ResolvedHost};
+use crate::matchers::{self, BodyRule};
+use crate::progress::Progress;
+use crate::ratelimit::RateLimits;
+use crate::{
+    cdn, cloud, dangling, favicon, jarm, metrics, proxy, robots, sources, synthetic, takeover,
+    tech, title, wildcard, CertificateInfo, SubdomainResult,
+};
+use hyper::client::connect::dns::Name;
+use regex::Regex;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, LOCATION, PROXY_AUTHENTICATE, WWW_AUTHENTICATE};
+use reqwest::tls::TlsInfo;
+use reqwest::{redirect, Client, ClientBuilder, Method, Response, StatusCode, Url, Version};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, OnceCell};
+
+/// Settings for the HTTP probe phase.
+#[derive(Debug, Clone)]
+pub struct ProbeOptions {
+    /// Maximum hosts probed at once.
+    pub concurrency: usize,
+    /// User agent for probes. `None` sends no `User-Agent` header.
+    pub user_agent: Option<String>,
+    /// Extra headers sent with every probe, including `--cookie` values.
+    pub headers: HeaderMap,
+    /// HTTP proxy probes go through.
+    pub proxy: Option<Url>,
+    /// Ports to probe, each as its own result. Empty means 443 then 80.
+    pub ports: Vec<u16>,
+    /// Record both `https` and `http` on every port instead of stopping at
+    /// the first scheme that answers.
+    pub both_schemes: bool,
+    /// How each probe request is sent.
+    pub method: ProbeMethod,
+    /// Follow up to [`MAX_REDIRECTS`] redirects. When off, the 30x itself is
+    /// reported along with its `Location`.
+    pub follow_redirects: bool,
+    /// Responses slower than this many milliseconds are marked `slow`.
+    pub slow_threshold_ms: Option<u64>,
+    /// Response headers to copy into `SubdomainResult::headers`.
+    pub capture_headers: HeaderCapture,
+    /// Stop reading a body after this many bytes; the title, takeover
+    /// fingerprints and leads only look at what was read.
+    pub max_body_bytes: usize,
+    /// Patterns run over each body; every named group that matches lands
+    /// in `SubdomainResult::extracted` under its name.
+    pub extract: Vec<Regex>,
+    /// `--match-body` rules run over each body.
+    pub body_rules: Vec<BodyRule>,
+    /// Fetch `/favicon.ico` from every answering host and record its hash.
+    pub favicon: bool,
+    /// Fetch `/robots.txt` and `/sitemap.xml` from every answering host,
+    /// recording interesting disallowed paths and, with `scrape_domain`,
+    /// the hostnames both name as leads.
+    pub robots: bool,
+    /// Take the JARM fingerprint of every host answering over HTTPS.
+    pub jarm: bool,
+    /// Paths requested on every answering host, each starting with `/`.
+    /// Those that don't answer 404 land in `SubdomainResult::paths`.
+    pub paths: Vec<String>,
+    /// Collect hostnames under this domain from certificates, CSP headers
+    /// and bodies into `SubdomainResult::leads`.
+    pub scrape_domain: Option<String>,
+    /// Extra attempts for requests that timed out or had their connection
+    /// reset. Refused connections are not retried.
+    pub retries: u32,
+    /// Delay before the first retry, doubling for each one after.
+    pub retry_backoff: Duration,
+    /// Address family probes connect over. Not applied through `proxy`,
+    /// which makes the connection itself.
+    pub ip_version: IpVersion,
+    /// Request rate limits every probe request waits on.
+    pub rate_limits: RateLimits,
+    /// Permits shared with other probe runs, so concurrent scans stay
+    /// within one budget. `None` gives each run its own fixed
+    /// `concurrency`.
+    pub shared_permits: Option<Concurrency>,
+}
+
+impl Default for ProbeOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 50,
+            user_agent: None,
+            headers: HeaderMap::new(),
+            proxy: None,
+            ports: Vec::new(),
+            both_schemes: false,
+            method: ProbeMethod::default(),
+            follow_redirects: true,
+            slow_threshold_ms: None,
+            capture_headers: HeaderCapture::None,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            extract: Vec::new(),
+            body_rules: Vec::new(),
+            favicon: false,
+            robots: false,
+            jarm: false,
+            paths: Vec::new(),
+            scrape_domain: None,
+            retries: 0,
+            retry_backoff: Duration::from_millis(500),
+            ip_version: IpVersion::Any,
+            rate_limits: RateLimits::default(),
+            shared_permits: None,
+        }
+    }
+}
+
+/// Default cap on body bytes read per response.
+pub const DEFAULT_MAX_BODY_BYTES: usize = 512 * 1024;
+
+impl ProbeOptions {
+    /// Redirects followed per request: [`MAX_REDIRECTS`], or none without
+    /// [`follow_redirects`](Self::follow_redirects).
+    pub fn max_redirects(&self) -> usize {
+        if self.follow_redirects {
+            MAX_REDIRECTS
+        } else {
+            0
+        }
+    }
+}
+
+/// Request method of the probes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ProbeMethod {
+    #[default]
+    Get,
+    /// HEAD, then GET where the server rejects HEAD. Status, headers and
+    /// certificate cost no body, but nothing body-based (title, takeover
+    /// fingerprints, leads) is recorded for hosts that accept HEAD.
+    HeadFirst,
+    /// Any other method, sent as-is for every request of a probe.
+    Other(Method),
+}
+
+impl FromStr for ProbeMethod {
+    type Err = String;
+
+    /// `head-first`, or a method name such as `OPTIONS`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("head-first") {
+            return Ok(Self::HeadFirst);
+        }
+        let method = Method::from_bytes(s.to_ascii_uppercase().as_bytes())
+            .map_err(|_| format!("invalid HTTP method '{}'", s))?;
+        Ok(if method == Method::GET {
+            Self::Get
+        } else {
+            Self::Other(method)
+        })
+    }
+}
+
+/// Statuses that mean a server won't take HEAD, so `HeadFirst` retries
+/// with GET.
+const HEAD_REJECTED: &[u16] = &[400, 405, 501];
+
+/// Which response headers end up in the output.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum HeaderCapture {
+    #[default]
+    None,
+    All,
+    /// Lowercase header names.
+    Only(Vec<String>),
+}
+
+impl FromStr for HeaderCapture {
+    type Err = String;
+
+    /// `all`, or a comma-separated list of names. `csp` is accepted for
+    /// `content-security-policy` and `hsts` for `strict-transport-security`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(Self::All);
+        }
+        let names: Vec<String> = s
+            .split(',')
+            .map(|n| n.trim().to_ascii_lowercase())
+            .filter(|n| !n.is_empty())
+            .map(|n| match n.as_str() {
+                "csp" => "content-security-policy".to_string(),
+                "hsts" => "strict-transport-security".to_string(),
+                _ => n,
+            })
+            .collect();
+        if names.is_empty() {
+            return Err(format!("no header names in '{}'", s));
+        }
+        Ok(Self::Only(names))
+    }
+}
+
+impl HeaderCapture {
+    /// The selected headers, repeated ones joined with `, `.
+    pub fn capture(&self, headers: &HeaderMap) -> BTreeMap<String, String> {
+        let mut out = BTreeMap::new();
+        if *self == Self::None {
+            return out;
+        }
+        for (name, value) in headers {
+            let name = name.as_str();
+            if let Self::Only(ref names) = *self {
+                if !names.iter().any(|n| n == name) {
+                    continue;
+                }
+            }
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            out.entry(name.to_string())
+                .and_modify(|v: &mut String| {
+                    v.push_str(", ");
+                    v.push_str(&value);
+                })
+                .or_insert(value);
+        }
+        out
+    }
+}
+
+pub async fn probe_http(
+    targets: Vec<ResolvedHost>,
+    options: &ProbeOptions,
+) -> Vec<SubdomainResult> {
+    let mut rx = probe_http_stream(targets, options);
+    let mut results = Vec::new();
+    while let Some(result) = rx.recv().await {
+        results.push(result);
+    }
+    results
+}
+
+/// Like [`probe_http`], but hands each result over as soon as its host is
+/// done. The channel closes once every target has been probed.
+pub fn probe_http_stream(
+    targets: Vec<ResolvedHost>,
+    options: &ProbeOptions,
+) -> mpsc::UnboundedReceiver<SubdomainResult> {
+    let client = client_builder(options)
+        .timeout(Duration::from_secs(8))
+        // Redirects are followed by hand so every hop can be recorded
+        .redirect(redirect::Policy::none())
+        .danger_accept_invalid_certs(true)
+        .tls_info(true)
+        .build()
+        .unwrap_or_default();
+
+    let client = Arc::new(client);
+    let catch_alls = Arc::new(CatchAlls::default());
+    let semaphore = options
+        .shared_permits
+        .clone()
+        .unwrap_or_else(|| Concurrency::fixed(options.concurrency));
+    let (tx, rx) = mpsc::unbounded_channel();
+    let bar = Arc::new(Progress::new("HTTP", targets.len() as u64));
+
+    for host in targets {
+        let progress = bar.clone();
+        let c = client.clone();
+        let catch_alls = catch_alls.clone();
+        let s = semaphore.clone();
+        let tx = tx.clone();
+        let extract = options.extract.clone();
+        let body_rules = options.body_rules.clone();
+
+        let ports = options.ports.clone();
+        let both_schemes = options.both_schemes;
+        let method = options.method.clone();
+        let scope = options.scrape_domain.clone();
+        let fetch_favicon = options.favicon;
+        let fetch_robots = options.robots;
+        let fetch_jarm = options.jarm;
+        let paths = options.paths.clone();
+        let proxy = options.proxy.clone();
+        let slow_threshold = options.slow_threshold_ms;
+        let capture = options.capture_headers.clone();
+        let max_body = options.max_body_bytes;
+        let limits = options.rate_limits.clone();
+        let (retries, backoff) = (options.retries, options.retry_backoff);
+        let max_redirects = options.max_redirects();
+
+        tokio::spawn(async move {
+            let _permit = s.acquire().await;
+
+            let mut answered = false;
+            let mut refused = false;
+            let mut error = None;
+            // Hosts without addresses are dangling CNAMEs, with nothing to
+            // connect to
+            let attempts = if host.ips.is_empty() {
+                Vec::new()
+            } else {
+                attempts(&ports)
+            };
+            let mut skipped = false;
+            'attempts: for (port, schemes) in attempts {
+                for scheme in schemes {
+                    if limits.exhausted() {
+                        skipped = true;
+                        break 'attempts;
+                    }
+                    let url = match port {
+                        Some(p) => format!("{}://{}:{}", scheme, host.subdomain, p),
+                        None => format!("{}://{}", scheme, host.subdomain),
+                    };
+                    let mut attempt = 0;
+                    let (started, fetched) = loop {
+                        let started = Instant::now();
+                        let fetched = fetch(&c, &limits, &method, &url, max_redirects).await;
+                        match fetched {
+                            Ok(_) => s.record(true),
+                            Err(ref e) if is_reset(e) => s.record(false),
+                            Err(_) => {}
+                        }
+                        match fetched {
+                            Err(e) if attempt < retries && is_transient(&e) => {
+                                tokio::time::sleep(
+                                    backoff.saturating_mul(2u32.saturating_pow(attempt)),
+                                )
+                                .await;
+                                attempt += 1;
+                            }
+                            fetched => break (started, fetched),
+                        }
+                    };
+                    let (sent, resp, chain) = match fetched {
+                        Ok(fetched) => fetched,
+                        Err(e) => {
+                            refused |= is_refused(&e);
+                            error = Some(describe(&e));
+                            continue;
+                        }
+                    };
+                    metrics::http_probe(started.elapsed());
+                    let elapsed = started.elapsed().as_millis() as u64;
+
+                    let mut result = unprobed(&host, port);
+                    result.scheme = Some(scheme.to_string());
+                    if sent != Method::GET {
+                        result.method = Some(sent.to_string());
+                    }
+                    result.retried |= attempt > 0;
+                    result.response_time_ms = Some(elapsed);
+                    result.slow = slow_threshold.is_some_and(|t| elapsed > t);
+                    result.final_url = Some(resp.url().to_string());
+                    result.suspect_honeypot = synthetic::redirect_loop(&chain, resp.url().as_str());
+                    result.redirect_chain = chain;
+                    if resp.status().is_redirection() {
+                        result.location = location(&resp).map(str::to_string);
+                    }
+                    result.status_code = Some(resp.status().as_u16());
+                    result.http_version = http_version(resp.version());
+                    result.address_family =
+                        resp.remote_addr().map(|a| dns::family(&a.ip()).to_string());
+                    result.tls = resp
+                        .extensions()
+                        .get::<TlsInfo>()
+                        .and_then(TlsInfo::peer_certificate)
+                        .and_then(|der| CertificateInfo::from_der(der, &host.subdomain));
+                    result.content_length = match sent {
+                        // The body of a HEAD response is empty whatever its
+                        // header says
+                        Method::HEAD => resp
+                            .headers()
+                            .get(CONTENT_LENGTH)
+                            .and_then(|v| v.to_str().ok()?.parse().ok()),
+                        _ => resp.content_length(),
+                    };
+                    if let Some(h) = resp.headers().get("server") {
+                        result.server = h.to_str().ok().map(|s| s.to_string());
+                    }
+                    let headers = resp.headers().clone();
+                    let challenge = match resp.status().as_u16() {
+                        401 => headers.get(WWW_AUTHENTICATE),
+                        407 => headers.get(PROXY_AUTHENTICATE),
+                        _ => None,
+                    };
+                    if let Some((scheme, realm)) =
+                        challenge.and_then(|v| auth_challenge(v.to_str().ok()?))
+                    {
+                        result.auth_scheme = Some(scheme);
+                        result.auth_realm = realm;
+                    }
+                    result.headers = capture.capture(&headers);
+                    result.cdn = cdn::detect(&host.ips, &host.cname_chain, Some(&headers));
+                    let content_type = resp
+                        .headers()
+                        .get("content-type")
+                        .and_then(|h| h.to_str().ok())
+                        .map(str::to_string);
+                    let csp = resp
+                        .headers()
+                        .get("content-security-policy")
+                        .and_then(|h| h.to_str().ok())
+                        .map(str::to_string);
+                    let status = resp.status();
+                    let body = match sent {
+                        Method::HEAD => None,
+                        _ => read_body(resp, &limits, max_body).await,
+                    };
+                    result.waf_challenge = synthetic::challenge(&headers, "").map(str::to_string);
+                    if let Some((bytes, complete)) = body {
+                        if result.content_length.is_none() && complete {
+                            result.content_length = Some(bytes.len() as u64);
+                        }
+                        let text = title::decode_body(&bytes, content_type.as_deref());
+                        result.waf_challenge =
+                            synthetic::challenge(&headers, &text).map(str::to_string);
+                        result.title = match result.waf_challenge {
+                            Some(_) => None,
+                            None => title::extract(&text),
+                        };
+                        result.body_hash = wildcard::body_hash(&text);
+                        result.body_simhash = wildcard::simhash(&text);
+                        result.extracted = extract_fields(&extract, &text);
+                        result.body_matches = matchers::matching(&body_rules, &text);
+                        result.takeover = takeover::check(&host.cname_chain, &text);
+                        result.dangling_evidence = dangling::error_page(&host, &text);
+                        result.dangling = result.dangling_evidence.is_some();
+                        result.technologies = tech::detect(&headers, &text);
+                        if let Some(ref domain) = scope {
+                            result.leads = leads(&result, csp.as_deref(), &text, domain);
+                        }
+                    }
+                    // One page for a path that can't exist either
+                    let room = !limits.exhausted();
+                    if room
+                        && status == StatusCode::OK
+                        && result.waf_challenge.is_none()
+                        && result.suspect_honeypot.is_none()
+                    {
+                        if let Some(ref hash) = result.body_hash {
+                            let page = result.final_url.as_deref().unwrap_or(&url);
+                            let key = (host.ips.first().cloned().unwrap_or_default(), hash.clone());
+                            let cell = catch_alls.cell(key);
+                            let verdict = cell
+                                .get_or_init(|| catch_all(&c, &limits, page, max_body, hash))
+                                .await;
+                            if *verdict {
+                                result.suspect_honeypot = Some(synthetic::CATCH_ALL.to_string());
+                            }
+                        }
+                    }
+                    if fetch_favicon && room {
+                        result.favicon_hash = favicon_hash(&c, &limits, &url, max_body).await;
+                    }
+                    if fetch_jarm && scheme == "https" && room {
+                        limits.wait(&host.subdomain).await;
+                        result.jarm = jarm::fingerprint(
+                            &host.subdomain,
+                            port.unwrap_or(443),
+                            proxy.as_ref(),
+                            &limits,
+                        )
+                        .await;
+                    }
+                    if !paths.is_empty() && room {
+                        result.paths = check_paths(&c, &limits, &url, &paths).await;
+                    }
+                    if fetch_robots && room {
+                        let (paths, mut hosts) =
+                            crawl_hints(&c, &limits, &url, max_body, scope.as_deref()).await;
+                        result.disallowed_paths = paths;
+                        hosts.remove(&result.subdomain);
+                        hosts.retain(|h| !result.leads.contains(h));
+                        result.leads.extend(hosts);
+                    }
+                    answered = true;
+                    let _ = tx.send(result);
+                    if !both_schemes {
+                        break;
+                    }
+                }
+            }
+
+            // Resolved but silent hosts are still reported once
+            if !answered {
+                let mut result = unprobed(&host, None);
+                if skipped {
+                    let why = if limits.deadline.passed() {
+                        DEADLINE_PASSED
+                    } else {
+                        BUDGET_EXHAUSTED
+                    };
+                    result.probe_error = Some(why.to_string());
+                    result.partial = true;
+                } else {
+                    result.probe_error = error;
+                    result.dangling_evidence = dangling::unanswered(&host, refused);
+                    result.dangling = result.dangling_evidence.is_some();
+                }
+                let _ = tx.send(result);
+            }
+            progress.inc();
+        });
+    }
+
+    rx
+}
+
+/// A client builder carrying the probe user agent and extra headers, for
+/// anything that should look like the probe to the target.
+pub fn client_builder(options: &ProbeOptions) -> ClientBuilder {
+    let mut builder = proxy::apply(
+        Client::builder().default_headers(options.headers.clone()),
+        options.proxy.as_ref(),
+    );
+    if let Some(ref ua) = options.user_agent {
+        builder = builder.user_agent(ua);
+    }
+    if options.ip_version != IpVersion::Any && options.proxy.is_none() {
+        builder = builder.dns_resolver(Arc::new(FamilyResolver(options.ip_version)));
+    }
+    builder
+}
+
+/// System DNS narrowed to one address family, so connections only go
+/// over that one.
+struct FamilyResolver(IpVersion);
+
+impl Resolve for FamilyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let version = self.0;
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|a| version.allows(&a.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(
+                    format!("{} has no IPv{} address", name.as_str(), version.name()).into(),
+                );
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Ports usually serving TLS; others are tried over plain HTTP first.
+const TLS_PORTS: &[u16] = &[443, 4443, 8443, 9443, 10443];
+
+/// `(port, schemes to try in order)` per probe. No ports means the default
+/// port of each scheme.
+fn attempts(ports: &[u16]) -> Vec<(Option<u16>, [&'static str; 2])> {
+    if ports.is_empty() {
+        return vec![(None, ["https", "http"])];
+    }
+    ports
+        .iter()
+        .map(|&p| {
+            let schemes = if TLS_PORTS.contains(&p) {
+                ["https", "http"]
+            } else {
+                ["http", "https"]
+            };
+            (Some(p), schemes)
+        })
+        .collect()
+}
+
+/// `probe_error` of hosts left unprobed once `--max-requests` or
+/// `--max-bandwidth` ran out.
+pub const BUDGET_EXHAUSTED: &str = "not probed: the HTTP budget ran out";
+
+/// `probe_error` of hosts left unprobed once the probe phase's deadline
+/// passed.
+pub const DEADLINE_PASSED: &str = "not probed: the deadline passed";
+
+/// Redirects followed per probe.
+pub const MAX_REDIRECTS: usize = 3;
+
+/// Requests `url` the way `method` says, following up to `max` redirects.
+/// Returns the method of the last response along with what
+/// [`request_following`] does.
+async fn fetch(
+    client: &Client,
+    limits: &RateLimits,
+    method: &ProbeMethod,
+    url: &str,
+    max: usize,
+) -> reqwest::Result<(Method, Response, Vec<String>)> {
+    let first = match method {
+        ProbeMethod::Get => Method::GET,
+        ProbeMethod::HeadFirst => Method::HEAD,
+        ProbeMethod::Other(m) => m.clone(),
+    };
+    let (resp, chain) = request_following(client, limits, first.clone(), url, max).await?;
+    if first == Method::HEAD && HEAD_REJECTED.contains(&resp.status().as_u16()) {
+        let (resp, chain) = get_following(client, limits, url, max).await?;
+        return Ok((Method::GET, resp, chain));
+    }
+    Ok((first, resp, chain))
+}
+
+/// GETs `url`, following up to `max` redirects. Returns the last response
+/// and every URL requested before it.
+async fn get_following(
+    client: &Client,
+    limits: &RateLimits,
+    url: &str,
+    max: usize,
+) -> reqwest::Result<(Response, Vec<String>)> {
+    request_following(client, limits, Method::GET, url, max).await
+}
+
+/// Like [`get_following`] with any method, which redirects keep.
+pub(crate) async fn request_following(
+    client: &Client,
+    limits: &RateLimits,
+    method: Method,
+    url: &str,
+    max: usize,
+) -> reqwest::Result<(Response, Vec<String>)> {
+    let mut chain = Vec::new();
+    let mut current = url.to_string();
+    loop {
+        let req = client.request(method.clone(), &current).build()?;
+        limits.wait(req.url().host_str().unwrap_or_default()).await;
+        limits.count_request();
+        let resp = client.execute(req).await?;
+        limits.count_bytes(head_size(&resp));
+        // An exhausted budget stops at the redirect
+        if resp.status().is_redirection() && chain.len() < max && !limits.exhausted() {
+            let next = location(&resp).and_then(|l| resp.url().join(l).ok());
+            if let Some(next) = next {
+                chain.push(current);
+                current = next.to_string();
+                continue;
+            }
+        }
+        return Ok((resp, chain));
+    }
+}
+
+/// Roughly how many bytes the status line and headers of `resp` took.
+pub(crate) fn head_size(resp: &Response) -> u64 {
+    let headers: usize = resp
+        .headers()
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + 4)
+        .sum();
+    (headers + 17) as u64
+}
+
+/// Reads at most `max` bytes of the body, chunk by chunk, so huge or
+/// endless responses don't pin memory or a worker. The flag is false when
+/// the body was cut short. What is read counts against the budget.
+pub(crate) async fn read_body(
+    mut resp: Response,
+    limits: &RateLimits,
+    max: usize,
+) -> Option<(Vec<u8>, bool)> {
+    let mut body = Vec::new();
+    let mut complete = true;
+    while let Some(chunk) = resp.chunk().await.ok()? {
+        limits.count_bytes(chunk.len() as u64);
+        let room = max - body.len();
+        if chunk.len() >= room {
+            body.extend_from_slice(&chunk[..room]);
+            complete = chunk.len() == room && resp.chunk().await.ok()?.is_none();
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Some((body, complete))
+}
+
+/// First match of each named group across `patterns`.
+fn extract_fields(patterns: &[Regex], body: &str) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    for re in patterns {
+        let Some(caps) = re.captures(body) else {
+            continue;
+        };
+        for name in re.capture_names().flatten() {
+            if let Some(m) = caps.name(name) {
+                fields
+                    .entry(name.to_string())
+                    .or_insert_with(|| m.as_str().trim().to_string());
+            }
+        }
+    }
+    fields
+}
+
+/// The kind of the I/O error underneath `e`, if there is one.
+fn io_error_kind(e: &reqwest::Error) -> Option<ErrorKind> {
+    let mut source = std::error::Error::source(e);
+    while let Some(err) = source {
+        if let Some(io) = err.downcast_ref::<std::io::Error>() {
+            return Some(io.kind());
+        }
+        source = err.source();
+    }
+    None
+}
+
+/// The connection was actively refused rather than timing out.
+fn is_refused(e: &reqwest::Error) -> bool {
+    io_error_kind(e) == Some(ErrorKind::ConnectionRefused)
+}
+
+/// The connection was dropped halfway. Unlike timeouts, which silent hosts
+/// produce whatever the load, a spike of these means the link or the
+/// targets are overloaded.
+fn is_reset(e: &reqwest::Error) -> bool {
+    matches!(
+        io_error_kind(e),
+        Some(ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe)
+    )
+}
+
+/// A short reason a request got no response.
+fn describe(e: &reqwest::Error) -> String {
+    if e.is_timeout() {
+        return "timed out".to_string();
+    }
+    if is_refused(e) {
+        return "connection refused".to_string();
+    }
+    // The innermost cause names the actual problem, e.g. a certificate or
+    // DNS error, where the outer ones only repeat the URL
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(err) = source {
+        message = err.to_string();
+        source = err.source();
+    }
+    message
+}
+
+/// Timeouts and dropped connections, which may go away; refusals and TLS
+/// or protocol errors won't.
+fn is_transient(e: &reqwest::Error) -> bool {
+    e.is_timeout()
+        || matches!(
+            io_error_kind(e),
+            Some(
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::UnexpectedEof
+                    | ErrorKind::TimedOut
+            )
+        )
+}
+
+fn http_version(version: Version) -> Option<String> {
+    let name = match version {
+        Version::HTTP_09 => "http/0.9",
+        Version::HTTP_10 => "http/1.0",
+        Version::HTTP_11 => "http/1.1",
+        Version::HTTP_2 => "h2",
+        Version::HTTP_3 => "h3",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Scheme and realm of the first challenge in a `WWW-Authenticate` value,
+/// e.g. `Basic` and `Jenkins` for `Basic realm="Jenkins", charset="UTF-8"`.
+fn auth_challenge(value: &str) -> Option<(String, Option<String>)> {
+    let value = value.trim();
+    let (scheme, params) = value.split_once(char::is_whitespace).unwrap_or((value, ""));
+    let scheme = scheme.trim_end_matches(',');
+    if scheme.is_empty() {
+        return None;
+    }
+    let realm = auth_params(params)
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("realm"))
+        .map(|(_, value)| value);
+    Some((scheme.to_string(), realm.filter(|r| !r.is_empty())))
+}
+
+/// The `name=value` parameters at the start of `params`, up to the next
+/// challenge. Names may have whitespace around `=` and values may be
+/// quoted strings with `\` escapes (RFC 7235).
+fn auth_params(params: &str) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    let mut chars = params.chars().peekable();
+    loop {
+        while chars.next_if(|c| *c == ',' || c.is_whitespace()).is_some() {}
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=' && *c != ',' && !c.is_whitespace()) {
+            name.push(c);
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        // A name without `=` is the scheme of the next challenge
+        if name.is_empty() || chars.next_if_eq(&'=').is_none() {
+            return found;
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => value.extend(chars.next()),
+                    c => value.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',' && !c.is_whitespace()) {
+                value.push(c);
+            }
+        }
+        found.push((name, value));
+    }
+}
+
+fn location(resp: &Response) -> Option<&str> {
+    resp.headers().get(LOCATION).and_then(|v| v.to_str().ok())
+}
+
+async fn favicon_hash(
+    client: &Client,
+    limits: &RateLimits,
+    base: &str,
+    max_body: usize,
+) -> Option<i32> {
+    let url = format!("{}/favicon.ico", base);
+    let (resp, _) = get_following(client, limits, &url, MAX_REDIRECTS)
+        .await
+        .ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    // Error pages served with 200 aren't icons
+    let is_html = resp
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/html"));
+    if is_html {
+        return None;
+    }
+    // A cut-off icon would hash to something no one else sees
+    let (bytes, complete) = read_body(resp, limits, max_body).await?;
+    if !complete || bytes.is_empty() {
+        return None;
+    }
+    Some(favicon::shodan_hash(&bytes))
+}
+
+/// Statuses of the `paths` under `base` that didn't answer 404, redirects
+/// not followed. On hosts answering a random path with something else,
+/// only paths answering differently count, so catch-all routes don't
+/// report every path.
+async fn check_paths(
+    client: &Client,
+    limits: &RateLimits,
+    base: &str,
+    paths: &[String],
+) -> BTreeMap<String, u16> {
+    let random = format!("{}/{}", base, wildcard::random_label());
+    let baseline = path_status(client, limits, &random)
+        .await
+        .filter(|&s| s != 404);
+    let mut found = BTreeMap::new();
+    for path in paths {
+        let Some(status) = path_status(client, limits, &format!("{}{}", base, path)).await else {
+            continue;
+        };
+        if status != 404 && Some(status) != baseline {
+            found.insert(path.clone(), status);
+        }
+    }
+    found
+}
+
+/// [`catch_all`] verdicts by address and body hash, so the hosts of one
+/// shared server cost one extra request between them, even when probed at
+/// the same time.
+#[derive(Default)]
+struct CatchAlls(Mutex<HashMap<PageKey, Arc<OnceCell<bool>>>>);
+
+/// An address, and the hash of the page a host there answered with.
+type PageKey = (String, String);
+
+impl CatchAlls {
+    fn cell(&self, key: PageKey) -> Arc<OnceCell<bool>> {
+        let mut seen = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        seen.entry(key).or_default().clone()
+    }
+}
+
+/// Whether a random path next to `page` answers 200 with the same body,
+/// by [`wildcard::body_hash`].
+async fn catch_all(
+    client: &Client,
+    limits: &RateLimits,
+    page: &str,
+    max_body: usize,
+    hash: &str,
+) -> bool {
+    let Ok(random) =
+        Url::parse(page).and_then(|u| u.join(&format!("/{}", wildcard::random_label())))
+    else {
+        return false;
+    };
+    let Ok((resp, _)) = get_following(client, limits, random.as_str(), 0).await else {
+        return false;
+    };
+    if resp.status() != StatusCode::OK {
+        return false;
+    }
+    let content_type = resp
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let Some((bytes, _)) = read_body(resp, limits, max_body).await else {
+        return false;
+    };
+    let text = title::decode_body(&bytes, content_type.as_deref());
+    wildcard::body_hash(&text).is_some_and(|h| h == hash)
+}
+
+async fn path_status(client: &Client, limits: &RateLimits, url: &str) -> Option<u16> {
+    let (resp, _) = get_following(client, limits, url, 0).await.ok()?;
+    Some(resp.status().as_u16())
+}
+
+/// The interesting disallowed paths of `base`'s `robots.txt`, and the
+/// hostnames under `domain` it and the sitemaps mention. Sitemaps on other
+/// sites aren't fetched.
+async fn crawl_hints(
+    client: &Client,
+    limits: &RateLimits,
+    base: &str,
+    max_body: usize,
+    domain: Option<&str>,
+) -> (Vec<String>, HashSet<String>) {
+    let mut hosts = HashSet::new();
+    let mut scrape = |text: &str| {
+        if let Some(domain) = domain {
+            hosts.extend(sources::scrape_hosts(text, domain));
+        }
+    };
+    let mut sitemaps = vec![format!("{}/sitemap.xml", base)];
+    let mut paths = Vec::new();
+    if let Some(text) = fetch_text(client, limits, &format!("{}/robots.txt", base), max_body).await
+    {
+        let parsed = robots::parse(&text);
+        paths = robots::interesting(&parsed.disallowed);
+        sitemaps.extend(parsed.sitemaps);
+        scrape(&text);
+    }
+
+    let base_host = Url::parse(base)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string));
+    let fetchable = |url: &str| {
+        let Some(host) = Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+        else {
+            return false;
+        };
+        Some(&host) == base_host.as_ref()
+            || domain.is_some_and(|d| host == d || host.ends_with(&format!(".{}", d)))
+    };
+    let mut fetched = HashSet::new();
+    while let Some(url) = sitemaps.pop() {
+        if fetched.len() >= robots::MAX_SITEMAPS {
+            break;
+        }
+        if !fetchable(&url) || !fetched.insert(url.clone()) {
+            continue;
+        }
+        let Some(xml) = fetch_text(client, limits, &url, max_body).await else {
+            continue;
+        };
+        sitemaps.extend(
+            robots::sitemap_locations(&xml)
+                .into_iter()
+                .filter(|loc| robots::is_sitemap(loc)),
+        );
+        scrape(&xml);
+    }
+    (paths, hosts)
+}
+
+/// The body of a successful non-HTML response to `url`. Sites that answer
+/// everything with their front page don't have the file.
+async fn fetch_text(
+    client: &Client,
+    limits: &RateLimits,
+    url: &str,
+    max_body: usize,
+) -> Option<String> {
+    let (resp, _) = get_following(client, limits, url, MAX_REDIRECTS)
+        .await
+        .ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    let content_type = resp
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    if content_type
+        .as_deref()
+        .is_some_and(|t| t.contains("text/html"))
+    {
+        return None;
+    }
+    let (bytes, _) = read_body(resp, limits, max_body).await?;
+    Some(title::decode_body(&bytes, content_type.as_deref()))
+}
+
+/// Hostnames under `domain` mentioned by a response, other than the host
+/// itself. Wildcard SANs contribute their base name.
+fn leads(result: &SubdomainResult, csp: Option<&str>, body: &str, domain: &str) -> Vec<String> {
+    let mut found = sources::scrape_hosts(body, domain);
+    if let Some(csp) = csp {
+        found.extend(sources::scrape_hosts(csp, domain));
+    }
+    if let Some(ref cert) = result.tls {
+        let sans = cert
+            .sans
+            .iter()
+            .map(|n| n.trim_start_matches("*.").to_string())
+            .collect();
+        found.extend(sources::filter_subs(sans, domain));
+    }
+    found.remove(&result.subdomain);
+    found.into_iter().collect()
+}
+
+fn dual_stack(ips: &[String]) -> bool {
+    let families: HashSet<bool> = ips
+        .iter()
+        .filter_map(|ip| ip.parse::<IpAddr>().ok())
+        .map(|ip| ip.is_ipv4())
+        .collect();
+    families.len() == 2
+}
+
+pub(crate) fn unprobed(host: &ResolvedHost, port: Option<u16>) -> SubdomainResult {
+    SubdomainResult {
+        subdomain: host.subdomain.clone(),
+        apex: None,
+        source: None,
+        sources: Vec::new(),
+        ips: host.ips.clone(),
+        dual_stack: dual_stack(&host.ips),
+        cname_chain: host.cname_chain.clone(),
+        resolver: host.resolver.clone(),
+        dns_latency_ms: host.latency_ms,
+        dns_ttl: host.ttl,
+        port,
+        scheme: None,
+        http_version: None,
+        address_family: None,
+        method: None,
+        final_url: None,
+        redirect_chain: Vec::new(),
+        location: None,
+        response_time_ms: None,
+        slow: false,
+        retried: host.retried,
+        probe_error: None,
+        headers: BTreeMap::new(),
+        extracted: BTreeMap::new(),
+        body_matches: Vec::new(),
+        cdn: cdn::detect(&host.ips, &host.cname_chain, None),
+        provider: cloud::provider(&host.ips, &host.cname_chain),
+        status_code: None,
+        title: None,
+        server: None,
+        content_length: None,
+        body_hash: None,
+        auth_scheme: None,
+        auth_realm: None,
+        takeover: None,
+        dangling: false,
+        dangling_evidence: None,
+        waf_challenge: None,
+        suspect_honeypot: None,
+        technologies: Vec::new(),
+        tags: Vec::new(),
+        favicon_hash: None,
+        disallowed_paths: Vec::new(),
+        paths: BTreeMap::new(),
+        tls: None,
+        jarm: None,
+        zone_transfer: None,
+        vhost: false,
+        open_ports: Vec::new(),
+        banners: BTreeMap::new(),
+        ptr: Vec::new(),
+        dns_records: None,
+        dnssec: None,
+        asn: None,
+        as_org: None,
+        netblock: None,
+        netblock_owner: None,
+        country: None,
+        city: None,
+        shodan_ports: None,
+        partial: false,
+        screenshot: None,
+        seen_in: Vec::new(),
+        wildcard_similarity: None,
+        filtered_reason: None,
+        wildcard_zone: None,
+        body_simhash: None,
+        leads: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn realm(value: &str) -> Option<String> {
+        auth_challenge(value).and_then(|(_, realm)| realm)
+    }
+
+    #[test]
+    fn realm_names_ignore_case_and_spacing() {
+        assert_eq!(
+            realm(r#"Basic Realm="Jenkins""#).as_deref(),
+            Some("Jenkins")
+        );
+        assert_eq!(realm(r#"Basic realm = "x y""#).as_deref(), Some("x y"));
+        assert_eq!(
+            realm("Basic realm=plain, charset=UTF-8").as_deref(),
+            Some("plain")
+        );
+    }
+
+    #[test]
+    fn realm_inside_another_parameter_is_not_the_realm() {
+        let value = r#"Digest nonce="realm=fake", realm="real", qop="auth""#;
+        assert_eq!(realm(value).as_deref(), Some("real"));
+        assert_eq!(realm(r#"Digest opaque="a\"realm=b""#), None);
+    }
+
+    #[test]
+    fn quoted_realms_unescape() {
+        assert_eq!(realm(r#"Basic realm="a\"b""#).as_deref(), Some("a\"b"));
+    }
+
+    #[test]
+    fn parameters_stop_at_the_next_challenge() {
+        let (scheme, realm) = auth_challenge(r#"Bearer, Basic realm="later""#).unwrap();
+        assert_eq!(scheme, "Bearer");
+        assert_eq!(realm, None);
+    }
+}
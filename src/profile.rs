@@ -0,0 +1,130 @@
+//! `--profile` presets: sources, active steps, concurrency and rate limits
+//! chosen together for one kind of engagement. A profile replaces what the
+//! config file set for these; flags on the command line still win.
+
+use crate::banner::BannerScope;
+use crate::ratelimit::Rate;
+use crate::wordlists::Builtin;
+use crate::ScanOptions;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Profile {
+    /// Passive sources and `--input` only: no DNS query and no HTTP request
+    /// goes to the target, for OSINT-only engagements.
+    Passive,
+    /// Resolves and probes what passive sources know, slowly and on few
+    /// connections, without brute force, zone transfers or port scans.
+    Stealth,
+    /// The defaults.
+    #[default]
+    Normal,
+    /// Every discovery step, at high concurrency and without rate limits.
+    Aggressive,
+}
+
+impl Profile {
+    pub const NAMES: &'static [&'static str] = &["passive", "stealth", "normal", "aggressive"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Profile::Passive => "passive",
+            Profile::Stealth => "stealth",
+            Profile::Normal => "normal",
+            Profile::Aggressive => "aggressive",
+        }
+    }
+
+    pub fn apply(self, o: &mut ScanOptions) {
+        match self {
+            Profile::Passive => {
+                o.passive = true;
+                o.feedback = false;
+                o.check_resolvers = false;
+                o.dangling = false;
+                o.records.clear();
+                o.ptr = false;
+                o.dnssec = false;
+                o.screenshots = None;
+                // Nothing resolves, so there are no addresses to enrich
+                o.asn = false;
+                o.rdap = false;
+                o.reverse_ip = false;
+                o.shodan_ports = false;
+                o.geoip_db = None;
+                o.find_related = false;
+                quiet(o);
+            }
+            Profile::Stealth => {
+                quiet(o);
+                o.dns_concurrency = 20;
+                o.adaptive_concurrency = false;
+                o.probe.concurrency = 5;
+                o.probe.both_schemes = false;
+                o.rate = Some(Rate { per_second: 5.0 });
+                o.per_host_rate = Some(Rate { per_second: 0.5 });
+            }
+            Profile::Normal => {}
+            Profile::Aggressive => {
+                if o.wordlist.is_none() {
+                    o.wordlist_builtin = Some(Builtin::Medium);
+                }
+                o.permute = true;
+                o.markov = 500;
+                o.recursion_depth = o.recursion_depth.max(1);
+                o.axfr = true;
+                o.nsec_walk = true;
+                o.dangling = true;
+                o.vhosts = true;
+                o.crtsh_postgres = true;
+                o.banners = BannerScope::All;
+                o.dns_concurrency = 1000;
+                o.probe.concurrency = 200;
+                o.probe.both_schemes = true;
+                o.probe.robots = true;
+                o.probe.favicon = true;
+                o.retries = 2;
+                o.rate = None;
+                o.per_host_rate = None;
+            }
+        }
+    }
+}
+
+/// Turns off the steps that stand out in the target's logs: guessed
+/// names, zone transfers and walks, virtual host and port scans, service
+/// banners and requests beyond the page itself.
+fn quiet(o: &mut ScanOptions) {
+    o.wordlist = None;
+    o.wordlist_builtin = None;
+    o.permute = false;
+    o.markov = 0;
+    o.recursion_depth = 0;
+    o.axfr = false;
+    o.nsec_walk = false;
+    o.vhosts = false;
+    o.scan_ports.clear();
+    o.banners = BannerScope::Off;
+    o.probe.favicon = false;
+    o.probe.robots = false;
+    o.probe.jarm = false;
+    o.probe.paths.clear();
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "passive" => Ok(Profile::Passive),
+            "stealth" => Ok(Profile::Stealth),
+            "normal" => Ok(Profile::Normal),
+            "aggressive" => Ok(Profile::Aggressive),
+            _ => Err(format!(
+                "Unknown profile: {} (known: {})",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! A one-line progress indicator on stderr for the DNS and HTTP phases.
+
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The bar currently on screen, redrawn under every log line.
+static LINE: Mutex<Option<String>> = Mutex::new(None);
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Turns progress bars on, if stderr is a terminal. Off by default so
+/// library users and redirected output never see them.
+pub fn enable(on: bool) {
+    ENABLED.store(on && std::io::stderr().is_terminal(), Ordering::Relaxed);
+}
+
+/// Writes `text` to stderr without tearing an active bar.
+pub(crate) fn print_above(text: &str) {
+    let line = LINE.lock().unwrap_or_else(|e| e.into_inner());
+    let mut err = std::io::stderr().lock();
+    match line.as_deref() {
+        Some(bar) => {
+            let _ = write!(err, "\r\x1b[2K{}\n{}", text, bar);
+        }
+        None => {
+            let _ = writeln!(err, "{}", text);
+        }
+    }
+    let _ = err.flush();
+}
+
+/// Completed/total and rate of one phase, cleared when dropped.
+#[derive(Debug)]
+pub struct Progress {
+    label: &'static str,
+    /// 0 when the total isn't known up front (wordlists are streamed).
+    total: u64,
+    done: AtomicU64,
+    started: Instant,
+    last_draw: Mutex<Instant>,
+}
+
+impl Progress {
+    pub fn new(label: &'static str, total: u64) -> Self {
+        Self {
+            label,
+            total,
+            done: AtomicU64::new(0),
+            started: Instant::now(),
+            last_draw: Mutex::new(Instant::now() - REDRAW_INTERVAL),
+        }
+    }
+
+    pub fn inc(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if !ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+        {
+            let Ok(mut last) = self.last_draw.try_lock() else {
+                return;
+            };
+            if last.elapsed() < REDRAW_INTERVAL && done != self.total {
+                return;
+            }
+            *last = Instant::now();
+        }
+        self.draw(done);
+    }
+
+    fn draw(&self, done: u64) {
+        let secs = self.started.elapsed().as_secs_f64().max(0.001);
+        let rate = done as f64 / secs;
+        let bar = if self.total > 0 {
+            format!(
+                "[{}] {}/{} ({}%) {:.0}/s",
+                self.label,
+                done,
+                self.total,
+                done * 100 / self.total,
+                rate
+            )
+        } else {
+            format!("[{}] {} done {:.0}/s", self.label, done, rate)
+        };
+        let mut line = LINE.lock().unwrap_or_else(|e| e.into_inner());
+        let mut err = std::io::stderr().lock();
+        let _ = write!(err, "\r\x1b[2K{}", bar);
+        let _ = err.flush();
+        *line = Some(bar);
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut line = LINE.lock().unwrap_or_else(|e| e.into_inner());
+        if line.take().is_some() {
+            let mut err = std::io::stderr().lock();
+            let _ = write!(err, "\r\x1b[2K");
+            let _ = err.flush();
+        }
+    }
+}
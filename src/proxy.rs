@@ -0,0 +1,375 @@
+//! Routing traffic through an HTTP or SOCKS5 proxy.
+//!
+//! Clients only ever see an HTTP proxy. A SOCKS5 proxy is fronted by a
+//! small local bridge that accepts HTTP proxy requests and forwards each
+//! connection over SOCKS, so Tor or an SSH `-D` pivot works without
+//! reqwest's optional SOCKS support. The bridge only serves requests
+//! carrying a random per-process password, as its URL (which clients get)
+//! has it, so other local users can't ride the tunnel.
+
+use crate::Result;
+use base64::Engine;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use reqwest::{ClientBuilder, Proxy, Url};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Largest request head the bridge and CONNECT handshakes accept.
+const MAX_HEAD: usize = 16 * 1024;
+
+/// User name in bridge URLs; the password is [`bridge_token`].
+const BRIDGE_USER: &str = "subpeek";
+
+/// Parses `--proxy` and returns the HTTP proxy clients should use. For
+/// `socks5://` (local DNS) and `socks5h://` (proxy-side DNS) this starts a
+/// bridge on 127.0.0.1 and returns its address; user and password in the
+/// URL are passed on to the SOCKS server.
+pub fn resolve(raw: &str) -> Result<Url> {
+    let url = Url::parse(raw).map_err(|e| format!("invalid proxy '{}': {}", raw, e))?;
+    if url.host_str().is_none() {
+        return Err(format!("invalid proxy '{}': missing host", raw).into());
+    }
+    match url.scheme() {
+        "http" | "https" => Ok(url),
+        "socks5" | "socks5h" => bridge(url),
+        other => Err(format!("unsupported proxy scheme '{}'", other).into()),
+    }
+}
+
+/// A SOCKS5 proxy from `ALL_PROXY`, `HTTPS_PROXY` or `HTTP_PROXY` (either
+/// case). reqwest picks up HTTP proxies from the environment by itself but
+/// would silently ignore SOCKS ones and connect directly.
+pub fn socks_from_env() -> Option<String> {
+    ["ALL_PROXY", "HTTPS_PROXY", "HTTP_PROXY"]
+        .iter()
+        .flat_map(|v| [v.to_string(), v.to_lowercase()])
+        .filter_map(|v| std::env::var(v).ok())
+        .find(|v| v.starts_with("socks5://") || v.starts_with("socks5h://"))
+}
+
+/// Sends every request from `builder` through `proxy`, if any, with the
+/// user and password in its URL.
+pub fn apply(builder: ClientBuilder, proxy: Option<&Url>) -> ClientBuilder {
+    let Some(url) = proxy else {
+        return builder;
+    };
+    match Proxy::all(url.as_str()) {
+        Ok(p) if !url.username().is_empty() => {
+            builder.proxy(p.basic_auth(url.username(), url.password().unwrap_or("")))
+        }
+        Ok(p) => builder.proxy(p),
+        Err(_) => builder,
+    }
+}
+
+/// The `--proxy-server` to hand Chrome, which can't authenticate to a
+/// bridge: the SOCKS server behind it (Chrome resolves names there), or
+/// `proxy` itself.
+pub fn for_browser(proxy: &Url) -> String {
+    let bridges = bridges();
+    let socks = bridges
+        .iter()
+        .find(|(_, local)| *local == proxy)
+        .and_then(|(socks, _)| Url::parse(socks).ok());
+    match socks {
+        Some(socks) => format!(
+            "socks5://{}:{}",
+            socks.host_str().unwrap_or_default(),
+            socks.port().unwrap_or(1080)
+        ),
+        None => proxy.to_string(),
+    }
+}
+
+/// A TCP connection to `host:port`, tunnelled with `CONNECT` when a proxy
+/// is set. For raw protocols outside reqwest.
+pub async fn connect(proxy: Option<&Url>, host: &str, port: u16) -> Result<TcpStream> {
+    let Some(proxy) = proxy else {
+        return Ok(TcpStream::connect((host, port)).await?);
+    };
+    if proxy.scheme() != "http" {
+        return Err(format!("cannot tunnel raw TCP through a {} proxy", proxy.scheme()).into());
+    }
+    let addr = (
+        proxy.host_str().unwrap_or_default(),
+        proxy.port_or_known_default().unwrap_or(8080),
+    );
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+    if !proxy.username().is_empty() {
+        let credentials = format!("{}:{}", proxy.username(), proxy.password().unwrap_or(""));
+        request.push_str(&format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            base64::engine::general_purpose::STANDARD.encode(credentials)
+        ));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+    let (head, _) = read_head(&mut stream).await?;
+    let status = head.split_whitespace().nth(1).unwrap_or_default();
+    if status != "200" {
+        return Err(format!("proxy refused CONNECT to {}:{} ({})", host, port, status).into());
+    }
+    Ok(stream)
+}
+
+/// Bridge URLs by the SOCKS URL they front.
+fn bridges() -> MutexGuard<'static, HashMap<String, Url>> {
+    static BRIDGES: OnceLock<Mutex<HashMap<String, Url>>> = OnceLock::new();
+    BRIDGES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// The password of every bridge of this process.
+fn bridge_token() -> &'static str {
+    static TOKEN: OnceLock<String> = OnceLock::new();
+    TOKEN.get_or_init(|| {
+        rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect()
+    })
+}
+
+/// Starts (once per SOCKS URL) a bridge thread and returns its HTTP URL.
+fn bridge(socks: Url) -> Result<Url> {
+    let mut bridges = bridges();
+    if let Some(url) = bridges.get(socks.as_str()) {
+        return Ok(url.clone());
+    }
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    listener.set_nonblocking(true)?;
+    let local = Url::parse(&format!(
+        "http://{}:{}@{}",
+        BRIDGE_USER,
+        bridge_token(),
+        listener.local_addr()?
+    ))?;
+    let upstream = socks.clone();
+    // Its own runtime, so the bridge outlives whichever runtime (or none)
+    // the caller is on
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .expect("proxy bridge runtime");
+        runtime.block_on(async move {
+            let listener = TcpListener::from_std(listener).expect("proxy bridge listener");
+            while let Ok((client, _)) = listener.accept().await {
+                let upstream = upstream.clone();
+                tokio::spawn(async move {
+                    let _ = serve(client, &upstream).await;
+                });
+            }
+        });
+    });
+
+    bridges.insert(socks.to_string(), local.clone());
+    Ok(local)
+}
+
+/// One proxied connection: `CONNECT host:port` for HTTPS, or an
+/// absolute-form request for plain HTTP, rewritten to origin form.
+async fn serve(mut client: TcpStream, socks: &Url) -> Result<()> {
+    let (head, rest) = read_head(&mut client).await?;
+    if !authorized(&head) {
+        client
+            .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic realm=\"subpeek\"\r\n\r\n")
+            .await?;
+        return Err("proxy request without the bridge password".into());
+    }
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(m), Some(t), Some(v)) => (m, t, v),
+        _ => return Err("malformed proxy request".into()),
+    };
+
+    if method.eq_ignore_ascii_case("CONNECT") {
+        let (host, port) = target.rsplit_once(':').ok_or("CONNECT without port")?;
+        let port = port.parse().map_err(|_| "CONNECT with bad port")?;
+        let mut upstream = match socks_connect(socks, host.trim_matches(['[', ']']), port).await {
+            Ok(s) => s,
+            Err(e) => {
+                client
+                    .write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n")
+                    .await?;
+                return Err(e);
+            }
+        };
+        client
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await?;
+        upstream.write_all(&rest).await?;
+        tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+        return Ok(());
+    }
+
+    let url = Url::parse(target).map_err(|_| "proxy request without absolute URL")?;
+    let host = url.host_str().ok_or("proxy request without host")?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let mut upstream = match socks_connect(socks, host.trim_matches(['[', ']']), port).await {
+        Ok(s) => s,
+        Err(e) => {
+            client
+                .write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n")
+                .await?;
+            return Err(e);
+        }
+    };
+    let path = match url.query() {
+        Some(q) => format!("{}?{}", url.path(), q),
+        None => url.path().to_string(),
+    };
+    let mut forwarded = format!("{} {} {}\r\n", method, path, version);
+    for line in lines.filter(|l| !l.is_empty()) {
+        if !line.to_ascii_lowercase().starts_with("proxy-") {
+            forwarded.push_str(line);
+            forwarded.push_str("\r\n");
+        }
+    }
+    forwarded.push_str("\r\n");
+    upstream.write_all(forwarded.as_bytes()).await?;
+    upstream.write_all(&rest).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+/// Whether the request head carries the bridge's credentials.
+fn authorized(head: &str) -> bool {
+    let expected = base64::engine::general_purpose::STANDARD.encode(format!(
+        "{}:{}",
+        BRIDGE_USER,
+        bridge_token()
+    ));
+    head.split("\r\n").skip(1).any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("proxy-authorization")
+                && value.trim().split_once(' ').is_some_and(|(scheme, creds)| {
+                    scheme.eq_ignore_ascii_case("basic") && creds.trim() == expected
+                })
+        })
+    })
+}
+
+/// Reads up to the blank line ending a request or response head. Returns
+/// the head and whatever arrived after it.
+async fn read_head(stream: &mut TcpStream) -> Result<(String, Vec<u8>)> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 2048];
+    loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&buf[..end]).into_owned();
+            return Ok((head, buf[end + 4..].to_vec()));
+        }
+        if buf.len() > MAX_HEAD {
+            return Err("proxy head too large".into());
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err("connection closed during proxy handshake".into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// RFC 1928 CONNECT, with RFC 1929 username/password auth when the URL
+/// carries credentials. `socks5h` hands the name to the proxy to resolve.
+async fn socks_connect(socks: &Url, host: &str, port: u16) -> Result<TcpStream> {
+    let addr = (
+        socks.host_str().unwrap_or_default(),
+        socks.port().unwrap_or(1080),
+    );
+    let mut stream = TcpStream::connect(addr).await?;
+
+    let user = socks.username();
+    let pass = socks.password().unwrap_or("");
+    if user.len() > 255 || pass.len() > 255 {
+        return Err("SOCKS5 credentials too long".into());
+    }
+    if user.is_empty() {
+        stream.write_all(&[5, 1, 0]).await?;
+    } else {
+        stream.write_all(&[5, 2, 0, 2]).await?;
+    }
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    match reply {
+        [5, 0] => {}
+        [5, 2] if !user.is_empty() => {
+            let mut auth = vec![1, user.len() as u8];
+            auth.extend_from_slice(user.as_bytes());
+            auth.push(pass.len() as u8);
+            auth.extend_from_slice(pass.as_bytes());
+            stream.write_all(&auth).await?;
+            stream.read_exact(&mut reply).await?;
+            if reply[1] != 0 {
+                return Err("SOCKS5 authentication failed".into());
+            }
+        }
+        _ => return Err("SOCKS5 proxy accepted no offered auth method".into()),
+    }
+
+    let ip = match host.parse::<IpAddr>() {
+        Ok(ip) => Some(ip),
+        Err(_) if socks.scheme() == "socks5h" => None,
+        Err(_) => Some(
+            tokio::net::lookup_host((host, port))
+                .await?
+                .next()
+                .ok_or("no address for host")?
+                .ip(),
+        ),
+    };
+    let mut request = vec![5, 1, 0];
+    match ip {
+        Some(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Some(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        None => {
+            if host.len() > 255 {
+                return Err("hostname too long for SOCKS5".into());
+            }
+            request.push(3);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await?;
+    if head[1] != 0 {
+        return Err(format!(
+            "SOCKS5 connect to {}:{} failed (code {})",
+            host, port, head[1]
+        )
+        .into());
+    }
+    // Skip the bound address
+    let skip = match head[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await? as usize,
+        _ => return Err("SOCKS5 reply with unknown address type".into()),
+    };
+    let mut bound = vec![0u8; skip + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(stream)
+}
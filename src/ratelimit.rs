@@ -0,0 +1,220 @@
+//! Request rate limits for the DNS and HTTP phases, on top of the
+//! concurrency caps, and the request and bandwidth budget of the HTTP
+//! phase.
+
+use crate::deadline::Deadline;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A request rate such as `50/s`, `300/m` or `1000/h`. A bare number is
+/// per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    pub per_second: f64,
+}
+
+impl FromStr for Rate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (count, unit) = s.trim().split_once('/').unwrap_or((s.trim(), "s"));
+        let count: f64 = count
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid rate '{}'", s))?;
+        let seconds = match unit.trim() {
+            "s" | "sec" => 1.0,
+            "m" | "min" => 60.0,
+            "h" | "hour" => 3600.0,
+            _ => return Err(format!("invalid rate unit in '{}' (use /s, /m or /h)", s)),
+        };
+        if !(count > 0.0 && count.is_finite()) {
+            return Err(format!("rate must be positive: '{}'", s));
+        }
+        Ok(Self {
+            per_second: count / seconds,
+        })
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/s", self.per_second)
+    }
+}
+
+/// A token bucket holding a single token: callers are spaced evenly at the
+/// configured rate, with no bursts.
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: Rate) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / rate.per_second),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits for this caller's slot.
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+/// One [`RateLimiter`] per hostname, created on first use.
+#[derive(Debug)]
+pub struct HostRateLimiter {
+    rate: Rate,
+    hosts: Mutex<HashMap<String, Arc<RateLimiter>>>,
+}
+
+impl HostRateLimiter {
+    pub fn new(rate: Rate) -> Self {
+        Self {
+            rate,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn acquire(&self, host: &str) {
+        let limiter = self
+            .hosts
+            .lock()
+            .unwrap()
+            .entry(host.to_ascii_lowercase())
+            .or_insert_with(|| Arc::new(RateLimiter::new(self.rate)))
+            .clone();
+        limiter.acquire().await;
+    }
+}
+
+/// A byte count such as `100MB`, `1.5GB` or `512KiB`. `K`, `M` and `G`
+/// are powers of 1000, `KiB`, `MiB` and `GiB` of 1024; a bare number is
+/// bytes.
+pub fn parse_bytes(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (count, unit) = s.split_at(split);
+    let count: f64 = count.parse().map_err(|_| format!("invalid size '{}'", s))?;
+    let scale = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" => 1e3,
+        "m" | "mb" => 1e6,
+        "g" | "gb" => 1e9,
+        "kib" => 1024.0,
+        "mib" => 1024.0 * 1024.0,
+        "gib" => 1024.0 * 1024.0 * 1024.0,
+        _ => return Err(format!("invalid size unit in '{}' (use KB, MB or GB)", s)),
+    };
+    let bytes = count * scale;
+    if !(bytes >= 1.0 && bytes.is_finite()) {
+        return Err(format!("size must be positive: '{}'", s));
+    }
+    Ok(bytes as u64)
+}
+
+/// How many HTTP requests, and response bytes, a run may use. Once either
+/// runs out no new request is started; those in flight finish.
+#[derive(Debug, Default)]
+pub struct Budget {
+    max_requests: Option<u64>,
+    max_bytes: Option<u64>,
+    requests: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl Budget {
+    pub fn new(max_requests: Option<u64>, max_bytes: Option<u64>) -> Self {
+        Self {
+            max_requests,
+            max_bytes,
+            ..Self::default()
+        }
+    }
+
+    pub fn exhausted(&self) -> bool {
+        self.max_requests
+            .is_some_and(|max| self.requests.load(Ordering::Relaxed) >= max)
+            || self
+                .max_bytes
+                .is_some_and(|max| self.bytes.load(Ordering::Relaxed) >= max)
+    }
+
+    /// Starts over for another run, as `monitor` and `serve` scan again with
+    /// the same engine.
+    pub fn reset(&self) {
+        self.requests.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+    }
+
+    /// Requests sent and bytes received so far.
+    pub fn used(&self) -> (u64, u64) {
+        (
+            self.requests.load(Ordering::Relaxed),
+            self.bytes.load(Ordering::Relaxed),
+        )
+    }
+
+    fn spend(&self, requests: u64, bytes: u64) {
+        self.requests.fetch_add(requests, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+/// The limits an HTTP request waits on: the scan-wide one, shared with
+/// DNS, and the one for its host. The budget is shared by every request;
+/// the deadline is the probing phase's.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimits {
+    pub global: Option<Arc<RateLimiter>>,
+    pub per_host: Option<Arc<HostRateLimiter>>,
+    pub budget: Option<Arc<Budget>>,
+    pub deadline: Deadline,
+}
+
+impl RateLimits {
+    /// The budget has run out or the deadline passed, so no request should
+    /// be started.
+    pub fn exhausted(&self) -> bool {
+        self.budget.as_ref().is_some_and(|b| b.exhausted()) || self.deadline.passed()
+    }
+
+    /// Counts one request against the budget.
+    pub fn count_request(&self) {
+        if let Some(ref budget) = self.budget {
+            budget.spend(1, 0);
+        }
+    }
+
+    /// Counts received bytes against the budget.
+    pub fn count_bytes(&self, bytes: u64) {
+        if let Some(ref budget) = self.budget {
+            budget.spend(0, bytes);
+        }
+    }
+
+    pub async fn wait(&self, host: &str) {
+        if let Some(ref global) = self.global {
+            global.acquire().await;
+        }
+        if let Some(ref per_host) = self.per_host {
+            per_host.acquire(host).await;
+        }
+    }
+}
@@ -0,0 +1,474 @@
+//! A raw UDP resolution backend for `--dns-engine raw`, in the style of
+//! massdns: queries are written straight to a small pool of sockets and
+//! matched to their answers by transaction id, instead of going through a
+//! full stub resolver per lookup. Only plain UDP upstreams are supported.
+
+use crate::dns::{cname_chain, normalize_name};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::{oneshot, OnceCell};
+use tokio::task::JoinHandle;
+use tracing::debug;
+use trust_dns_resolver::proto::op::{Edns, Message, MessageType, Query, ResponseCode};
+use trust_dns_resolver::proto::rr::{Name, RData, Record, RecordType};
+
+/// Lookups in flight when `--dns-engine raw` is picked without
+/// `--dns-concurrency`: enough to keep a few resolvers busy at 50k/s.
+pub const RAW_DNS_CONCURRENCY: usize = 5000;
+
+/// Sockets per address family. Each carries up to 65536 queries at once.
+const SOCKETS: usize = 16;
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Attempts beyond the first even when `--retries` asks for fewer: at these
+/// rates some UDP loss is normal.
+const MIN_RETRIES: u32 = 2;
+
+/// Advertised EDNS payload size, the DNS flag day recommendation.
+const EDNS_PAYLOAD: u16 = 1232;
+
+/// What one lookup came to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawOutcome {
+    Resolved {
+        ips: Vec<IpAddr>,
+        cname_chain: Vec<String>,
+        /// Server that answered and how long it took.
+        server: SocketAddr,
+        latency: Duration,
+        /// Lowest TTL among the answer records.
+        ttl: Option<u32>,
+    },
+    /// NXDOMAIN, or an answer without addresses.
+    NoAddress,
+    /// Every attempt timed out or got SERVFAIL/REFUSED.
+    Failed,
+}
+
+struct Pending {
+    server: SocketAddr,
+    name: Name,
+    record_type: RecordType,
+    reply: oneshot::Sender<Message>,
+}
+
+impl Pending {
+    /// Whether `message`, which came from `from` with this query's id,
+    /// answers its question.
+    fn answered_by(&self, from: SocketAddr, message: &Message) -> bool {
+        self.server == from
+            && message.queries().first().is_some_and(|q| {
+                q.query_type() == self.record_type
+                    && normalize_name(&q.name().to_ascii()) == normalize_name(&self.name.to_ascii())
+            })
+    }
+}
+
+struct Socket {
+    socket: UdpSocket,
+    ipv6: bool,
+    pending: Mutex<HashMap<u16, Pending>>,
+}
+
+impl Socket {
+    fn pending(&self) -> std::sync::MutexGuard<'_, HashMap<u16, Pending>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+struct Sockets {
+    sockets: Vec<Arc<Socket>>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl Drop for Sockets {
+    fn drop(&mut self) {
+        for reader in &self.readers {
+            reader.abort();
+        }
+    }
+}
+
+/// Rotates queries across `servers` over a shared socket pool.
+pub struct RawResolver {
+    servers: Vec<SocketAddr>,
+    /// Bound on first use, so the resolver can be built outside a runtime.
+    sockets: OnceCell<Sockets>,
+    next: AtomicUsize,
+    /// Servers the health check took out of rotation.
+    evicted: Vec<AtomicBool>,
+}
+
+impl RawResolver {
+    pub fn new(servers: Vec<SocketAddr>) -> Self {
+        assert!(!servers.is_empty(), "raw resolver needs a server");
+        Self {
+            evicted: servers.iter().map(|_| AtomicBool::new(false)).collect(),
+            servers,
+            sockets: OnceCell::new(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn servers(&self) -> &[SocketAddr] {
+        &self.servers
+    }
+
+    /// Looks up the A and AAAA records of `name`, moving to the next server
+    /// after a timeout or server failure. Also returns how many attempts
+    /// were made beyond the first.
+    pub async fn lookup(&self, name: &str, retries: u32) -> (RawOutcome, u32) {
+        let Ok(qname) = Name::from_ascii(name) else {
+            return (RawOutcome::NoAddress, 0);
+        };
+        let Some(sockets) = self.sockets().await else {
+            return (RawOutcome::Failed, 0);
+        };
+        let retries = retries.max(MIN_RETRIES);
+        for attempt in 0..=retries {
+            let server = self.next_server();
+            let started = Instant::now();
+            let (a, aaaa) = tokio::join!(
+                query(sockets, server, &qname, RecordType::A),
+                query(sockets, server, &qname, RecordType::AAAA)
+            );
+            let answers: Vec<Message> = a.into_iter().chain(aaaa).collect();
+            let usable = |m: &&Message| {
+                matches!(
+                    m.response_code(),
+                    ResponseCode::NoError | ResponseCode::NXDomain
+                )
+            };
+            let records: Vec<Record> = answers
+                .iter()
+                .filter(usable)
+                .flat_map(|m| m.answers().iter().cloned())
+                .collect();
+            let mut ips: Vec<IpAddr> = records
+                .iter()
+                .filter_map(|r| match r.data() {
+                    Some(RData::A(a)) => Some(IpAddr::V4(a.0)),
+                    Some(RData::AAAA(aaaa)) => Some(IpAddr::V6(aaaa.0)),
+                    _ => None,
+                })
+                .collect();
+            if !ips.is_empty() {
+                ips.sort();
+                ips.dedup();
+                let outcome = RawOutcome::Resolved {
+                    ips,
+                    cname_chain: cname_chain(&records, name),
+                    server,
+                    latency: started.elapsed(),
+                    ttl: records.iter().map(|r| r.ttl()).min(),
+                };
+                return (outcome, attempt);
+            }
+            // An empty answer is only final once both families replied
+            // without a server failure
+            if answers
+                .iter()
+                .any(|m| m.response_code() == ResponseCode::NXDomain)
+                || (answers.len() == 2 && answers.iter().all(|m| usable(&m)))
+            {
+                return (RawOutcome::NoAddress, attempt);
+            }
+        }
+        (RawOutcome::Failed, retries)
+    }
+
+    /// Stops sending queries to the server written as `label`, e.g.
+    /// `1.1.1.1:53`.
+    pub fn evict(&self, label: &str) {
+        for (server, evicted) in self.servers.iter().zip(&self.evicted) {
+            if server.to_string() == label {
+                evicted.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn next_server(&self) -> SocketAddr {
+        let len = self.servers.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let i = (0..len)
+            .map(|k| (start + k) % len)
+            .find(|&i| !self.evicted[i].load(Ordering::Relaxed))
+            .unwrap_or(start % len);
+        self.servers[i]
+    }
+
+    async fn sockets(&self) -> Option<&Sockets> {
+        let servers = &self.servers;
+        self.sockets
+            .get_or_try_init(|| bind(servers))
+            .await
+            .map_err(|e| debug!("Could not open DNS sockets: {}", e))
+            .ok()
+    }
+}
+
+impl std::fmt::Debug for RawResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RawResolver")
+            .field("servers", &self.servers)
+            .finish()
+    }
+}
+
+/// Opens the sockets for each family the servers use, with a reader each.
+async fn bind(servers: &[SocketAddr]) -> std::io::Result<Sockets> {
+    let mut sockets = Vec::new();
+    let mut readers = Vec::new();
+    for (ipv6, local) in [(false, "0.0.0.0:0"), (true, "[::]:0")] {
+        if !servers.iter().any(|s| s.is_ipv6() == ipv6) {
+            continue;
+        }
+        for _ in 0..SOCKETS {
+            let socket = Arc::new(Socket {
+                socket: UdpSocket::bind(local).await?,
+                ipv6,
+                pending: Mutex::new(HashMap::new()),
+            });
+            readers.push(tokio::spawn(read_answers(socket.clone())));
+            sockets.push(socket);
+        }
+    }
+    Ok(Sockets { sockets, readers })
+}
+
+/// Hands every answer to the query waiting for it. Answers from the wrong
+/// address or for another question are dropped.
+async fn read_answers(socket: Arc<Socket>) {
+    let mut buf = vec![0u8; 65535];
+    loop {
+        let (len, from) = match socket.socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            // ICMP errors from earlier sends surface here on some systems
+            Err(_) => continue,
+        };
+        let Ok(message) = Message::from_vec(&buf[..len]) else {
+            continue;
+        };
+        if message.message_type() != MessageType::Response {
+            continue;
+        }
+        let mut pending = socket.pending();
+        let Some(waiting) = pending.get(&message.id()) else {
+            continue;
+        };
+        if !waiting.answered_by(from, &message) {
+            continue;
+        }
+        if let Some(waiting) = pending.remove(&message.id()) {
+            let _ = waiting.reply.send(message);
+        }
+    }
+}
+
+/// One question to one server. `None` on timeout or a send error.
+async fn query(
+    sockets: &Sockets,
+    server: SocketAddr,
+    name: &Name,
+    record_type: RecordType,
+) -> Option<Message> {
+    let family: Vec<&Arc<Socket>> = sockets
+        .sockets
+        .iter()
+        .filter(|s| s.ipv6 == server.is_ipv6())
+        .collect();
+    let socket = family[rand::random::<usize>() % family.len()];
+
+    let (tx, rx) = oneshot::channel();
+    let id = {
+        let mut pending = socket.pending();
+        // Random ids make blind spoofing harder; give up if the socket is
+        // somehow saturated
+        let id = (0..64)
+            .map(|_| rand::random::<u16>())
+            .find(|id| !pending.contains_key(id))?;
+        pending.insert(
+            id,
+            Pending {
+                server,
+                name: name.clone(),
+                record_type,
+                reply: tx,
+            },
+        );
+        id
+    };
+
+    let mut message = Message::new();
+    message
+        .set_id(id)
+        .set_recursion_desired(true)
+        .add_query(Query::query(name.clone(), record_type));
+    let mut edns = Edns::new();
+    edns.set_max_payload(EDNS_PAYLOAD);
+    message.set_edns(edns);
+
+    let sent = match message.to_vec() {
+        Ok(bytes) => socket.socket.send_to(&bytes, server).await.is_ok(),
+        Err(_) => false,
+    };
+    let answer = if sent {
+        tokio::time::timeout(QUERY_TIMEOUT, rx)
+            .await
+            .ok()
+            .and_then(|reply| reply.ok())
+    } else {
+        None
+    };
+    if answer.is_none() {
+        socket.pending().remove(&id);
+    }
+    answer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use trust_dns_resolver::proto::rr::rdata::{A, CNAME};
+
+    fn pending(server: SocketAddr, name: &str, record_type: RecordType) -> Pending {
+        Pending {
+            server,
+            name: Name::from_ascii(name).unwrap(),
+            record_type,
+            reply: oneshot::channel().0,
+        }
+    }
+
+    fn response(name: &str, record_type: RecordType) -> Message {
+        let mut message = Message::new();
+        message
+            .set_message_type(MessageType::Response)
+            .add_query(Query::query(Name::from_ascii(name).unwrap(), record_type));
+        message
+    }
+
+    #[test]
+    fn answers_match_server_type_and_name() {
+        let server: SocketAddr = "192.0.2.1:53".parse().unwrap();
+        let waiting = pending(server, "www.example.com", RecordType::A);
+
+        assert!(waiting.answered_by(server, &response("www.example.com.", RecordType::A)));
+        // Case and the trailing dot don't matter
+        assert!(waiting.answered_by(server, &response("WWW.Example.COM", RecordType::A)));
+
+        let other: SocketAddr = "192.0.2.2:53".parse().unwrap();
+        assert!(!waiting.answered_by(other, &response("www.example.com", RecordType::A)));
+        assert!(!waiting.answered_by(server, &response("www.example.com", RecordType::AAAA)));
+        assert!(!waiting.answered_by(server, &response("mail.example.com", RecordType::A)));
+        assert!(!waiting.answered_by(server, &Message::new()));
+    }
+
+    /// A UDP server on localhost answering each query with `answer`, or
+    /// not at all when that gives `None`.
+    async fn fake_server<F>(answer: F) -> SocketAddr
+    where
+        F: Fn(&Message) -> Option<Message> + Send + 'static,
+    {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; 4096];
+            while let Ok((len, from)) = socket.recv_from(&mut buf).await {
+                let Ok(query) = Message::from_vec(&buf[..len]) else {
+                    continue;
+                };
+                if let Some(mut reply) = answer(&query) {
+                    reply.set_id(query.id());
+                    let _ = socket.send_to(&reply.to_vec().unwrap(), from).await;
+                }
+            }
+        });
+        addr
+    }
+
+    /// Answers A queries for `name` through a CNAME, AAAA with nothing,
+    /// and everything else with NXDOMAIN.
+    fn zone(query: &Message) -> Option<Message> {
+        let q = query.queries().first()?;
+        let mut reply = response(&q.name().to_ascii(), q.query_type());
+        if normalize_name(&q.name().to_ascii()) != "www.example.com" {
+            reply.set_response_code(ResponseCode::NXDomain);
+        } else if q.query_type() == RecordType::A {
+            let target = Name::from_ascii("edge.example.net.").unwrap();
+            reply.add_answer(Record::from_rdata(
+                q.name().clone(),
+                300,
+                RData::CNAME(CNAME(target.clone())),
+            ));
+            reply.add_answer(Record::from_rdata(
+                target,
+                60,
+                RData::A(A(Ipv4Addr::new(192, 0, 2, 7))),
+            ));
+        }
+        Some(reply)
+    }
+
+    #[tokio::test]
+    async fn resolves_through_cnames() {
+        let server = fake_server(zone).await;
+        let resolver = RawResolver::new(vec![server]);
+        let (outcome, retried) = resolver.lookup("www.example.com", 0).await;
+        let RawOutcome::Resolved {
+            ips,
+            cname_chain,
+            server: answered,
+            ttl,
+            ..
+        } = outcome
+        else {
+            panic!("not resolved: {:?}", outcome);
+        };
+        assert_eq!(ips, [IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7))]);
+        assert_eq!(cname_chain, ["edge.example.net"]);
+        assert_eq!(answered, server);
+        assert_eq!(ttl, Some(60));
+        assert_eq!(retried, 0);
+    }
+
+    #[tokio::test]
+    async fn nxdomain_is_no_address() {
+        let server = fake_server(zone).await;
+        let resolver = RawResolver::new(vec![server]);
+        let (outcome, retried) = resolver.lookup("nope.example.com", 0).await;
+        assert_eq!(outcome, RawOutcome::NoAddress);
+        assert_eq!(retried, 0);
+    }
+
+    #[tokio::test]
+    async fn answers_to_other_questions_are_ignored() {
+        // Every reply names another host, so nothing may be taken from it
+        let liar = fake_server(|query| {
+            let q = query.queries().first()?;
+            let mut reply = response("evil.example.org", q.query_type());
+            reply.add_answer(Record::from_rdata(
+                Name::from_ascii("evil.example.org.").unwrap(),
+                60,
+                RData::A(A(Ipv4Addr::new(203, 0, 113, 1))),
+            ));
+            Some(reply)
+        })
+        .await;
+        let honest = fake_server(zone).await;
+        let resolver = RawResolver::new(vec![liar, honest]);
+        let (outcome, retried) = resolver.lookup("www.example.com", 0).await;
+        assert!(
+            matches!(outcome, RawOutcome::Resolved { server, .. } if server == honest),
+            "{:?}",
+            outcome
+        );
+        assert_eq!(retried, 1);
+    }
+}
@@ -0,0 +1,189 @@
+//! NS, MX and TXT collection for `--records`. SPF includes and mail and
+//! name servers often reveal more of the target's infrastructure, so their
+//! in-scope hostnames are fed back into discovery.
+
+use crate::dns::{normalize_name, ResolverPool};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use trust_dns_resolver::proto::rr::{RData, RecordType};
+
+/// A record type `--records` can gather.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    Ns,
+    Mx,
+    /// TXT records, SPF included.
+    Txt,
+}
+
+impl FromStr for RecordKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ns" => Ok(Self::Ns),
+            "mx" => Ok(Self::Mx),
+            "txt" | "spf" => Ok(Self::Txt),
+            other => Err(format!(
+                "unknown record type '{}' (expected ns, mx or txt)",
+                other
+            )),
+        }
+    }
+}
+
+/// A comma separated list such as `ns,mx,txt`.
+pub fn parse_kinds(spec: &str) -> Result<Vec<RecordKind>, String> {
+    let mut kinds = Vec::new();
+    for part in spec.split(',').filter(|p| !p.trim().is_empty()) {
+        let kind = part.parse()?;
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    if kinds.is_empty() {
+        return Err("--records needs at least one of ns, mx or txt".into());
+    }
+    Ok(kinds)
+}
+
+/// Extra records of one name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsRecords {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ns: Vec<String>,
+    /// Mail exchangers, most preferred first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mx: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub txt: Vec<String>,
+    /// Domains named by `include:` and `redirect=` in the SPF record.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spf_includes: Vec<String>,
+}
+
+impl DnsRecords {
+    pub fn is_empty(&self) -> bool {
+        self.ns.is_empty() && self.mx.is_empty() && self.txt.is_empty()
+    }
+
+    /// Every hostname the records point at.
+    pub fn hostnames(&self) -> impl Iterator<Item = &String> {
+        self.ns.iter().chain(&self.mx).chain(&self.spf_includes)
+    }
+}
+
+/// Looks up `kinds` for `name`.
+pub async fn lookup(resolver: &ResolverPool, name: &str, kinds: &[RecordKind]) -> DnsRecords {
+    let mut records = DnsRecords::default();
+    for kind in kinds {
+        match kind {
+            RecordKind::Ns => {
+                records.ns = resolver
+                    .records(name, RecordType::NS)
+                    .await
+                    .iter()
+                    .filter_map(|r| match r.data() {
+                        Some(RData::NS(ns)) => Some(normalize_name(&ns.0.to_ascii())),
+                        _ => None,
+                    })
+                    .collect();
+                records.ns.sort();
+                records.ns.dedup();
+            }
+            RecordKind::Mx => {
+                let mut mx: Vec<(u16, String)> = resolver
+                    .records(name, RecordType::MX)
+                    .await
+                    .iter()
+                    .filter_map(|r| match r.data() {
+                        Some(RData::MX(mx)) => {
+                            Some((mx.preference(), normalize_name(&mx.exchange().to_ascii())))
+                        }
+                        _ => None,
+                    })
+                    // A null MX (RFC 7505) says the name takes no mail
+                    .filter(|(_, host)| !host.is_empty())
+                    .collect();
+                mx.sort();
+                mx.dedup();
+                records.mx = mx.into_iter().map(|(_, host)| host).collect();
+            }
+            RecordKind::Txt => {
+                records.txt = resolver
+                    .records(name, RecordType::TXT)
+                    .await
+                    .iter()
+                    .filter_map(|r| match r.data() {
+                        // Long records are split into strings that belong
+                        // together
+                        Some(RData::TXT(txt)) => Some(
+                            txt.txt_data()
+                                .iter()
+                                .map(|part| String::from_utf8_lossy(part))
+                                .collect::<String>(),
+                        ),
+                        _ => None,
+                    })
+                    .collect();
+                records.txt.sort();
+                records.txt.dedup();
+                records.spf_includes = records
+                    .txt
+                    .iter()
+                    .flat_map(|txt| spf_includes(txt))
+                    .collect();
+            }
+        }
+    }
+    records
+}
+
+/// Looks up `kinds` for every name, keeping those that have any.
+pub async fn lookup_all(
+    resolver: Arc<ResolverPool>,
+    names: Vec<String>,
+    kinds: &[RecordKind],
+) -> HashMap<String, DnsRecords> {
+    let mut tasks = tokio::task::JoinSet::new();
+    for name in names {
+        let r = resolver.clone();
+        let kinds = kinds.to_vec();
+        tasks.spawn(async move {
+            let records = lookup(&r, &name, &kinds).await;
+            (name, records)
+        });
+    }
+    let mut found = HashMap::new();
+    while let Some(res) = tasks.join_next().await {
+        if let Ok((name, records)) = res {
+            if !records.is_empty() {
+                found.insert(name, records);
+            }
+        }
+    }
+    found
+}
+
+/// Domains an SPF record pulls in with `include:` or `redirect=`.
+pub fn spf_includes(txt: &str) -> Vec<String> {
+    let mut terms = txt.split_ascii_whitespace();
+    if !terms
+        .next()
+        .is_some_and(|v| v.eq_ignore_ascii_case("v=spf1"))
+    {
+        return Vec::new();
+    }
+    terms
+        .filter_map(|term| {
+            let term = term.trim_start_matches(['+', '-', '~', '?']);
+            let (key, value) = term.split_once([':', '='])?;
+            (key.eq_ignore_ascii_case("include") || key.eq_ignore_ascii_case("redirect"))
+                .then(|| normalize_name(value))
+        })
+        // Macros such as %{i}._spf.example.com can't be resolved as is
+        .filter(|domain| !domain.is_empty() && !domain.contains('%'))
+        .collect()
+}
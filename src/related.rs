@@ -0,0 +1,217 @@
+//! Sibling apex domains of the same organisation for `--find-related`:
+//! the organisation named in the target's certificates and in its WHOIS
+//! registrant is searched across certificate transparency logs, and the
+//! apexes the matching certificates cover are suggested for a next scan.
+
+use crate::{proxy, Result, SubdomainResult};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
+
+const WHOIS_PORT: u16 = 43;
+const WHOIS_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Organisations searched per domain; certificates of a big company can
+/// name many subsidiaries.
+const MAX_ORGANIZATIONS: usize = 5;
+
+/// Second-level labels under which registrations sit one level deeper,
+/// as in `example.co.uk`.
+const SECOND_LEVEL: &[&str] = &["co", "com", "net", "org", "gov", "ac", "edu", "ne", "or"];
+
+/// Registrant values that are a privacy service rather than an owner.
+const REDACTED: &[&str] = &[
+    "redacted",
+    "privacy",
+    "private",
+    "proxy",
+    "withheld",
+    "not disclosed",
+    "data protected",
+    "statutory masking",
+    "contact privacy",
+];
+
+/// An apex that looks like it belongs to the scanned organisation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RelatedDomain {
+    pub domain: String,
+    /// Why, e.g. `certificate organization "Example Inc"`.
+    pub via: Vec<String>,
+}
+
+/// Subject organisations of the certificates `results` presented. Hosts
+/// behind a CDN are skipped: a shared edge certificate names the CDN.
+pub fn certificate_organizations(results: &[SubdomainResult]) -> BTreeSet<String> {
+    results
+        .iter()
+        .filter(|r| r.cdn.is_none())
+        .filter_map(|r| r.tls.as_ref()?.organization.clone())
+        .map(|o| o.trim().to_string())
+        .filter(|o| !o.is_empty())
+        .collect()
+}
+
+/// Apexes sharing an organisation with `domain`, from its certificates
+/// in `results` and its WHOIS registrant. `domain` and apexes under it
+/// are left out.
+pub async fn find(
+    client: &Client,
+    domain: &str,
+    results: &[SubdomainResult],
+    proxy: Option<&Url>,
+) -> Result<Vec<RelatedDomain>> {
+    let mut pivots: Vec<(String, String)> = certificate_organizations(results)
+        .into_iter()
+        .map(|o| (format!("certificate organization \"{}\"", o), o))
+        .collect();
+    match whois_registrant(domain, proxy).await {
+        Ok(Some(org)) => pivots.push((format!("registrant \"{}\"", org), org)),
+        Ok(None) => {}
+        Err(e) => tracing::debug!("WHOIS for {} failed: {}", domain, e),
+    }
+    pivots.dedup_by(|a, b| a.1.eq_ignore_ascii_case(&b.1));
+    if pivots.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let suffix = format!(".{}", domain);
+    let mut related: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut last_error = None;
+    for (via, org) in pivots.into_iter().take(MAX_ORGANIZATIONS) {
+        let names = match names_by_organization(client, &org).await {
+            Ok(names) => names,
+            Err(e) => {
+                last_error = Some(e);
+                continue;
+            }
+        };
+        for apex in names.iter().filter_map(|n| apex(n)) {
+            if apex == domain || apex.ends_with(&suffix) {
+                continue;
+            }
+            related.entry(apex).or_default().insert(via.clone());
+        }
+    }
+    if related.is_empty() {
+        if let Some(e) = last_error {
+            return Err(e);
+        }
+    }
+    Ok(related
+        .into_iter()
+        .map(|(domain, via)| RelatedDomain {
+            domain,
+            via: via.into_iter().collect(),
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct CrtShEntry {
+    name_value: String,
+}
+
+/// Every name on certificates crt.sh has for subject organisation `org`.
+async fn names_by_organization(client: &Client, org: &str) -> Result<BTreeSet<String>> {
+    let res = client
+        .get("https://crt.sh/")
+        .query(&[("O", org), ("output", "json")])
+        .timeout(Duration::from_secs(60))
+        .send()
+        .await?;
+    if !res.status().is_success() {
+        return Err(format!("HTTP {}", res.status()).into());
+    }
+    let entries: Vec<CrtShEntry> = serde_json::from_str(&res.text().await?)?;
+    Ok(entries
+        .iter()
+        .flat_map(|e| e.name_value.split('\n'))
+        .map(|n| n.trim().trim_start_matches("*.").to_lowercase())
+        .filter(|n| n.contains('.') && !n.contains(' '))
+        .collect())
+}
+
+/// The registrable domain of `name`: its last two labels, or three under
+/// a second-level label such as `co.uk`. No public suffix list, so this
+/// is a guess for unusual suffixes.
+pub fn apex(name: &str) -> Option<String> {
+    let labels: Vec<&str> = name.trim_end_matches('.').split('.').collect();
+    if labels.len() < 2 || labels.iter().any(|l| l.is_empty()) {
+        return None;
+    }
+    let n = labels.len();
+    let take = if n >= 3 && labels[n - 1].len() == 2 && SECOND_LEVEL.contains(&labels[n - 2]) {
+        3
+    } else {
+        2
+    };
+    Some(labels[n - take..].join("."))
+}
+
+/// The registrant organisation (or name) of `domain`, following the IANA
+/// referral to the registry and from there to the registrar. Privacy
+/// services don't count.
+pub async fn whois_registrant(domain: &str, proxy: Option<&Url>) -> Result<Option<String>> {
+    let tld = domain.rsplit('.').next().unwrap_or(domain);
+    let iana = whois("whois.iana.org", tld, proxy).await?;
+    let Some(mut server) = field(&iana, &["refer"]) else {
+        return Ok(None);
+    };
+    let mut seen = BTreeSet::new();
+    while seen.insert(server.clone()) {
+        let response = whois(&server, domain, proxy).await?;
+        if let Some(org) = field(
+            &response,
+            &[
+                "registrant organization",
+                "registrant organisation",
+                "registrant name",
+                "org-name",
+                "registrant",
+            ],
+        )
+        .filter(|o| !is_redacted(o))
+        {
+            return Ok(Some(org));
+        }
+        match field(&response, &["registrar whois server", "whois server"]) {
+            Some(next) => server = next.trim_start_matches("whois://").to_string(),
+            None => break,
+        }
+    }
+    Ok(None)
+}
+
+async fn whois(server: &str, query: &str, proxy: Option<&Url>) -> Result<String> {
+    let exchange = async {
+        let mut stream = proxy::connect(proxy, server, WHOIS_PORT).await?;
+        stream
+            .write_all(format!("{}\r\n", query).as_bytes())
+            .await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, crate::Error>(String::from_utf8_lossy(&response).into_owned())
+    };
+    timeout(WHOIS_TIMEOUT, exchange)
+        .await
+        .map_err(|_| format!("WHOIS {} timed out", server))?
+}
+
+/// The first non-empty `key: value` line for any of `keys`.
+fn field(response: &str, keys: &[&str]) -> Option<String> {
+    response.lines().find_map(|line| {
+        let (key, value) = line.trim().split_once(':')?;
+        let value = value.trim();
+        (keys.contains(&key.trim().to_ascii_lowercase().as_str()) && !value.is_empty())
+            .then(|| value.to_string())
+    })
+}
+
+fn is_redacted(value: &str) -> bool {
+    let value = value.to_ascii_lowercase();
+    REDACTED.iter().any(|r| value.contains(r))
+}
@@ -0,0 +1,140 @@
+use super::{display_name, is_anomaly, Summary};
+use crate::SubdomainResult;
+use std::fmt::Write;
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+h1 { margin-bottom: 0.2em; }
+.stats { display: flex; gap: 1em; flex-wrap: wrap; margin: 1em 0; }
+.stat { border: 1px solid #ddd; border-radius: 6px; padding: 0.6em 1em; }
+.stat b { display: block; font-size: 1.4em; }
+input { padding: 0.4em; width: 20em; margin-bottom: 0.8em; }
+table { border-collapse: collapse; width: 100%; font-size: 0.9em; }
+th, td { border-bottom: 1px solid #eee; padding: 0.35em 0.6em; text-align: left; vertical-align: top; }
+th { cursor: pointer; background: #f6f6f6; user-select: none; }
+tr.anomaly { background: #fff1f0; }
+.tag { background: #c0392b; color: #fff; border-radius: 3px; padding: 0 0.4em; font-size: 0.85em; }
+";
+
+const SCRIPT: &str = "
+const table = document.getElementById('results');
+const rows = () => Array.from(table.tBodies[0].rows);
+document.getElementById('filter').addEventListener('input', e => {
+  const q = e.target.value.toLowerCase();
+  rows().forEach(r => { r.style.display = r.textContent.toLowerCase().includes(q) ? '' : 'none'; });
+});
+table.querySelectorAll('th').forEach((th, i) => th.addEventListener('click', () => {
+  const asc = th.dataset.asc !== 'true';
+  th.dataset.asc = asc;
+  const key = r => r.cells[i].dataset.sort ?? r.cells[i].textContent;
+  const sorted = rows().sort((a, b) => {
+    const x = key(a), y = key(b);
+    const n = x !== '' && y !== '' && !isNaN(x) && !isNaN(y) ? x - y : x.localeCompare(y);
+    return asc ? n : -n;
+  });
+  sorted.forEach(r => table.tBodies[0].appendChild(r));
+}));
+";
+
+/// A self-contained HTML page (inline CSS and JS, no external assets) with
+/// summary stats and a sortable, filterable results table.
+pub fn render(domain: &str, results: &[SubdomainResult]) -> String {
+    let summary = Summary::of(results);
+    let mut out = String::new();
+
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>SubPeek report: {domain}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+         <h1>SubPeek report: {domain}</h1>\n<div class=\"stats\">\n",
+        domain = escape(domain),
+    );
+    for (label, value) in [
+        ("Subdomains", summary.total),
+        ("Live over HTTP", summary.live),
+        ("Unique IPs", summary.unique_ips),
+        ("Takeover candidates", summary.takeovers),
+        ("Leaked via AXFR", summary.zone_leaks),
+    ] {
+        let _ = writeln!(out, "<div class=\"stat\"><b>{}</b>{}</div>", value, label);
+    }
+    for (status, count) in &summary.by_status {
+        let label = status.map_or("no response".to_string(), |s| format!("HTTP {}", s));
+        let _ = writeln!(out, "<div class=\"stat\"><b>{}</b>{}</div>", count, label);
+    }
+    out.push_str("</div>\n<input id=\"filter\" placeholder=\"Filter...\">\n");
+
+    out.push_str(
+        "<table id=\"results\">\n<thead><tr><th>Subdomain</th><th>Status</th><th>Title</th>\
+         <th>Server</th><th>Tech</th><th>IP</th><th>CNAME</th><th>Notes</th></tr></thead>\n<tbody>\n",
+    );
+    let mut sorted: Vec<&SubdomainResult> = results.iter().collect();
+    sorted.sort_by(|a, b| (&a.subdomain, a.port).cmp(&(&b.subdomain, b.port)));
+    for r in sorted {
+        let class = if is_anomaly(r) {
+            " class=\"anomaly\""
+        } else {
+            ""
+        };
+        let status = r.status_code.map(|s| s.to_string()).unwrap_or_default();
+        let mut notes = Vec::new();
+        if let Some(ref t) = r.takeover {
+            notes.push(format!(
+                "<span class=\"tag\">takeover</span> {} ({})",
+                escape(&t.service),
+                escape(&t.cname)
+            ));
+        }
+        if let Some(ref ns) = r.zone_transfer {
+            notes.push(format!("<span class=\"tag\">AXFR</span> {}", escape(ns)));
+        }
+        if r.slow {
+            notes.push(format!(
+                "<span class=\"tag\">slow</span> {} ms",
+                r.response_time_ms.unwrap_or_default()
+            ));
+        }
+        if let Some(ref cert) = r.tls {
+            if cert.expired {
+                notes.push(format!(
+                    "<span class=\"tag\">cert expired</span> {}",
+                    escape(&cert.not_after)
+                ));
+            }
+            if cert.name_mismatch {
+                notes.push(format!(
+                    "<span class=\"tag\">cert mismatch</span> {}",
+                    escape(&cert.subject)
+                ));
+            }
+        }
+        let _ = writeln!(
+            out,
+            "<tr{}><td>{}</td><td data-sort=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            class,
+            escape(&display_name(r)),
+            status,
+            status,
+            escape(r.title.as_deref().unwrap_or("")),
+            escape(r.server.as_deref().unwrap_or("")),
+            escape(&r.technologies.join(", ")),
+            escape(&r.ips.join(", ")),
+            escape(&r.cname_chain.join(" → ")),
+            notes.join("<br>"),
+        );
+    }
+    let _ = write!(
+        out,
+        "</tbody>\n</table>\n<script>{}</script>\n</body>\n</html>\n",
+        SCRIPT
+    );
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
@@ -0,0 +1,87 @@
+use super::{display_name, is_anomaly, is_odd_status, Summary};
+use crate::SubdomainResult;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// A Markdown document grouping hosts by status code, then by `Server`
+/// header, ready to paste into a report or issue.
+pub fn render(domain: &str, results: &[SubdomainResult]) -> String {
+    let summary = Summary::of(results);
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# Subdomain enumeration: {}\n", cell(domain));
+    out.push_str("## Summary\n\n");
+    let _ = writeln!(out, "- **Subdomains:** {}", summary.total);
+    let _ = writeln!(out, "- **Live over HTTP:** {}", summary.live);
+    let _ = writeln!(out, "- **Unique IPs:** {}", summary.unique_ips);
+    let _ = writeln!(out, "- **Takeover candidates:** {}", summary.takeovers);
+    let _ = writeln!(out, "- **Leaked via AXFR:** {}", summary.zone_leaks);
+    out.push('\n');
+
+    let anomalies: Vec<&SubdomainResult> = results.iter().filter(|r| is_anomaly(r)).collect();
+    if !anomalies.is_empty() {
+        out.push_str("## Notable findings\n\n");
+        for r in anomalies {
+            let mut notes = Vec::new();
+            if let Some(ref t) = r.takeover {
+                notes.push(format!("possible {} takeover via `{}`", t.service, t.cname));
+            }
+            if let Some(ref ns) = r.zone_transfer {
+                notes.push(format!("leaked by AXFR from `{}`", ns));
+            }
+            if let Some(ref cert) = r.tls {
+                if cert.expired {
+                    notes.push(format!("certificate expired {}", cert.not_after));
+                }
+                if cert.name_mismatch {
+                    notes.push(format!("certificate issued to `{}`", cert.subject));
+                }
+            }
+            if let Some(code) = r.status_code.filter(|c| is_odd_status(*c)) {
+                notes.push(format!("HTTP {}", code));
+            }
+            let _ = writeln!(out, "- `{}`: {}", display_name(r), notes.join(", "));
+        }
+        out.push('\n');
+    }
+
+    // status -> server -> hosts
+    let mut groups: BTreeMap<Option<u16>, BTreeMap<String, Vec<&SubdomainResult>>> =
+        BTreeMap::new();
+    for r in results {
+        let server = r.server.clone().unwrap_or_else(|| "unknown".to_string());
+        groups
+            .entry(r.status_code)
+            .or_default()
+            .entry(server)
+            .or_default()
+            .push(r);
+    }
+
+    for (status, servers) in groups {
+        let heading = status.map_or("No HTTP response".to_string(), |s| format!("HTTP {}", s));
+        let count: usize = servers.values().map(Vec::len).sum();
+        let _ = writeln!(out, "## {} ({})\n", heading, count);
+        for (server, mut hosts) in servers {
+            hosts.sort_by(|a, b| (&a.subdomain, a.port).cmp(&(&b.subdomain, b.port)));
+            let _ = writeln!(out, "### Server: {}\n", cell(&server));
+            out.push_str("| Subdomain | IP | Title |\n| --- | --- | --- |\n");
+            for r in hosts {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} |",
+                    cell(&display_name(r)),
+                    cell(&r.ips.join(", ")),
+                    cell(r.title.as_deref().unwrap_or("")),
+                );
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Keeps a value on one table row.
+fn cell(s: &str) -> String {
+    s.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
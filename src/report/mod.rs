@@ -0,0 +1,70 @@
+//! Human-readable reports of a finished scan.
+
+pub mod html;
+pub mod markdown;
+
+use crate::SubdomainResult;
+use std::collections::{BTreeMap, HashSet};
+
+/// Headline numbers shown at the top of every report.
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub total: usize,
+    /// Hosts that answered over HTTP(S).
+    pub live: usize,
+    pub unique_ips: usize,
+    pub takeovers: usize,
+    pub zone_leaks: usize,
+    /// Hosts per status code, `None` for no HTTP response.
+    pub by_status: BTreeMap<Option<u16>, usize>,
+}
+
+impl Summary {
+    pub fn of(results: &[SubdomainResult]) -> Self {
+        let mut summary = Summary {
+            total: results.len(),
+            ..Summary::default()
+        };
+        let mut ips = HashSet::new();
+        for r in results {
+            if r.status_code.is_some() {
+                summary.live += 1;
+            }
+            if r.takeover.is_some() {
+                summary.takeovers += 1;
+            }
+            if r.zone_transfer.is_some() {
+                summary.zone_leaks += 1;
+            }
+            ips.extend(r.ips.iter());
+            *summary.by_status.entry(r.status_code).or_default() += 1;
+        }
+        summary.unique_ips = ips.len();
+        summary
+    }
+}
+
+/// Status codes worth a second look: server errors and auth walls.
+pub fn is_odd_status(code: u16) -> bool {
+    code >= 500 || matches!(code, 401 | 403 | 407)
+}
+
+/// Whether a result should be highlighted in a report.
+pub fn is_anomaly(r: &SubdomainResult) -> bool {
+    r.takeover.is_some()
+        || r.zone_transfer.is_some()
+        || r.status_code.is_some_and(is_odd_status)
+        || r.tls.as_ref().is_some_and(|c| c.expired || c.name_mismatch)
+}
+
+/// `host`, with the port when one was probed and the scheme when known.
+pub fn display_name(r: &SubdomainResult) -> String {
+    let host = match r.port {
+        Some(p) => format!("{}:{}", r.subdomain, p),
+        None => r.subdomain.clone(),
+    };
+    match r.scheme {
+        Some(ref scheme) => format!("{}://{}", scheme, host),
+        None => host,
+    }
+}
@@ -0,0 +1,111 @@
+//! `/robots.txt` and `/sitemap.xml` for `--robots`: paths a site asks
+//! crawlers to stay out of are often the ones worth a look, and both files
+//! name other hosts of the site.
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Disallowed paths kept per host.
+pub const MAX_PATHS: usize = 50;
+
+/// Sitemaps fetched per host, `/sitemap.xml` and the ones it or
+/// `robots.txt` points at included.
+pub const MAX_SITEMAPS: usize = 5;
+
+/// Parts of a path that make it worth following up by hand.
+const INTERESTING: &[&str] = &[
+    "admin",
+    "api",
+    "backup",
+    "bak",
+    "config",
+    "console",
+    "dashboard",
+    "debug",
+    "dev",
+    "internal",
+    "login",
+    "manage",
+    "old",
+    "panel",
+    "private",
+    "secret",
+    "staging",
+    "test",
+    "tmp",
+    "upload",
+    "wp-admin",
+    ".env",
+    ".git",
+    ".sql",
+    ".zip",
+    ".tar",
+];
+
+/// What a `robots.txt` lists.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Robots {
+    /// `Disallow` values of every user agent group, without duplicates.
+    pub disallowed: Vec<String>,
+    /// `Sitemap` URLs.
+    pub sitemaps: Vec<String>,
+}
+
+/// Parses a `robots.txt`. Comments and unknown fields are ignored, as is
+/// an empty `Disallow`, which allows everything.
+pub fn parse(text: &str) -> Robots {
+    let mut robots = Robots::default();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let Some((field, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        match field.trim().to_ascii_lowercase().as_str() {
+            "disallow" if !robots.disallowed.iter().any(|p| p == value) => {
+                robots.disallowed.push(value.to_string())
+            }
+            "sitemap" if !robots.sitemaps.iter().any(|s| s == value) => {
+                robots.sitemaps.push(value.to_string())
+            }
+            _ => {}
+        }
+    }
+    robots
+}
+
+/// The `disallowed` paths worth following up: those naming something from
+/// [`INTERESTING`], up to [`MAX_PATHS`].
+pub fn interesting(disallowed: &[String]) -> Vec<String> {
+    disallowed
+        .iter()
+        .filter(|p| {
+            let p = p.to_ascii_lowercase();
+            INTERESTING.iter().any(|k| p.contains(k))
+        })
+        .take(MAX_PATHS)
+        .cloned()
+        .collect()
+}
+
+/// The `<loc>` URLs of a sitemap or sitemap index.
+pub fn sitemap_locations(xml: &str) -> Vec<String> {
+    static LOC: OnceLock<Regex> = OnceLock::new();
+    let re = LOC.get_or_init(|| {
+        Regex::new(r"(?is)<loc>\s*(?:<!\[CDATA\[)?(.*?)(?:\]\]>)?\s*</loc>").unwrap()
+    });
+    re.captures_iter(xml)
+        .map(|c| c[1].trim().replace("&amp;", "&"))
+        .filter(|u| !u.is_empty())
+        .collect()
+}
+
+/// Whether a `<loc>` of a sitemap index is another sitemap to fetch
+/// rather than a page.
+pub fn is_sitemap(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.ends_with(".xml")
+}
@@ -0,0 +1,102 @@
+//! Which hosts a scan may probe: `--include-regex`, `--exclude-pattern` and
+//! bug-bounty out-of-scope lists.
+
+use crate::{input, Result};
+use regex::{Regex, RegexBuilder};
+use std::path::Path;
+
+/// Names discovered but outside these rules are never probed, port-scanned
+/// or enriched. The default allows everything.
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    /// A name must match one of these, if any are set.
+    pub include: Vec<Regex>,
+    /// A name matching any of these is dropped.
+    pub exclude: Vec<Regex>,
+}
+
+impl Scope {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.');
+        (self.include.is_empty() || self.include.iter().any(|re| re.is_match(name)))
+            && !self.exclude.iter().any(|re| re.is_match(name))
+    }
+
+    pub fn include_regex(&mut self, pattern: &str) -> Result<()> {
+        let re = RegexBuilder::new(pattern).case_insensitive(true).build()?;
+        self.include.push(re);
+        Ok(())
+    }
+
+    /// A shell-style pattern over the whole name: `*` matches any run of
+    /// characters, dots included, and `?` one character. `*.cdn.example.com`
+    /// covers every name under `cdn.example.com` but not that name itself.
+    pub fn exclude_pattern(&mut self, glob: &str) -> Result<()> {
+        self.exclude.push(glob_regex(glob)?);
+        Ok(())
+    }
+
+    /// Excludes every host in a program's out-of-scope list: one asset per
+    /// line, or a HackerOne-style CSV export whose first column is the
+    /// asset. URLs are reduced to their host, and `*.example.com` excludes
+    /// `example.com` as well as everything under it. Entries that aren't
+    /// hostnames (IP ranges, app store IDs) are skipped.
+    pub fn load_out_of_scope(&mut self, path: &Path) -> Result<usize> {
+        let mut added = 0;
+        for line in input::load_list(path)? {
+            let Some(host) = scope_host(&line) else {
+                continue;
+            };
+            match host.strip_prefix("*.") {
+                Some(apex) => {
+                    self.exclude.push(glob_regex(apex)?);
+                    self.exclude.push(glob_regex(&host)?);
+                }
+                None => self.exclude.push(glob_regex(&host)?),
+            }
+            added += 1;
+        }
+        Ok(added)
+    }
+}
+
+fn glob_regex(glob: &str) -> Result<Regex> {
+    let body: String = glob
+        .trim()
+        .trim_end_matches('.')
+        .chars()
+        .map(|c| match c {
+            '*' => ".*".to_string(),
+            '?' => ".".to_string(),
+            c => regex::escape(&c.to_string()),
+        })
+        .collect();
+    Ok(RegexBuilder::new(&format!("^{}$", body))
+        .case_insensitive(true)
+        .build()?)
+}
+
+/// The hostname (or wildcard) an out-of-scope entry names, if any.
+fn scope_host(entry: &str) -> Option<String> {
+    let field = entry.split(',').next()?.trim().trim_matches('"');
+    let field = field.split_once("://").map_or(field, |(_, rest)| rest);
+    let host = field.split(['/', '?']).next()?;
+    let host = host.rsplit_once(':').map_or(host, |(h, port)| {
+        if port.chars().all(|c| c.is_ascii_digit()) {
+            h
+        } else {
+            host
+        }
+    });
+    let host = host.trim_end_matches('.');
+    let valid = host.contains('.')
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '*' | '_'))
+        && !host.chars().all(|c| c.is_ascii_digit() || c == '.');
+    valid.then(|| host.to_string())
+}
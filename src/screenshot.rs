@@ -0,0 +1,384 @@
+//! Screenshots of live hosts, taken by driving headless Chrome/Chromium
+//! over the DevTools protocol.
+
+use crate::{proxy, Result, SubdomainResult};
+use base64::Engine as _;
+use rand::Rng;
+use reqwest::Url;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::{sleep, timeout, Instant};
+use tracing::warn;
+
+/// Tabs open at once.
+pub const SCREENSHOT_CONCURRENCY: usize = 4;
+
+/// How long a page may take to fire its load event before it is captured
+/// as it stands.
+pub const LOAD_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Upper bound for one tab, load and capture included.
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Extra time after the load event for late scripts and fonts.
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+/// Executables tried on `PATH` when `SUBPEEK_CHROME` is unset.
+const CHROME_NAMES: &[&str] = &[
+    "chromium",
+    "chromium-browser",
+    "google-chrome",
+    "google-chrome-stable",
+    "chrome",
+];
+
+/// Screenshots every result that got an HTTP response into `dir`, setting
+/// its `screenshot` to the PNG's path.
+pub async fn capture_all(
+    results: &mut [SubdomainResult],
+    dir: &Path,
+    proxy: Option<&Url>,
+) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let browser = Arc::new(Browser::launch(proxy).await?);
+    let permits = Arc::new(Semaphore::new(SCREENSHOT_CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    for (i, r) in results.iter().enumerate() {
+        if r.status_code.is_none() {
+            continue;
+        }
+        let url = page_url(r);
+        let path = dir.join(file_name(r));
+        let browser = browser.clone();
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire().await.ok()?;
+            match browser.capture(&url, &path).await {
+                Ok(()) => Some((i, path)),
+                Err(e) => {
+                    warn!("Screenshot of {} failed: {}", url, e);
+                    None
+                }
+            }
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok(Some((i, path))) = joined {
+            results[i].screenshot = Some(path.display().to_string());
+        }
+    }
+    Ok(())
+}
+
+fn page_url(r: &SubdomainResult) -> String {
+    if let Some(ref url) = r.final_url {
+        return url.clone();
+    }
+    let scheme = r.scheme.as_deref().unwrap_or("https");
+    match r.port {
+        Some(p) => format!("{}://{}:{}", scheme, r.subdomain, p),
+        None => format!("{}://{}", scheme, r.subdomain),
+    }
+}
+
+/// `https_app.example.com_8443.png`, unique per probed endpoint.
+fn file_name(r: &SubdomainResult) -> String {
+    let scheme = r.scheme.as_deref().unwrap_or("https");
+    let safe: String = r
+        .subdomain
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match r.port {
+        Some(p) => format!("{}_{}_{}.png", scheme, safe, p),
+        None => format!("{}_{}.png", scheme, safe),
+    }
+}
+
+fn find_chrome() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("SUBPEEK_CHROME") {
+        return Some(PathBuf::from(path));
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .flat_map(|dir| CHROME_NAMES.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+/// A headless browser process, killed and its profile removed on drop.
+struct Browser {
+    _child: Child,
+    /// DevTools HTTP and WebSocket port on localhost.
+    port: u16,
+    profile: PathBuf,
+    http: reqwest::Client,
+}
+
+impl Drop for Browser {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.profile);
+    }
+}
+
+impl Browser {
+    async fn launch(proxy: Option<&Url>) -> Result<Self> {
+        let chrome = find_chrome()
+            .ok_or("no Chrome or Chromium found on PATH; set SUBPEEK_CHROME to its path")?;
+        let profile = std::env::temp_dir().join(format!(
+            "subpeek-chrome-{}-{}",
+            std::process::id(),
+            rand::thread_rng().gen::<u32>()
+        ));
+        let mut cmd = Command::new(&chrome);
+        cmd.args([
+            "--headless=new",
+            "--disable-gpu",
+            "--no-sandbox",
+            "--no-first-run",
+            "--no-default-browser-check",
+            "--hide-scrollbars",
+            "--mute-audio",
+            "--ignore-certificate-errors",
+            "--window-size=1280,800",
+            "--remote-debugging-port=0",
+        ])
+        .arg(format!("--user-data-dir={}", profile.display()));
+        if let Some(proxy) = proxy {
+            cmd.arg(format!("--proxy-server={}", proxy::for_browser(proxy)));
+        }
+        let mut child = cmd
+            .arg("about:blank")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("could not start {}: {}", chrome.display(), e))?;
+
+        // Chrome announces its DevTools endpoint on stderr
+        let stderr = child.stderr.take().ok_or("browser stderr unavailable")?;
+        let mut lines = BufReader::new(stderr).lines();
+        let port = timeout(Duration::from_secs(20), async {
+            while let Ok(Some(line)) = lines.next_line().await {
+                if let Some(rest) = line.split("ws://").nth(1) {
+                    let port = rest
+                        .split('/')
+                        .next()
+                        .and_then(|authority| authority.rsplit(':').next())
+                        .and_then(|p| p.parse::<u16>().ok());
+                    if port.is_some() {
+                        return port;
+                    }
+                }
+            }
+            None
+        })
+        .await
+        .ok()
+        .flatten()
+        .ok_or("browser did not announce a DevTools endpoint")?;
+        // Keep draining stderr so the browser never blocks on a full pipe
+        tokio::spawn(async move { while let Ok(Some(_)) = lines.next_line().await {} });
+
+        Ok(Self {
+            _child: child,
+            port,
+            profile,
+            http: reqwest::Client::new(),
+        })
+    }
+
+    /// Opens `url` in a fresh tab and writes a PNG of the viewport to `path`.
+    async fn capture(&self, url: &str, path: &Path) -> Result<()> {
+        let target: Value = self
+            .http
+            .put(format!(
+                "http://127.0.0.1:{}/json/new?about:blank",
+                self.port
+            ))
+            .send()
+            .await?
+            .json()
+            .await?;
+        let id = target["id"].as_str().ok_or("DevTools returned no tab id")?;
+        let ws_url = target["webSocketDebuggerUrl"]
+            .as_str()
+            .ok_or("DevTools returned no tab WebSocket")?;
+
+        let shot = timeout(CAPTURE_TIMEOUT, async {
+            let mut tab = WebSocket::connect(ws_url).await?;
+            tab.call(1, "Page.enable", json!({})).await?;
+            tab.send(&json!({"id": 2, "method": "Page.navigate", "params": {"url": url}}))
+                .await?;
+            let deadline = Instant::now() + LOAD_TIMEOUT;
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                match timeout(remaining, tab.recv()).await {
+                    Ok(msg) => {
+                        let msg = msg?;
+                        if msg["id"] == 2 {
+                            if let Some(err) = msg["result"]["errorText"].as_str() {
+                                return Err(err.to_string().into());
+                            }
+                        }
+                        if msg["method"] == "Page.loadEventFired" {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+            sleep(SETTLE_DELAY).await;
+            let result = tab
+                .call(3, "Page.captureScreenshot", json!({"format": "png"}))
+                .await?;
+            let data = result["data"].as_str().ok_or("no screenshot data")?;
+            let png = base64::engine::general_purpose::STANDARD.decode(data)?;
+            tokio::fs::write(path, png).await?;
+            Ok(())
+        })
+        .await
+        .unwrap_or_else(|_| Err("timed out".into()));
+
+        let _ = self
+            .http
+            .get(format!("http://127.0.0.1:{}/json/close/{}", self.port, id))
+            .send()
+            .await;
+        shot
+    }
+}
+
+/// Just enough of a WebSocket client (RFC 6455) for DevTools: unencrypted,
+/// text frames, no extensions.
+struct WebSocket {
+    stream: BufReader<TcpStream>,
+}
+
+impl WebSocket {
+    async fn connect(url: &str) -> Result<Self> {
+        let url = Url::parse(url)?;
+        let host = url.host_str().ok_or("WebSocket URL has no host")?;
+        let port = url.port().unwrap_or(80);
+        let mut stream = BufReader::new(TcpStream::connect((host, port)).await?);
+        let key = base64::engine::general_purpose::STANDARD.encode(rand::random::<[u8; 16]>());
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}:{}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            url.path(),
+            host,
+            port,
+            key
+        );
+        stream.get_mut().write_all(request.as_bytes()).await?;
+
+        let mut status = String::new();
+        stream.read_line(&mut status).await?;
+        if status.split_whitespace().nth(1) != Some("101") {
+            return Err(format!("WebSocket upgrade refused: {}", status.trim()).into());
+        }
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+                break;
+            }
+        }
+        Ok(Self { stream })
+    }
+
+    /// Sends a command and waits for its reply, skipping events.
+    async fn call(&mut self, id: u64, method: &str, params: Value) -> Result<Value> {
+        self.send(&json!({"id": id, "method": method, "params": params}))
+            .await?;
+        loop {
+            let msg = self.recv().await?;
+            if msg["id"] == id {
+                if let Some(err) = msg.get("error") {
+                    return Err(format!("{} failed: {}", method, err).into());
+                }
+                return Ok(msg["result"].clone());
+            }
+        }
+    }
+
+    async fn send(&mut self, msg: &Value) -> Result<()> {
+        self.write_frame(0x1, msg.to_string().as_bytes()).await
+    }
+
+    async fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<()> {
+        let mut frame = vec![0x80 | opcode];
+        match payload.len() {
+            n if n < 126 => frame.push(0x80 | n as u8),
+            n if n <= u16::MAX as usize => {
+                frame.push(0x80 | 126);
+                frame.extend((n as u16).to_be_bytes());
+            }
+            n => {
+                frame.push(0x80 | 127);
+                frame.extend((n as u64).to_be_bytes());
+            }
+        }
+        // Client frames must be masked
+        let mask: [u8; 4] = rand::random();
+        frame.extend(mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        self.stream.get_mut().write_all(&frame).await?;
+        Ok(())
+    }
+
+    /// The next complete text message, parsed as JSON.
+    async fn recv(&mut self) -> Result<Value> {
+        let mut message = Vec::new();
+        loop {
+            let mut head = [0u8; 2];
+            self.stream.read_exact(&mut head).await?;
+            let fin = head[0] & 0x80 != 0;
+            let opcode = head[0] & 0x0f;
+            let len = match head[1] & 0x7f {
+                126 => self.stream.read_u16().await? as u64,
+                127 => self.stream.read_u64().await?,
+                n => n as u64,
+            };
+            let mask = if head[1] & 0x80 != 0 {
+                let mut m = [0u8; 4];
+                self.stream.read_exact(&mut m).await?;
+                Some(m)
+            } else {
+                None
+            };
+            let mut payload = vec![0u8; len as usize];
+            self.stream.read_exact(&mut payload).await?;
+            if let Some(m) = mask {
+                payload
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(i, b)| *b ^= m[i % 4]);
+            }
+            match opcode {
+                0x0..=0x2 => {
+                    message.extend(payload);
+                    if fin {
+                        return Ok(serde_json::from_slice(&message)?);
+                    }
+                }
+                0x8 => return Err("DevTools closed the connection".into()),
+                0x9 => self.write_frame(0xa, &payload).await?,
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,429 @@
+//! `serve`: a small JSON API to queue scans and fetch their results, for
+//! tooling that would rather not spawn a process per scan.
+//!
+//! - `POST /scans` with `{"domain": "example.com"}` or `{"domains": [...]}`
+//!   queues a scan and answers `202` with its status.
+//! - `GET /scans` lists the scans the server remembers.
+//! - `GET /scans/<id>` is one scan's status.
+//! - `GET /scans/<id>/results[?format=jsonl|csv|hosts]` is what
+//!   `subpeek -l` prints for the same domains, so far if still running.
+//! - `GET /metrics` is [`metrics::render`] for Prometheus.
+//!
+//! Scans run one at a time on a single engine, whose DNS and HTTP budgets
+//! are already shared across the domains of a scan.
+//!
+//! Web pages the operator visits must not drive the API: `POST /scans`
+//! takes `application/json` only, which browsers can't send cross-origin
+//! without a preflight, and requests whose `Host` isn't the listen address
+//! are refused, which defeats DNS rebinding.
+
+use crate::{metrics, output, OutputFormat, Result, SubdomainResult, SubpeekEngine};
+use hyper::body::HttpBody;
+use hyper::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, HOST, LOCATION};
+use hyper::http::uri::Authority;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+
+/// Scans waiting to start; further submissions get `503`.
+pub const DEFAULT_QUEUE_SIZE: usize = 16;
+
+/// Finished scans kept for their results; older ones are forgotten.
+const MAX_FINISHED: usize = 100;
+
+/// Largest `POST /scans` body read.
+const MAX_BODY: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanStatus {
+    Queued,
+    Running,
+    Done,
+}
+
+/// What `GET /scans/<id>` answers.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanInfo {
+    pub id: u64,
+    pub domains: Vec<String>,
+    pub status: ScanStatus,
+    /// Unix timestamps.
+    pub submitted_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+    /// Results so far.
+    pub found: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScanRequest {
+    #[serde(default)]
+    domain: Option<String>,
+    #[serde(default)]
+    domains: Vec<String>,
+}
+
+struct Job {
+    info: ScanInfo,
+    results: Vec<(String, Vec<SubdomainResult>)>,
+}
+
+struct State {
+    /// The listen address, which the `Host` of every request has to name.
+    addr: SocketAddr,
+    jobs: Mutex<BTreeMap<u64, Job>>,
+    next_id: Mutex<u64>,
+    queue: mpsc::Sender<u64>,
+}
+
+impl State {
+    fn jobs(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, Job>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whether `host` names the listen address: its IP, `localhost` when
+    /// that is a loopback one, or any name when listening on every
+    /// interface. The port has to match in each case.
+    fn host_allowed(&self, host: Option<&HeaderValue>) -> bool {
+        let Some(host) = host.and_then(|v| v.to_str().ok()?.parse::<Authority>().ok()) else {
+            return false;
+        };
+        if host.port_u16().unwrap_or(80) != self.addr.port() {
+            return false;
+        }
+        let ip = self.addr.ip();
+        if ip.is_unspecified() {
+            return true;
+        }
+        let name = host.host().trim_start_matches('[').trim_end_matches(']');
+        name.parse::<IpAddr>() == Ok(ip)
+            || (ip.is_loopback() && name.eq_ignore_ascii_case("localhost"))
+    }
+}
+
+/// Serves the API on `addr` until the process exits, running every
+/// submitted scan with `engine`.
+pub async fn serve(addr: SocketAddr, engine: SubpeekEngine, queue_size: usize) -> Result<()> {
+    let builder = hyper::Server::try_bind(&addr)?;
+    let (tx, rx) = mpsc::channel(queue_size.max(1));
+    let state = Arc::new(State {
+        addr: builder.local_addr(),
+        jobs: Mutex::new(BTreeMap::new()),
+        next_id: Mutex::new(1),
+        queue: tx,
+    });
+    tokio::spawn(worker(engine, state.clone(), rx));
+
+    let make = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(route(&state, req).await) }
+            }))
+        }
+    });
+    let server = builder.serve(make);
+    if !addr.ip().is_loopback() {
+        warn!(
+            "Listening on {} without authentication; anyone who can reach it can start scans.",
+            addr
+        );
+    }
+    info!("Serving the scan API on http://{}", server.local_addr());
+    server.await?;
+    Ok(())
+}
+
+/// Serves only `GET /metrics`, for `monitor --metrics-listen`.
+pub async fn serve_metrics(addr: SocketAddr) -> Result<()> {
+    let make = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+            Ok::<_, Infallible>(match (req.method(), req.uri().path()) {
+                (&Method::GET, "/metrics") => metrics_response(),
+                _ => error(StatusCode::NOT_FOUND, "not found"),
+            })
+        }))
+    });
+    let server = hyper::Server::try_bind(&addr)?.serve(make);
+    info!("Serving metrics on http://{}/metrics", server.local_addr());
+    server.await?;
+    Ok(())
+}
+
+async fn worker(engine: SubpeekEngine, state: Arc<State>, mut rx: mpsc::Receiver<u64>) {
+    while let Some(id) = rx.recv().await {
+        let domains = {
+            let mut jobs = state.jobs();
+            let Some(job) = jobs.get_mut(&id) else {
+                continue;
+            };
+            job.info.status = ScanStatus::Running;
+            job.info.started_at = Some(now());
+            job.results = job
+                .info
+                .domains
+                .iter()
+                .map(|d| (d.clone(), Vec::new()))
+                .collect();
+            job.info.domains.clone()
+        };
+        info!("Starting scan #{} of {}", id, domains.join(", "));
+
+        let groups = engine
+            .run_many_streaming(&domains, |domain, r| {
+                let mut jobs = state.jobs();
+                if let Some(job) = jobs.get_mut(&id) {
+                    if let Some((_, results)) = job.results.iter_mut().find(|(d, _)| d == domain) {
+                        results.push(r.clone());
+                        job.info.found += 1;
+                    }
+                }
+            })
+            .await;
+
+        let mut jobs = state.jobs();
+        if let Some(job) = jobs.get_mut(&id) {
+            // The returned results carry enrichment the streamed ones lack
+            job.info.found = groups.iter().map(|(_, results)| results.len()).sum();
+            job.info.status = ScanStatus::Done;
+            job.info.finished_at = Some(now());
+            job.results = groups;
+            info!("Scan #{} done: {} results", id, job.info.found);
+        }
+        forget_old(&mut jobs);
+    }
+}
+
+/// Drops the oldest finished scans beyond [`MAX_FINISHED`].
+fn forget_old(jobs: &mut BTreeMap<u64, Job>) {
+    let finished: Vec<u64> = jobs
+        .iter()
+        .filter(|(_, job)| job.info.status == ScanStatus::Done)
+        .map(|(id, _)| *id)
+        .collect();
+    for id in finished
+        .iter()
+        .take(finished.len().saturating_sub(MAX_FINISHED))
+    {
+        jobs.remove(id);
+    }
+}
+
+async fn route(state: &State, req: Request<Body>) -> Response<Body> {
+    if !state.host_allowed(req.headers().get(HOST)) {
+        return error(StatusCode::MISDIRECTED_REQUEST, "unexpected Host header");
+    }
+    let path = req.uri().path().trim_end_matches('/').to_string();
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+    match (req.method(), segments.as_slice()) {
+        (&Method::POST, ["scans"]) => submit(state, req).await,
+        (&Method::GET, ["scans"]) => {
+            let list: Vec<ScanInfo> = state.jobs().values().map(|j| j.info.clone()).collect();
+            json(StatusCode::OK, &list)
+        }
+        (&Method::GET, ["scans", id]) => match lookup(state, id) {
+            Some(info) => json(StatusCode::OK, &info),
+            None => error(StatusCode::NOT_FOUND, "no such scan"),
+        },
+        (&Method::GET, ["scans", id, "results"]) => results(state, id, req.uri().query()),
+        (&Method::GET, ["metrics"]) => metrics_response(),
+        (_, ["scans", ..]) => error(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"),
+        _ => error(StatusCode::NOT_FOUND, "not found"),
+    }
+}
+
+async fn submit(state: &State, req: Request<Body>) -> Response<Body> {
+    let media_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(str::trim);
+    if !media_type.is_some_and(|t| t.eq_ignore_ascii_case("application/json")) {
+        return error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Content-Type must be application/json",
+        );
+    }
+    let declared = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > MAX_BODY) {
+        return error(StatusCode::PAYLOAD_TOO_LARGE, "request body too large");
+    }
+    // Chunked bodies declare no length, so the limit holds while reading
+    let body = match read_limited(req.into_body(), MAX_BODY).await {
+        Ok(Some(body)) => body,
+        Ok(None) => return error(StatusCode::PAYLOAD_TOO_LARGE, "request body too large"),
+        Err(e) => return error(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let request: ScanRequest = match serde_json::from_slice(&body) {
+        Ok(r) => r,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &format!("invalid JSON: {}", e)),
+    };
+
+    let mut domains: Vec<String> = Vec::new();
+    for d in request.domain.into_iter().chain(request.domains) {
+        let d = d.trim().trim_end_matches('.').to_lowercase();
+        if !is_domain(&d) {
+            return error(StatusCode::BAD_REQUEST, &format!("invalid domain: {}", d));
+        }
+        if !domains.contains(&d) {
+            domains.push(d);
+        }
+    }
+    if domains.is_empty() {
+        return error(StatusCode::BAD_REQUEST, "no domain given");
+    }
+
+    let info = {
+        let mut next = state.next_id.lock().unwrap_or_else(|e| e.into_inner());
+        let info = ScanInfo {
+            id: *next,
+            domains,
+            status: ScanStatus::Queued,
+            submitted_at: now(),
+            started_at: None,
+            finished_at: None,
+            found: 0,
+        };
+        // Reserve the queue slot first so a full queue leaves no trace
+        let permit = match state.queue.try_reserve() {
+            Ok(permit) => permit,
+            Err(_) => return error(StatusCode::SERVICE_UNAVAILABLE, "scan queue is full"),
+        };
+        *next += 1;
+        state.jobs().insert(
+            info.id,
+            Job {
+                info: info.clone(),
+                results: Vec::new(),
+            },
+        );
+        permit.send(info.id);
+        info
+    };
+    let mut res = json(StatusCode::ACCEPTED, &info);
+    if let Ok(location) = format!("/scans/{}", info.id).parse() {
+        res.headers_mut().insert(LOCATION, location);
+    }
+    res
+}
+
+/// Reads `body` whole, or answers `None` as soon as it runs past `limit`
+/// bytes.
+async fn read_limited(mut body: Body, limit: u64) -> hyper::Result<Option<Vec<u8>>> {
+    let mut read = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if (read.len() + chunk.len()) as u64 > limit {
+            return Ok(None);
+        }
+        read.extend_from_slice(&chunk);
+    }
+    Ok(Some(read))
+}
+
+fn results(state: &State, id: &str, query: Option<&str>) -> Response<Body> {
+    let format = match query_param(query, "format").map(str::parse::<OutputFormat>) {
+        None => OutputFormat::Json,
+        Some(Ok(f)) => f,
+        Some(Err(e)) => return error(StatusCode::BAD_REQUEST, &e),
+    };
+    let mut buf = Vec::new();
+    let written = {
+        let jobs = state.jobs();
+        let Some(job) = id.parse().ok().and_then(|id: u64| jobs.get(&id)) else {
+            return error(StatusCode::NOT_FOUND, "no such scan");
+        };
+        output::write_grouped(&mut buf, format, &job.results)
+    };
+    if let Err(e) = written {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string());
+    }
+    let content_type = match format {
+        OutputFormat::Json => "application/json",
+        OutputFormat::Jsonl | OutputFormat::Amass => "application/x-ndjson",
+        OutputFormat::Csv => "text/csv",
+        OutputFormat::Hosts => "text/plain",
+    };
+    response(StatusCode::OK, content_type, buf.into())
+}
+
+fn metrics_response() -> Response<Body> {
+    response(
+        StatusCode::OK,
+        "text/plain; version=0.0.4",
+        metrics::render().into(),
+    )
+}
+
+fn lookup(state: &State, id: &str) -> Option<ScanInfo> {
+    let id: u64 = id.parse().ok()?;
+    state.jobs().get(&id).map(|j| j.info.clone())
+}
+
+fn query_param<'a>(query: Option<&'a str>, name: &str) -> Option<&'a str> {
+    query?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+/// A plausible DNS name: dotted labels of letters, digits and hyphens.
+pub(crate) fn is_domain(name: &str) -> bool {
+    name.len() <= 253
+        && name.contains('.')
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+fn json<T: Serialize>(status: StatusCode, value: &T) -> Response<Body> {
+    match serde_json::to_vec_pretty(value) {
+        Ok(body) => response(status, "application/json", body.into()),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Body> {
+    let body = serde_json::json!({ "error": message }).to_string();
+    response(status, "application/json", body.into())
+}
+
+fn response(status: StatusCode, content_type: &'static str, body: Body) -> Response<Body> {
+    let mut res = Response::new(body);
+    *res.status_mut() = status;
+    res.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    res
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,115 @@
+use super::{filter_subs, DataSource, SOURCE_REQUEST_TIMEOUT};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::time::Duration;
+
+#[derive(Deserialize)]
+struct OtxUrl {
+    #[serde(default)]
+    hostname: String,
+}
+
+#[derive(Deserialize)]
+struct OtxUrlPage {
+    #[serde(default)]
+    url_list: Vec<OtxUrl>,
+    #[serde(default)]
+    has_next: bool,
+}
+
+#[derive(Deserialize)]
+struct OtxDnsRecord {
+    #[serde(default)]
+    hostname: String,
+}
+
+#[derive(Deserialize)]
+struct OtxPassiveDns {
+    #[serde(default)]
+    passive_dns: Vec<OtxDnsRecord>,
+}
+
+/// AlienVault OTX: `passive_dns` plus every page of `url_list`.
+pub struct AlienVault {
+    pub max_pages: usize,
+}
+
+impl Default for AlienVault {
+    fn default() -> Self {
+        Self { max_pages: 50 }
+    }
+}
+
+const OTX_API: &str = "https://otx.alienvault.com/api/v1/indicators/domain";
+
+#[async_trait]
+impl DataSource for AlienVault {
+    fn name(&self) -> &'static str {
+        "otx"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!("{}/{}/passive_dns", OTX_API, domain)
+    }
+
+    /// Passive DNS and then up to `max_pages` of URLs, one after another.
+    fn timeout(&self) -> Duration {
+        SOURCE_REQUEST_TIMEOUT * (self.max_pages as u32 + 1)
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let mut hosts = Vec::new();
+
+        // Passive DNS is usually the richer of the two; a failure in either
+        // only matters when nothing at all came back.
+        let mut first_err = match passive_dns(client, domain).await {
+            Ok(found) => {
+                hosts.extend(found);
+                None
+            }
+            Err(e) => Some(e),
+        };
+
+        for page in 1..=self.max_pages {
+            match url_page(client, domain, page).await {
+                Ok(resp) => {
+                    hosts.extend(resp.url_list.into_iter().map(|u| u.hostname));
+                    if !resp.has_next {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    first_err.get_or_insert(e);
+                    break;
+                }
+            }
+        }
+
+        if let (true, Some(e)) = (hosts.is_empty(), first_err) {
+            return Err(e);
+        }
+        Ok(filter_subs(hosts, domain))
+    }
+}
+
+async fn url_page(client: &Client, domain: &str, page: usize) -> Result<OtxUrlPage> {
+    let url = format!("{}/{}/url_list?limit=500&page={}", OTX_API, domain, page);
+    let res = client.get(&url).send().await?;
+    if !res.status().is_success() {
+        return Err(format!("HTTP {}", res.status()).into());
+    }
+    Ok(res.json().await?)
+}
+
+async fn passive_dns(client: &Client, domain: &str) -> Result<Vec<String>> {
+    let url = format!("{}/{}/passive_dns", OTX_API, domain);
+    let res = client.get(&url).send().await?;
+    if !res.status().is_success() {
+        return Err(format!("HTTP {}", res.status()).into());
+    }
+    let resp: OtxPassiveDns = res.json().await?;
+    Ok(resp.passive_dns.into_iter().map(|r| r.hostname).collect())
+}
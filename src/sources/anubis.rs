@@ -0,0 +1,29 @@
+use super::{check_rate_limit, filter_subs, parse_json, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use std::collections::HashSet;
+
+pub struct Anubis;
+
+#[async_trait]
+impl DataSource for Anubis {
+    fn name(&self) -> &'static str {
+        "anubis"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!("https://jldc.me/anubis/subdomains/{}", domain)
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let url = self.url(domain);
+        let res = client.get(&url).send().await?;
+        check_rate_limit(&res)?;
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()).into());
+        }
+        let entries: Vec<String> = parse_json(&res.text().await?)?;
+        Ok(filter_subs(entries, domain))
+    }
+}
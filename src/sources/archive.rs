@@ -0,0 +1,122 @@
+use super::{filter_subs, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Archive indexes are slow for large domains.
+const ARCHIVE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Hostnames of every parseable URL, with or without a scheme.
+fn hosts_from_urls<'a, I: IntoIterator<Item = &'a str>>(urls: I) -> Vec<String> {
+    urls.into_iter()
+        .filter_map(|raw| {
+            let raw = raw.trim();
+            let parsed = if raw.contains("://") {
+                Url::parse(raw)
+            } else {
+                Url::parse(&format!("http://{}", raw))
+            };
+            parsed.ok()?.host_str().map(|h| h.to_string())
+        })
+        .collect()
+}
+
+/// The Wayback Machine CDX index.
+pub struct Wayback;
+
+#[async_trait]
+impl DataSource for Wayback {
+    fn name(&self) -> &'static str {
+        "wayback"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!(
+            "https://web.archive.org/cdx/search/cdx?url=*.{}/*&output=txt&fl=original&collapse=urlkey",
+            domain
+        )
+    }
+
+    fn timeout(&self) -> Duration {
+        ARCHIVE_TIMEOUT * 3
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let url = self.url(domain);
+        let res = client.get(&url).timeout(ARCHIVE_TIMEOUT).send().await?;
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()).into());
+        }
+        let text = res.text().await?;
+        Ok(filter_subs(hosts_from_urls(text.lines()), domain))
+    }
+}
+
+#[derive(Deserialize)]
+struct CcCollection {
+    #[serde(rename = "cdx-api")]
+    cdx_api: String,
+}
+
+#[derive(Deserialize)]
+struct CcRecord {
+    url: String,
+}
+
+const CC_COLLECTIONS: &str = "https://index.commoncrawl.org/collinfo.json";
+
+/// The most recent Common Crawl CDX index.
+pub struct CommonCrawl;
+
+#[async_trait]
+impl DataSource for CommonCrawl {
+    fn name(&self) -> &'static str {
+        "commoncrawl"
+    }
+
+    /// The index to query is only known once this answers.
+    fn url(&self, _domain: &str) -> String {
+        CC_COLLECTIONS.to_string()
+    }
+
+    fn timeout(&self) -> Duration {
+        ARCHIVE_TIMEOUT * 3
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let collections: Vec<CcCollection> =
+            client.get(CC_COLLECTIONS).send().await?.json().await?;
+        let latest = collections.first().ok_or("no Common Crawl index")?;
+
+        let res = client
+            .get(&latest.cdx_api)
+            .query(&[
+                ("url", format!("*.{}", domain)),
+                ("output", "json".to_string()),
+                ("fl", "url".to_string()),
+            ])
+            .timeout(ARCHIVE_TIMEOUT)
+            .send()
+            .await?;
+        // 404 means the index simply has no captures for the domain
+        if res.status().as_u16() == 404 {
+            return Ok(HashSet::new());
+        }
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()).into());
+        }
+        let text = res.text().await?;
+        let urls: Vec<String> = text
+            .lines()
+            .filter_map(|l| serde_json::from_str::<CcRecord>(l).ok())
+            .map(|r| r.url)
+            .collect();
+        Ok(filter_subs(
+            hosts_from_urls(urls.iter().map(String::as_str)),
+            domain,
+        ))
+    }
+}
@@ -0,0 +1,30 @@
+use super::{scrape_hosts, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use std::collections::HashSet;
+
+/// dns.bufferover.run forward/reverse DNS data. Entries are `ip,host` pairs,
+/// so hostnames are pulled out of the raw body.
+pub struct BufferOver;
+
+#[async_trait]
+impl DataSource for BufferOver {
+    fn name(&self) -> &'static str {
+        "bufferover"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!("https://dns.bufferover.run/dns?q=.{}", domain)
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let url = self.url(domain);
+        let res = client.get(&url).send().await?;
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()).into());
+        }
+        let text = res.text().await?;
+        Ok(scrape_hosts(&text, domain))
+    }
+}
@@ -0,0 +1,150 @@
+//! On-disk cache of passive source answers, so repeat runs against the
+//! same domain don't hit rate-limited sources again.
+
+use super::DataSource;
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    /// Seconds since the Unix epoch.
+    fetched_at: u64,
+    subdomains: HashSet<String>,
+}
+
+/// One JSON file per source and domain under `dir`.
+#[derive(Debug, Clone)]
+pub struct SourceCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl SourceCache {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self { dir, ttl }
+    }
+
+    /// `$XDG_CACHE_HOME/subpeek/sources`, falling back to `~/.cache`.
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))
+            .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+        Some(base.join("subpeek").join("sources"))
+    }
+
+    fn path(&self, source: &str, domain: &str) -> PathBuf {
+        let safe: String = domain
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(source).join(format!("{}.json", safe))
+    }
+
+    /// A fresh cached answer, if there is one.
+    pub fn get(&self, source: &str, domain: &str) -> Option<HashSet<String>> {
+        let text = std::fs::read_to_string(self.path(source, domain)).ok()?;
+        let entry: Entry = serde_json::from_str(&text).ok()?;
+        let age = now().saturating_sub(entry.fetched_at);
+        (age < self.ttl.as_secs()).then_some(entry.subdomains)
+    }
+
+    pub fn put(&self, source: &str, domain: &str, subdomains: &HashSet<String>) -> Result<()> {
+        let path = self.path(source, domain);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let entry = Entry {
+            fetched_at: now(),
+            subdomains: subdomains.clone(),
+        };
+        write_atomic(&path, &serde_json::to_vec(&entry)?)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// A source answered from the cache while its entry is fresh. Failed and
+/// empty answers are never cached.
+pub struct Cached {
+    pub inner: Arc<dyn DataSource>,
+    pub cache: SourceCache,
+}
+
+#[async_trait]
+impl DataSource for Cached {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn url(&self, domain: &str) -> String {
+        self.inner.url(domain)
+    }
+
+    fn timeout(&self) -> Duration {
+        self.inner.timeout()
+    }
+
+    fn retries(&self) -> u32 {
+        self.inner.retries()
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let name = self.inner.name();
+        if let Some(hit) = self.cache.get(name, domain) {
+            debug!("{} answered from cache for {}", name, domain);
+            return Ok(hit);
+        }
+        let subs = self.inner.fetch(client, domain).await?;
+        if !subs.is_empty() {
+            if let Err(e) = self.cache.put(name, domain, &subs) {
+                warn!("Could not cache {} results: {}", name, e);
+            }
+        }
+        Ok(subs)
+    }
+}
+
+/// Wraps every source in [`Cached`].
+pub fn with_cache(
+    sources: Vec<Arc<dyn DataSource>>,
+    cache: &SourceCache,
+) -> Vec<Arc<dyn DataSource>> {
+    sources
+        .into_iter()
+        .map(|inner| {
+            Arc::new(Cached {
+                inner,
+                cache: cache.clone(),
+            }) as Arc<dyn DataSource>
+        })
+        .collect()
+}
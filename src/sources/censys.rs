@@ -0,0 +1,97 @@
+use super::{filter_subs, with_query, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+#[derive(Deserialize)]
+struct CensysHit {
+    #[serde(default)]
+    names: Vec<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct CensysLinks {
+    #[serde(default)]
+    next: String,
+}
+
+#[derive(Deserialize)]
+struct CensysResult {
+    #[serde(default)]
+    hits: Vec<CensysHit>,
+    #[serde(default)]
+    links: CensysLinks,
+}
+
+#[derive(Deserialize)]
+struct CensysResp {
+    result: CensysResult,
+}
+
+/// Censys certificate search, pulling SANs and walking the result cursor.
+pub struct Censys {
+    pub api_id: String,
+    pub api_secret: String,
+    pub max_pages: usize,
+}
+
+impl Censys {
+    pub fn new(api_id: String, api_secret: String) -> Self {
+        Self {
+            api_id,
+            api_secret,
+            max_pages: 10,
+        }
+    }
+}
+
+const CENSYS_SEARCH: &str = "https://search.censys.io/api/v2/certificates/search";
+
+#[async_trait]
+impl DataSource for Censys {
+    fn name(&self) -> &'static str {
+        "censys"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        with_query(
+            CENSYS_SEARCH,
+            &[("q", &format!("names: {}", domain)), ("per_page", "100")],
+        )
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let query = format!("names: {}", domain);
+        let mut cursor = String::new();
+        let mut hosts = Vec::new();
+
+        for _ in 0..self.max_pages {
+            let mut req = client
+                .get(CENSYS_SEARCH)
+                .basic_auth(&self.api_id, Some(&self.api_secret))
+                .query(&[("q", query.as_str()), ("per_page", "100")]);
+            if !cursor.is_empty() {
+                req = req.query(&[("cursor", cursor.as_str())]);
+            }
+            let res = req.send().await?;
+            if !res.status().is_success() {
+                if hosts.is_empty() {
+                    return Err(format!("HTTP {}", res.status()).into());
+                }
+                break;
+            }
+            let resp: CensysResp = res.json().await?;
+            for hit in resp.result.hits {
+                hosts.extend(hit.names.into_iter().filter(|n| !n.contains('*')));
+            }
+            cursor = resp.result.links.next;
+            if cursor.is_empty() {
+                break;
+            }
+        }
+
+        Ok(filter_subs(hosts, domain))
+    }
+}
@@ -0,0 +1,92 @@
+use super::{filter_subs, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::header::LINK;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+#[derive(Deserialize)]
+struct Issuance {
+    #[serde(default)]
+    dns_names: Vec<String>,
+}
+
+/// SSLMate CertSpotter issuances, following `Link: <...>; rel="next"`.
+pub struct CertSpotter {
+    pub api_key: Option<String>,
+    pub max_pages: usize,
+}
+
+impl CertSpotter {
+    pub fn new(api_key: Option<String>) -> Self {
+        Self {
+            api_key,
+            max_pages: 20,
+        }
+    }
+}
+
+#[async_trait]
+impl DataSource for CertSpotter {
+    fn name(&self) -> &'static str {
+        "certspotter"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!(
+            "https://api.certspotter.com/v1/issuances?domain={}&include_subdomains=true&expand=dns_names",
+            domain
+        )
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let mut url = Some(self.url(domain));
+        let mut hosts = Vec::new();
+
+        for _ in 0..self.max_pages {
+            let Some(current) = url.take() else {
+                break;
+            };
+            let mut req = client.get(&current);
+            if let Some(ref key) = self.api_key {
+                req = req.bearer_auth(key);
+            }
+            let res = req.send().await?;
+            if !res.status().is_success() {
+                if hosts.is_empty() {
+                    return Err(format!("HTTP {}", res.status()).into());
+                }
+                break;
+            }
+            url = res
+                .headers()
+                .get(LINK)
+                .and_then(|v| v.to_str().ok())
+                .and_then(next_link);
+            let issuances: Vec<Issuance> = res.json().await?;
+            for i in issuances {
+                hosts.extend(i.dns_names.into_iter().filter(|n| !n.contains('*')));
+            }
+        }
+
+        Ok(filter_subs(hosts, domain))
+    }
+}
+
+/// Extracts the `rel="next"` target from an RFC 8288 `Link` header.
+fn next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|part| {
+        let (target, params) = part.split_once(';')?;
+        let is_next = params
+            .split(';')
+            .any(|p| matches!(p.trim(), "rel=\"next\"" | "rel=next"));
+        is_next.then(|| {
+            target
+                .trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_string()
+        })
+    })
+}
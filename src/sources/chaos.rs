@@ -0,0 +1,48 @@
+use super::{filter_subs, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+#[derive(Deserialize)]
+struct ChaosResp {
+    #[serde(default)]
+    subdomains: Vec<String>,
+}
+
+/// ProjectDiscovery's Chaos dataset.
+pub struct Chaos {
+    pub api_key: String,
+}
+
+#[async_trait]
+impl DataSource for Chaos {
+    fn name(&self) -> &'static str {
+        "chaos"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!("https://dns.projectdiscovery.io/dns/{}/subdomains", domain)
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let url = self.url(domain);
+        let res = client
+            .get(&url)
+            .header("Authorization", &self.api_key)
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()).into());
+        }
+        let resp: ChaosResp = res.json().await?;
+        let hosts = resp
+            .subdomains
+            .into_iter()
+            .filter(|label| !label.is_empty() && !label.contains('*'))
+            .map(|label| format!("{}.{}", label, domain))
+            .collect();
+        Ok(filter_subs(hosts, domain))
+    }
+}
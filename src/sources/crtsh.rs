@@ -0,0 +1,291 @@
+use super::{check_rate_limit, parse_json, with_query, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout};
+use tracing::warn;
+
+#[derive(Deserialize)]
+struct CrtShEntry {
+    name_value: String,
+}
+
+/// crt.sh certificate transparency search.
+///
+/// The single `%.domain` query times out for large organisations, so on
+/// failure it is retried with backoff, then split into one query per
+/// leading character, and finally (when enabled) answered from crt.sh's
+/// public PostgreSQL replica.
+pub struct CrtSh {
+    pub retries: u32,
+    pub backoff: Duration,
+    pub postgres_fallback: bool,
+    /// Tunnelled through with `CONNECT` for the PostgreSQL fallback.
+    pub proxy: Option<Url>,
+}
+
+impl Default for CrtSh {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            backoff: Duration::from_secs(2),
+            postgres_fallback: false,
+            proxy: None,
+        }
+    }
+}
+
+/// First characters used to split one big query into smaller ones.
+const CHUNK_PREFIXES: &str = "abcdefghijklmnopqrstuvwxyz0123456789";
+
+#[async_trait]
+impl DataSource for CrtSh {
+    fn name(&self) -> &'static str {
+        "crtsh"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        with_query(
+            "https://crt.sh/",
+            &[("q", &format!("%.{}", domain)), ("output", "json")],
+        )
+    }
+
+    /// Retries, chunked queries and the PostgreSQL fallback all take time.
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(15 * 60)
+    }
+
+    fn retries(&self) -> u32 {
+        0
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let full = self
+            .query_with_retry(client, &format!("%.{}", domain), domain)
+            .await;
+        let err = match full {
+            Ok(subs) => return Ok(subs),
+            Err(e) => e,
+        };
+        warn!(
+            "crt.sh full query failed ({}), trying chunked queries...",
+            err
+        );
+
+        let mut subs = HashSet::new();
+        let mut failed_chunks = 0;
+        for c in CHUNK_PREFIXES.chars() {
+            match self
+                .query_with_retry(client, &format!("{}%.{}", c, domain), domain)
+                .await
+            {
+                Ok(s) => subs.extend(s),
+                Err(_) => failed_chunks += 1,
+            }
+        }
+        if failed_chunks == 0 {
+            return Ok(subs);
+        }
+
+        if self.postgres_fallback {
+            warn!(
+                "{} crt.sh chunks failed, querying PostgreSQL...",
+                failed_chunks
+            );
+            match timeout(
+                Duration::from_secs(120),
+                query_postgres(domain, self.proxy.as_ref()),
+            )
+            .await
+            {
+                Ok(Ok(pg)) => {
+                    subs.extend(pg);
+                    return Ok(subs);
+                }
+                Ok(Err(e)) => warn!("crt.sh PostgreSQL failed: {}", e),
+                Err(_) => warn!("crt.sh PostgreSQL timed out"),
+            }
+        }
+
+        if subs.is_empty() {
+            return Err(err);
+        }
+        Ok(subs)
+    }
+}
+
+impl CrtSh {
+    async fn query_with_retry(
+        &self,
+        client: &Client,
+        query: &str,
+        domain: &str,
+    ) -> Result<HashSet<String>> {
+        let mut delay = self.backoff;
+        let mut attempt = 0;
+        loop {
+            match query_json(client, query, domain).await {
+                Ok(subs) => return Ok(subs),
+                Err(e) if attempt >= self.retries => return Err(e),
+                Err(_) => {
+                    sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+async fn query_json(client: &Client, query: &str, domain: &str) -> Result<HashSet<String>> {
+    let res = client
+        .get("https://crt.sh/")
+        .query(&[("q", query), ("output", "json")])
+        .timeout(Duration::from_secs(60))
+        .send()
+        .await?;
+    check_rate_limit(&res)?;
+    if !res.status().is_success() {
+        return Err(format!("HTTP {}", res.status()).into());
+    }
+
+    let text = res.text().await?;
+    let entries: Vec<CrtShEntry> = parse_json(&text)?;
+    Ok(names_in_scope(
+        entries.iter().map(|e| e.name_value.as_str()),
+        domain,
+    ))
+}
+
+fn names_in_scope<'a, I: IntoIterator<Item = &'a str>>(values: I, domain: &str) -> HashSet<String> {
+    let mut subs = HashSet::new();
+    let suffix = format!(".{}", domain);
+    for value in values {
+        for line in value.split('\n') {
+            let clean = line.trim().to_lowercase();
+            if !clean.contains('*') && (clean == domain || clean.ends_with(&suffix)) {
+                subs.insert(clean);
+            }
+        }
+    }
+    subs
+}
+
+// --- PostgreSQL fallback ---
+//
+// crt.sh exposes its database read-only as `guest@crt.sh:5432/certwatch`
+// without a password. Only the startup handshake and the simple query
+// protocol are needed, so they're spoken directly.
+
+const PG_HOST: &str = "crt.sh";
+const PG_PORT: u16 = 5432;
+/// Largest backend message read; a row of names is far smaller.
+const PG_MAX_MESSAGE: usize = 1024 * 1024;
+
+async fn query_postgres(domain: &str, proxy: Option<&Url>) -> Result<HashSet<String>> {
+    if !domain
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+    {
+        return Err("refusing to embed unusual domain in SQL".into());
+    }
+    let sql = format!(
+        "SELECT ci.NAME_VALUE FROM certificate_and_identities ci \
+         WHERE plainto_tsquery('certwatch', '{d}') @@ identities(ci.CERTIFICATE) \
+         AND ci.NAME_VALUE ILIKE '%.{d}'",
+        d = domain
+    );
+
+    let mut stream = crate::proxy::connect(proxy, PG_HOST, PG_PORT).await?;
+
+    let mut startup = Vec::new();
+    startup.extend_from_slice(&196608u32.to_be_bytes()); // protocol 3.0
+    for (k, v) in [
+        ("user", "guest"),
+        ("database", "certwatch"),
+        ("application_name", "subpeek"),
+    ] {
+        startup.extend_from_slice(k.as_bytes());
+        startup.push(0);
+        startup.extend_from_slice(v.as_bytes());
+        startup.push(0);
+    }
+    startup.push(0);
+    stream.write_u32(startup.len() as u32 + 4).await?;
+    stream.write_all(&startup).await?;
+    read_until_ready(&mut stream, &mut Vec::new()).await?;
+
+    let mut query = sql.into_bytes();
+    query.push(0);
+    stream.write_u8(b'Q').await?;
+    stream.write_u32(query.len() as u32 + 4).await?;
+    stream.write_all(&query).await?;
+
+    let mut rows = Vec::new();
+    read_until_ready(&mut stream, &mut rows).await?;
+
+    // Terminate
+    stream.write_u8(b'X').await?;
+    stream.write_u32(4).await?;
+
+    Ok(names_in_scope(rows.iter().map(String::as_str), domain))
+}
+
+/// Reads backend messages until ReadyForQuery, collecting the first column
+/// of every DataRow.
+async fn read_until_ready(stream: &mut TcpStream, rows: &mut Vec<String>) -> Result<()> {
+    loop {
+        let tag = stream.read_u8().await?;
+        let len = stream.read_u32().await? as usize;
+        if len > PG_MAX_MESSAGE {
+            return Err(format!("PostgreSQL message of {} bytes is too large", len).into());
+        }
+        let mut body = vec![0u8; len.saturating_sub(4)];
+        stream.read_exact(&mut body).await?;
+
+        match tag {
+            b'R' => {
+                let code = u32::from_be_bytes(body.get(..4).ok_or("short auth")?.try_into()?);
+                if code != 0 {
+                    return Err(format!("unsupported PostgreSQL auth method {}", code).into());
+                }
+            }
+            b'D' => {
+                if let Some(value) = first_column(&body) {
+                    rows.push(value);
+                }
+            }
+            b'E' => return Err(error_message(&body).into()),
+            b'Z' => return Ok(()),
+            // ParameterStatus, BackendKeyData, RowDescription, CommandComplete, notices
+            _ => {}
+        }
+    }
+}
+
+fn first_column(body: &[u8]) -> Option<String> {
+    let cols = u16::from_be_bytes(body.get(..2)?.try_into().ok()?);
+    if cols == 0 {
+        return None;
+    }
+    let len = i32::from_be_bytes(body.get(2..6)?.try_into().ok()?);
+    if len < 0 {
+        return None;
+    }
+    let value = body.get(6..6 + len as usize)?;
+    Some(String::from_utf8_lossy(value).into_owned())
+}
+
+/// The `M` (message) field of an ErrorResponse.
+fn error_message(body: &[u8]) -> String {
+    body.split(|b| *b == 0)
+        .find_map(|field| field.strip_prefix(b"M"))
+        .map(|m| String::from_utf8_lossy(m).into_owned())
+        .unwrap_or_else(|| "PostgreSQL error".to_string())
+}
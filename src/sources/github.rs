@@ -0,0 +1,99 @@
+use super::{scrape_hosts, with_query, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::time::Duration;
+
+#[derive(Deserialize)]
+struct TextMatch {
+    #[serde(default)]
+    fragment: String,
+}
+
+#[derive(Deserialize)]
+struct CodeItem {
+    #[serde(default)]
+    text_matches: Vec<TextMatch>,
+}
+
+#[derive(Deserialize)]
+struct CodeSearchResp {
+    #[serde(default)]
+    items: Vec<CodeItem>,
+}
+
+/// GitHub code search for hostnames leaked into public repositories.
+pub struct GitHub {
+    pub token: String,
+    /// Code search allows 10 authenticated requests per minute.
+    pub interval: Duration,
+    pub max_pages: usize,
+}
+
+impl GitHub {
+    pub fn new(token: String) -> Self {
+        Self {
+            token,
+            interval: Duration::from_secs(6),
+            max_pages: 5,
+        }
+    }
+}
+
+const GITHUB_SEARCH: &str = "https://api.github.com/search/code";
+
+#[async_trait]
+impl DataSource for GitHub {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        with_query(
+            GITHUB_SEARCH,
+            &[("q", &format!("\"{}\"", domain)), ("per_page", "100")],
+        )
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let query = format!("\"{}\"", domain);
+        let mut subs = HashSet::new();
+
+        for page in 1..=self.max_pages {
+            if page > 1 {
+                tokio::time::sleep(self.interval).await;
+            }
+            let page_str = page.to_string();
+            let res = client
+                .get(GITHUB_SEARCH)
+                .query(&[
+                    ("q", query.as_str()),
+                    ("per_page", "100"),
+                    ("page", page_str.as_str()),
+                ])
+                .header("Accept", "application/vnd.github.v3.text-match+json")
+                .bearer_auth(&self.token)
+                .send()
+                .await?;
+            if !res.status().is_success() {
+                if subs.is_empty() {
+                    return Err(format!("HTTP {}", res.status()).into());
+                }
+                break;
+            }
+            let resp: CodeSearchResp = res.json().await?;
+            if resp.items.is_empty() {
+                break;
+            }
+            for item in resp.items {
+                for m in item.text_matches {
+                    subs.extend(scrape_hosts(&m.fragment, domain));
+                }
+            }
+        }
+
+        Ok(subs)
+    }
+}
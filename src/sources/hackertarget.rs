@@ -0,0 +1,43 @@
+use super::{check_rate_limit, check_rate_limit_body, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use std::collections::HashSet;
+
+pub struct HackerTarget;
+
+#[async_trait]
+impl DataSource for HackerTarget {
+    fn name(&self) -> &'static str {
+        "hackertarget"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!("https://api.hackertarget.com/hostsearch/?q={}", domain)
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let url = self.url(domain);
+        let res = client.get(&url).send().await?;
+        check_rate_limit(&res)?;
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()).into());
+        }
+        let text = res.text().await?;
+        check_rate_limit_body(&text)?;
+        if text.starts_with("error") {
+            return Err(text.lines().next().unwrap_or_default().to_string().into());
+        }
+        let mut subs = HashSet::new();
+        let suffix = format!(".{}", domain);
+        for line in text.lines() {
+            if let Some(host) = line.split(',').next() {
+                let clean = host.trim().to_lowercase();
+                if clean == domain || clean.ends_with(&suffix) {
+                    subs.insert(clean);
+                }
+            }
+        }
+        Ok(subs)
+    }
+}
@@ -0,0 +1,568 @@
+mod alienvault;
+mod anubis;
+mod archive;
+mod bufferover;
+pub mod cache;
+mod censys;
+mod certspotter;
+mod chaos;
+mod crtsh;
+mod github;
+mod hackertarget;
+pub mod plugin;
+mod rapiddns;
+mod securitytrails;
+mod shodan;
+mod sublist3r;
+mod urlscan;
+mod virustotal;
+
+pub use alienvault::AlienVault;
+pub use anubis::Anubis;
+pub use archive::{CommonCrawl, Wayback};
+pub use bufferover::BufferOver;
+pub use censys::Censys;
+pub use certspotter::CertSpotter;
+pub use chaos::Chaos;
+pub use crtsh::CrtSh;
+pub use github::GitHub;
+pub use hackertarget::HackerTarget;
+pub use rapiddns::RapidDns;
+pub use securitytrails::SecurityTrails;
+pub use shodan::Shodan;
+pub use sublist3r::Sublist3r;
+pub use urlscan::UrlScan;
+pub use virustotal::VirusTotal;
+
+use crate::deadline::Deadline;
+use crate::options::DEFAULT_USER_AGENT;
+use crate::{metrics, Result, ScanOptions};
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::{Client, Url};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// A passive source of subdomains.
+#[async_trait]
+pub trait DataSource: Send + Sync {
+    /// Short identifier used in logs and on the command line.
+    fn name(&self) -> &'static str;
+
+    /// The first URL `fetch` requests for `domain`, without credentials,
+    /// for `--dry-run`. Paged and chunked sources go on from there.
+    fn url(&self, domain: &str) -> String;
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>>;
+
+    /// Upper bound for one `fetch`, including any retries it does itself.
+    fn timeout(&self) -> Duration {
+        DEFAULT_SOURCE_TIMEOUT
+    }
+
+    /// Further attempts after a rate limit or a dropped connection.
+    fn retries(&self) -> u32 {
+        2
+    }
+}
+
+pub const DEFAULT_SOURCE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Upper bound for each request on [`source_client`].
+pub const SOURCE_REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Wait before retrying a rate-limited source when it doesn't say how long,
+/// doubled for each further attempt.
+const SOURCE_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Longest wait honoured from a `Retry-After` header.
+const MAX_SOURCE_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A source refused the query for exceeding its rate limit or quota.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Option<Duration>,
+    pub detail: String,
+}
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rate limited: {}", self.detail)
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// A source answered with something other than what it is parsed as: an
+/// HTML error page, an error object instead of a list. Usually a passing
+/// outage, so it is retried; when it persists the API may have changed.
+#[derive(Debug)]
+pub struct UnexpectedResponse {
+    pub detail: String,
+}
+
+impl std::fmt::Display for UnexpectedResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unexpected response: {}", self.detail)
+    }
+}
+
+impl std::error::Error for UnexpectedResponse {}
+
+/// Characters of an unexpected body quoted in the error.
+const QUOTED_BODY: usize = 80;
+
+/// Parses `text` as `T`. A body that isn't `T` fails with [`RateLimited`]
+/// when it is a quota message and [`UnexpectedResponse`] otherwise, so
+/// garbage never passes for an answer without results.
+pub(crate) fn parse_json<T: serde::de::DeserializeOwned>(text: &str) -> Result<T> {
+    let err = match serde_json::from_str(text) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+    check_rate_limit_body(text)?;
+    let trimmed = text.trim_start();
+    let detail = if trimmed.is_empty() {
+        "empty body".to_string()
+    } else if trimmed.starts_with('<') {
+        match crate::title::extract(text) {
+            Some(title) => format!("HTML page \"{}\"", title),
+            None => "HTML page".to_string(),
+        }
+    } else if let Some(message) = error_message(text) {
+        format!("error \"{}\"", message)
+    } else {
+        let quoted: String = trimmed.chars().take(QUOTED_BODY).collect();
+        format!("{} ({})", quoted.lines().next().unwrap_or_default(), err)
+    };
+    Err(UnexpectedResponse { detail }.into())
+}
+
+/// The `error` or `message` of a JSON error object.
+fn error_message(text: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(text).ok()?;
+    ["error", "message", "detail"].iter().find_map(|key| {
+        let field = value.get(key)?;
+        Some(match field.as_str() {
+            Some(s) => s.to_string(),
+            None => field.to_string(),
+        })
+    })
+}
+
+/// `base` with `params` as its query string, for [`DataSource::url`].
+pub(crate) fn with_query(base: &str, params: &[(&str, &str)]) -> String {
+    Url::parse_with_params(base, params)
+        .map(String::from)
+        .unwrap_or_else(|_| base.to_string())
+}
+
+/// Fails with [`RateLimited`] on a 429, honouring `Retry-After` seconds.
+pub(crate) fn check_rate_limit(res: &reqwest::Response) -> Result<()> {
+    if res.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Ok(());
+    }
+    let retry_after = res
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs);
+    Err(RateLimited {
+        retry_after,
+        detail: format!("HTTP {}", res.status()),
+    }
+    .into())
+}
+
+/// Fails with [`RateLimited`] when a 200 body is really a quota message,
+/// as HackerTarget's `API count exceeded` is.
+pub(crate) fn check_rate_limit_body(body: &str) -> Result<()> {
+    const MARKERS: &[&str] = &[
+        "api count exceeded",
+        "rate limit",
+        "too many requests",
+        "quota exceeded",
+        "limit exceeded",
+    ];
+    let head: String = body.chars().take(300).collect::<String>().to_lowercase();
+    match MARKERS.iter().find(|m| head.contains(*m)) {
+        Some(_) => Err(RateLimited {
+            retry_after: None,
+            detail: head.lines().next().unwrap_or_default().trim().to_string(),
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+fn is_rate_limited(err: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    err.downcast_ref::<RateLimited>().is_some() || err.to_string().starts_with("HTTP 429")
+}
+
+fn is_retryable(err: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    is_rate_limited(err)
+        || err.downcast_ref::<UnexpectedResponse>().is_some()
+        || err
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_timeout() || e.is_connect())
+}
+
+/// Runs one source under its timeout, backing off and retrying while it
+/// is rate limited or the connection drops.
+async fn fetch_with_policy(
+    source: &dyn DataSource,
+    client: &Client,
+    domain: &str,
+) -> Result<HashSet<String>> {
+    let mut delay = SOURCE_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        let err = match tokio::time::timeout(source.timeout(), source.fetch(client, domain)).await {
+            Ok(Ok(subs)) => return Ok(subs),
+            Ok(Err(e)) => e,
+            Err(_) => return Err(format!("timed out after {}s", source.timeout().as_secs()).into()),
+        };
+        if attempt >= source.retries() || !is_retryable(&*err) {
+            return Err(err);
+        }
+        let wait = err
+            .downcast_ref::<RateLimited>()
+            .and_then(|r| r.retry_after)
+            .unwrap_or(delay)
+            .min(MAX_SOURCE_BACKOFF);
+        debug!(
+            "{} failed ({}), retrying in {}s",
+            source.name(),
+            err,
+            wait.as_secs()
+        );
+        tokio::time::sleep(wait).await;
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+/// Every built-in source. New sources only need to be added here.
+///
+/// Authenticated sources are only included when their API key is set.
+pub fn registry(options: &ScanOptions) -> Vec<Arc<dyn DataSource>> {
+    let keys = &options.api_keys;
+    let mut sources: Vec<Arc<dyn DataSource>> = vec![
+        Arc::new(CrtSh {
+            postgres_fallback: options.crtsh_postgres,
+            proxy: options.proxy.clone(),
+            ..CrtSh::default()
+        }),
+        Arc::new(Anubis),
+        Arc::new(HackerTarget),
+        Arc::new(Sublist3r),
+        Arc::new(AlienVault::default()),
+        Arc::new(Wayback),
+        Arc::new(CommonCrawl),
+        Arc::new(RapidDns),
+        Arc::new(BufferOver),
+        Arc::new(UrlScan::new(keys.get("urlscan"))),
+        Arc::new(CertSpotter::new(keys.get("certspotter"))),
+    ];
+    if let Some(key) = keys.get("virustotal") {
+        sources.push(Arc::new(VirusTotal::new(key)));
+    }
+    if let Some(api_key) = keys.get("securitytrails") {
+        sources.push(Arc::new(SecurityTrails { api_key }));
+    }
+    if let (Some(id), Some(secret)) = (keys.get("censys_id"), keys.get("censys_secret")) {
+        sources.push(Arc::new(Censys::new(id, secret)));
+    }
+    if let Some(api_key) = keys.get("shodan") {
+        sources.push(Arc::new(Shodan { api_key }));
+    }
+    if let Some(api_key) = keys.get("chaos") {
+        sources.push(Arc::new(Chaos { api_key }));
+    }
+    if let Some(token) = keys.get("github") {
+        sources.push(Arc::new(GitHub::new(token)));
+    }
+    if let Some(ref dir) = options.plugin_dir {
+        match plugin::load_dir(dir) {
+            Ok(plugins) => {
+                for p in plugins {
+                    // A built-in keeps its name, keyed or not
+                    let taken = KEYED_SOURCES.contains(&p.name())
+                        || sources.iter().any(|s| s.name() == p.name());
+                    if taken {
+                        debug!("Plugin {} shadows a built-in source, skipped", p.name());
+                    } else {
+                        sources.push(Arc::new(p));
+                    }
+                }
+            }
+            Err(e) => warn!("Could not read plugins from {}: {}", dir.display(), e),
+        }
+    }
+    sources
+}
+
+/// Sources that are only registered when their API key is configured.
+pub const KEYED_SOURCES: &[&str] = &[
+    "virustotal",
+    "securitytrails",
+    "censys",
+    "shodan",
+    "chaos",
+    "github",
+];
+
+/// Every source name accepted by `--sources`, keyed or not, plus the
+/// plugins of `options`.
+pub fn known_names(options: &ScanOptions) -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = registry(options).iter().map(|s| s.name()).collect();
+    for name in KEYED_SOURCES {
+        if !names.contains(name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Narrows `sources` to `include` (all when empty) minus `exclude`.
+pub fn select(
+    sources: Vec<Arc<dyn DataSource>>,
+    include: &[String],
+    exclude: &[String],
+) -> Vec<Arc<dyn DataSource>> {
+    for name in include {
+        if KEYED_SOURCES.contains(&name.as_str()) && !sources.iter().any(|s| s.name() == name) {
+            warn!(
+                "Source {} was requested but has no API key configured.",
+                name
+            );
+        }
+    }
+    sources
+        .into_iter()
+        .filter(|s| include.is_empty() || include.iter().any(|n| n == s.name()))
+        .filter(|s| !exclude.iter().any(|n| n == s.name()))
+        .collect()
+}
+
+/// How one source fared during discovery.
+#[derive(Debug, Clone)]
+pub struct SourceReport {
+    pub name: &'static str,
+    /// Distinct in-scope hosts returned.
+    pub found: usize,
+    /// Hosts no other source returned.
+    pub unique: usize,
+    pub duration: Duration,
+    pub error: Option<String>,
+    /// The source was still refusing for its rate limit or quota after the
+    /// retries, so `found` says nothing about the domain.
+    pub rate_limited: bool,
+}
+
+/// The merged output of a discovery run.
+#[derive(Debug, Clone, Default)]
+pub struct Discovery {
+    pub subdomains: HashSet<String>,
+    /// The sources that returned each of `subdomains`.
+    pub found_by: HashMap<String, Vec<&'static str>>,
+    pub reports: Vec<SourceReport>,
+}
+
+pub async fn fetch_all_subdomains(domain: &str) -> HashSet<String> {
+    let client = source_client(DEFAULT_USER_AGENT, None);
+    fetch_from_sources(&client, registry(&ScanOptions::default()), domain)
+        .await
+        .subdomains
+}
+
+/// The shared client passive sources are queried with.
+pub fn source_client(user_agent: &str, proxy: Option<&Url>) -> Client {
+    crate::proxy::apply(Client::builder(), proxy)
+        .timeout(SOURCE_REQUEST_TIMEOUT)
+        .user_agent(user_agent)
+        .build()
+        .unwrap_or_default()
+}
+
+pub async fn fetch_from_sources(
+    client: &Client,
+    sources: Vec<Arc<dyn DataSource>>,
+    domain: &str,
+) -> Discovery {
+    fetch_from_sources_until(client, sources, domain, Deadline::NONE).await
+}
+
+/// Like [`fetch_from_sources`], giving up on the sources still running
+/// once `deadline` and its grace period have passed. They are reported as
+/// failed.
+pub async fn fetch_from_sources_until(
+    client: &Client,
+    sources: Vec<Arc<dyn DataSource>>,
+    domain: &str,
+    deadline: Deadline,
+) -> Discovery {
+    let client = Arc::new(client.clone());
+    let mut handles = Vec::new();
+    let started = Instant::now();
+
+    for source in sources {
+        let c = client.clone();
+        let d = domain.to_string();
+        let name = source.name();
+        let handle = tokio::spawn(async move {
+            let started = Instant::now();
+            debug!("Querying {} for {}", source.name(), d);
+            let result = fetch_with_policy(source.as_ref(), &c, &d).await;
+            match result {
+                Ok(ref subs) => {
+                    debug!("{} returned {} names", source.name(), subs.len());
+                    metrics::source_answered(source.name(), subs.len());
+                }
+                Err(ref e) if e.downcast_ref::<UnexpectedResponse>().is_some() => {
+                    warn!(
+                        "{} still answers in an unexpected format after retries, its API may have changed: {}",
+                        source.name(),
+                        e
+                    );
+                    metrics::source_failed(source.name(), false);
+                }
+                Err(ref e) => {
+                    debug!("{} failed: {}", source.name(), e);
+                    metrics::source_failed(source.name(), is_rate_limited(&**e));
+                }
+            }
+            (source.name(), result, started.elapsed())
+        });
+        handles.push((name, handle));
+    }
+
+    let mut per_source = Vec::new();
+    for (name, mut h) in handles {
+        match deadline.within(&mut h).await {
+            Some(Ok(outcome)) => per_source.push(outcome),
+            Some(Err(_)) => {}
+            None => {
+                h.abort();
+                debug!("{} cut off at the discovery deadline", name);
+                let cut: Result<HashSet<String>> = Err("stopped at the discovery deadline".into());
+                per_source.push((name, cut, started.elapsed()));
+            }
+        }
+    }
+
+    let mut seen_by: HashMap<&String, usize> = HashMap::new();
+    for (_, result, _) in &per_source {
+        if let Ok(subs) = result {
+            for sub in subs {
+                *seen_by.entry(sub).or_default() += 1;
+            }
+        }
+    }
+
+    let mut reports = Vec::new();
+    for (name, result, duration) in &per_source {
+        let report = match result {
+            Ok(subs) => SourceReport {
+                name,
+                found: subs.len(),
+                unique: subs.iter().filter(|s| seen_by.get(s) == Some(&1)).count(),
+                duration: *duration,
+                error: None,
+                rate_limited: false,
+            },
+            Err(e) => SourceReport {
+                name,
+                found: 0,
+                unique: 0,
+                duration: *duration,
+                error: Some(e.to_string()),
+                rate_limited: is_rate_limited(&**e),
+            },
+        };
+        reports.push(report);
+    }
+
+    let mut found_by: HashMap<String, Vec<&'static str>> = HashMap::new();
+    for (name, result, _) in per_source {
+        for sub in result.into_iter().flatten() {
+            found_by.entry(sub).or_default().push(name);
+        }
+    }
+    let subdomains = found_by.keys().cloned().collect();
+
+    print_source_summary(&reports);
+    Discovery {
+        subdomains,
+        found_by,
+        reports,
+    }
+}
+
+pub fn print_source_summary(reports: &[SourceReport]) {
+    info!("Source summary:");
+    for r in reports {
+        match r.error {
+            Some(ref e) if r.rate_limited => warn!(
+                "  {:<15} rate limited after {:.1}s, results missing: {}",
+                r.name,
+                r.duration.as_secs_f64(),
+                e
+            ),
+            Some(ref e) => info!(
+                "  {:<15} failed after {:.1}s: {}",
+                r.name,
+                r.duration.as_secs_f64(),
+                e
+            ),
+            None => info!(
+                "  {:<15} {:>6} hosts {:>6} unique {:>6.1}s",
+                r.name,
+                r.found,
+                r.unique,
+                r.duration.as_secs_f64()
+            ),
+        }
+    }
+}
+
+pub fn filter_subs(raw: Vec<String>, domain: &str) -> HashSet<String> {
+    let mut s = HashSet::new();
+    let suffix = format!(".{}", domain);
+    for r in raw {
+        let clean = r.trim().to_lowercase();
+        if clean == domain || clean.ends_with(&suffix) {
+            s.insert(clean);
+        }
+    }
+    s
+}
+
+/// Every hostname under `domain` mentioned anywhere in `text` (HTML, JSON,
+/// CSV...). Useful for sources without a structured API.
+pub fn scrape_hosts(text: &str, domain: &str) -> HashSet<String> {
+    let pattern = format!(
+        r"(?i)(?:[a-z0-9_](?:[a-z0-9_-]*[a-z0-9])?\.)*{}",
+        regex::escape(domain)
+    );
+    let Ok(re) = Regex::new(&pattern) else {
+        return HashSet::new();
+    };
+    let found: Vec<String> = re
+        .find_iter(text)
+        .filter(|m| !preceded_by_label_char(text, m.start()))
+        .map(|m| m.as_str().to_string())
+        .collect();
+    filter_subs(found, domain)
+}
+
+/// Rejects matches like `notexample.com` when scraping for `example.com`.
+fn preceded_by_label_char(text: &str, start: usize) -> bool {
+    text[..start]
+        .chars()
+        .next_back()
+        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
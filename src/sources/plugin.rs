@@ -0,0 +1,124 @@
+//! Executables from `--plugin-dir` as passive sources: each is run with
+//! the domain as its only argument and prints hostnames on stdout, one per
+//! line. Names outside the domain are dropped, as are comments and blank
+//! lines; a non-zero exit fails the source with the last line of stderr.
+
+use super::DataSource;
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
+use tokio::process::Command;
+
+pub struct Plugin {
+    name: &'static str,
+    path: PathBuf,
+}
+
+impl Plugin {
+    /// A plugin named after its file, without the extension.
+    pub fn new(path: PathBuf) -> Self {
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        Self {
+            name: intern(stem),
+            path,
+        }
+    }
+}
+
+/// Source names are `&'static str`; each plugin name is leaked once per
+/// process, however often the registry is built.
+fn intern(name: String) -> &'static str {
+    static NAMES: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut names = NAMES
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    match names.get(name.as_str()) {
+        Some(known) => known,
+        None => {
+            let leaked: &'static str = Box::leak(name.into_boxed_str());
+            names.insert(leaked);
+            leaked
+        }
+    }
+}
+
+/// The executables in `dir`, in name order. Hidden files, directories and
+/// files without an execute bit are skipped.
+pub fn load_dir(dir: &Path) -> io::Result<Vec<Plugin>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if !hidden && is_executable(&path) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths.into_iter().map(Plugin::new).collect())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    path.is_file() && matches!(ext.as_deref(), Some("exe" | "bat" | "cmd"))
+}
+
+#[async_trait]
+impl DataSource for Plugin {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!("{} {}", self.path.display(), domain)
+    }
+
+    async fn fetch(&self, _client: &Client, domain: &str) -> Result<HashSet<String>> {
+        // A plugin that outlives its timeout is killed with the future
+        let output = Command::new(&self.path)
+            .arg(domain)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| format!("could not run {}: {}", self.path.display(), e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let last = stderr.lines().rfind(|l| !l.trim().is_empty());
+            return Err(match last {
+                Some(line) => format!("{}: {}", output.status, line.trim()),
+                None => output.status.to_string(),
+            }
+            .into());
+        }
+
+        let suffix = format!(".{}", domain);
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.trim().trim_start_matches("*.").to_lowercase())
+            .filter(|l| !l.starts_with('#'))
+            .filter(|l| *l == domain || l.ends_with(&suffix))
+            .collect())
+    }
+
+    /// A failing plugin fails the same way again.
+    fn retries(&self) -> u32 {
+        0
+    }
+}
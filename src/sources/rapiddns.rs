@@ -0,0 +1,29 @@
+use super::{scrape_hosts, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use std::collections::HashSet;
+
+/// rapiddns.io, scraped from its HTML results table.
+pub struct RapidDns;
+
+#[async_trait]
+impl DataSource for RapidDns {
+    fn name(&self) -> &'static str {
+        "rapiddns"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!("https://rapiddns.io/subdomain/{}?full=1", domain)
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let url = self.url(domain);
+        let res = client.get(&url).send().await?;
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()).into());
+        }
+        let text = res.text().await?;
+        Ok(scrape_hosts(&text, domain))
+    }
+}
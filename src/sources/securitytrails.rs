@@ -0,0 +1,50 @@
+use super::{filter_subs, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+#[derive(Deserialize)]
+struct StResp {
+    #[serde(default)]
+    subdomains: Vec<String>,
+}
+
+pub struct SecurityTrails {
+    pub api_key: String,
+}
+
+#[async_trait]
+impl DataSource for SecurityTrails {
+    fn name(&self) -> &'static str {
+        "securitytrails"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!(
+            "https://api.securitytrails.com/v1/domain/{}/subdomains?children_only=false",
+            domain
+        )
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let url = self.url(domain);
+        let res = client
+            .get(&url)
+            .header("APIKEY", &self.api_key)
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()).into());
+        }
+        let resp: StResp = res.json().await?;
+        // Entries are bare labels ("www", "api.eu")
+        let hosts = resp
+            .subdomains
+            .into_iter()
+            .map(|label| format!("{}.{}", label, domain))
+            .collect();
+        Ok(filter_subs(hosts, domain))
+    }
+}
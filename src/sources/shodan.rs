@@ -0,0 +1,47 @@
+use super::{filter_subs, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+#[derive(Deserialize)]
+struct ShodanDnsResp {
+    #[serde(default)]
+    subdomains: Vec<String>,
+}
+
+pub struct Shodan {
+    pub api_key: String,
+}
+
+#[async_trait]
+impl DataSource for Shodan {
+    fn name(&self) -> &'static str {
+        "shodan"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!("https://api.shodan.io/dns/domain/{}", domain)
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let url = self.url(domain);
+        let res = client
+            .get(&url)
+            .query(&[("key", self.api_key.as_str())])
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()).into());
+        }
+        let resp: ShodanDnsResp = res.json().await?;
+        let hosts = resp
+            .subdomains
+            .into_iter()
+            .filter(|label| !label.is_empty())
+            .map(|label| format!("{}.{}", label, domain))
+            .collect();
+        Ok(filter_subs(hosts, domain))
+    }
+}
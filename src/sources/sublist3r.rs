@@ -0,0 +1,30 @@
+use super::{check_rate_limit, filter_subs, parse_json, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use std::collections::HashSet;
+
+pub struct Sublist3r;
+
+#[async_trait]
+impl DataSource for Sublist3r {
+    fn name(&self) -> &'static str {
+        "sublist3r"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!("https://api.sublist3r.com/search.php?domain={}", domain)
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let url = self.url(domain);
+        let res = client.get(&url).send().await?;
+        check_rate_limit(&res)?;
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()).into());
+        }
+        // Quota and error pages come back as text, not as a JSON list
+        let entries: Vec<String> = parse_json(&res.text().await?)?;
+        Ok(filter_subs(entries, domain))
+    }
+}
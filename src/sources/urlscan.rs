@@ -0,0 +1,108 @@
+use super::{filter_subs, with_query, DataSource};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+#[derive(Deserialize)]
+struct UrlscanPage {
+    domain: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UrlscanResult {
+    page: Option<UrlscanPage>,
+    #[serde(default)]
+    sort: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+struct UrlscanResp {
+    #[serde(default)]
+    results: Vec<UrlscanResult>,
+    #[serde(default)]
+    has_more: bool,
+}
+
+/// urlscan.io search. Works anonymously; a key raises page size and quota.
+pub struct UrlScan {
+    pub api_key: Option<String>,
+    pub max_pages: usize,
+}
+
+impl UrlScan {
+    pub fn new(api_key: Option<String>) -> Self {
+        Self {
+            api_key,
+            max_pages: 5,
+        }
+    }
+}
+
+const URLSCAN_SEARCH: &str = "https://urlscan.io/api/v1/search/";
+
+#[async_trait]
+impl DataSource for UrlScan {
+    fn name(&self) -> &'static str {
+        "urlscan"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        with_query(URLSCAN_SEARCH, &[("q", &format!("domain:{}", domain))])
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let query = format!("domain:{}", domain);
+        let size = if self.api_key.is_some() {
+            "1000"
+        } else {
+            "100"
+        };
+        let mut search_after: Option<String> = None;
+        let mut hosts = Vec::new();
+
+        for _ in 0..self.max_pages {
+            let mut req = client
+                .get(URLSCAN_SEARCH)
+                .query(&[("q", query.as_str()), ("size", size)]);
+            if let Some(ref after) = search_after {
+                req = req.query(&[("search_after", after.as_str())]);
+            }
+            if let Some(ref key) = self.api_key {
+                req = req.header("API-Key", key);
+            }
+            let res = req.send().await?;
+            if !res.status().is_success() {
+                if hosts.is_empty() {
+                    return Err(format!("HTTP {}", res.status()).into());
+                }
+                break;
+            }
+            let resp: UrlscanResp = res.json().await?;
+
+            // Next page continues after the sort key of the last hit
+            search_after = resp.results.last().map(|r| {
+                r.sort
+                    .iter()
+                    .map(|v| match v {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",")
+            });
+            hosts.extend(
+                resp.results
+                    .into_iter()
+                    .filter_map(|r| r.page.and_then(|p| p.domain)),
+            );
+            if !resp.has_more || search_after.is_none() {
+                break;
+            }
+        }
+
+        Ok(filter_subs(hosts, domain))
+    }
+}
@@ -0,0 +1,100 @@
+use super::{filter_subs, DataSource, SOURCE_REQUEST_TIMEOUT};
+use crate::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::time::Duration;
+
+#[derive(Deserialize)]
+struct VtObject {
+    id: String,
+}
+
+#[derive(Deserialize, Default)]
+struct VtLinks {
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct VtResp {
+    #[serde(default)]
+    data: Vec<VtObject>,
+    #[serde(default)]
+    links: VtLinks,
+}
+
+/// VirusTotal v3 `domains/{domain}/subdomains`, following `links.next`.
+pub struct VirusTotal {
+    pub api_key: String,
+    /// Pause between pages. Free keys allow 4 requests per minute.
+    pub interval: Duration,
+    pub max_pages: usize,
+}
+
+impl VirusTotal {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            api_key,
+            interval: Duration::from_secs(15),
+            max_pages: 20,
+        }
+    }
+
+    async fn page(&self, client: &Client, url: &str) -> Result<VtResp> {
+        let res = client
+            .get(url)
+            .header("x-apikey", &self.api_key)
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()).into());
+        }
+        Ok(res.json().await?)
+    }
+}
+
+#[async_trait]
+impl DataSource for VirusTotal {
+    fn name(&self) -> &'static str {
+        "virustotal"
+    }
+
+    fn url(&self, domain: &str) -> String {
+        format!(
+            "https://www.virustotal.com/api/v3/domains/{}/subdomains?limit=40",
+            domain
+        )
+    }
+
+    /// Every page waits out `interval` first.
+    fn timeout(&self) -> Duration {
+        (self.interval + SOURCE_REQUEST_TIMEOUT) * self.max_pages as u32
+    }
+
+    async fn fetch(&self, client: &Client, domain: &str) -> Result<HashSet<String>> {
+        let mut url = Some(self.url(domain));
+        let mut hosts = Vec::new();
+
+        for page in 0..self.max_pages {
+            let Some(current) = url.take() else {
+                break;
+            };
+            if page > 0 {
+                tokio::time::sleep(self.interval).await;
+            }
+            match self.page(client, &current).await {
+                Ok(resp) => {
+                    hosts.extend(resp.data.into_iter().map(|o| o.id));
+                    url = resp.links.next;
+                }
+                // Keep the pages already read rather than have a retry
+                // start over from the first
+                Err(_) if !hosts.is_empty() => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(filter_subs(hosts, domain))
+    }
+}
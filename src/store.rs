@@ -0,0 +1,171 @@
+//! Scan history in a SQLite database.
+//!
+//! Statements are run through the `sqlite3` command-line shell, which must
+//! be on `PATH`. Each run adds one row to `scans` plus its `subdomains` and
+//! `http_probes`, so earlier scans can be compared or queried with SQL.
+
+use crate::{Result, SubdomainResult};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS scans (
+    id INTEGER PRIMARY KEY,
+    domain TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    finished_at INTEGER NOT NULL,
+    total INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS scans_domain ON scans (domain, started_at);
+CREATE TABLE IF NOT EXISTS subdomains (
+    scan_id INTEGER NOT NULL REFERENCES scans (id),
+    name TEXT NOT NULL,
+    ips TEXT NOT NULL,
+    cname_chain TEXT NOT NULL,
+    takeover_service TEXT,
+    takeover_cname TEXT,
+    zone_transfer TEXT,
+    PRIMARY KEY (scan_id, name)
+);
+CREATE TABLE IF NOT EXISTS http_probes (
+    scan_id INTEGER NOT NULL REFERENCES scans (id),
+    subdomain TEXT NOT NULL,
+    port INTEGER,
+    scheme TEXT,
+    status_code INTEGER,
+    title TEXT,
+    server TEXT,
+    content_length INTEGER,
+    PRIMARY KEY (scan_id, subdomain, port, scheme)
+);
+";
+
+/// A scan history database.
+#[derive(Debug, Clone)]
+pub struct Store {
+    path: PathBuf,
+}
+
+impl Store {
+    /// Opens `path`, creating the database and its tables if needed.
+    pub fn open(path: &Path) -> Result<Self> {
+        let store = Self {
+            path: path.to_path_buf(),
+        };
+        store.execute(SCHEMA)?;
+        Ok(store)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Saves one finished scan and returns its id.
+    pub fn record_scan(
+        &self,
+        domain: &str,
+        started: SystemTime,
+        finished: SystemTime,
+        results: &[SubdomainResult],
+    ) -> Result<i64> {
+        let mut sql = String::from("BEGIN;\n");
+        sql.push_str(&format!(
+            "INSERT INTO scans (domain, started_at, finished_at, total) VALUES ({}, {}, {}, {});\n",
+            quote(domain),
+            unix(started),
+            unix(finished),
+            results.len()
+        ));
+        sql.push_str("CREATE TEMP TABLE current_scan AS SELECT last_insert_rowid() AS id;\n");
+
+        for r in results {
+            sql.push_str(&format!(
+                "INSERT OR REPLACE INTO subdomains VALUES ((SELECT id FROM current_scan), {}, {}, {}, {}, {}, {});\n",
+                quote(&r.subdomain),
+                quote(&r.ips.join(",")),
+                quote(&r.cname_chain.join(",")),
+                quote_opt(r.takeover.as_ref().map(|t| t.service.as_str())),
+                quote_opt(r.takeover.as_ref().map(|t| t.cname.as_str())),
+                quote_opt(r.zone_transfer.as_deref()),
+            ));
+            if r.status_code.is_some() {
+                sql.push_str(&format!(
+                    "INSERT OR REPLACE INTO http_probes VALUES ((SELECT id FROM current_scan), {}, {}, {}, {}, {}, {}, {});\n",
+                    quote(&r.subdomain),
+                    number_opt(r.port),
+                    quote_opt(r.scheme.as_deref()),
+                    number_opt(r.status_code),
+                    quote_opt(r.title.as_deref()),
+                    quote_opt(r.server.as_deref()),
+                    number_opt(r.content_length),
+                ));
+            }
+        }
+        sql.push_str("SELECT id FROM current_scan;\nCOMMIT;\n");
+
+        let out = self.execute(&sql)?;
+        out.trim()
+            .parse()
+            .map_err(|_| format!("unexpected sqlite3 output: {}", out.trim()).into())
+    }
+
+    /// Runs `sql` in one `sqlite3` session and returns what it printed.
+    pub fn execute(&self, sql: &str) -> Result<String> {
+        self.run(&[], sql)
+    }
+
+    /// Runs a query and returns its rows as JSON objects.
+    pub fn query(&self, sql: &str) -> Result<Vec<serde_json::Value>> {
+        let out = self.run(&["-json"], sql)?;
+        if out.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&out)?)
+    }
+
+    fn run(&self, flags: &[&str], sql: &str) -> Result<String> {
+        let mut child = Command::new("sqlite3")
+            .args(flags)
+            .arg("-bail")
+            .arg(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("could not run sqlite3 (is it installed?): {}", e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(sql.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr)
+                .trim()
+                .to_string()
+                .into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// A SQL string literal. NULs can't be passed through the shell's input.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\0', "").replace('\'', "''"))
+}
+
+fn quote_opt(value: Option<&str>) -> String {
+    value.map(quote).unwrap_or_else(|| "NULL".to_string())
+}
+
+fn number_opt<T: ToString>(value: Option<T>) -> String {
+    value
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "NULL".to_string())
+}
+
+fn unix(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,94 @@
+//! Responses that aren't the site behind a name: WAF and bot-protection
+//! challenges, whose titles ("Just a moment...") say nothing about the
+//! host, and hosts that answer like honeypots or tarpits, redirecting
+//! without end or serving one page for any path.
+
+use reqwest::header::HeaderMap;
+
+/// The challenge or block page of one vendor.
+#[derive(Debug, Clone, Copy)]
+pub struct Challenge {
+    pub vendor: &'static str,
+    /// Response headers, by lowercase name, and a substring of the value
+    /// that only a challenge carries.
+    pub headers: &'static [(&'static str, &'static str)],
+    /// Body snippets of the challenge page.
+    pub body: &'static [&'static str],
+}
+
+pub const CHALLENGES: &[Challenge] = &[
+    Challenge {
+        vendor: "Cloudflare",
+        headers: &[("cf-mitigated", "challenge")],
+        body: &[
+            "/cdn-cgi/challenge-platform/",
+            "window._cf_chl_opt",
+            "<title>Just a moment...</title>",
+            "Attention Required! | Cloudflare",
+        ],
+    },
+    Challenge {
+        vendor: "Akamai",
+        headers: &[],
+        body: &[
+            "/_sec/cp_challenge/",
+            "sec-if-cpt-container",
+            "bm-verify=",
+            "You don't have permission to access \"http://",
+        ],
+    },
+    Challenge {
+        vendor: "AWS WAF",
+        headers: &[
+            ("x-amzn-waf-action", "challenge"),
+            ("x-amzn-waf-action", "captcha"),
+        ],
+        body: &["awsWafCookieDomainList", "challenge.js\" defer></script>"],
+    },
+    Challenge {
+        vendor: "Imperva",
+        headers: &[],
+        body: &["/_Incapsula_Resource?", "Incapsula incident ID"],
+    },
+    Challenge {
+        vendor: "Sucuri",
+        headers: &[],
+        body: &[
+            "Sucuri WebSite Firewall - Access Denied",
+            "sucuri_cloudproxy_js",
+        ],
+    },
+    Challenge {
+        vendor: "DDoS-Guard",
+        headers: &[],
+        body: &["/.well-known/ddos-guard/", "DDoS-Guard</title>"],
+    },
+];
+
+/// The vendor whose challenge `headers` and `body` are.
+pub fn challenge(headers: &HeaderMap, body: &str) -> Option<&'static str> {
+    CHALLENGES
+        .iter()
+        .find(|c| {
+            c.headers.iter().any(|(name, value)| {
+                headers
+                    .get_all(*name)
+                    .iter()
+                    .any(|v| v.to_str().is_ok_and(|v| v.contains(value)))
+            }) || c.body.iter().any(|snippet| body.contains(snippet))
+        })
+        .map(|c| c.vendor)
+}
+
+/// Evidence that a redirect chain runs in circles: a URL requested twice
+/// on the way to `last`.
+pub fn redirect_loop(chain: &[String], last: &str) -> Option<String> {
+    chain
+        .iter()
+        .enumerate()
+        .find(|&(i, u)| u == last || chain[i + 1..].contains(u))
+        .map(|(_, u)| format!("redirects in a loop back to {}", u))
+}
+
+/// Evidence for a host serving its page for a path it can't have.
+pub const CATCH_ALL: &str = "answers any path with the same page";
@@ -0,0 +1,162 @@
+//! Environment labels (`dev`, `staging`, `prod`, ...) guessed from a host's
+//! name, page title and certificate issuer, as a first triage step, and
+//! `waf_challenge` and `suspect_honeypot` for responses that aren't the
+//! site (see [`crate::synthetic`]), and the tags of `--match-body` rules the
+//! body matched.
+
+use crate::SubdomainResult;
+
+/// How to recognise one environment.
+#[derive(Debug, Clone, Copy)]
+pub struct Tag {
+    pub name: &'static str,
+    /// Labels or parts of labels of the name below the apex, split on `.`,
+    /// `-` and `_` and without trailing digits (`dev01` is `dev`).
+    pub tokens: &'static [&'static str],
+    /// Words in the page title, case-insensitive.
+    pub title: &'static [&'static str],
+    /// Substrings of the certificate issuer, case-insensitive.
+    pub issuer: &'static [&'static str],
+}
+
+pub const TAGS: &[Tag] = &[
+    Tag {
+        name: "dev",
+        tokens: &[
+            "dev",
+            "devel",
+            "develop",
+            "development",
+            "test",
+            "tst",
+            "testing",
+            "qa",
+            "sandbox",
+            "sbx",
+            "local",
+        ],
+        title: &["development", "sandbox"],
+        issuer: &["fake le"],
+    },
+    Tag {
+        name: "staging",
+        tokens: &[
+            "staging", "stage", "stg", "stag", "preprod", "pre", "preview", "beta",
+        ],
+        title: &["staging", "preprod", "pre-production"],
+        issuer: &["(staging)"],
+    },
+    Tag {
+        name: "uat",
+        tokens: &["uat", "acceptance", "acc"],
+        title: &["uat"],
+        issuer: &[],
+    },
+    Tag {
+        name: "internal",
+        tokens: &[
+            "internal", "intranet", "intra", "int", "corp", "private", "priv", "lan",
+        ],
+        title: &["intranet", "internal"],
+        // Leaf issuers of public CAs never say this; private ones often do
+        issuer: &[
+            "internal",
+            "intranet",
+            "corp ca",
+            "corporate ca",
+            "enterprise ca",
+        ],
+    },
+    Tag {
+        name: "vpn",
+        tokens: &[
+            "vpn",
+            "sslvpn",
+            "openvpn",
+            "ipsec",
+            "wireguard",
+            "remote",
+            "anyconnect",
+            "globalprotect",
+        ],
+        title: &[
+            "vpn",
+            "globalprotect",
+            "anyconnect",
+            "fortigate",
+            "pulse",
+            "netscaler",
+        ],
+        issuer: &[],
+    },
+    Tag {
+        name: "api",
+        tokens: &[
+            "api", "apis", "graphql", "rest", "rpc", "grpc", "gateway", "gw",
+        ],
+        title: &["api", "swagger", "graphql", "graphiql", "redoc"],
+        issuer: &[],
+    },
+    Tag {
+        name: "prod",
+        tokens: &["prod", "production", "prd", "live"],
+        title: &["production"],
+        issuer: &[],
+    },
+];
+
+/// The tags of `result`, in [`TAGS`] order. `domain` is the scanned apex,
+/// whose own labels say nothing about the host. A `pre-prod` name is
+/// staging only, not prod as well.
+pub fn classify(result: &SubdomainResult, domain: &str) -> Vec<String> {
+    let name = result
+        .subdomain
+        .strip_suffix(domain)
+        .unwrap_or(&result.subdomain);
+    let tokens: Vec<&str> = name
+        .split(['.', '-', '_'])
+        .map(|t| t.trim_end_matches(|c: char| c.is_ascii_digit()))
+        .filter(|t| !t.is_empty())
+        .collect();
+    let title_words: Vec<String> = result
+        .title
+        .as_deref()
+        .unwrap_or("")
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '-')
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect();
+    let issuer = result
+        .tls
+        .as_ref()
+        .map(|t| t.issuer.to_lowercase())
+        .unwrap_or_default();
+
+    let mut tags: Vec<String> = TAGS
+        .iter()
+        .filter(|tag| {
+            tokens
+                .iter()
+                .any(|t| tag.tokens.iter().any(|k| t.eq_ignore_ascii_case(k)))
+                || title_words.iter().any(|w| tag.title.contains(&w.as_str()))
+                || (!issuer.is_empty() && tag.issuer.iter().any(|i| issuer.contains(i)))
+        })
+        .map(|tag| tag.name.to_string())
+        .collect();
+    if tags.len() > 1 && tags.iter().any(|t| t == "staging") {
+        tags.retain(|t| t != "prod");
+    }
+    if result.waf_challenge.is_some() {
+        tags.push("waf_challenge".to_string());
+    }
+    if result.suspect_honeypot.is_some() {
+        tags.push("suspect_honeypot".to_string());
+    }
+    for tag in &result.body_matches {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+    tags
+}
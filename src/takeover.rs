@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+
+/// A hosted service known to be claimable when a CNAME points at an
+/// unclaimed resource.
+#[derive(Debug, Clone, Copy)]
+pub struct Fingerprint {
+    pub service: &'static str,
+    /// Suffixes of the CNAME target that identify the service.
+    pub cnames: &'static [&'static str],
+    /// Body snippets served when the resource behind the name is unclaimed.
+    pub body: &'static [&'static str],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TakeoverFinding {
+    pub service: String,
+    pub cname: String,
+    pub evidence: String,
+}
+
+pub const FINGERPRINTS: &[Fingerprint] = &[
+    Fingerprint {
+        service: "AWS S3",
+        cnames: &["s3.amazonaws.com", "amazonaws.com"],
+        body: &["NoSuchBucket", "The specified bucket does not exist"],
+    },
+    Fingerprint {
+        service: "GitHub Pages",
+        cnames: &["github.io"],
+        body: &["There isn't a GitHub Pages site here."],
+    },
+    Fingerprint {
+        service: "Heroku",
+        cnames: &["herokuapp.com", "herokudns.com", "herokussl.com"],
+        body: &["No such app", "herokucdn.com/error-pages/no-such-app.html"],
+    },
+    Fingerprint {
+        service: "Azure",
+        cnames: &[
+            "azurewebsites.net",
+            "cloudapp.net",
+            "cloudapp.azure.com",
+            "trafficmanager.net",
+            "blob.core.windows.net",
+            "azureedge.net",
+        ],
+        body: &[
+            "404 Web Site not found",
+            "The resource you are looking for has been removed",
+        ],
+    },
+    Fingerprint {
+        service: "Fastly",
+        cnames: &["fastly.net"],
+        body: &["Fastly error: unknown domain"],
+    },
+    Fingerprint {
+        service: "Shopify",
+        cnames: &["myshopify.com"],
+        body: &[
+            "Sorry, this shop is currently unavailable.",
+            "Only one step left!",
+        ],
+    },
+    Fingerprint {
+        service: "Netlify",
+        cnames: &["netlify.app", "netlify.com"],
+        body: &["Not Found - Request ID"],
+    },
+    Fingerprint {
+        service: "Surge.sh",
+        cnames: &["surge.sh"],
+        body: &["project not found"],
+    },
+    Fingerprint {
+        service: "Bitbucket",
+        cnames: &["bitbucket.io"],
+        body: &["Repository not found"],
+    },
+    Fingerprint {
+        service: "Pantheon",
+        cnames: &["pantheonsite.io"],
+        body: &["The gods are wise, but do not know of the site which you seek."],
+    },
+    Fingerprint {
+        service: "Tumblr",
+        cnames: &["domains.tumblr.com"],
+        body: &["Whatever you were looking for doesn't currently exist at this address"],
+    },
+    Fingerprint {
+        service: "Zendesk",
+        cnames: &["zendesk.com"],
+        body: &["Help Center Closed"],
+    },
+    Fingerprint {
+        service: "Ghost",
+        cnames: &["ghost.io"],
+        body: &["The thing you were looking for is no longer here, or never was"],
+    },
+    Fingerprint {
+        service: "Unbounce",
+        cnames: &["unbouncepages.com"],
+        body: &["The requested URL was not found on this server."],
+    },
+    Fingerprint {
+        service: "ReadMe",
+        cnames: &["readme.io"],
+        body: &["Project doesnt exist... yet!"],
+    },
+    Fingerprint {
+        service: "WordPress.com",
+        cnames: &["wordpress.com"],
+        body: &["Do you want to register"],
+    },
+];
+
+/// The fingerprint whose CNAME suffix matches anywhere in the chain.
+pub fn service_for(cname_chain: &[String]) -> Option<(&'static Fingerprint, &String)> {
+    cname_chain.iter().find_map(|cname| {
+        FINGERPRINTS
+            .iter()
+            .find(|fp| fp.cnames.iter().any(|s| cname_matches(cname, s)))
+            .map(|fp| (fp, cname))
+    })
+}
+
+/// Flags a host whose CNAME points at a known service that is serving its
+/// "unclaimed resource" page.
+pub fn check(cname_chain: &[String], body: &str) -> Option<TakeoverFinding> {
+    let (fp, cname) = service_for(cname_chain)?;
+    let evidence = fp.body.iter().find(|sig| body.contains(*sig))?;
+    Some(TakeoverFinding {
+        service: fp.service.to_string(),
+        cname: cname.clone(),
+        evidence: evidence.to_string(),
+    })
+}
+
+fn cname_matches(cname: &str, suffix: &str) -> bool {
+    cname == suffix || cname.ends_with(&format!(".{}", suffix))
+}
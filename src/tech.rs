@@ -0,0 +1,246 @@
+use reqwest::header::{HeaderMap, SET_COOKIE};
+
+/// How to recognise a product from one HTTP response.
+#[derive(Debug, Clone, Copy)]
+pub struct Technology {
+    pub name: &'static str,
+    /// `(header, substring)`, case-insensitive. An empty substring only
+    /// requires the header to be present.
+    pub headers: &'static [(&'static str, &'static str)],
+    /// Cookie names set by the product.
+    pub cookies: &'static [&'static str],
+    /// Body snippets, case-sensitive.
+    pub body: &'static [&'static str],
+}
+
+pub const TECHNOLOGIES: &[Technology] = &[
+    Technology {
+        name: "nginx",
+        headers: &[("server", "nginx")],
+        cookies: &[],
+        body: &[],
+    },
+    Technology {
+        name: "Apache",
+        headers: &[("server", "apache")],
+        cookies: &[],
+        body: &[],
+    },
+    Technology {
+        name: "Microsoft IIS",
+        headers: &[("server", "microsoft-iis")],
+        cookies: &[],
+        body: &[],
+    },
+    Technology {
+        name: "LiteSpeed",
+        headers: &[("server", "litespeed")],
+        cookies: &[],
+        body: &[],
+    },
+    Technology {
+        name: "Caddy",
+        headers: &[("server", "caddy")],
+        cookies: &[],
+        body: &[],
+    },
+    Technology {
+        name: "Envoy",
+        headers: &[("server", "envoy"), ("x-envoy-upstream-service-time", "")],
+        cookies: &[],
+        body: &[],
+    },
+    Technology {
+        name: "Cloudflare",
+        headers: &[("server", "cloudflare"), ("cf-ray", "")],
+        cookies: &["__cf_bm"],
+        body: &[],
+    },
+    Technology {
+        name: "Amazon CloudFront",
+        headers: &[("x-amz-cf-id", ""), ("via", "cloudfront")],
+        cookies: &[],
+        body: &[],
+    },
+    Technology {
+        name: "Amazon S3",
+        headers: &[("server", "amazons3")],
+        cookies: &[],
+        body: &[],
+    },
+    Technology {
+        name: "Varnish",
+        headers: &[("x-varnish", ""), ("via", "varnish")],
+        cookies: &[],
+        body: &[],
+    },
+    Technology {
+        name: "Apache Tomcat",
+        headers: &[],
+        cookies: &[],
+        body: &["Apache Tomcat/"],
+    },
+    Technology {
+        name: "Java",
+        headers: &[],
+        cookies: &["JSESSIONID"],
+        body: &[],
+    },
+    Technology {
+        name: "Spring Boot",
+        headers: &[],
+        cookies: &[],
+        body: &["Whitelabel Error Page"],
+    },
+    Technology {
+        name: "PHP",
+        headers: &[("x-powered-by", "php")],
+        cookies: &["PHPSESSID"],
+        body: &[],
+    },
+    Technology {
+        name: "ASP.NET",
+        headers: &[("x-aspnet-version", ""), ("x-powered-by", "asp.net")],
+        cookies: &["ASP.NET_SessionId", ".ASPXAUTH"],
+        body: &["__VIEWSTATE"],
+    },
+    Technology {
+        name: "Express",
+        headers: &[("x-powered-by", "express")],
+        cookies: &[],
+        body: &[],
+    },
+    Technology {
+        name: "Next.js",
+        headers: &[("x-powered-by", "next.js")],
+        cookies: &[],
+        body: &["__NEXT_DATA__"],
+    },
+    Technology {
+        name: "Laravel",
+        headers: &[],
+        cookies: &["laravel_session"],
+        body: &[],
+    },
+    Technology {
+        name: "Django",
+        headers: &[],
+        cookies: &["django_language"],
+        body: &["csrfmiddlewaretoken"],
+    },
+    Technology {
+        name: "WordPress",
+        headers: &[("link", "api.w.org")],
+        cookies: &[],
+        body: &["/wp-content/", "/wp-includes/"],
+    },
+    Technology {
+        name: "Drupal",
+        headers: &[("x-generator", "drupal"), ("x-drupal-cache", "")],
+        cookies: &[],
+        body: &["Drupal.settings", "drupal-settings-json"],
+    },
+    Technology {
+        name: "Joomla",
+        headers: &[],
+        cookies: &[],
+        body: &["content=\"Joomla!", "/media/jui/"],
+    },
+    Technology {
+        name: "Shopify",
+        headers: &[("x-shopify-stage", "")],
+        cookies: &[],
+        body: &["cdn.shopify.com"],
+    },
+    Technology {
+        name: "Jenkins",
+        headers: &[("x-jenkins", "")],
+        cookies: &[],
+        body: &["<title>Dashboard [Jenkins]</title>"],
+    },
+    Technology {
+        name: "GitLab",
+        headers: &[],
+        cookies: &["_gitlab_session"],
+        body: &["gitlab-logo"],
+    },
+    Technology {
+        name: "Grafana",
+        headers: &[],
+        cookies: &["grafana_session"],
+        body: &["window.grafanaBootData"],
+    },
+    Technology {
+        name: "Kibana",
+        headers: &[("kbn-name", ""), ("kbn-version", "")],
+        cookies: &[],
+        body: &[],
+    },
+    Technology {
+        name: "Elasticsearch",
+        headers: &[],
+        cookies: &[],
+        body: &["\"You Know, for Search\""],
+    },
+    Technology {
+        name: "Prometheus",
+        headers: &[],
+        cookies: &[],
+        body: &["<title>Prometheus Time Series Collection and Processing Server</title>"],
+    },
+    Technology {
+        name: "Jira",
+        headers: &[("x-arequestid", "")],
+        cookies: &["atlassian.xsrf.token"],
+        body: &["ajs-jira-base-url"],
+    },
+    Technology {
+        name: "Confluence",
+        headers: &[("x-confluence-request-time", "")],
+        cookies: &[],
+        body: &["ajs-confluence-base-url"],
+    },
+    Technology {
+        name: "phpMyAdmin",
+        headers: &[],
+        cookies: &["phpMyAdmin"],
+        body: &["<title>phpMyAdmin"],
+    },
+    Technology {
+        name: "Outlook Web App",
+        headers: &[("x-owa-version", "")],
+        cookies: &[],
+        body: &["/owa/auth/"],
+    },
+    Technology {
+        name: "Swagger UI",
+        headers: &[],
+        cookies: &[],
+        body: &["swagger-ui-bundle", "id=\"swagger-ui\""],
+    },
+];
+
+/// Names of every technology the response matches, in table order.
+pub fn detect(headers: &HeaderMap, body: &str) -> Vec<String> {
+    let cookies: Vec<&str> = headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|c| c.split('=').next())
+        .map(str::trim)
+        .collect();
+
+    TECHNOLOGIES
+        .iter()
+        .filter(|t| {
+            t.headers.iter().any(|(name, needle)| {
+                headers
+                    .get(*name)
+                    .and_then(|v| v.to_str().ok())
+                    .is_some_and(|v| v.to_ascii_lowercase().contains(needle))
+            }) || t.cookies.iter().any(|c| cookies.contains(c))
+                || t.body.iter().any(|b| body.contains(b))
+        })
+        .map(|t| t.name.to_string())
+        .collect()
+}
@@ -0,0 +1,113 @@
+//! Body decoding and `<title>` extraction.
+
+use encoding_rs::{Encoding, UTF_8};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Decodes a body using the `charset` of its `Content-Type`, else a
+/// `<meta charset>` near the top of the document, else UTF-8. Invalid
+/// sequences become U+FFFD.
+pub fn decode_body(bytes: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(charset_param)
+        .or_else(|| meta_charset(bytes))
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(UTF_8);
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// Text of the first `<title>` element with entities decoded and runs of
+/// whitespace collapsed. Attributes and line breaks inside the tag are
+/// fine. Empty titles count as missing.
+pub fn extract(body: &str) -> Option<String> {
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    let re = TITLE.get_or_init(|| Regex::new(r"(?is)<title\b[^>]*>(.*?)</title\s*>").unwrap());
+    let raw = re.captures(body)?.get(1)?.as_str();
+    let title = normalize_whitespace(&decode_entities(raw));
+    (!title.is_empty()).then_some(title)
+}
+
+fn normalize_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn charset_param(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches(['"', '\'']).to_string())
+    })
+}
+
+/// Looks at the first 1 KB, as browsers do, for `<meta charset=...>` or
+/// the `http-equiv` form.
+fn meta_charset(bytes: &[u8]) -> Option<String> {
+    static META: OnceLock<Regex> = OnceLock::new();
+    let re = META.get_or_init(|| {
+        Regex::new(r#"(?i)<meta\b[^>]*?charset\s*=\s*["']?\s*([A-Za-z0-9_.:-]+)"#).unwrap()
+    });
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]);
+    Some(re.captures(&head)?.get(1)?.as_str().to_string())
+}
+
+/// Named entities common in titles; anything else is kept verbatim.
+const ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", ' '),
+    ("copy", '©'),
+    ("reg", '®'),
+    ("trade", '™'),
+    ("ndash", '–'),
+    ("mdash", '—'),
+    ("hellip", '…'),
+    ("laquo", '«'),
+    ("raquo", '»'),
+    ("middot", '·'),
+    ("bull", '•'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+];
+
+/// Decodes numeric references and the named entities in [`ENTITIES`].
+pub fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| entity(&rest[1..=end]).map(|c| (c, end + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn entity(name: &str) -> Option<char> {
+    if let Some(num) = name.strip_prefix('#') {
+        let code = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    ENTITIES.iter().find(|(n, _)| *n == name).map(|&(_, c)| c)
+}
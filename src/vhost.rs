@@ -0,0 +1,200 @@
+//! Name-based virtual host discovery for `--vhosts`: candidate names that
+//! have no DNS record are sent as the Host of requests to addresses already
+//! found, and kept when the answer differs from what the address serves
+//! for a made-up name.
+
+use crate::adaptive::Concurrency;
+use crate::dns::ResolvedHost;
+use crate::probe::{self, ProbeOptions};
+use crate::wildcard::{body_hash, random_label, simhash, similarity};
+use crate::{title, SubdomainResult};
+use reqwest::{redirect, Client};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+/// Names tried against every address; the rest of a huge candidate list is
+/// left out.
+pub const MAX_VHOST_NAMES: usize = 5000;
+
+const VHOST_TIMEOUT: Duration = Duration::from_secs(8);
+
+/// What an address answered for one Host.
+#[derive(Debug, Clone)]
+struct Page {
+    scheme: &'static str,
+    status: u16,
+    title: Option<String>,
+    server: Option<String>,
+    length: u64,
+    body_hash: Option<String>,
+    simhash: Option<u64>,
+}
+
+impl Page {
+    /// Whether this is a different site than `baseline`, rather than the
+    /// default site or an error for an unknown Host.
+    fn differs_from(&self, baseline: &Page, threshold: f64) -> bool {
+        // Servers refusing the Host outright
+        if matches!(self.status, 400 | 421) || self.status >= 500 {
+            return false;
+        }
+        if self.status != baseline.status {
+            return true;
+        }
+        match (self.simhash, baseline.simhash) {
+            (Some(a), Some(b)) => similarity(a, b) < threshold,
+            _ => self.title != baseline.title || self.length != baseline.length,
+        }
+    }
+}
+
+/// Tries `names` as the Host of a request to each of `ips`. Every name
+/// that gets its own site back is returned once, with the addresses that
+/// serve it.
+pub async fn discover(
+    ips: Vec<String>,
+    names: Vec<String>,
+    domain: &str,
+    options: &ProbeOptions,
+    threshold: f64,
+) -> Vec<SubdomainResult> {
+    let names: Arc<Vec<String>> = Arc::new(names);
+    let semaphore = options
+        .shared_permits
+        .clone()
+        .unwrap_or_else(|| Concurrency::fixed(options.concurrency));
+    let mut tasks = JoinSet::new();
+    for ip in ips {
+        let Ok(addr) = ip.parse::<IpAddr>() else {
+            continue;
+        };
+        let baseline_name = format!("{}.{}", random_label(), domain);
+        // The client connects every name to this address, so TLS gets the
+        // right SNI as well as the Host header
+        let builder = names
+            .iter()
+            .chain([&baseline_name])
+            .fold(probe::client_builder(options), |b, name| {
+                b.resolve(name, SocketAddr::new(addr, 0))
+            });
+        let Ok(client) = builder
+            .timeout(VHOST_TIMEOUT)
+            .redirect(redirect::Policy::none())
+            .danger_accept_invalid_certs(true)
+            .build()
+        else {
+            continue;
+        };
+        let names = names.clone();
+        let semaphore = semaphore.clone();
+        let options = options.clone();
+        tasks.spawn(async move {
+            let Some(baseline) = fetch(&client, &options, &baseline_name, None).await else {
+                return Vec::new();
+            };
+            let mut checks = JoinSet::new();
+            for name in names.iter().cloned() {
+                let client = client.clone();
+                let semaphore = semaphore.clone();
+                let options = options.clone();
+                let scheme = baseline.scheme;
+                checks.spawn(async move {
+                    let _permit = semaphore.acquire().await?;
+                    let page = fetch(&client, &options, &name, Some(scheme)).await?;
+                    Some((name, page))
+                });
+            }
+            let mut found = Vec::new();
+            while let Some(res) = checks.join_next().await {
+                if let Ok(Some((name, page))) = res {
+                    if page.differs_from(&baseline, threshold) {
+                        found.push((name, page));
+                    }
+                }
+            }
+            found
+                .into_iter()
+                .map(|(name, page)| (name, addr.to_string(), page))
+                .collect::<Vec<_>>()
+        });
+    }
+
+    let mut results: BTreeMap<String, SubdomainResult> = BTreeMap::new();
+    while let Some(res) = tasks.join_next().await {
+        for (name, ip, page) in res.unwrap_or_default() {
+            if let Some(existing) = results.get_mut(&name) {
+                existing.ips.push(ip);
+                continue;
+            }
+            let host = ResolvedHost {
+                subdomain: name.clone(),
+                ips: vec![ip],
+                ..Default::default()
+            };
+            let mut result = probe::unprobed(&host, None);
+            result.vhost = true;
+            result.scheme = Some(page.scheme.to_string());
+            result.final_url = Some(format!("{}://{}/", page.scheme, name));
+            result.status_code = Some(page.status);
+            result.title = page.title;
+            result.server = page.server;
+            result.content_length = Some(page.length);
+            result.body_hash = page.body_hash;
+            result.body_simhash = page.simhash;
+            results.insert(name, result);
+        }
+    }
+    results.into_values().collect()
+}
+
+/// GETs `/` for `name`, over `scheme` or else HTTPS then HTTP.
+async fn fetch(
+    client: &Client,
+    options: &ProbeOptions,
+    name: &str,
+    scheme: Option<&'static str>,
+) -> Option<Page> {
+    let schemes = match scheme {
+        Some(scheme) => vec![scheme],
+        None => vec!["https", "http"],
+    };
+    for scheme in schemes {
+        if options.rate_limits.exhausted() {
+            return None;
+        }
+        options.rate_limits.wait(name).await;
+        options.rate_limits.count_request();
+        let Ok(resp) = client.get(format!("{}://{}/", scheme, name)).send().await else {
+            continue;
+        };
+        options.rate_limits.count_bytes(probe::head_size(&resp));
+        let status = resp.status().as_u16();
+        let server = resp
+            .headers()
+            .get("server")
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string);
+        let content_type = resp
+            .headers()
+            .get("content-type")
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string);
+        let (bytes, _) = probe::read_body(resp, &options.rate_limits, options.max_body_bytes)
+            .await
+            .unwrap_or_default();
+        let text = title::decode_body(&bytes, content_type.as_deref());
+        return Some(Page {
+            scheme,
+            status,
+            title: title::extract(&text),
+            server,
+            length: bytes.len() as u64,
+            body_hash: body_hash(&text),
+            simhash: simhash(&text),
+        });
+    }
+    None
+}
@@ -0,0 +1,356 @@
+use crate::dns::ResolverPool;
+use crate::hash;
+use crate::probe::{self, ProbeOptions};
+use crate::{title, SubdomainResult};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use reqwest::{redirect, Method};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+/// What a wildcard record under `zone` answers with.
+#[derive(Serialize, Debug, Clone)]
+pub struct WildcardProfile {
+    /// The level the wildcard covers: `dev.example.com` for
+    /// `*.dev.example.com`.
+    pub zone: String,
+    /// Every address seen across the random probes, so round-robin
+    /// wildcards are covered.
+    pub ips: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_length: Option<u64>,
+    /// [`body_hash`] of the wildcard page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_hash: Option<String>,
+    /// [`simhash`] of the wildcard page.
+    #[serde(skip)]
+    pub body_simhash: Option<u64>,
+}
+
+/// Random names resolved per level.
+pub const WILDCARD_PROBES: usize = 3;
+
+/// Resolves [`WILDCARD_PROBES`] random names under `domain` and, if any
+/// answer, fetches one over HTTP(S). The request carries the probe's user
+/// agent and headers so the profile matches what probing will see.
+pub async fn detect_wildcard(
+    resolver: &ResolverPool,
+    domain: &str,
+    probe: &ProbeOptions,
+) -> Option<WildcardProfile> {
+    let mut ips: Vec<String> = Vec::new();
+    let mut sample = None;
+    for _ in 0..WILDCARD_PROBES {
+        let name = format!("{}.{}", random_label(), domain);
+        if let Some(host) = resolver.resolve(&name).await {
+            ips.extend(host.ips);
+            sample.get_or_insert(name);
+        }
+    }
+    // Nothing resolved: no wildcard DNS (usually)
+    let sample = sample?;
+    ips.sort();
+    ips.dedup();
+
+    // Redirects are followed as probes follow them, so a wildcard's 30x
+    // compares with theirs
+    let client = probe::client_builder(probe)
+        .timeout(Duration::from_secs(5))
+        .danger_accept_invalid_certs(true)
+        .redirect(redirect::Policy::none())
+        .build()
+        .unwrap_or_default();
+    let limits = &probe.rate_limits;
+
+    let mut status = None;
+    let mut page_title = None;
+    let mut content_length = None;
+    let mut body_hash = None;
+    let mut body_simhash = None;
+    for proto in ["https", "http"] {
+        let url = format!("{}://{}", proto, sample);
+        let sent =
+            probe::request_following(&client, limits, Method::GET, &url, probe.max_redirects());
+        if let Ok((resp, _)) = sent.await {
+            status = Some(resp.status().as_u16());
+            content_length = resp.content_length();
+            let content_type = resp
+                .headers()
+                .get("content-type")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let body = probe::read_body(resp, limits, probe.max_body_bytes).await;
+            if let Some((bytes, complete)) = body {
+                // If content length wasn't in header, use body length
+                if content_length.is_none() && complete {
+                    content_length = Some(bytes.len() as u64);
+                }
+                let text = title::decode_body(&bytes, content_type.as_deref());
+                page_title = title::extract(&text);
+                body_hash = self::body_hash(&text);
+                body_simhash = simhash(&text);
+            }
+            break;
+        }
+    }
+
+    Some(WildcardProfile {
+        zone: domain.to_string(),
+        ips,
+        status_code: status,
+        title: page_title,
+        content_length,
+        body_hash,
+        body_simhash,
+    })
+}
+
+/// Runs [`detect_wildcard`] against each of `zones` concurrently and
+/// returns the levels that have one.
+pub async fn detect_wildcards(
+    resolver: Arc<ResolverPool>,
+    zones: HashSet<String>,
+    probe: &ProbeOptions,
+) -> Vec<WildcardProfile> {
+    let mut tasks = JoinSet::new();
+    for zone in zones {
+        let resolver = resolver.clone();
+        let probe = probe.clone();
+        tasks.spawn(async move { detect_wildcard(&resolver, &zone, &probe).await });
+    }
+    let mut found = Vec::new();
+    while let Some(res) = tasks.join_next().await {
+        if let Ok(Some(profile)) = res {
+            found.push(profile);
+        }
+    }
+    found
+}
+
+/// The most specific profile whose zone `result` sits under.
+pub fn profile_for<'a>(
+    result: &SubdomainResult,
+    profiles: &'a [WildcardProfile],
+) -> Option<&'a WildcardProfile> {
+    profiles
+        .iter()
+        .filter(|p| result.subdomain.ends_with(&format!(".{}", p.zone)))
+        .max_by_key(|p| p.zone.len())
+}
+
+pub(crate) fn random_label() -> String {
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(12)
+        .map(|c| (c as char).to_ascii_lowercase())
+        .collect();
+    format!("wildcard-test-{}", suffix)
+}
+
+/// Body similarity at or above which a result counts as the wildcard page.
+pub const DEFAULT_SIMILARITY_THRESHOLD: f64 = 0.85;
+
+impl WildcardProfile {
+    /// Round-robin wildcards hand out a subset per query, so any shared
+    /// address counts.
+    pub fn shares_address(&self, result: &SubdomainResult) -> bool {
+        result.ips.iter().any(|ip| self.ips.contains(ip))
+    }
+}
+
+/// How alike `result`'s body is to the wildcard page, from 0 to 1. `None`
+/// when either body was empty or not fetched.
+pub fn body_similarity(result: &SubdomainResult, profile: &WildcardProfile) -> Option<f64> {
+    Some(similarity(result.body_simhash?, profile.body_simhash?))
+}
+
+pub fn is_wildcard_match(
+    result: &SubdomainResult,
+    profile: &WildcardProfile,
+    threshold: f64,
+) -> bool {
+    wildcard_match_reason(result, profile, threshold).is_some()
+}
+
+/// Why [`is_wildcard_match`] takes `result` for `profile`'s wildcard, e.g.
+/// `same title "Parked"`; `None` when it doesn't.
+pub fn wildcard_match_reason(
+    result: &SubdomainResult,
+    profile: &WildcardProfile,
+    threshold: f64,
+) -> Option<String> {
+    // IP Match is the strongest indicator if combined with same content.
+    if !profile.shares_address(result) {
+        return None;
+    }
+
+    // If IPs match, check content similarity
+    // 1. Title Match
+    if let Some(ref title) = result.title {
+        if profile.title.as_ref() == Some(title) {
+            return Some(format!("same title \"{}\"", title));
+        }
+    }
+
+    // 2. The very same page, down to the byte
+    if result.body_hash.is_some() && result.body_hash == profile.body_hash {
+        return Some("same body".to_string());
+    }
+
+    // 3. Body similarity, which survives reflected hostnames, timestamps
+    // and other per-request noise
+    if let Some(score) = body_similarity(result, profile) {
+        return (score >= threshold).then(|| format!("body {:.0}% similar", score * 100.0));
+    }
+
+    // 4. Nothing to compare: exact status code match and no title
+    (result.status_code == profile.status_code && result.title.is_none() && profile.title.is_none())
+        .then(|| match result.status_code {
+            Some(code) => format!("same status {} and no title", code),
+            None => "no answer, like the wildcard".to_string(),
+        })
+}
+
+/// Words of at most this many bytes of a body feed the fingerprint.
+const SIMHASH_SAMPLE: usize = 64 * 1024;
+
+/// A 64-bit simhash over the words at the start of `body`.
+/// Similar documents get fingerprints a small Hamming distance apart.
+pub fn simhash(body: &str) -> Option<u64> {
+    let mut end = body.len().min(SIMHASH_SAMPLE);
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    let words: Vec<String> = body[..end]
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+
+    let mut weights = [0i64; 64];
+    for word in &words {
+        let hash = fnv1a(word.as_bytes());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash >> bit & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+    Some(
+        weights
+            .iter()
+            .enumerate()
+            .filter(|(_, &w)| w > 0)
+            .fold(0, |acc, (bit, _)| acc | 1 << bit),
+    )
+}
+
+/// Normalised bytes of a body that feed [`body_hash`].
+const BODY_HASH_SAMPLE: usize = 64 * 1024;
+
+/// Hex SHA-256 of the start of `body` with every run of whitespace made a
+/// single space, so identical pages match however they are indented or
+/// line-wrapped. `None` for an empty body.
+pub fn body_hash(body: &str) -> Option<String> {
+    let normalized = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    if normalized.is_empty() {
+        return None;
+    }
+    let mut end = normalized.len().min(BODY_HASH_SAMPLE);
+    while !normalized.is_char_boundary(end) {
+        end -= 1;
+    }
+    Some(hash::hex(&hash::sha256(&normalized.as_bytes()[..end])))
+}
+
+/// Share of matching bits between two simhashes.
+pub fn similarity(a: u64, b: u64) -> f64 {
+    1.0 - f64::from((a ^ b).count_ones()) / 64.0
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parked(host: &str, stamp: &str) -> String {
+        format!(
+            "<html><head><title>Domain parked</title></head><body>\
+             <h1>{host} is parked free of charge</h1>\
+             <p>This domain name was registered and is currently parked. \
+             Interested in buying a domain like this one? Contact the \
+             registrar for pricing, transfers and hosting offers. Search \
+             the web for related links, sponsored listings and advertising \
+             from our partners below.</p>\
+             <footer>Generated {stamp} by the parking service</footer>\
+             </body></html>"
+        )
+    }
+
+    fn score(a: &str, b: &str) -> f64 {
+        similarity(simhash(a).unwrap(), simhash(b).unwrap())
+    }
+
+    #[test]
+    fn identical_pages_score_one() {
+        let page = parked("a.example.com", "2026-10-14 08:00");
+        assert_eq!(score(&page, &page), 1.0);
+        // Case and markup punctuation are not words
+        assert_eq!(simhash("Hello, World!"), simhash("hello world"));
+    }
+
+    #[test]
+    fn reflected_noise_stays_above_the_threshold() {
+        let a = parked("a.example.com", "2026-10-14 08:00");
+        let b = parked("zz-top.example.com", "2026-10-14 08:01:59");
+        assert!(score(&a, &b) >= DEFAULT_SIMILARITY_THRESHOLD);
+    }
+
+    #[test]
+    fn unrelated_pages_fall_below_the_threshold() {
+        let a = parked("a.example.com", "2026-10-14 08:00");
+        let b = "<html><head><title>Grafana</title></head><body>Sign in to \
+                 your dashboards with email or username and password, or \
+                 continue with single sign-on. Forgot your password?</body></html>";
+        assert!(score(&a, b) < DEFAULT_SIMILARITY_THRESHOLD);
+    }
+
+    #[test]
+    fn similarity_counts_matching_bits() {
+        assert_eq!(similarity(0, u64::MAX), 0.0);
+        assert_eq!(similarity(0, 0xffff), 0.75);
+    }
+
+    #[test]
+    fn empty_bodies_have_no_fingerprint() {
+        assert_eq!(simhash(""), None);
+        assert_eq!(simhash("<> </> --"), None);
+        assert_eq!(body_hash(" \n\t"), None);
+    }
+
+    #[test]
+    fn body_hash_ignores_whitespace() {
+        assert_eq!(
+            body_hash("<p>\n  Parked\tpage </p>"),
+            body_hash("<p> Parked page </p>")
+        );
+        assert_ne!(body_hash("<p>Parked</p>"), body_hash("<p>parked</p>"));
+    }
+}
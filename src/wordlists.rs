@@ -0,0 +1,116 @@
+//! Built-in brute force wordlists for `--wordlist-builtin`, so brute
+//! forcing works without a SecLists checkout. `small` ships in the binary;
+//! `medium` and `large` are downloaded into the config directory on first
+//! use and read from there afterwards.
+
+use crate::{Config, Result};
+use reqwest::{Client, Url};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+const SMALL: &str = include_str!("../wordlists/small.txt");
+
+const SECLISTS: &str =
+    "https://raw.githubusercontent.com/danielmiessler/SecLists/master/Discovery/DNS";
+
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// One of the built-in wordlists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    /// About 500 common labels, curated.
+    Small,
+    /// SecLists' 20,000 most common labels.
+    Medium,
+    /// SecLists' 110,000 most common labels.
+    Large,
+}
+
+impl FromStr for Builtin {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "small" => Ok(Self::Small),
+            "medium" => Ok(Self::Medium),
+            "large" => Ok(Self::Large),
+            other => Err(format!(
+                "unknown wordlist '{}' (expected small, medium or large)",
+                other
+            )),
+        }
+    }
+}
+
+impl Builtin {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Small => "small",
+            Self::Medium => "medium",
+            Self::Large => "large",
+        }
+    }
+
+    /// Roughly how many names the list has.
+    pub fn approx_len(self) -> usize {
+        match self {
+            Self::Small => SMALL.lines().filter(|l| !l.trim().is_empty()).count(),
+            Self::Medium => 20_000,
+            Self::Large => 110_000,
+        }
+    }
+
+    fn url(self) -> Option<String> {
+        let file = match self {
+            Self::Small => return None,
+            Self::Medium => "subdomains-top1million-20000.txt",
+            Self::Large => "subdomains-top1million-110000.txt",
+        };
+        Some(format!("{}/{}", SECLISTS, file))
+    }
+}
+
+/// `wordlists` next to the config file.
+pub fn cache_dir() -> Option<PathBuf> {
+    Some(Config::default_path()?.parent()?.join("wordlists"))
+}
+
+/// The file holding `list` if it was written or downloaded before.
+pub fn local_path(list: Builtin) -> Option<PathBuf> {
+    let path = cache_dir()?.join(format!("{}.txt", list.name()));
+    path.exists().then_some(path)
+}
+
+/// The file holding `list`, written or downloaded first if it isn't there
+/// yet.
+pub async fn path(list: Builtin, proxy: Option<&Url>) -> Result<PathBuf> {
+    let dir = cache_dir().ok_or("no config directory to keep wordlists in")?;
+    let path = dir.join(format!("{}.txt", list.name()));
+    if path.exists() {
+        return Ok(path);
+    }
+    let text = match list.url() {
+        None => SMALL.to_string(),
+        Some(url) => {
+            tracing::info!("Downloading the {} wordlist from {}...", list.name(), url);
+            let client = crate::proxy::apply(Client::builder(), proxy)
+                .timeout(DOWNLOAD_TIMEOUT)
+                .build()?;
+            client
+                .get(&url)
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?
+        }
+    };
+    tokio::fs::create_dir_all(&dir).await?;
+    // Written aside and renamed, so an interrupted download isn't mistaken
+    // for the whole list next time
+    let partial = path.with_extension("txt.part");
+    tokio::fs::write(&partial, text).await?;
+    tokio::fs::rename(&partial, &path).await?;
+    Ok(path)
+}
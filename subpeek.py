@@ -0,0 +1,75 @@
+"""In-process scans through the core's C ABI, for Python recon frameworks
+that would rather not spawn the binary and parse its stdout.
+
+Build the shared library first:
+
+    cargo build --release --features ffi
+
+then:
+
+    import subpeek
+    for host in subpeek.enumerate("example.com", sources=["crtsh"], ports=[443]):
+        print(host["subdomain"], host.get("status_code"))
+
+Options are the keys listed in src/ffi.rs. SUBPEEK_LIB points at the
+library when it isn't in target/release.
+"""
+
+import ctypes
+import json
+import os
+import sys
+
+__all__ = ["SubpeekError", "enumerate"]
+
+
+class SubpeekError(Exception):
+    pass
+
+
+def _library_path():
+    if os.environ.get("SUBPEEK_LIB"):
+        return os.environ["SUBPEEK_LIB"]
+    if sys.platform == "win32":
+        name = "subpeek_core.dll"
+    elif sys.platform == "darwin":
+        name = "libsubpeek_core.dylib"
+    else:
+        name = "libsubpeek_core.so"
+    here = os.path.dirname(os.path.abspath(__file__))
+    return os.path.join(here, "target", "release", name)
+
+
+_lib = None
+
+
+def _load():
+    global _lib
+    if _lib is None:
+        lib = ctypes.CDLL(_library_path())
+        lib.subpeek_enumerate.argtypes = [ctypes.c_char_p, ctypes.c_char_p]
+        # A plain pointer, so the string can be handed back to subpeek_free
+        lib.subpeek_enumerate.restype = ctypes.c_void_p
+        lib.subpeek_free.argtypes = [ctypes.c_void_p]
+        lib.subpeek_free.restype = None
+        _lib = lib
+    return _lib
+
+
+def enumerate(domain, **options):
+    """Scans domain and returns its results as a list of dicts.
+
+    Blocks until the scan is done; run it in a thread to keep an event
+    loop going.
+    """
+    lib = _load()
+    raw = lib.subpeek_enumerate(
+        domain.encode("utf-8"), json.dumps(options).encode("utf-8")
+    )
+    try:
+        answer = json.loads(ctypes.string_at(raw).decode("utf-8"))
+    finally:
+        lib.subpeek_free(raw)
+    if isinstance(answer, dict) and "error" in answer:
+        raise SubpeekError(answer["error"])
+    return answer